@@ -0,0 +1,17 @@
+//! Generates the gRPC service code when the `grpc` feature is enabled
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/elasticube.proto");
+
+        // protox parses the definition in pure Rust, so no protoc is needed
+        let descriptors = protox::compile(["proto/elasticube.proto"], ["proto"])
+            .expect("Failed to parse proto/elasticube.proto");
+        tonic_build::configure()
+            .compile_fds(descriptors)
+            .expect("Failed to generate gRPC code");
+    }
+}
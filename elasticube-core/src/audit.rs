@@ -0,0 +1,94 @@
+//! Audit trail of who queried which cube
+//!
+//! An [`AuditSink`] installed on a cube receives an [`AuditEvent`] for every
+//! query, successful or not, naming the principal the query was run as
+//! ([`QueryBuilder::as_principal`]). Unlike the [`QueryLog`], which keeps a
+//! bounded in-memory history for tuning, sinks are meant to forward events
+//! to durable storage such as a SIEM, a log pipeline or an audit table.
+//!
+//! [`QueryBuilder::as_principal`]: crate::QueryBuilder::as_principal
+//! [`QueryLog`]: crate::QueryLog
+
+use crate::time::{Duration, SystemTime};
+use std::sync::Mutex;
+
+/// One query, as recorded for auditing
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    /// User or service the query was run as, if one was given
+    pub principal: Option<String>,
+
+    /// Name of the queried cube
+    pub cube: String,
+
+    /// SQL that ran, after calculated fields were expanded
+    pub sql: String,
+
+    /// When the query started
+    pub started_at: SystemTime,
+
+    /// Time until the result was available
+    pub duration: Duration,
+
+    /// Rows in the result
+    pub rows_returned: usize,
+
+    /// Error message, if the query failed
+    pub error: Option<String>,
+}
+
+/// Destination for audit events
+///
+/// `record` is called on the querying task once the query finishes, so
+/// implementations that do slow IO should hand events to a background
+/// writer (a channel, a buffered appender) rather than block.
+///
+/// # Example
+/// ```rust,ignore
+/// #[derive(Debug)]
+/// struct SyslogSink(UdpSocket);
+///
+/// impl AuditSink for SyslogSink {
+///     fn record(&self, event: &AuditEvent) {
+///         let principal = event.principal.as_deref().unwrap_or("anonymous");
+///         let line = format!("{} queried {}: {}", principal, event.cube, event.sql);
+///         let _ = self.0.send(line.as_bytes());
+///     }
+/// }
+///
+/// cube.set_audit_sink(Arc::new(SyslogSink(socket)));
+/// ```
+pub trait AuditSink: Send + Sync + std::fmt::Debug {
+    /// Record a finished query
+    fn record(&self, event: &AuditEvent);
+}
+
+/// Sink keeping every event in memory, mainly for tests
+#[derive(Debug, Default)]
+pub struct MemoryAuditSink {
+    events: Mutex<Vec<AuditEvent>>,
+}
+
+impl MemoryAuditSink {
+    /// Create an empty sink
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The recorded events, oldest first
+    pub fn events(&self) -> Vec<AuditEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+impl AuditSink for MemoryAuditSink {
+    fn record(&self, event: &AuditEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(event.clone());
+    }
+}
@@ -0,0 +1,145 @@
+//! Serve cube definition files over the network
+//!
+//! Builds a cube from each definition file and serves them all, named after
+//! the cubes, over HTTP and, when built with their features, Arrow Flight
+//! SQL, the PostgreSQL wire protocol and gRPC:
+//!
+//! ```bash
+//! cargo run --release --features server,flight-sql,pgwire --bin elasticube-server -- \
+//!     --http 0.0.0.0:8080 --postgres 0.0.0.0:5432 cubes/sales.yaml cubes/budget.json
+//! ```
+//!
+//! None of the protocols authenticate clients, so every server listens on
+//! localhost unless given another address, and gRPC only accepts writes
+//! with `--grpc-allow-writes`.
+
+use elasticube_core::{CubeCatalog, Error, RestServer, Result};
+use std::net::SocketAddr;
+
+const USAGE: &str = "Usage: elasticube-server [OPTIONS] DEFINITION...
+
+Options:
+  --http ADDR          Address to serve the HTTP API on (default 127.0.0.1:8080)
+  --flight-sql ADDR    Address to serve Arrow Flight SQL on (default 127.0.0.1:50051,
+                       requires the flight-sql feature)
+  --postgres ADDR      Address to serve the PostgreSQL protocol on (default
+                       127.0.0.1:5432, requires the pgwire feature)
+  --grpc ADDR          Address to serve the gRPC service on (default 127.0.0.1:50052,
+                       requires the grpc feature)
+  --grpc-allow-writes  Accept AppendRows over gRPC; requests are not authenticated
+  -h, --help           Print this help";
+
+/// Parsed command line
+struct Args {
+    http: SocketAddr,
+    #[cfg_attr(not(feature = "flight-sql"), allow(dead_code))]
+    flight_sql: SocketAddr,
+    #[cfg_attr(not(feature = "pgwire"), allow(dead_code))]
+    postgres: SocketAddr,
+    #[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+    grpc: SocketAddr,
+    #[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+    grpc_allow_writes: bool,
+    definitions: Vec<String>,
+}
+
+fn parse_addr(option: &str, value: Option<String>) -> Result<SocketAddr> {
+    let addr = value.ok_or_else(|| Error::config(format!("{} needs an address", option)))?;
+    addr.parse()
+        .map_err(|e| Error::config(format!("Invalid address '{}': {}", addr, e)))
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Args>> {
+    let mut http: SocketAddr = ([127, 0, 0, 1], 8080).into();
+    let mut flight_sql: SocketAddr = ([127, 0, 0, 1], 50051).into();
+    let mut postgres: SocketAddr = ([127, 0, 0, 1], 5432).into();
+    let mut grpc: SocketAddr = ([127, 0, 0, 1], 50052).into();
+    let mut grpc_allow_writes = false;
+    let mut definitions = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--http" => http = parse_addr(&arg, args.next())?,
+            "--flight-sql" if cfg!(feature = "flight-sql") => {
+                flight_sql = parse_addr(&arg, args.next())?
+            }
+            "--postgres" if cfg!(feature = "pgwire") => {
+                postgres = parse_addr(&arg, args.next())?
+            }
+            "--grpc" if cfg!(feature = "grpc") => grpc = parse_addr(&arg, args.next())?,
+            "--grpc-allow-writes" if cfg!(feature = "grpc") => grpc_allow_writes = true,
+            other if other.starts_with('-') => {
+                return Err(Error::config(format!("Unknown option '{}'", other)));
+            }
+            _ => definitions.push(arg),
+        }
+    }
+
+    if definitions.is_empty() {
+        return Err(Error::config("No cube definition files given"));
+    }
+    Ok(Some(Args {
+        http,
+        flight_sql,
+        postgres,
+        grpc,
+        grpc_allow_writes,
+        definitions,
+    }))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let catalog = CubeCatalog::new();
+    for path in &args.definitions {
+        let name = catalog.load_definition(path)?;
+        let rows = catalog.snapshot(&name).map_or(0, |cube| cube.row_count());
+        println!("Loaded cube '{}' ({} rows) from {}", name, rows, path);
+    }
+
+    let mut servers = tokio::task::JoinSet::new();
+    println!("Serving HTTP on {}", args.http);
+    servers.spawn(RestServer::new(catalog.clone()).serve(args.http));
+
+    #[cfg(feature = "flight-sql")]
+    {
+        println!("Serving Flight SQL on {}", args.flight_sql);
+        let server = elasticube_core::FlightSqlServer::new(catalog.clone());
+        servers.spawn(server.serve(args.flight_sql));
+    }
+
+    #[cfg(feature = "pgwire")]
+    {
+        println!("Serving PostgreSQL protocol on {}", args.postgres);
+        let server = elasticube_core::PostgresServer::new(catalog.clone());
+        servers.spawn(server.serve(args.postgres));
+    }
+
+    #[cfg(feature = "grpc")]
+    {
+        println!("Serving gRPC on {}", args.grpc);
+        let server = elasticube_core::GrpcServer::new(catalog.clone())
+            .with_writes(args.grpc_allow_writes);
+        servers.spawn(server.serve(args.grpc));
+    }
+
+    // Servers only return when they fail
+    while let Some(result) = servers.join_next().await {
+        result.map_err(|e| Error::io(format!("Server task failed: {}", e)))??;
+    }
+    Ok(())
+}
@@ -1,19 +1,50 @@
 //! Query result caching for improved performance
 //!
 //! Implements an LRU (Least Recently Used) cache for query results to avoid
-//! re-executing identical queries.
-
+//! re-executing identical queries. Entries can expire after a time-to-live,
+//! and the cache can be bounded by the total size of its results as well as
+//! by entry count. An optional disk tier keeps results as Arrow IPC files so
+//! they survive process restarts. Identical queries submitted while one is
+//! already running wait for its result instead of running again.
+
+use crate::error::{Error, Result};
+use crate::optimization::OptimizationConfig;
 use crate::query::QueryResult;
+use arrow::datatypes::Schema;
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
 use lru::LruCache;
-use std::hash::Hash;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter};
 use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use crate::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+/// Schema metadata entry holding the full key of a cached result file
+const KEY_METADATA: &str = "elasticube.cache_key";
+
+/// Schema metadata entry holding when a result file was written (Unix ms)
+const CACHED_AT_METADATA: &str = "elasticube.cached_at";
+
+/// Schema metadata entry marking a truncated result
+const TRUNCATED_METADATA: &str = "elasticube.truncated";
+
+/// Schema metadata entry holding the version of the data a result came from
+const DATA_VERSION_METADATA: &str = "elasticube.data_version";
 
 /// A query cache key based on the SQL query string
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct QueryCacheKey {
     /// The SQL query string (normalized)
     query: String,
+
+    /// Hash of the cube data the query runs against
+    data_version: Option<u64>,
 }
 
 impl QueryCacheKey {
@@ -24,20 +55,134 @@ impl QueryCacheKey {
         let normalized = query.trim().to_lowercase();
         Self {
             query: normalized,
+            data_version: None,
         }
     }
+
+    /// Tie the key to the cube data with the hash `version`
+    ///
+    /// The disk tier only serves such a result for data with the same hash,
+    /// in this process or any other.
+    pub(crate) fn with_data_version(mut self, version: u64) -> Self {
+        self.data_version = Some(version);
+        self
+    }
+
+    /// Hash of the key that is the same in every process
+    fn stable_hash(&self) -> u64 {
+        stable_hash(&self.query)
+    }
+}
+
+/// 64-bit FNV-1a hash, stable across processes and Rust versions
+pub(crate) fn stable_hash(value: &str) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write(value.as_bytes());
+    hasher.finish()
+}
+
+/// Hasher behind [`stable_hash`], for hashing more than a string
+pub(crate) struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Feed the values of `batches` to `hasher`
+///
+/// Equal batches laid out alike in memory hash alike in every process; the
+/// same values sliced or padded differently may not.
+pub(crate) fn hash_batches(batches: &[RecordBatch], hasher: &mut StableHasher) {
+    fn hash_data(data: &arrow::array::ArrayData, hasher: &mut StableHasher) {
+        hasher.write_u64(data.len() as u64);
+        hasher.write_u64(data.offset() as u64);
+        for buffer in data.buffers() {
+            hasher.write(buffer.as_slice());
+        }
+        if let Some(nulls) = data.nulls() {
+            hasher.write_u64(nulls.offset() as u64);
+            hasher.write(nulls.buffer().as_slice());
+        }
+        for child in data.child_data() {
+            hash_data(child, hasher);
+        }
+    }
+
+    for batch in batches {
+        hasher.write_u64(batch.num_rows() as u64);
+        for column in batch.columns() {
+            hash_data(&column.to_data(), hasher);
+        }
+    }
+}
+
+/// Hash of the size and modification time of the file at `path`, or of
+/// every file under it if it's a directory
+///
+/// Results of queries reading files in place are keyed by it, so they go
+/// stale when a file is rewritten. `None` if a file can't be inspected, as
+/// for URLs; such queries are not cached.
+pub(crate) fn path_stamp(path: &str) -> Option<u64> {
+    let mut stamps = Vec::new();
+    collect_stamps(Path::new(path), &mut stamps).ok()?;
+    stamps.sort();
+    Some(stable_hash(&format!("{:?}", stamps)))
+}
+
+fn collect_stamps(
+    path: &Path,
+    stamps: &mut Vec<(PathBuf, u64, std::time::SystemTime)>,
+) -> std::io::Result<()> {
+    let metadata = std::fs::metadata(path)?;
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            collect_stamps(&entry?.path(), stamps)?;
+        }
+    } else {
+        stamps.push((path.to_path_buf(), metadata.len(), metadata.modified()?));
+    }
+    Ok(())
 }
 
 /// Query result cache with LRU eviction policy
 pub struct QueryCache {
     /// LRU cache storing query results
-    cache: Arc<Mutex<LruCache<QueryCacheKey, QueryResult>>>,
+    cache: Arc<Mutex<Entries>>,
+
+    /// How long an entry stays valid after it is cached
+    ttl: Option<Duration>,
+
+    /// Maximum total in-memory size of the cached results
+    max_bytes: Option<usize>,
 
     /// Number of cache hits
     hits: Arc<Mutex<usize>>,
 
     /// Number of cache misses
     misses: Arc<Mutex<usize>>,
+
+    /// Number of executions that waited for an identical running query
+    coalesced: Arc<Mutex<usize>>,
+
+    /// Queries currently executing, with a channel for their result
+    in_flight: Arc<Mutex<HashMap<QueryCacheKey, watch::Receiver<Option<QueryResult>>>>>,
+
+    /// Optional on-disk tier behind the in-memory entries
+    disk: Option<Arc<DiskCache>>,
 }
 
 impl QueryCache {
@@ -49,12 +194,77 @@ impl QueryCache {
         let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::new(100).unwrap());
 
         Self {
-            cache: Arc::new(Mutex::new(LruCache::new(capacity))),
+            cache: Arc::new(Mutex::new(Entries {
+                lru: LruCache::new(capacity),
+                bytes: 0,
+            })),
+            ttl: None,
+            max_bytes: None,
             hits: Arc::new(Mutex::new(0)),
             misses: Arc::new(Mutex::new(0)),
+            coalesced: Arc::new(Mutex::new(0)),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            disk: None,
         }
     }
 
+    /// Create a cache with the limits of an optimization configuration
+    pub fn from_config(config: &OptimizationConfig) -> Result<Self> {
+        let mut cache = Self::new(config.max_cache_entries);
+        if let Some(ttl) = config.cache_ttl {
+            cache = cache.with_ttl(ttl);
+        }
+        if let Some(max_bytes) = config.max_cache_bytes {
+            cache = cache.with_max_bytes(max_bytes);
+        }
+        if let Some(dir) = &config.query_cache_dir {
+            cache = cache.with_disk_tier(dir, config.max_disk_cache_bytes)?;
+        }
+        Ok(cache)
+    }
+
+    /// Expire entries `ttl` after they were cached
+    ///
+    /// Expired entries are treated as misses, so the query runs again and
+    /// its fresh result is cached. This applies to the disk tier too.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Bound the total in-memory size of the cached results
+    ///
+    /// Least recently used entries are evicted once the results together
+    /// exceed `max_bytes`. A result larger than the bound on its own is not
+    /// kept in memory.
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Also keep results on disk, in `dir`, up to `max_bytes` in total
+    ///
+    /// Each result is written as an Arrow IPC file named after its key, so
+    /// a cache created over the same directory after a restart finds them
+    /// again. When the directory grows past `max_bytes` the least recently
+    /// used files are deleted. Unreadable or partially written files are
+    /// treated as misses and removed.
+    ///
+    /// Cube query results are tied to a hash of the cube's data, so any
+    /// cube holding the same data, in this process or a later one, finds
+    /// them, and a result computed before the data changed is never served.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let cache = QueryCache::new(100).with_disk_tier("/var/cache/elasticube", 1 << 30)?;
+    /// ```
+    pub fn with_disk_tier(mut self, dir: impl Into<PathBuf>, max_bytes: u64) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        self.disk = Some(Arc::new(DiskCache { dir, max_bytes }));
+        Ok(self)
+    }
+
     /// Get a cached query result if it exists
     ///
     /// # Arguments
@@ -64,15 +274,34 @@ impl QueryCache {
     /// Some(QueryResult) if the query is cached, None otherwise
     pub fn get(&self, key: &QueryCacheKey) -> Option<QueryResult> {
         let mut cache = self.cache.lock().unwrap();
-        if let Some(result) = cache.get(key) {
-            // Cache hit
-            *self.hits.lock().unwrap() += 1;
-            Some(result.clone())
-        } else {
-            // Cache miss
-            *self.misses.lock().unwrap() += 1;
-            None
+        match cache.lru.get(key) {
+            Some(entry) if !self.is_expired(entry.cached_at.elapsed()) => {
+                // Cache hit
+                *self.hits.lock().unwrap() += 1;
+                return Some(entry.result.clone());
+            }
+            Some(_) => cache.remove(key),
+            None => {}
+        }
+
+        // Fall back to the disk tier, promoting hits into memory
+        if let Some(disk) = &self.disk {
+            match disk.get(key) {
+                Some((_, age)) if self.is_expired(age) => disk.remove(key),
+                Some((result, age)) => {
+                    let cached_at = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+                    let entry = CacheEntry::new(result.clone(), cached_at);
+                    cache.insert(key.clone(), entry, self.max_bytes);
+                    *self.hits.lock().unwrap() += 1;
+                    return Some(result);
+                }
+                None => {}
+            }
         }
+
+        // Cache miss
+        *self.misses.lock().unwrap() += 1;
+        None
     }
 
     /// Insert a query result into the cache
@@ -80,23 +309,64 @@ impl QueryCache {
     /// # Arguments
     /// * `key` - The query cache key
     /// * `result` - The query result to cache
+    ///
+    /// With a disk tier, the result is written to disk as well. Failing to
+    /// write it only means it won't survive a restart, so errors are
+    /// ignored.
     pub fn put(&self, key: QueryCacheKey, result: QueryResult) {
+        if let Some(disk) = &self.disk {
+            let _ = disk.put(&key, &result);
+        }
         let mut cache = self.cache.lock().unwrap();
-        cache.put(key, result);
+        cache.insert(key, CacheEntry::new(result, Instant::now()), self.max_bytes);
+    }
+
+    /// Join the executions of a query that missed the cache
+    ///
+    /// The first caller becomes the leader and runs the query; callers
+    /// arriving while it runs get a follower handle that waits for the
+    /// leader's result.
+    pub(crate) fn join_flight(&self, key: &QueryCacheKey) -> Flight {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if let Some(receiver) = in_flight.get(key) {
+            *self.coalesced.lock().unwrap() += 1;
+            return Flight::Follower(receiver.clone());
+        }
+        let (sender, receiver) = watch::channel(None);
+        in_flight.insert(key.clone(), receiver);
+        Flight::Leader(FlightGuard {
+            in_flight: Arc::clone(&self.in_flight),
+            key: key.clone(),
+            sender,
+        })
     }
 
-    /// Clear all cached results
+    /// Clear all cached results, including those on disk
     pub fn clear(&self) {
+        if let Some(disk) = &self.disk {
+            disk.clear();
+        }
         let mut cache = self.cache.lock().unwrap();
-        cache.clear();
+        cache.lru.clear();
+        cache.bytes = 0;
         *self.hits.lock().unwrap() = 0;
         *self.misses.lock().unwrap() = 0;
+        *self.coalesced.lock().unwrap() = 0;
     }
 
     /// Get the current cache size (number of entries)
     pub fn len(&self) -> usize {
         let cache = self.cache.lock().unwrap();
-        cache.len()
+        cache.lru.len()
+    }
+
+    /// Total in-memory size of the cached results
+    pub fn size_bytes(&self) -> usize {
+        self.cache.lock().unwrap().bytes
+    }
+
+    fn is_expired(&self, age: Duration) -> bool {
+        self.ttl.is_some_and(|ttl| age >= ttl)
     }
 
     /// Check if the cache is empty
@@ -121,10 +391,278 @@ impl QueryCache {
             total_requests: total,
             hit_rate,
             entries: self.len(),
+            bytes: self.size_bytes(),
+            coalesced: *self.coalesced.lock().unwrap(),
         }
     }
 }
 
+impl std::fmt::Debug for QueryCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryCache")
+            .field("ttl", &self.ttl)
+            .field("max_bytes", &self.max_bytes)
+            .field("stats", &self.stats())
+            .field("disk", &self.disk)
+            .finish()
+    }
+}
+
+/// A caller's part in executing a query, from [`QueryCache::join_flight`]
+pub(crate) enum Flight {
+    /// Nobody else is running the query: run it and publish the result
+    Leader(FlightGuard),
+    /// An identical query is running: wait for its result
+    Follower(watch::Receiver<Option<QueryResult>>),
+}
+
+impl Flight {
+    /// Wait for the leader's result
+    ///
+    /// Returns `None` if the leader failed or was cancelled; the caller
+    /// then runs the query itself.
+    pub(crate) async fn wait(
+        mut receiver: watch::Receiver<Option<QueryResult>>,
+    ) -> Option<QueryResult> {
+        let result = receiver.wait_for(Option::is_some).await.ok()?;
+        (*result).clone()
+    }
+}
+
+/// Leadership of an in-flight query, released when dropped
+pub(crate) struct FlightGuard {
+    in_flight: Arc<Mutex<HashMap<QueryCacheKey, watch::Receiver<Option<QueryResult>>>>>,
+    key: QueryCacheKey,
+    sender: watch::Sender<Option<QueryResult>>,
+}
+
+impl FlightGuard {
+    /// Hand the result to every caller waiting for it
+    ///
+    /// Call after caching the result, so callers arriving once the flight
+    /// is over find it in the cache.
+    pub(crate) fn finish(self, result: &QueryResult) {
+        let _ = self.sender.send(Some(result.clone()));
+    }
+}
+
+impl Drop for FlightGuard {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        in_flight.remove(&self.key);
+    }
+}
+
+/// In-memory entries of a [`QueryCache`] and their total size
+struct Entries {
+    lru: LruCache<QueryCacheKey, CacheEntry>,
+    bytes: usize,
+}
+
+impl Entries {
+    /// Add an entry, evicting the least recently used beyond `max_bytes`
+    fn insert(&mut self, key: QueryCacheKey, entry: CacheEntry, max_bytes: Option<usize>) {
+        if max_bytes.is_some_and(|max| entry.bytes > max) {
+            self.remove(&key);
+            return;
+        }
+        self.bytes += entry.bytes;
+        // `push` hands back either the replaced entry or the evicted one
+        if let Some((_, old)) = self.lru.push(key, entry) {
+            self.bytes -= old.bytes;
+        }
+        while max_bytes.is_some_and(|max| self.bytes > max) {
+            match self.lru.pop_lru() {
+                Some((_, old)) => self.bytes -= old.bytes,
+                None => break,
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &QueryCacheKey) {
+        if let Some(old) = self.lru.pop(key) {
+            self.bytes -= old.bytes;
+        }
+    }
+}
+
+/// A cached result with its size and insertion time
+struct CacheEntry {
+    result: QueryResult,
+    bytes: usize,
+    cached_at: Instant,
+}
+
+impl CacheEntry {
+    fn new(result: QueryResult, cached_at: Instant) -> Self {
+        let bytes = result
+            .batches()
+            .iter()
+            .map(|batch| batch.get_array_memory_size())
+            .sum();
+        Self {
+            result,
+            bytes,
+            cached_at,
+        }
+    }
+}
+
+/// Result files of a [`QueryCache`] disk tier
+#[derive(Debug)]
+struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl DiskCache {
+    fn path(&self, key: &QueryCacheKey) -> PathBuf {
+        self.dir.join(format!("{:016x}.arrow", key.stable_hash()))
+    }
+
+    /// Read a cached result and its age, removing the file if it can't be used
+    fn get(&self, key: &QueryCacheKey) -> Option<(QueryResult, Duration)> {
+        let path = self.path(key);
+        if !path.exists() {
+            return None;
+        }
+        match read_result(&path, key) {
+            Ok(Lookup::Hit(cached)) => {
+                // Touch the file so eviction sees it as recently used
+                if let Ok(file) = File::options().append(true).open(&path) {
+                    let _ = file.set_modified(SystemTime::now());
+                }
+                Some(cached)
+            }
+            // A different key with the same hash, or a result of other data,
+            // which the next result for the key replaces
+            Ok(Lookup::Other) => None,
+            Err(_) => {
+                let _ = std::fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// Write a result, then evict old files beyond the size limit
+    fn put(&self, key: &QueryCacheKey, result: &QueryResult) -> Result<()> {
+        let path = self.path(key);
+        // Write under a temporary name so readers never see a partial file
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        if let Err(e) = write_result(&tmp, key, result) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e);
+        }
+        std::fs::rename(&tmp, &path)?;
+        self.evict();
+        Ok(())
+    }
+
+    fn remove(&self, key: &QueryCacheKey) {
+        let _ = std::fs::remove_file(self.path(key));
+    }
+
+    /// Delete the least recently used files until the tier fits its limit
+    fn evict(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut files: Vec<_> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let metadata = entry.metadata().ok()?;
+                let is_result = entry.path().extension().is_some_and(|e| e == "arrow");
+                (is_result && metadata.is_file())
+                    .then(|| (metadata.modified().ok(), metadata.len(), entry.path()))
+            })
+            .collect();
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        files.sort();
+        for (_, len, path) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(entries) = std::fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                if entry.path().extension().is_some_and(|e| e == "arrow") {
+                    let _ = std::fs::remove_file(entry.path());
+                }
+            }
+        }
+    }
+}
+
+/// Write a result as an IPC file tagged with its key
+fn write_result(path: &Path, key: &QueryCacheKey, result: &QueryResult) -> Result<()> {
+    let base = result
+        .batches()
+        .first()
+        .map(|batch| batch.schema())
+        .unwrap_or_else(|| Arc::new(Schema::empty()));
+    let mut metadata = HashMap::new();
+    metadata.insert(KEY_METADATA.to_string(), key.query.clone());
+    if let Some(version) = key.data_version {
+        metadata.insert(DATA_VERSION_METADATA.to_string(), version.to_string());
+    }
+    metadata.insert(TRUNCATED_METADATA.to_string(), result.is_truncated().to_string());
+    let cached_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    metadata.insert(CACHED_AT_METADATA.to_string(), cached_at.as_millis().to_string());
+    let schema = Arc::new(Schema::new_with_metadata(base.fields().clone(), metadata));
+
+    let mut writer = FileWriter::try_new(BufWriter::new(File::create(path)?), &schema)?;
+    for batch in result.batches() {
+        writer.write(&RecordBatch::try_new(schema.clone(), batch.columns().to_vec())?)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// What a result file holds for a key
+enum Lookup {
+    /// The result and its age
+    Hit((QueryResult, Duration)),
+    /// A result for another key, or for other data
+    Other,
+}
+
+/// Read a result file and its age, if it holds the result for `key`
+fn read_result(path: &Path, key: &QueryCacheKey) -> Result<Lookup> {
+    let reader = FileReader::try_new(BufReader::new(File::open(path)?), None)?;
+    let schema = reader.schema();
+    if schema.metadata().get(KEY_METADATA) != Some(&key.query) {
+        return Ok(Lookup::Other);
+    }
+    let version = schema
+        .metadata()
+        .get(DATA_VERSION_METADATA)
+        .and_then(|value| value.parse::<u64>().ok());
+    if version != key.data_version {
+        return Ok(Lookup::Other);
+    }
+    let truncated = schema.metadata().get(TRUNCATED_METADATA).is_some_and(|v| v == "true");
+    let cached_at = schema
+        .metadata()
+        .get(CACHED_AT_METADATA)
+        .and_then(|millis| millis.parse().ok())
+        .map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+        .ok_or_else(|| Error::data("Cached result has no timestamp"))?;
+    let age = SystemTime::now().duration_since(cached_at).unwrap_or_default();
+
+    let clean = Arc::new(Schema::new(schema.fields().clone()));
+    let batches = reader
+        .map(|batch| Ok(RecordBatch::try_new(clean.clone(), batch?.columns().to_vec())?))
+        .collect::<Result<Vec<_>>>()?;
+    let result = QueryResult::from_batches(batches).with_truncated(truncated);
+    Ok(Lookup::Hit((result, age)))
+}
+
 /// Cache statistics
 #[derive(Debug, Clone, PartialEq)]
 pub struct CacheStats {
@@ -142,14 +680,20 @@ pub struct CacheStats {
 
     /// Current number of cached entries
     pub entries: usize,
+
+    /// Total in-memory size of the cached results (in bytes)
+    pub bytes: usize,
+
+    /// Executions that waited for an identical query already running
+    pub coalesced: usize,
 }
 
 impl std::fmt::Display for CacheStats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Cache Stats: {} hits, {} misses, {:.2}% hit rate, {} entries",
-            self.hits, self.misses, self.hit_rate, self.entries
+            "Cache Stats: {} hits, {} misses, {:.2}% hit rate, {} entries, {} bytes, {} coalesced",
+            self.hits, self.misses, self.hit_rate, self.entries, self.bytes, self.coalesced
         )
     }
 }
@@ -232,4 +776,115 @@ mod tests {
         assert_eq!(stats.total_requests, 2);
         assert_eq!(stats.hit_rate, 50.0);
     }
+
+    fn create_batch_result(values: Vec<i64>) -> QueryResult {
+        use arrow::array::Int64Array;
+        use arrow::datatypes::{DataType, Field};
+
+        let schema = Arc::new(Schema::new(vec![Field::new("value", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(values))]).unwrap();
+        QueryResult::from_batches(vec![batch])
+    }
+
+    #[test]
+    fn test_disk_tier() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = QueryCacheKey::new("SELECT value FROM cube");
+
+        let cache = QueryCache::new(10).with_disk_tier(dir.path(), 1 << 20).unwrap();
+        cache.put(key.clone(), create_batch_result(vec![1, 2, 3]));
+
+        // A new cache over the same directory finds the result
+        let restarted = QueryCache::new(10).with_disk_tier(dir.path(), 1 << 20).unwrap();
+        let cached = restarted.get(&key).unwrap();
+        assert_eq!(cached.row_count(), 3);
+        assert!(!cached.is_truncated());
+        assert!(cached.batches()[0].schema().metadata().is_empty());
+        assert_eq!(restarted.stats().hits, 1);
+
+        // Corrupt files are misses and get removed
+        let path = dir.path().join(format!("{:016x}.arrow", key.stable_hash()));
+        std::fs::write(&path, b"not an arrow file").unwrap();
+        let fresh = QueryCache::new(10).with_disk_tier(dir.path(), 1 << 20).unwrap();
+        assert!(fresh.get(&key).is_none());
+        assert!(!path.exists());
+
+        // The size limit evicts the oldest files
+        let small = QueryCache::new(10).with_disk_tier(dir.path(), 1).unwrap();
+        small.put(key.clone(), create_batch_result(vec![4, 5]));
+        assert!(!path.exists());
+
+        // Clearing removes the files too
+        cache.put(key.clone(), create_batch_result(vec![1]));
+        cache.clear();
+        assert!(std::fs::read_dir(dir.path()).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn test_disk_tier_data_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let query = "SELECT value FROM cube";
+        let key = QueryCacheKey::new(query).with_data_version(7);
+        let path = dir.path().join(format!("{:016x}.arrow", key.stable_hash()));
+        let cache = QueryCache::new(10).with_disk_tier(dir.path(), 1 << 20).unwrap();
+        cache.put(key.clone(), create_batch_result(vec![1, 2, 3]));
+
+        let disk = || QueryCache::new(10).with_disk_tier(dir.path(), 1 << 20).unwrap();
+        assert_eq!(disk().get(&key).unwrap().row_count(), 3);
+
+        // Other data can't use the result, and the next result replaces it
+        let other = QueryCacheKey::new(query).with_data_version(8);
+        assert!(disk().get(&other).is_none());
+        assert!(disk().get(&QueryCacheKey::new(query)).is_none());
+        assert!(path.exists());
+        cache.put(other.clone(), create_batch_result(vec![1]));
+        assert_eq!(disk().get(&other).unwrap().row_count(), 1);
+        assert!(disk().get(&key).is_none());
+    }
+
+    #[test]
+    fn test_hash_batches() {
+        let hash = |values: Vec<i64>| {
+            let mut hasher = StableHasher::default();
+            hash_batches(create_batch_result(values).batches(), &mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(vec![1, 2, 3]), hash(vec![1, 2, 3]));
+        assert_ne!(hash(vec![1, 2, 3]), hash(vec![1, 2, 4]));
+    }
+
+    #[test]
+    fn test_cache_ttl_and_max_bytes() {
+        let key = QueryCacheKey::new("SELECT value FROM cube");
+        let result = create_batch_result(vec![1, 2, 3]);
+        let size = CacheEntry::new(result.clone(), Instant::now()).bytes;
+
+        // Entries expire after the TTL
+        let cache = QueryCache::new(10).with_ttl(Duration::ZERO);
+        cache.put(key.clone(), result.clone());
+        assert!(cache.get(&key).is_none());
+        assert!(cache.is_empty());
+        assert_eq!(cache.size_bytes(), 0);
+
+        // The byte bound evicts the least recently used entry
+        let cache = QueryCache::new(10).with_max_bytes(size * 2);
+        let keys: Vec<_> = (0..3).map(|i| QueryCacheKey::new(format!("q{}", i))).collect();
+        cache.put(keys[0].clone(), result.clone());
+        cache.put(keys[1].clone(), result.clone());
+        assert!(cache.get(&keys[0]).is_some());
+        cache.put(keys[2].clone(), result.clone());
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.size_bytes(), size * 2);
+        assert!(cache.get(&keys[1]).is_none());
+        assert!(cache.get(&keys[0]).is_some());
+
+        // Replacing an entry doesn't count it twice
+        cache.put(keys[0].clone(), result.clone());
+        assert_eq!(cache.stats().bytes, size * 2);
+
+        // Results larger than the bound aren't kept
+        let cache = QueryCache::new(10).with_max_bytes(size - 1);
+        cache.put(key.clone(), result);
+        assert!(cache.is_empty());
+    }
 }
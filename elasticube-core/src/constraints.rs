@@ -0,0 +1,310 @@
+//! Data validation rules checked as data is loaded
+//!
+//! Constraints declared on the builder are checked against the cube's data
+//! when it is built and on every refresh, after columns are mapped,
+//! transformed and cast. Each rule that some rows break becomes a
+//! [`ConstraintViolation`]; the [`ViolationPolicy`] decides whether that
+//! fails the load or is only reported in
+//! [`ElastiCube::load_report`](crate::ElastiCube::load_report).
+
+use crate::cube::rename_identifier;
+use crate::error::{Error, Result};
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{DataType, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use arrow::row::{RowConverter, SortField};
+use datafusion::common::DFSchema;
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::prelude::SessionContext;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+/// Number of offending rows listed per violation
+const MAX_EXAMPLES: usize = 10;
+
+/// A rule every row of a cube must satisfy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    /// A SQL boolean expression over the cube's columns, such as
+    /// `quantity >= 0`; rows where it is NULL pass, as in a SQL `CHECK`
+    Check(String),
+
+    /// The column has no NULL values
+    NotNull(String),
+
+    /// No two rows share the same values in these columns; rows with a NULL
+    /// in any of them are not compared
+    Unique(Vec<String>),
+}
+
+impl Constraint {
+    /// Follow a column rename
+    pub(crate) fn rename_column(&mut self, old: &str, new: &str) {
+        match self {
+            Constraint::Check(sql) => *sql = rename_identifier(sql, old, new),
+            Constraint::NotNull(column) => {
+                if column == old {
+                    *column = new.to_string();
+                }
+            }
+            Constraint::Unique(columns) => {
+                for column in columns.iter_mut().filter(|column| *column == old) {
+                    *column = new.to_string();
+                }
+            }
+        }
+    }
+}
+
+/// A constraint prepared for checking the batches of one schema
+///
+/// A `CHECK` expression is planned once here rather than for every batch.
+struct Checker<'a> {
+    constraint: &'a Constraint,
+
+    /// Planned expression of a `CHECK` constraint
+    check: Option<Arc<dyn PhysicalExpr>>,
+
+    /// Keys seen so far by a uniqueness constraint
+    seen: HashSet<Vec<u8>>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(constraint: &'a Constraint, schema: &SchemaRef) -> Result<Self> {
+        let check = match constraint {
+            Constraint::Check(sql) => {
+                let df_schema = DFSchema::try_from(schema.as_ref().clone())?;
+                let ctx = SessionContext::new();
+                let expr = ctx
+                    .parse_sql_expr(sql, &df_schema)
+                    .and_then(|expr| ctx.create_physical_expr(expr, &df_schema))
+                    .map_err(|e| Error::data(format!("Invalid constraint {}: {}", constraint, e)))?;
+                Some(expr)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            constraint,
+            check,
+            seen: HashSet::new(),
+        })
+    }
+
+    /// Rows of `batch` breaking the constraint, counting keys seen in
+    /// earlier batches for a uniqueness constraint
+    fn violations(&mut self, batch: &RecordBatch) -> Result<Vec<usize>> {
+        let schema = batch.schema();
+        let constraint = self.constraint;
+        let index = |column: &str| {
+            schema.index_of(column).map_err(|_| {
+                Error::data(format!(
+                    "Constraint {} refers to unknown column '{}'",
+                    constraint, column
+                ))
+            })
+        };
+
+        match constraint {
+            Constraint::Check(_) => {
+                let Some(expr) = &self.check else {
+                    return Ok(Vec::new());
+                };
+                let result = expr.evaluate(batch)?.into_array(batch.num_rows())?;
+                if result.data_type() != &DataType::Boolean {
+                    return Err(Error::data(format!(
+                        "Constraint {} is {} rather than a condition",
+                        constraint,
+                        result.data_type()
+                    )));
+                }
+                let passed = result.as_boolean();
+                Ok((0..batch.num_rows())
+                    .filter(|&row| passed.is_valid(row) && !passed.value(row))
+                    .collect())
+            }
+            Constraint::NotNull(column) => {
+                let array = batch.column(index(column)?);
+                Ok((0..batch.num_rows()).filter(|&row| array.is_null(row)).collect())
+            }
+            Constraint::Unique(columns) => {
+                let arrays = columns
+                    .iter()
+                    .map(|column| Ok(batch.column(index(column)?).clone()))
+                    .collect::<Result<Vec<_>>>()?;
+                let fields = arrays
+                    .iter()
+                    .map(|array| SortField::new(array.data_type().clone()))
+                    .collect();
+                let rows = RowConverter::new(fields)?.convert_columns(&arrays)?;
+                Ok((0..batch.num_rows())
+                    .filter(|&row| arrays.iter().all(|array| array.is_valid(row)))
+                    .filter(|&row| !self.seen.insert(rows.row(row).as_ref().to_vec()))
+                    .collect())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constraint::Check(sql) => write!(f, "CHECK ({})", sql),
+            Constraint::NotNull(column) => write!(f, "NOT NULL ({})", column),
+            Constraint::Unique(columns) => write!(f, "UNIQUE ({})", columns.join(", ")),
+        }
+    }
+}
+
+/// A constraint that some loaded rows break
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintViolation {
+    /// The constraint broken
+    pub constraint: Constraint,
+
+    /// Number of rows breaking it
+    pub rows: usize,
+
+    /// Positions in the loaded data of the first offending rows
+    pub examples: Vec<usize>,
+}
+
+impl fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let examples: Vec<String> = self.examples.iter().map(|row| row.to_string()).collect();
+        write!(
+            f,
+            "{} broken by {} row(s), e.g. row {}",
+            self.constraint,
+            self.rows,
+            examples.join(", ")
+        )
+    }
+}
+
+/// What to do when loaded data breaks a constraint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ViolationPolicy {
+    /// Fail the build or refresh
+    #[default]
+    Fail,
+    /// Load the data anyway and list the violations in the load report
+    Report,
+}
+
+/// Check `batches` against every constraint
+pub(crate) fn check_constraints(
+    constraints: &[Constraint],
+    batches: &[RecordBatch],
+) -> Result<Vec<ConstraintViolation>> {
+    let Some(first) = batches.first() else {
+        return Ok(Vec::new());
+    };
+    let schema = first.schema();
+
+    let mut violations = Vec::new();
+    for constraint in constraints {
+        let mut checker = Checker::new(constraint, &schema)?;
+        let mut violation = ConstraintViolation {
+            constraint: constraint.clone(),
+            rows: 0,
+            examples: Vec::new(),
+        };
+        let mut offset = 0;
+        for batch in batches {
+            let rows = checker.violations(batch)?;
+            violation.rows += rows.len();
+            let room = MAX_EXAMPLES - violation.examples.len();
+            violation.examples.extend(rows.iter().take(room).map(|row| offset + row));
+            offset += batch.num_rows();
+        }
+        if violation.rows > 0 {
+            violations.push(violation);
+        }
+    }
+    Ok(violations)
+}
+
+/// Check `batches`, failing on violations unless `policy` only reports them
+pub(crate) fn enforce(
+    constraints: &[Constraint],
+    policy: ViolationPolicy,
+    schema: &SchemaRef,
+    batches: &[RecordBatch],
+) -> Result<Vec<ConstraintViolation>> {
+    if constraints.is_empty() {
+        return Ok(Vec::new());
+    }
+    // Check the columns exist even when there is no data to find them in
+    if batches.is_empty() {
+        let empty = RecordBatch::new_empty(schema.clone());
+        check_constraints(constraints, &[empty])?;
+    }
+
+    let violations = check_constraints(constraints, batches)?;
+    if policy == ViolationPolicy::Fail && !violations.is_empty() {
+        let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        return Err(Error::data(format!(
+            "Loaded data breaks {} constraint(s): {}",
+            violations.len(),
+            messages.join("; ")
+        )));
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{Field, Schema as ArrowSchema};
+
+    fn batch(ids: Vec<i64>, regions: Vec<Option<&str>>, quantities: Vec<i64>) -> RecordBatch {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("region", DataType::Utf8, true),
+            Field::new("quantity", DataType::Int64, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(ids)),
+                Arc::new(StringArray::from(regions)),
+                Arc::new(Int64Array::from(quantities)),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check_constraints() {
+        let batches = vec![
+            batch(vec![1, 2, 3], vec![Some("North"), None, Some("South")], vec![5, -1, 2]),
+            batch(vec![3, 4], vec![Some("East"), None], vec![1, -7]),
+        ];
+        let constraints = vec![
+            Constraint::Check("quantity >= 0".into()),
+            Constraint::NotNull("region".into()),
+            Constraint::Unique(vec!["id".into()]),
+            Constraint::Check("quantity < 100".into()),
+        ];
+
+        let violations = check_constraints(&constraints, &batches).unwrap();
+        assert_eq!(violations.len(), 3);
+        assert_eq!((violations[0].rows, violations[0].examples.clone()), (2, vec![1, 4]));
+        assert_eq!((violations[1].rows, violations[1].examples.clone()), (2, vec![1, 4]));
+        assert_eq!((violations[2].rows, violations[2].examples.clone()), (1, vec![3]));
+        assert_eq!(
+            violations[2].to_string(),
+            "UNIQUE (id) broken by 1 row(s), e.g. row 3"
+        );
+
+        let schema = batches[0].schema();
+        assert!(enforce(&constraints, ViolationPolicy::Fail, &schema, &batches).is_err());
+        let reported = enforce(&constraints, ViolationPolicy::Report, &schema, &batches).unwrap();
+        assert_eq!(reported, violations);
+
+        let unknown = [Constraint::NotNull("country".into())];
+        assert!(enforce(&unknown, ViolationPolicy::Report, &schema, &[]).is_err());
+    }
+}
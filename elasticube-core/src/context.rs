@@ -0,0 +1,619 @@
+//! Shared query context spanning multiple cubes
+//!
+//! A [`CubeContext`] registers several cubes as named tables in a single
+//! DataFusion session so SQL queries can join them, e.g. comparing an
+//! `actuals` cube against a `budget` cube.
+
+use crate::cube::ElastiCube;
+use crate::error::{Error, Result};
+use crate::optimization::OptimizationConfig;
+use crate::query::{quote_ident, read_only_sql, QueryRecorder, QueryResult, QueryStream};
+use arrow::datatypes::SchemaRef;
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::common::TableReference;
+use datafusion::dataframe::DataFrame;
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::prelude::SessionContext;
+use indexmap::IndexMap;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+/// A set of cubes queried together as named tables
+///
+/// # Example
+/// ```rust,ignore
+/// let mut ctx = CubeContext::new();
+/// ctx.register("sales", Arc::new(sales_cube))?;
+/// ctx.register("budget", Arc::new(budget_cube))?;
+///
+/// let result = ctx.query()
+///     .sql("SELECT s.region, SUM(s.amount) AS actual, SUM(b.amount) AS plan \
+///           FROM sales s JOIN budget b ON s.region = b.region \
+///           GROUP BY s.region")
+///     .execute()
+///     .await?;
+/// ```
+pub struct CubeContext {
+    /// Session every registered cube is attached to
+    ctx: SessionContext,
+
+    /// Registered cubes by table name
+    cubes: IndexMap<String, Arc<ElastiCube>>,
+}
+
+impl CubeContext {
+    /// Create an empty context with default optimization settings
+    pub fn new() -> Self {
+        let config = OptimizationConfig::default();
+        Self::from_session(SessionContext::new_with_config(config.to_session_config()))
+    }
+
+    /// Create an empty context with custom optimization settings
+    ///
+    /// Fails if the configured spill path can't be used.
+    pub fn with_config(config: OptimizationConfig) -> Result<Self> {
+        let ctx =
+            SessionContext::new_with_config_rt(config.to_session_config(), config.to_runtime_env()?);
+        Ok(Self::from_session(ctx))
+    }
+
+    fn from_session(ctx: SessionContext) -> Self {
+        crate::functions::register_statistical_functions(&ctx);
+
+        Self {
+            ctx,
+            cubes: IndexMap::new(),
+        }
+    }
+
+    /// Register a cube as a table
+    ///
+    /// The cube's user-defined functions are registered as well.
+    /// Registering an existing name replaces the previous cube.
+    pub fn register(&mut self, name: impl Into<String>, cube: Arc<ElastiCube>) -> Result<()> {
+        let name = name.into();
+        if name.is_empty() {
+            return Err(Error::query("Table name cannot be empty"));
+        }
+
+        let table = cube.table_provider(None)?;
+
+        self.ctx.deregister_table(name.as_str())?;
+        self.ctx
+            .register_table(name.as_str(), table)
+            .map_err(|e| Error::query(format!("Failed to register table '{}': {}", name, e)))?;
+        for udf in cube.udfs() {
+            self.ctx.register_udf(udf.clone());
+        }
+
+        self.cubes.insert(name, cube);
+        Ok(())
+    }
+
+    /// Remove a cube from the context, returning it if it was registered
+    pub fn deregister(&mut self, name: &str) -> Result<Option<Arc<ElastiCube>>> {
+        self.ctx.deregister_table(name)?;
+        Ok(self.cubes.shift_remove(name))
+    }
+
+    /// Get a registered cube by table name
+    pub fn cube(&self, name: &str) -> Option<&Arc<ElastiCube>> {
+        self.cubes.get(name)
+    }
+
+    /// Names of all registered tables
+    pub fn table_names(&self) -> Vec<&str> {
+        self.cubes.keys().map(|s| s.as_str()).collect()
+    }
+
+    /// Join each row of `left` to the latest row of `right` at or before it
+    ///
+    /// For every row of `left`, picks the `right` row with the greatest
+    /// `right_time <= left_time` among rows whose `by` columns match (e.g.
+    /// the last quote before each trade for the same symbol). Left rows
+    /// without such a match are kept with NULL right columns. The `by`
+    /// columns appear once; other right columns that clash with a left
+    /// column name are prefixed with the right table name (`quotes_price`).
+    ///
+    /// Both tables are sorted together by time rather than joined row by
+    /// row, so memory stays linear in their sizes.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let result = ctx
+    ///     .asof_join("trades", "quotes", "traded_at", "quoted_at", &["symbol"])?
+    ///     .execute()
+    ///     .await?;
+    /// ```
+    pub fn asof_join(
+        &self,
+        left: &str,
+        right: &str,
+        left_time: &str,
+        right_time: &str,
+        by: &[impl AsRef<str>],
+    ) -> Result<ContextQuery> {
+        let lookup = |name: &str| {
+            self.cubes
+                .get(name)
+                .ok_or_else(|| Error::query(format!("Table '{}' is not registered", name)))
+        };
+        let left_schema = lookup(left)?.arrow_schema().clone();
+        let right_schema = lookup(right)?.arrow_schema().clone();
+        let by: Vec<&str> = by.iter().map(|c| c.as_ref()).collect();
+
+        let mut required = vec![(&left_schema, left, left_time), (&right_schema, right, right_time)];
+        for column in &by {
+            required.push((&left_schema, left, column));
+            required.push((&right_schema, right, column));
+        }
+        for (schema, table, column) in required {
+            if schema.field_with_name(column).is_err() {
+                return Err(Error::query(format!(
+                    "Column '{}' not found in table '{}'",
+                    column, table
+                )));
+            }
+        }
+
+        let right_columns: Vec<String> = right_schema
+            .fields()
+            .iter()
+            .map(|f| f.name())
+            .filter(|name| !by.contains(&name.as_str()))
+            .map(|name| {
+                let alias = if left_schema.field_with_name(name).is_ok() {
+                    format!("{}_{}", right, name)
+                } else {
+                    name.clone()
+                };
+                format!("r.{} AS {}", quote_ident(name), quote_ident(&alias))
+            })
+            .collect();
+
+        // Rather than joining each left row to every earlier right row, sort
+        // both sides together by time and carry the latest right row forward.
+        // Right rows are numbered in time order, so the running maximum of
+        // the numbers seen is the latest one, and right rows sort before left
+        // rows at the same time.
+        let by_columns: Vec<String> = by.iter().map(|c| quote_ident(c)).collect();
+        let present = |time: &str| {
+            let mut columns = by_columns.clone();
+            columns.push(quote_ident(time));
+            columns
+                .iter()
+                .map(|c| format!("{} IS NOT NULL", c))
+                .collect::<Vec<_>>()
+                .join(" AND ")
+        };
+        let keys = by_columns
+            .iter()
+            .map(|c| format!("{}, ", c))
+            .collect::<String>();
+        let partition = if by_columns.is_empty() {
+            String::new()
+        } else {
+            format!("PARTITION BY {} ", by_columns.join(", "))
+        };
+
+        let mut select = vec!["l.*".to_string()];
+        select.extend(right_columns);
+
+        let sql = format!(
+            "WITH __asof_left AS (\
+             SELECT *, CAST(ROW_NUMBER() OVER () AS BIGINT) AS __asof_row FROM {left}), \
+             __asof_right AS (\
+             SELECT *, CAST(ROW_NUMBER() OVER (ORDER BY {right_time}) AS BIGINT) AS __asof_match \
+             FROM {right}), \
+             __asof_events AS (\
+             SELECT {keys}{left_time} AS __asof_time, 1 AS __asof_side, __asof_row, \
+             CAST(NULL AS BIGINT) AS __asof_match FROM __asof_left WHERE {left_present} \
+             UNION ALL \
+             SELECT {keys}{right_time} AS __asof_time, 0 AS __asof_side, \
+             CAST(NULL AS BIGINT) AS __asof_row, __asof_match \
+             FROM __asof_right WHERE {right_present}), \
+             __asof_matched AS (SELECT __asof_row, __asof_match FROM (\
+             SELECT __asof_row, __asof_side, MAX(__asof_match) OVER ({partition}\
+             ORDER BY __asof_time, __asof_side \
+             ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW) AS __asof_match \
+             FROM __asof_events) WHERE __asof_side = 1) \
+             SELECT * EXCLUDE (__asof_row) FROM (\
+             SELECT {select} FROM __asof_left AS l \
+             LEFT JOIN __asof_matched AS m ON l.__asof_row = m.__asof_row \
+             LEFT JOIN __asof_right AS r ON r.__asof_match = m.__asof_match) \
+             ORDER BY __asof_row",
+            left = quote_ident(left),
+            right = quote_ident(right),
+            left_time = quote_ident(left_time),
+            right_time = quote_ident(right_time),
+            left_present = present(left_time),
+            right_present = present(right_time),
+            keys = keys,
+            partition = partition,
+            select = select.join(", "),
+        );
+
+        Ok(self.query().sql(sql))
+    }
+
+    /// Start a query against the registered cubes
+    pub fn query(&self) -> ContextQuery {
+        ContextQuery {
+            ctx: self.ctx.clone(),
+            cubes: self.cubes.clone(),
+            sql: None,
+            principal: None,
+        }
+    }
+}
+
+impl Default for CubeContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A SQL query over the tables of a [`CubeContext`]
+pub struct ContextQuery {
+    /// Session shared with the owning context
+    ctx: SessionContext,
+
+    /// Cubes registered when the query was started, by table name
+    cubes: IndexMap<String, Arc<ElastiCube>>,
+
+    /// SQL to execute
+    sql: Option<String>,
+
+    /// Who runs the query, for the audit trail
+    principal: Option<String>,
+}
+
+impl ContextQuery {
+    /// Set the SQL query; registered cubes are referenced by their table names
+    pub fn sql(mut self, query: impl Into<String>) -> Self {
+        self.sql = Some(query.into());
+        self
+    }
+
+    /// Record the query as run by `principal` in the audit trail of the
+    /// cubes it reads
+    ///
+    /// See [`ElastiCube::set_audit_sink`].
+    pub fn as_principal(mut self, principal: impl Into<String>) -> Self {
+        self.principal = Some(principal.into());
+        self
+    }
+
+    fn query_sql(&self) -> Result<&str> {
+        self.sql
+            .as_deref()
+            .ok_or_else(|| Error::query("No SQL query set on the context query"))
+    }
+
+    async fn plan(&self) -> Result<DataFrame> {
+        self.ctx
+            .sql_with_options(self.query_sql()?, read_only_sql())
+            .await
+            .map_err(|e| Error::query(format!("SQL execution failed: {}", e)))
+    }
+
+    /// Plan the query, keeping a recorder for the cubes the plan reads
+    ///
+    /// A query that fails to plan is recorded with every registered cube.
+    async fn plan_recorded(self) -> Result<(DataFrame, QueryRecorder)> {
+        let mut recorder = QueryRecorder {
+            cubes: self.cubes.values().cloned().collect(),
+            principal: self.principal.clone(),
+            sql: self.query_sql()?.to_string(),
+            started_at: SystemTime::now(),
+            start: Instant::now(),
+        };
+        match self.plan().await {
+            Ok(df) => {
+                recorder.cubes = scanned_cubes(df.logical_plan(), &self.cubes);
+                Ok((df, recorder))
+            }
+            Err(e) => {
+                recorder.finish(0, 0, false, Some(e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    /// Plan the query without running it and return the schema of its result
+    pub async fn schema(self) -> Result<SchemaRef> {
+        Ok(self.plan().await?.schema().inner().clone())
+    }
+
+    /// Execute the query and collect the results
+    ///
+    /// The query is recorded in the history and audit trail of each cube
+    /// it reads.
+    pub async fn execute(self) -> Result<QueryResult> {
+        let (df, recorder) = self.plan_recorded().await?;
+        match df.collect().await {
+            Ok(batches) => {
+                let result = QueryResult::from_batches(batches);
+                recorder.finish(0, result.row_count(), false, None);
+                Ok(result)
+            }
+            Err(e) => {
+                let e = Error::query(format!("Failed to collect query results: {}", e));
+                recorder.finish(0, 0, false, Some(e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    /// Execute the query, producing batches as they are computed
+    ///
+    /// The query is recorded with each cube it reads when the stream ends,
+    /// fails or is dropped.
+    pub async fn execute_stream(self) -> Result<QueryStream> {
+        let (df, recorder) = self.plan_recorded().await?;
+        match df.execute_stream().await {
+            Ok(inner) => Ok(QueryStream::new(inner).recorded(recorder)),
+            Err(e) => {
+                let e = Error::query(format!("Failed to start query stream: {}", e));
+                recorder.finish(0, 0, false, Some(e.to_string()));
+                Err(e)
+            }
+        }
+    }
+}
+
+/// The cubes among `cubes` whose tables `plan` scans
+fn scanned_cubes(
+    plan: &LogicalPlan,
+    cubes: &IndexMap<String, Arc<ElastiCube>>,
+) -> Vec<Arc<ElastiCube>> {
+    let mut tables = Vec::new();
+    // The visitor never fails
+    let _ = plan.apply_with_subqueries(|node| {
+        if let LogicalPlan::TableScan(scan) = node {
+            tables.push(scan.table_name.table().to_string());
+        }
+        Ok(TreeNodeRecursion::Continue)
+    });
+    cubes
+        .iter()
+        .filter(|(name, _)| {
+            // Table names are registered the way SQL normalizes them
+            let registered = TableReference::from(name.as_str());
+            tables.iter().any(|table| table == registered.table())
+        })
+        .map(|(_, cube)| Arc::clone(cube))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ElastiCubeBuilder;
+    use crate::cube::AggFunc;
+    use arrow::array::{Float64Array, Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+    use arrow::record_batch::RecordBatch;
+
+    fn amounts_cube(name: &str, amounts: Vec<f64>) -> Arc<ElastiCube> {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["North", "South", "East"])),
+                Arc::new(Float64Array::from(amounts)),
+            ],
+        )
+        .unwrap();
+
+        Arc::new(
+            ElastiCubeBuilder::new(name)
+                .add_dimension("region", DataType::Utf8)
+                .unwrap()
+                .add_measure("amount", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .load_record_batches(schema, vec![batch])
+                .unwrap()
+                .build()
+                .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_table_provider_in_user_session() {
+        let ctx = SessionContext::new();
+        let sales = amounts_cube("sales", vec![120.0, 80.0, 50.0]);
+        ctx.register_table("sales", sales.as_table_provider().unwrap())
+            .unwrap();
+        ctx.sql("CREATE TABLE targets (region VARCHAR, target DOUBLE) AS VALUES ('North', 100.0), ('South', 90.0)")
+            .await
+            .unwrap();
+
+        let batches = ctx
+            .sql("SELECT s.region FROM sales s JOIN targets t ON s.region = t.region WHERE s.amount > t.target")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+        let regions = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(regions.value(0), "North");
+    }
+
+    #[tokio::test]
+    async fn test_join_across_cubes() {
+        let mut ctx = CubeContext::new();
+        ctx.register("sales", amounts_cube("sales", vec![120.0, 80.0, 50.0]))
+            .unwrap();
+        ctx.register("budget", amounts_cube("budget", vec![100.0, 100.0, 100.0]))
+            .unwrap();
+        assert_eq!(ctx.table_names(), vec!["sales", "budget"]);
+
+        let result = ctx
+            .query()
+            .sql(
+                "SELECT s.region, s.amount - b.amount AS variance \
+                 FROM sales s JOIN budget b ON s.region = b.region \
+                 WHERE s.amount > b.amount",
+            )
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 1);
+
+        // Only queries are allowed; nothing can be written or defined
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("sales.csv");
+        let copy = format!("COPY sales TO '{}' STORED AS CSV", target.display());
+        let set = "SET datafusion.execution.batch_size = 1024";
+        for sql in [copy.as_str(), "CREATE TABLE t AS SELECT 1", set] {
+            assert!(ctx.query().sql(sql).execute().await.is_err());
+        }
+        assert!(!target.exists());
+
+        assert!(ctx.deregister("budget").unwrap().is_some());
+        assert!(ctx
+            .query()
+            .sql("SELECT * FROM budget")
+            .execute()
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_queries_audited_on_scanned_cubes() {
+        use crate::audit::MemoryAuditSink;
+        use futures::StreamExt;
+
+        let sink = Arc::new(MemoryAuditSink::new());
+        let mut sales = Arc::unwrap_or_clone(amounts_cube("sales", vec![120.0, 80.0, 50.0]));
+        sales.set_audit_sink(sink.clone());
+        let mut budget = Arc::unwrap_or_clone(amounts_cube("budget", vec![100.0, 100.0, 100.0]));
+        budget.set_audit_sink(sink.clone());
+        let (sales, budget) = (Arc::new(sales), Arc::new(budget));
+        let mut ctx = CubeContext::new();
+        ctx.register("sales", Arc::clone(&sales)).unwrap();
+        ctx.register("budget", Arc::clone(&budget)).unwrap();
+
+        let result = ctx
+            .query()
+            .sql("SELECT region FROM sales WHERE amount > 60")
+            .as_principal("carol")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 2);
+        let events = sink.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].cube, "sales");
+        assert_eq!(events[0].principal.as_deref(), Some("carol"));
+        assert_eq!(events[0].rows_returned, 2);
+        assert_eq!(budget.query_history().len(), 0);
+
+        // Streams are recorded once consumed, with every cube they join
+        let mut stream = ctx
+            .query()
+            .sql("SELECT s.region FROM sales s JOIN budget b ON s.region = b.region")
+            .execute_stream()
+            .await
+            .unwrap();
+        assert_eq!(sink.events().len(), 1);
+        while let Some(batch) = stream.next().await {
+            batch.unwrap();
+        }
+        assert_eq!(sink.events().len(), 3);
+        assert_eq!(budget.query_history().len(), 1);
+
+        // Planning failures are recorded with every registered cube
+        assert!(ctx.query().sql("SELECT missing FROM sales").execute().await.is_err());
+        let events = sink.events();
+        assert_eq!(events.len(), 5);
+        assert!(events[4].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_asof_join() {
+        let quotes_schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("symbol", DataType::Utf8, false),
+            Field::new("ts", DataType::Int64, false),
+            Field::new("price", DataType::Float64, false),
+        ]));
+        let quotes = RecordBatch::try_new(
+            quotes_schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["A", "A", "B"])),
+                Arc::new(Int64Array::from(vec![1, 5, 2])),
+                Arc::new(Float64Array::from(vec![10.0, 11.0, 20.0])),
+            ],
+        )
+        .unwrap();
+        let trades_schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("symbol", DataType::Utf8, false),
+            Field::new("ts", DataType::Int64, false),
+            Field::new("price", DataType::Float64, false),
+        ]));
+        let trades = RecordBatch::try_new(
+            trades_schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["A", "A", "B", "B"])),
+                Arc::new(Int64Array::from(vec![3, 6, 1, 4])),
+                Arc::new(Float64Array::from(vec![10.5, 11.2, 19.0, 20.1])),
+            ],
+        )
+        .unwrap();
+
+        let build = |name: &str, schema, batch| {
+            Arc::new(
+                ElastiCubeBuilder::new(name)
+                    .add_dimension("symbol", DataType::Utf8)
+                    .unwrap()
+                    .add_dimension("ts", DataType::Int64)
+                    .unwrap()
+                    .add_measure("price", DataType::Float64, AggFunc::Avg)
+                    .unwrap()
+                    .load_record_batches(schema, vec![batch])
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let mut ctx = CubeContext::new();
+        ctx.register("trades", build("trades", trades_schema, trades))
+            .unwrap();
+        ctx.register("quotes", build("quotes", quotes_schema, quotes))
+            .unwrap();
+
+        let result = ctx
+            .asof_join("trades", "quotes", "ts", "ts", &["symbol"])
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 4);
+
+        let batch = arrow::compute::concat_batches(&result.batches()[0].schema(), result.batches())
+            .unwrap();
+        let quoted = batch
+            .column_by_name("quotes_price")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(
+            quoted.iter().collect::<Vec<_>>(),
+            vec![Some(10.0), Some(11.0), None, Some(20.0)]
+        );
+
+        assert!(ctx
+            .asof_join("trades", "missing", "ts", "ts", &["symbol"])
+            .is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Pre-aggregations for routing coarse queries
+//!
+//! An aggregation holds the cube's numeric measures summed, counted and
+//! min/max'd by a subset of its dimensions. A query that only groups and
+//! filters by those dimensions can be answered from it instead of the full
+//! data; the query builder does that rewrite automatically (see
+//! `OptimizationConfig::with_aggregate_routing`).
+
+use super::rename::{rename_batch_column, rename_field};
+use super::ElastiCube;
+use crate::error::{Error, Result};
+use crate::optimization::OptimizationConfig;
+use crate::query::quote_ident;
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use datafusion::catalog::TableProvider;
+use datafusion::datasource::MemTable;
+use std::sync::Arc;
+
+/// A pre-aggregation of a cube by some of its dimensions
+#[derive(Debug, Clone)]
+pub(crate) struct Aggregation {
+    pub(crate) name: String,
+    pub(crate) dimensions: Vec<String>,
+
+    /// Measures with partial aggregates in the table
+    pub(crate) measures: Vec<String>,
+
+    /// The dimension columns as they appear in the cube
+    pub(crate) dimension_schema: SchemaRef,
+
+    batches: Vec<RecordBatch>,
+
+    /// Generation of the data the aggregation was computed from
+    generation: u64,
+}
+
+impl Aggregation {
+    /// Column holding the number of rows in each group
+    pub(crate) const COUNT_COLUMN: &'static str = "__count";
+
+    /// Column holding the per-group minimum of a measure
+    pub(crate) fn min_column(measure: &str) -> String {
+        format!("__min_{}", measure)
+    }
+
+    /// Column holding the per-group maximum of a measure
+    pub(crate) fn max_column(measure: &str) -> String {
+        format!("__max_{}", measure)
+    }
+
+    /// Column holding the per-group count of non-NULL values of a measure
+    pub(crate) fn count_column(measure: &str) -> String {
+        format!("__count_{}", measure)
+    }
+
+    pub(crate) fn row_count(&self) -> usize {
+        self.batches.iter().map(|batch| batch.num_rows()).sum()
+    }
+
+    /// The aggregated rows as a table
+    pub(crate) fn table(&self) -> Result<Arc<dyn TableProvider>> {
+        let schema = self
+            .batches
+            .first()
+            .map(|batch| batch.schema())
+            .ok_or_else(|| Error::query(format!("Aggregation '{}' is empty", self.name)))?;
+        Ok(Arc::new(MemTable::try_new(schema, vec![self.batches.clone()])?))
+    }
+}
+
+impl ElastiCube {
+    /// Pre-aggregate the cube by some of its dimensions
+    ///
+    /// Stores, per combination of `dimensions`, the row count and the sum,
+    /// minimum, maximum and count of every numeric measure. Queries that
+    /// group and filter only by these dimensions and select SUM, MIN, MAX,
+    /// COUNT or AVG of measures are then answered from the aggregation.
+    ///
+    /// Aggregations are not updated with the cube: after the data changes
+    /// queries ignore them until [`refresh_aggregations`] is called. They are
+    /// not saved with the cube. A lazy cube's aggregations reflect its files
+    /// until the cube is refreshed, so refresh it after rewriting them.
+    ///
+    /// [`refresh_aggregations`]: Self::refresh_aggregations
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// cube.add_aggregation("by_region_month", &["region", "month"]).await?;
+    ///
+    /// // Grouping by a subset of the dimensions reads the aggregation
+    /// let cube = Arc::new(cube);
+    /// let result = cube.query()?
+    ///     .select(&["region", "SUM(sales) AS total"])
+    ///     .group_by(&["region"])
+    ///     .execute()
+    ///     .await?;
+    /// ```
+    pub async fn add_aggregation(
+        &mut self,
+        name: impl Into<String>,
+        dimensions: &[impl AsRef<str>],
+    ) -> Result<()> {
+        let name = name.into();
+        if self.aggregations.iter().any(|a| a.name == name) {
+            return Err(Error::schema(format!("Aggregation '{}' already exists", name)));
+        }
+        let dimensions: Vec<String> = dimensions.iter().map(|d| d.as_ref().to_string()).collect();
+        let aggregation = self.compute_aggregation(name, dimensions).await?;
+        self.aggregations.push(aggregation);
+        Ok(())
+    }
+
+    /// Recompute every aggregation from the current data
+    pub async fn refresh_aggregations(&mut self) -> Result<()> {
+        let mut refreshed = Vec::with_capacity(self.aggregations.len());
+        for aggregation in &self.aggregations {
+            refreshed.push(
+                self.compute_aggregation(aggregation.name.clone(), aggregation.dimensions.clone())
+                    .await?,
+            );
+        }
+        self.aggregations = refreshed;
+        Ok(())
+    }
+
+    /// Remove an aggregation, returning whether it existed
+    pub fn drop_aggregation(&mut self, name: &str) -> bool {
+        let before = self.aggregations.len();
+        self.aggregations.retain(|a| a.name != name);
+        self.aggregations.len() != before
+    }
+
+    /// Names of the cube's aggregations
+    pub fn aggregation_names(&self) -> Vec<&str> {
+        self.aggregations.iter().map(|a| a.name.as_str()).collect()
+    }
+
+    /// Follow a column rename in every aggregation
+    ///
+    /// Aggregations that were current at `generation`, the cube's generation
+    /// before the rename, stay current.
+    pub(crate) fn rename_in_aggregations(
+        &mut self,
+        old: &str,
+        new: &str,
+        generation: u64,
+    ) -> Result<()> {
+        let current = self.generation();
+        let columns = [
+            (old.to_string(), new.to_string()),
+            (Aggregation::min_column(old), Aggregation::min_column(new)),
+            (Aggregation::max_column(old), Aggregation::max_column(new)),
+            (Aggregation::count_column(old), Aggregation::count_column(new)),
+        ];
+        for aggregation in &mut self.aggregations {
+            for name in aggregation.dimensions.iter_mut().chain(&mut aggregation.measures) {
+                if name == old {
+                    *name = new.to_string();
+                }
+            }
+            aggregation.dimension_schema = rename_field(&aggregation.dimension_schema, old, new);
+            for (from, to) in &columns {
+                aggregation.batches = aggregation
+                    .batches
+                    .iter()
+                    .map(|batch| rename_batch_column(batch, from, to))
+                    .collect::<Result<_>>()?;
+            }
+            if aggregation.generation == generation {
+                aggregation.generation = current;
+            }
+        }
+        Ok(())
+    }
+
+    /// The aggregations computed from the cube's current data
+    ///
+    /// Any change to the data gives the cube a new generation, retiring
+    /// every aggregation. A lazy cube's files aren't inspected on every
+    /// query; refreshing the cube gives it a new generation.
+    pub(crate) fn current_aggregations(&self) -> Vec<&Aggregation> {
+        self.aggregations
+            .iter()
+            .filter(|a| a.generation == self.generation())
+            .filter(|a| !a.batches.is_empty())
+            .collect()
+    }
+
+    async fn compute_aggregation(
+        &self,
+        name: String,
+        dimensions: Vec<String>,
+    ) -> Result<Aggregation> {
+        let mut dimension_indices = Vec::with_capacity(dimensions.len());
+        for dimension in &dimensions {
+            dimension_indices.push(self.arrow_schema.index_of(dimension).map_err(|_| {
+                Error::schema(format!(
+                    "Aggregation dimension '{}' not found in cube data",
+                    dimension
+                ))
+            })?);
+        }
+        let measures: Vec<String> = self
+            .schema
+            .measures()
+            .iter()
+            .filter(|m| self.arrow_schema.field_with_name(m.name()).is_ok())
+            .filter(|m| m.data_type().is_integer() || m.data_type().is_floating())
+            .map(|m| m.name().to_string())
+            .collect();
+
+        let mut selects: Vec<String> = dimensions.iter().map(|d| quote_ident(d)).collect();
+        selects.push(format!("COUNT(*) AS {}", quote_ident(Aggregation::COUNT_COLUMN)));
+        for measure in &measures {
+            let column = quote_ident(measure);
+            selects.push(format!("SUM({}) AS {}", column, column));
+            selects.push(format!(
+                "MIN({}) AS {}",
+                column,
+                quote_ident(&Aggregation::min_column(measure))
+            ));
+            selects.push(format!(
+                "MAX({}) AS {}",
+                column,
+                quote_ident(&Aggregation::max_column(measure))
+            ));
+            selects.push(format!(
+                "COUNT({}) AS {}",
+                column,
+                quote_ident(&Aggregation::count_column(measure))
+            ));
+        }
+        let mut sql = format!("SELECT {} FROM cube", selects.join(", "));
+        if !dimensions.is_empty() {
+            let groups: Vec<String> = dimensions.iter().map(|d| quote_ident(d)).collect();
+            sql.push_str(&format!(" GROUP BY {}", groups.join(", ")));
+        }
+
+        let config = OptimizationConfig::new()
+            .with_query_cache(false)
+            .with_aggregate_routing(false);
+        let result = Arc::new(self.clone())
+            .query_with_config(config)?
+            .sql(sql)
+            .execute()
+            .await?;
+
+        Ok(Aggregation {
+            name,
+            dimensions,
+            measures,
+            dimension_schema: Arc::new(self.arrow_schema.project(&dimension_indices)?),
+            batches: result.batches().to_vec(),
+            generation: self.generation(),
+        })
+    }
+}
@@ -0,0 +1,230 @@
+//! Many-to-many dimensions through bridge tables
+//!
+//! A bridge dimension links each cube row to any number of members through
+//! a separate table of `(key, member)` pairs, such as patients and their
+//! diagnoses. Joining such a table onto the facts repeats every row once
+//! per member, so a plain join followed by `SUM` over-counts. The query
+//! builder only joins the bridge when the query groups by its dimension,
+//! where each row belongs once to each of its members' groups; a filter on
+//! the dimension alone becomes a semi-join that keeps matching rows once.
+
+use super::rename::{rename_batch_column, rename_field};
+use super::ElastiCube;
+use crate::error::{Error, Result};
+use crate::query::quote_ident;
+use arrow::datatypes::{DataType, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use datafusion::catalog::TableProvider;
+use datafusion::datasource::MemTable;
+use std::sync::Arc;
+
+/// A dimension whose members are attached to cube rows by a bridge table
+///
+/// The bridge is queryable in raw SQL as `<name>_bridge`, with the key
+/// column and a column named after the dimension.
+#[derive(Debug, Clone)]
+pub struct BridgeDimension {
+    /// Dimension name, also the member column of the bridge
+    name: String,
+
+    /// Cube column the bridge's rows refer to
+    key: String,
+
+    /// Data type of the members
+    data_type: DataType,
+
+    /// The bridge rows, holding only the key and member columns
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+}
+
+impl BridgeDimension {
+    /// Create a bridge from loaded data, keeping its key and member columns
+    pub(crate) fn new(
+        name: impl Into<String>,
+        key: impl Into<String>,
+        schema: SchemaRef,
+        batches: Vec<RecordBatch>,
+    ) -> Result<Self> {
+        let name = name.into();
+        let key = key.into();
+        let index = |column: &str| {
+            schema.index_of(column).map_err(|_| {
+                Error::dimension(format!(
+                    "Bridge table for '{}' has no column '{}'",
+                    name, column
+                ))
+            })
+        };
+        let projection = [index(&key)?, index(&name)?];
+        let data_type = schema.field(projection[1]).data_type().clone();
+
+        let schema = Arc::new(schema.project(&projection)?);
+        let batches = batches
+            .iter()
+            .map(|batch| batch.project(&projection))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(Self {
+            name,
+            key,
+            data_type,
+            schema,
+            batches,
+        })
+    }
+
+    /// Get the dimension name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the cube column linking rows to the bridge
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the data type of the members
+    pub fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    /// Number of (key, member) rows in the bridge
+    pub fn row_count(&self) -> usize {
+        self.batches.iter().map(|batch| batch.num_rows()).sum()
+    }
+
+    /// The bridge rows
+    pub(crate) fn batches(&self) -> &[RecordBatch] {
+        &self.batches
+    }
+
+    /// Name of the bridge table in SQL
+    pub fn table_name(&self) -> String {
+        format!("{}_bridge", self.name)
+    }
+
+    /// Follow a rename of the cube's key column
+    pub(crate) fn rename_key(&mut self, new: &str) -> Result<()> {
+        self.schema = rename_field(&self.schema, &self.key, new);
+        self.batches = self
+            .batches
+            .iter()
+            .map(|batch| rename_batch_column(batch, &self.key, new))
+            .collect::<Result<_>>()?;
+        self.key = new.to_string();
+        Ok(())
+    }
+
+    /// The bridge rows as a table
+    pub(crate) fn table(&self) -> Result<Arc<dyn TableProvider>> {
+        let table = MemTable::try_new(Arc::clone(&self.schema), vec![self.batches.clone()])?;
+        Ok(Arc::new(table))
+    }
+
+    /// `relation` (named `cube`) with a row per distinct member of each row
+    ///
+    /// Rows without members are kept once, with a NULL member.
+    pub(crate) fn join_sql(&self, relation: &str) -> String {
+        let table = quote_ident(&self.table_name());
+        let key = quote_ident(&self.key);
+        format!(
+            "(SELECT cube.*, {table}.{member} FROM {relation} LEFT JOIN \
+             (SELECT DISTINCT {key}, {member} FROM {table}) AS {table} \
+             ON cube.{key} = {table}.{key}) AS cube",
+            table = table,
+            member = quote_ident(&self.name),
+            relation = relation,
+            key = key,
+        )
+    }
+
+    /// Condition keeping the rows with a member that satisfies `condition`
+    pub(crate) fn semi_join_sql(&self, condition: &str) -> String {
+        let key = quote_ident(&self.key);
+        format!(
+            "{key} IN (SELECT {key} FROM {table} WHERE {condition})",
+            key = key,
+            table = quote_ident(&self.table_name()),
+            condition = condition
+        )
+    }
+}
+
+impl ElastiCube {
+    /// The cube's bridge dimensions
+    pub fn bridges(&self) -> &[BridgeDimension] {
+        &self.bridges
+    }
+
+    /// Get a bridge dimension by name
+    pub fn get_bridge(&self, name: &str) -> Option<&BridgeDimension> {
+        self.bridges.iter().find(|bridge| bridge.name == name)
+    }
+
+    /// Attach a bridge dimension, checking it against the cube's columns
+    pub(crate) fn add_bridge(&mut self, bridge: BridgeDimension) -> Result<()> {
+        let Ok(key) = self.arrow_schema.field_with_name(&bridge.key) else {
+            return Err(Error::dimension(format!(
+                "Bridge dimension '{}' refers to unknown key column '{}'",
+                bridge.name, bridge.key
+            )));
+        };
+        if key.data_type() != bridge.schema.field(0).data_type() {
+            return Err(Error::dimension(format!(
+                "Bridge key '{}' is {:?} in the bridge but {:?} in the cube",
+                bridge.key,
+                bridge.schema.field(0).data_type(),
+                key.data_type()
+            )));
+        }
+        if self.arrow_schema.field_with_name(&bridge.name).is_ok()
+            || self.schema.get_virtual_dimension(&bridge.name).is_some()
+            || self.schema.get_calculated_measure(&bridge.name).is_some()
+            || self.get_bridge(&bridge.name).is_some()
+        {
+            return Err(Error::dimension(format!(
+                "Bridge dimension '{}' clashes with an existing column",
+                bridge.name
+            )));
+        }
+        self.bridges.push(bridge);
+        self.mark_changed();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{Field, Schema as ArrowSchema};
+
+    #[test]
+    fn test_bridge_projects_key_and_member() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("diagnosis", DataType::Utf8, false),
+            Field::new("recorded_by", DataType::Utf8, false),
+            Field::new("patient_id", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![
+                Arc::new(StringArray::from(vec!["flu", "asthma"])),
+                Arc::new(StringArray::from(vec!["dr_a", "dr_b"])),
+                Arc::new(Int64Array::from(vec![1, 1])),
+            ],
+        )
+        .unwrap();
+
+        let bridge =
+            BridgeDimension::new("diagnosis", "patient_id", Arc::clone(&schema), vec![batch])
+                .unwrap();
+        assert_eq!(bridge.table_name(), "diagnosis_bridge");
+        assert_eq!(bridge.data_type(), &DataType::Utf8);
+        assert_eq!(bridge.row_count(), 2);
+        assert_eq!(bridge.schema.fields().len(), 2);
+        assert_eq!(bridge.schema.field(0).name(), "patient_id");
+
+        assert!(BridgeDimension::new("diagnosis", "visit_id", schema, vec![]).is_err());
+    }
+}
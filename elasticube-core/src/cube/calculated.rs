@@ -5,6 +5,7 @@
 use arrow::datatypes::DataType;
 use serde::{Deserialize, Serialize};
 
+use super::format::DisplayFormat;
 use super::measure::AggFunc;
 use crate::error::{Error, Result};
 
@@ -33,6 +34,20 @@ use crate::error::{Error, Result};
 ///     AggFunc::Avg
 /// )?;
 /// ```
+///
+/// Row-level expressions are evaluated per row and aggregated afterwards,
+/// which is wrong for ratios: averaging `profit / revenue` per row weights
+/// every row equally. Use [`aggregate_expression`](Self::aggregate_expression)
+/// to compute from aggregates instead:
+///
+/// ```rust,ignore
+/// // margin = SUM(profit) / SUM(revenue), per group
+/// let margin = CalculatedMeasure::aggregate_expression(
+///     "margin",
+///     "SUM(profit) / SUM(revenue)",
+///     DataType::Float64
+/// )?;
+/// ```
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CalculatedMeasure {
     /// Name of the calculated measure
@@ -48,6 +63,10 @@ pub struct CalculatedMeasure {
     /// Default aggregation function
     default_agg: AggFunc,
 
+    /// Whether the expression aggregates by itself and is evaluated per group
+    #[serde(default)]
+    aggregate: bool,
+
     /// Whether the result can be null
     nullable: bool,
 
@@ -56,6 +75,14 @@ pub struct CalculatedMeasure {
 
     /// Format string for display
     format: Option<String>,
+
+    /// Unit shown after values (e.g., "kg", "USD")
+    #[serde(default)]
+    unit: Option<String>,
+
+    /// Number of decimals shown, overriding the format string
+    #[serde(default)]
+    precision: Option<u8>,
 }
 
 impl CalculatedMeasure {
@@ -99,9 +126,51 @@ impl CalculatedMeasure {
             expression,
             data_type,
             default_agg,
+            aggregate: false,
+            nullable: true,
+            description: None,
+            format: None,
+            unit: None,
+            precision: None,
+        })
+    }
+
+    /// Create a calculated measure computed from aggregates
+    ///
+    /// The expression is made of aggregate functions over measures, such as
+    /// `SUM(profit) / SUM(revenue)`, and is evaluated once per group after
+    /// aggregation. Select it next to the GROUP BY columns as it is; it must
+    /// not be wrapped in another aggregate, and filtering on it needs a
+    /// `HAVING` clause in raw SQL rather than [`filter`].
+    ///
+    /// [`filter`]: crate::QueryBuilder::filter
+    pub fn aggregate_expression(
+        name: impl Into<String>,
+        expression: impl Into<String>,
+        data_type: DataType,
+    ) -> Result<Self> {
+        let name = name.into();
+        let expression = expression.into();
+
+        if name.is_empty() {
+            return Err(Error::Schema("Calculated measure name cannot be empty".into()));
+        }
+        if expression.is_empty() {
+            return Err(Error::Schema("Expression cannot be empty".into()));
+        }
+
+        Ok(Self {
+            name,
+            expression,
+            data_type,
+            // Never applied: the expression aggregates by itself
+            default_agg: AggFunc::Sum,
+            aggregate: true,
             nullable: true,
             description: None,
             format: None,
+            unit: None,
+            precision: None,
         })
     }
 
@@ -121,8 +190,22 @@ impl CalculatedMeasure {
     }
 
     /// Get the default aggregation function
+    ///
+    /// Meaningless for [aggregate expressions](Self::is_aggregate), which
+    /// are never aggregated again.
     pub fn default_agg(&self) -> AggFunc {
-        self.default_agg
+        self.default_agg.clone()
+    }
+
+    /// Check if the expression is evaluated after aggregation
+    pub fn is_aggregate(&self) -> bool {
+        self.aggregate
+    }
+
+    /// Mark an expression that already aggregates, keeping its aggregation
+    pub(crate) fn into_aggregate(mut self) -> Self {
+        self.aggregate = true;
+        self
     }
 
     /// Check if the measure is nullable
@@ -140,6 +223,43 @@ impl CalculatedMeasure {
         self.format.as_deref()
     }
 
+    /// Get the unit shown after values
+    pub fn unit(&self) -> Option<&str> {
+        self.unit.as_deref()
+    }
+
+    /// Get the number of decimals shown
+    pub fn precision(&self) -> Option<u8> {
+        self.precision
+    }
+
+    /// Get the display format, if any part of it is set
+    pub fn display_format(&self) -> Option<DisplayFormat> {
+        let format = DisplayFormat {
+            pattern: self.format.clone(),
+            unit: self.unit.clone(),
+            precision: self.precision,
+        };
+        (!format.is_empty()).then_some(format)
+    }
+
+    /// Set the format string, unit and precision at once
+    pub fn set_display_format(&mut self, format: DisplayFormat) {
+        self.format = format.pattern;
+        self.unit = format.unit;
+        self.precision = format.precision;
+    }
+
+    /// Set the description
+    pub fn set_description(&mut self, description: impl Into<String>) {
+        self.description = Some(description.into());
+    }
+
+    /// Replace the expression, when a column it refers to is renamed
+    pub(crate) fn set_expression(&mut self, expression: String) {
+        self.expression = expression;
+    }
+
     /// Builder-style: set nullable
     pub fn with_nullable(mut self, nullable: bool) -> Self {
         self.nullable = nullable;
@@ -157,6 +277,18 @@ impl CalculatedMeasure {
         self.format = Some(format.into());
         self
     }
+
+    /// Builder-style: set the unit
+    pub fn with_unit(mut self, unit: impl Into<String>) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+
+    /// Builder-style: set the number of decimals
+    pub fn with_precision(mut self, precision: u8) -> Self {
+        self.precision = Some(precision);
+        self
+    }
 }
 
 /// A virtual dimension computed from an expression
@@ -269,6 +401,16 @@ impl VirtualDimension {
         self.description.as_deref()
     }
 
+    /// Set the description
+    pub fn set_description(&mut self, description: impl Into<String>) {
+        self.description = Some(description.into());
+    }
+
+    /// Replace the expression, when a column it refers to is renamed
+    pub(crate) fn set_expression(&mut self, expression: String) {
+        self.expression = expression;
+    }
+
     /// Builder-style: set nullable
     pub fn with_nullable(mut self, nullable: bool) -> Self {
         self.nullable = nullable;
@@ -324,6 +466,31 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_aggregate_expression() {
+        let margin = CalculatedMeasure::aggregate_expression(
+            "margin",
+            "SUM(profit) / SUM(revenue)",
+            DataType::Float64,
+        )
+        .unwrap();
+        assert!(margin.is_aggregate());
+        assert_eq!(margin.expression(), "SUM(profit) / SUM(revenue)");
+
+        let profit =
+            CalculatedMeasure::new("profit", "revenue - cost", DataType::Float64, AggFunc::Sum)
+                .unwrap();
+        assert!(!profit.is_aggregate());
+
+        // Schemas saved before the flag existed load as row-level measures
+        let mut json = serde_json::to_value(&profit).unwrap();
+        json.as_object_mut().unwrap().remove("aggregate");
+        let loaded: CalculatedMeasure = serde_json::from_value(json).unwrap();
+        assert!(!loaded.is_aggregate());
+
+        assert!(CalculatedMeasure::aggregate_expression("m", "", DataType::Float64).is_err());
+    }
+
     #[test]
     fn test_calculated_measure_builder() {
         let measure = CalculatedMeasure::new(
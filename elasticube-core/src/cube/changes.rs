@@ -0,0 +1,185 @@
+//! Change-data-capture event application
+//!
+//! Lets a cube mirror an OLTP table by replaying the insert, update and
+//! delete records a CDC pipeline emits. Events are matched to existing rows
+//! through the cube's primary key, so [`ElastiCube::apply_changes`] needs
+//! one declared (see `ElastiCubeBuilder::with_primary_key`).
+
+use super::ElastiCube;
+use crate::error::{Error, Result};
+use arrow::datatypes::Schema as ArrowSchema;
+use indexmap::IndexMap;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+/// A JSON object holding one row's column values
+pub type Row = Map<String, Value>;
+
+/// One change record from a source table
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeEvent {
+    /// A new row (replaces any row with the same key)
+    Insert(Row),
+    /// The new state of a row (inserted if the key is unknown)
+    Update(Row),
+    /// A removed row; only the key columns are needed
+    Delete(Row),
+}
+
+impl ChangeEvent {
+    /// Parse a Debezium change event
+    ///
+    /// Accepts the message value with or without the `schema`/`payload`
+    /// envelope. Create (`c`) and snapshot read (`r`) events become inserts,
+    /// `u` updates and `d` deletes. Tombstones (`null`) and events without an
+    /// operation, such as heartbeats, yield `None`.
+    ///
+    /// Column values are taken as they appear in the JSON, so types that
+    /// Debezium encodes specially (e.g., `Decimal` as base64) must be mapped
+    /// to plain JSON values by the connector configuration.
+    pub fn from_debezium(value: &Value) -> Result<Option<Self>> {
+        let payload = value.get("payload").unwrap_or(value);
+        if payload.is_null() {
+            return Ok(None);
+        }
+        let Some(op) = payload.get("op").and_then(Value::as_str) else {
+            return Ok(None);
+        };
+
+        let row = |field: &str| match payload.get(field) {
+            Some(Value::Object(row)) => Ok(row.clone()),
+            _ => Err(Error::data(format!(
+                "Debezium '{}' event has no '{}' row",
+                op, field
+            ))),
+        };
+
+        let event = match op {
+            "c" | "r" => ChangeEvent::Insert(row("after")?),
+            "u" => ChangeEvent::Update(row("after")?),
+            "d" => ChangeEvent::Delete(row("before")?),
+            other => {
+                return Err(Error::data(format!(
+                    "Unsupported Debezium operation '{}'",
+                    other
+                )))
+            }
+        };
+        Ok(Some(event))
+    }
+
+    fn row(&self) -> &Row {
+        match self {
+            ChangeEvent::Insert(row) | ChangeEvent::Update(row) | ChangeEvent::Delete(row) => row,
+        }
+    }
+}
+
+/// Counts of rows affected by [`ElastiCube::apply_changes`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChangeSummary {
+    /// Rows added under a new key
+    pub inserted: usize,
+    /// Existing rows replaced with a new state
+    pub updated: usize,
+    /// Existing rows removed
+    pub deleted: usize,
+}
+
+impl ElastiCube {
+    /// Apply a batch of change events as keyed upserts and deletes
+    ///
+    /// Events are applied in order, so later events for a key win. The whole
+    /// batch is applied at once: if any event is invalid the cube is left
+    /// unchanged. Deletes of unknown keys are ignored. With versioning
+    /// enabled, the batch is recorded as one "changes" version.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let events = messages
+    ///     .iter()
+    ///     .filter_map(|m| ChangeEvent::from_debezium(m).transpose())
+    ///     .collect::<Result<Vec<_>>>()?;
+    /// let summary = cube.apply_changes(events)?;
+    /// println!("{} updated, {} deleted", summary.updated, summary.deleted);
+    /// ```
+    pub fn apply_changes(
+        &mut self,
+        events: impl IntoIterator<Item = ChangeEvent>,
+    ) -> Result<ChangeSummary> {
+        self.ensure_in_memory("apply changes to")?;
+        self.thaw()?;
+        let key = self.schema.primary_key().cloned().ok_or_else(|| {
+            Error::data("Applying changes requires a primary key on the cube")
+        })?;
+        key.validate(&self.arrow_schema)?;
+
+        // Collapse the events to the final state of each key
+        let mut latest: IndexMap<String, Option<Row>> = IndexMap::new();
+        let mut deleted_keys: IndexMap<String, Row> = IndexMap::new();
+        for event in events {
+            let row = event.row();
+            let mut key_values = Row::new();
+            for column in key.columns() {
+                let value = row.get(column).filter(|v| !v.is_null()).ok_or_else(|| {
+                    Error::data(format!("Change event is missing key column '{}'", column))
+                })?;
+                key_values.insert(column.clone(), value.clone());
+            }
+            let id = Value::Object(key_values.clone()).to_string();
+
+            match event {
+                ChangeEvent::Insert(row) | ChangeEvent::Update(row) => {
+                    deleted_keys.shift_remove(&id);
+                    latest.insert(id, Some(row));
+                }
+                ChangeEvent::Delete(_) => {
+                    deleted_keys.insert(id.clone(), key_values);
+                    latest.insert(id, None);
+                }
+            }
+        }
+        if latest.is_empty() {
+            return Ok(ChangeSummary::default());
+        }
+
+        let upsert_rows: Vec<Value> = latest.into_values().flatten().map(Value::Object).collect();
+        let delete_rows: Vec<Value> = deleted_keys.into_values().map(Value::Object).collect();
+
+        let upserts = rows_to_batches(&upsert_rows, self.arrow_schema.clone())?;
+        let key_indices = key
+            .columns()
+            .iter()
+            .map(|c| self.arrow_schema.index_of(c))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let key_schema = Arc::new(self.arrow_schema.project(&key_indices)?);
+        let deletes = rows_to_batches(&delete_rows, key_schema)?;
+
+        let (data, updated, deleted) =
+            key.upsert(&self.arrow_schema, &self.data, upserts, &deletes)?;
+        self.row_count = data.iter().map(|b| b.num_rows()).sum();
+        self.data = data;
+        self.sorted = false;
+        self.mark_changed();
+        self.repartition()?;
+        self.record_version("changes");
+
+        Ok(ChangeSummary {
+            inserted: upsert_rows.len() - updated,
+            updated,
+            deleted,
+        })
+    }
+}
+
+/// Decode JSON rows into batches with the given schema
+fn rows_to_batches(
+    rows: &[Value],
+    schema: Arc<ArrowSchema>,
+) -> Result<Vec<arrow::record_batch::RecordBatch>> {
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+    let (_, batches) = crate::sources::json_values_to_batches(rows, Some(schema), rows.len())?;
+    Ok(batches)
+}
@@ -0,0 +1,145 @@
+//! In-memory compression of cold batches
+//!
+//! Batches that queries haven't touched for a while can be moved into a
+//! [`ColdStore`], which keeps them as compressed Arrow IPC buffers. Queries
+//! still see them: the store keeps their zone maps, so a filtered query only
+//! decompresses the cold batches it can't rule out.
+
+use crate::error::Result;
+use crate::optimization::{CubeStatistics, ZoneMap};
+use crate::predicate::Predicate;
+use arrow::datatypes::SchemaRef;
+use arrow::ipc::CompressionType;
+use arrow::record_batch::RecordBatch;
+use std::hash::Hasher;
+use std::sync::Arc;
+
+/// Codec used to compress cold batches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BatchCompression {
+    /// LZ4 frame compression: fast, moderate ratio
+    #[default]
+    Lz4,
+    /// Zstandard: slower, better ratio
+    Zstd,
+}
+
+impl BatchCompression {
+    pub(crate) fn ipc_type(self) -> CompressionType {
+        match self {
+            BatchCompression::Lz4 => CompressionType::LZ4_FRAME,
+            BatchCompression::Zstd => CompressionType::ZSTD,
+        }
+    }
+}
+
+/// One compressed batch
+#[derive(Debug, Clone)]
+struct ColdBatch {
+    /// The batch as a compressed IPC stream
+    bytes: Arc<[u8]>,
+    rows: usize,
+}
+
+/// Compressed batches of a cube, with their zone maps
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ColdStore {
+    batches: Vec<ColdBatch>,
+
+    /// Statistics of the batches before compression, in batch order
+    statistics: Option<CubeStatistics>,
+}
+
+impl ColdStore {
+    /// Compress `batches` and add them to the store
+    pub(crate) fn compress(
+        &mut self,
+        batches: Vec<RecordBatch>,
+        compression: BatchCompression,
+    ) -> Result<()> {
+        if batches.is_empty() {
+            return Ok(());
+        }
+        let statistics = CubeStatistics::from_batches(&batches);
+        for batch in &batches {
+            self.batches.push(ColdBatch {
+                bytes: crate::storage::compress_batch(batch, compression)?.into(),
+                rows: batch.num_rows(),
+            });
+        }
+        match &mut self.statistics {
+            Some(existing) => existing.extend(statistics),
+            None => self.statistics = Some(statistics),
+        }
+        Ok(())
+    }
+
+    /// Decompress the batches a query with the given WHERE filter needs
+    pub(crate) fn scan(&self, filter: Option<&str>, schema: &SchemaRef) -> Result<Vec<RecordBatch>> {
+        let mut keep = vec![true; self.batches.len()];
+
+        if let (Some(filter), Some(statistics)) = (filter, &self.statistics) {
+            let columns: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+            if let Some(predicate) = Predicate::compile_restricted(filter, schema, &columns) {
+                if let Some(zones) = statistics.prune(predicate.physical_expr(), schema.clone()) {
+                    keep = zones;
+                }
+            }
+        }
+
+        self.batches
+            .iter()
+            .zip(keep)
+            .filter(|(_, keep)| *keep)
+            .map(|(batch, _)| crate::storage::decompress_batch(&batch.bytes))
+            .collect()
+    }
+
+    /// Decompress every batch, leaving the store empty
+    pub(crate) fn thaw(&mut self) -> Result<Vec<RecordBatch>> {
+        let batches = self
+            .batches
+            .iter()
+            .map(|batch| crate::storage::decompress_batch(&batch.bytes))
+            .collect::<Result<Vec<_>>>()?;
+        self.batches.clear();
+        self.statistics = None;
+        Ok(batches)
+    }
+
+    /// Feed the compressed batches to `hasher`
+    pub(crate) fn hash(&self, hasher: &mut crate::cache::StableHasher) {
+        for batch in &self.batches {
+            hasher.write(&batch.bytes);
+        }
+    }
+
+    /// Number of compressed batches
+    pub(crate) fn len(&self) -> usize {
+        self.batches.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    /// Rows held in compressed batches
+    pub(crate) fn row_count(&self) -> usize {
+        self.batches.iter().map(|batch| batch.rows).sum()
+    }
+
+    /// Size of the compressed buffers
+    pub(crate) fn compressed_bytes(&self) -> usize {
+        self.batches.iter().map(|batch| batch.bytes.len()).sum()
+    }
+
+    /// Zone maps of `column` in batch order, if the store has statistics
+    pub(crate) fn zone_maps(&self, column: &str) -> Option<&[ZoneMap]> {
+        self.statistics
+            .as_ref()?
+            .column_stats
+            .iter()
+            .find(|stats| stats.column_name == column)
+            .map(|stats| stats.zone_maps.as_slice())
+    }
+}
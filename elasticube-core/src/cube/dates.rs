@@ -0,0 +1,152 @@
+//! Calendar attributes derived from a date column
+
+use arrow::datatypes::DataType;
+
+use super::calculated::VirtualDimension;
+use crate::error::Result;
+use crate::query::quote_ident;
+
+/// Which calendar attributes [`add_date_dimension`] derives
+///
+/// [`add_date_dimension`]: crate::ElastiCubeBuilder::add_date_dimension
+///
+/// # Example
+/// ```rust,ignore
+/// // Only year and month
+/// let parts = DateParts::none().with_year().with_month();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DateParts {
+    /// Calendar year (`<column>_year`)
+    pub year: bool,
+
+    /// Quarter of the year, 1-4 (`<column>_quarter`)
+    pub quarter: bool,
+
+    /// Month of the year, 1-12 (`<column>_month`)
+    pub month: bool,
+
+    /// ISO week of the year, 1-53 (`<column>_week`)
+    pub week: bool,
+
+    /// Day of the week, 0 = Sunday to 6 = Saturday (`<column>_day_of_week`)
+    pub day_of_week: bool,
+
+    /// Whether the day is a Saturday or Sunday (`<column>_is_weekend`)
+    pub is_weekend: bool,
+}
+
+impl DateParts {
+    /// Every attribute
+    pub fn all() -> Self {
+        Self {
+            year: true,
+            quarter: true,
+            month: true,
+            week: true,
+            day_of_week: true,
+            is_weekend: true,
+        }
+    }
+
+    /// No attributes, to pick from with the `with_*` methods
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Builder-style: include the year
+    pub fn with_year(mut self) -> Self {
+        self.year = true;
+        self
+    }
+
+    /// Builder-style: include the quarter
+    pub fn with_quarter(mut self) -> Self {
+        self.quarter = true;
+        self
+    }
+
+    /// Builder-style: include the month
+    pub fn with_month(mut self) -> Self {
+        self.month = true;
+        self
+    }
+
+    /// Builder-style: include the ISO week
+    pub fn with_week(mut self) -> Self {
+        self.week = true;
+        self
+    }
+
+    /// Builder-style: include the day of the week
+    pub fn with_day_of_week(mut self) -> Self {
+        self.day_of_week = true;
+        self
+    }
+
+    /// Builder-style: include the weekend flag
+    pub fn with_is_weekend(mut self) -> Self {
+        self.is_weekend = true;
+        self
+    }
+
+    /// Virtual dimensions computing the selected attributes of `column`
+    pub(crate) fn virtual_dimensions(&self, column: &str) -> Result<Vec<VirtualDimension>> {
+        let source = quote_ident(column);
+        let extract = |field: &str| format!("EXTRACT({} FROM {})", field, source);
+        let parts = [
+            (self.year, "year", extract("YEAR"), DataType::Int32),
+            (self.quarter, "quarter", extract("QUARTER"), DataType::Int32),
+            (self.month, "month", extract("MONTH"), DataType::Int32),
+            (self.week, "week", extract("WEEK"), DataType::Int32),
+            (self.day_of_week, "day_of_week", extract("DOW"), DataType::Int32),
+            (
+                self.is_weekend,
+                "is_weekend",
+                format!("{} IN (0, 6)", extract("DOW")),
+                DataType::Boolean,
+            ),
+        ];
+
+        parts
+            .into_iter()
+            .filter(|(selected, ..)| *selected)
+            .map(|(_, suffix, expression, data_type)| {
+                VirtualDimension::new(format!("{}_{}", column, suffix), expression, data_type)
+            })
+            .collect()
+    }
+
+    /// The selected attributes that nest, coarsest first
+    pub(crate) fn hierarchy_levels(&self, column: &str) -> Vec<String> {
+        [(self.year, "year"), (self.quarter, "quarter"), (self.month, "month")]
+            .into_iter()
+            .filter(|(selected, _)| *selected)
+            .map(|(_, suffix)| format!("{}_{}", column, suffix))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selected_parts() {
+        let parts = DateParts::none().with_year().with_month().with_is_weekend();
+        let names: Vec<String> = parts
+            .virtual_dimensions("order_date")
+            .unwrap()
+            .iter()
+            .map(|v| v.name().to_string())
+            .collect();
+        assert_eq!(names, vec!["order_date_year", "order_date_month", "order_date_is_weekend"]);
+        assert_eq!(
+            parts.hierarchy_levels("order_date"),
+            vec!["order_date_year", "order_date_month"]
+        );
+
+        assert_eq!(DateParts::all().virtual_dimensions("d").unwrap().len(), 6);
+        assert!(DateParts::none().virtual_dimensions("d").unwrap().is_empty());
+    }
+}
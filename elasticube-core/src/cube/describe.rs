@@ -0,0 +1,295 @@
+//! Structured metadata for catalogs and documentation
+//!
+//! [`ElastiCube::describe`] gathers everything the semantic layer knows
+//! about a cube (columns, types, aggregations, expressions, descriptions,
+//! tags and drill paths) into one serializable document, so catalog and
+//! documentation tools don't have to walk the schema themselves.
+
+use super::{DisplayFormat, ElastiCube};
+use crate::error::{Error, Result};
+use serde::Serialize;
+
+/// Metadata document for a cube
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CubeDescription {
+    /// Cube name
+    pub name: String,
+
+    /// Cube description, if set
+    pub description: Option<String>,
+
+    /// Number of rows
+    pub row_count: usize,
+
+    /// Columns that uniquely identify a row, if declared
+    pub primary_key: Option<Vec<String>>,
+
+    /// Time zone of the cube's timestamps, if set
+    pub timezone: Option<String>,
+
+    /// Every queryable column, in declaration order within each kind
+    pub columns: Vec<ColumnDescription>,
+
+    /// Drill paths over the dimensions
+    pub hierarchies: Vec<HierarchyDescription>,
+}
+
+/// What a described column is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnKind {
+    /// A stored dimension
+    Dimension,
+    /// A dimension computed from an expression
+    VirtualDimension,
+    /// A many-to-many dimension joined through a bridge table
+    BridgeDimension,
+    /// A stored measure
+    Measure,
+    /// A measure computed from an expression
+    CalculatedMeasure,
+}
+
+/// Metadata of one column
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColumnDescription {
+    /// Column name
+    pub name: String,
+
+    /// What kind of column it is
+    pub kind: ColumnKind,
+
+    /// Arrow data type, e.g. `Float64`
+    pub data_type: String,
+
+    /// Default aggregation of a measure (`aggregate` for calculated
+    /// measures evaluated after grouping)
+    pub aggregation: Option<String>,
+
+    /// SQL expression of a computed column, or the key of a bridge dimension
+    pub expression: Option<String>,
+
+    /// User-provided description
+    pub description: Option<String>,
+
+    /// Tags of a dimension or measure
+    pub tags: Vec<String>,
+
+    /// Display format of a measure
+    pub format: Option<DisplayFormat>,
+}
+
+/// Metadata of one hierarchy
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HierarchyDescription {
+    /// Hierarchy name
+    pub name: String,
+
+    /// Levels from coarsest to finest
+    pub levels: Vec<String>,
+
+    /// User-provided description
+    pub description: Option<String>,
+}
+
+impl CubeDescription {
+    /// Look up a column by name
+    pub fn column(&self, name: &str) -> Option<&ColumnDescription> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// Serialize the document to pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| Error::schema(format!("Failed to serialize cube description: {}", e)))
+    }
+}
+
+impl ColumnDescription {
+    fn new(name: &str, kind: ColumnKind, data_type: &arrow::datatypes::DataType) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            data_type: data_type.to_string(),
+            aggregation: None,
+            expression: None,
+            description: None,
+            tags: Vec::new(),
+            format: None,
+        }
+    }
+}
+
+impl ElastiCube {
+    /// Describe the cube's columns and hierarchies for catalogs and docs
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let description = cube.describe();
+    /// for column in &description.columns {
+    ///     println!("{} ({:?}): {}", column.name, column.kind,
+    ///              column.description.as_deref().unwrap_or(""));
+    /// }
+    /// std::fs::write("sales.json", description.to_json()?)?;
+    /// ```
+    pub fn describe(&self) -> CubeDescription {
+        let schema = self.schema();
+        let mut columns = Vec::new();
+
+        for dimension in schema.dimensions() {
+            let mut column = ColumnDescription::new(
+                dimension.name(),
+                ColumnKind::Dimension,
+                dimension.data_type(),
+            );
+            column.description = dimension.description().map(String::from);
+            column.tags = dimension.tags().to_vec();
+            columns.push(column);
+        }
+        for virtual_dim in schema.virtual_dimensions() {
+            let mut column = ColumnDescription::new(
+                virtual_dim.name(),
+                ColumnKind::VirtualDimension,
+                virtual_dim.data_type(),
+            );
+            column.expression = Some(virtual_dim.expression().to_string());
+            column.description = virtual_dim.description().map(String::from);
+            columns.push(column);
+        }
+        for bridge in self.bridges() {
+            let mut column = ColumnDescription::new(
+                bridge.name(),
+                ColumnKind::BridgeDimension,
+                bridge.data_type(),
+            );
+            column.expression = Some(bridge.key().to_string());
+            columns.push(column);
+        }
+        for measure in schema.measures() {
+            let mut column =
+                ColumnDescription::new(measure.name(), ColumnKind::Measure, measure.data_type());
+            column.aggregation = Some(measure.default_agg().to_string());
+            column.description = measure.description().map(String::from);
+            column.tags = measure.tags().to_vec();
+            column.format = measure.display_format();
+            columns.push(column);
+        }
+        for measure in schema.calculated_measures() {
+            let mut column = ColumnDescription::new(
+                measure.name(),
+                ColumnKind::CalculatedMeasure,
+                measure.data_type(),
+            );
+            column.aggregation = Some(if measure.is_aggregate() {
+                "aggregate".to_string()
+            } else {
+                measure.default_agg().to_string()
+            });
+            column.expression = Some(measure.expression().to_string());
+            column.description = measure.description().map(String::from);
+            column.format = measure.display_format();
+            columns.push(column);
+        }
+
+        CubeDescription {
+            name: schema.name().to_string(),
+            description: schema.description().map(String::from),
+            row_count: self.row_count(),
+            primary_key: schema.primary_key().map(|key| key.columns().to_vec()),
+            timezone: schema.timezone().map(String::from),
+            columns,
+            hierarchies: schema
+                .hierarchies()
+                .into_iter()
+                .map(|hierarchy| HierarchyDescription {
+                    name: hierarchy.name().to_string(),
+                    levels: hierarchy.levels().to_vec(),
+                    description: hierarchy.description().map(String::from),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::ElastiCubeBuilder;
+    use crate::cube::{AggFunc, ColumnKind, DisplayFormat};
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+    use arrow::record_batch::RecordBatch;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_describe() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("revenue", DataType::Float64, false),
+            Field::new("cost", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["north", "south"])),
+                Arc::new(Float64Array::from(vec![100.0, 200.0])),
+                Arc::new(Float64Array::from(vec![60.0, 150.0])),
+            ],
+        )
+        .unwrap();
+
+        let cube = ElastiCubeBuilder::new("sales")
+            .with_description("Sales by region")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_measure("revenue", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .add_measure("cost", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .format_measure("revenue", DisplayFormat::new("$#,##0"))
+            .unwrap()
+            .add_aggregate_measure(
+                "margin",
+                "SUM(revenue - cost) / SUM(revenue)",
+                DataType::Float64,
+            )
+            .unwrap()
+            .describe_column("revenue", "Net revenue")
+            .unwrap()
+            .describe_column("margin", "Share of revenue kept")
+            .unwrap()
+            .tag_column("revenue", "finance")
+            .unwrap()
+            .tag_column("revenue", "finance")
+            .unwrap()
+            .with_data(vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let description = cube.describe();
+        assert_eq!(description.name, "sales");
+        assert_eq!(description.description.as_deref(), Some("Sales by region"));
+        assert_eq!(description.row_count, 2);
+        assert_eq!(description.columns.len(), 4);
+
+        let revenue = description.column("revenue").unwrap();
+        assert_eq!(revenue.kind, ColumnKind::Measure);
+        assert_eq!(revenue.data_type, "Float64");
+        assert_eq!(revenue.aggregation.as_deref(), Some("SUM"));
+        assert_eq!(revenue.description.as_deref(), Some("Net revenue"));
+        assert_eq!(revenue.tags, vec!["finance"]);
+        assert_eq!(revenue.format, Some(DisplayFormat::new("$#,##0")));
+
+        let margin = description.column("margin").unwrap();
+        assert_eq!(margin.kind, ColumnKind::CalculatedMeasure);
+        assert_eq!(margin.aggregation.as_deref(), Some("aggregate"));
+        assert!(margin.expression.as_deref().unwrap().starts_with("SUM("));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&description.to_json().unwrap()).unwrap();
+        assert_eq!(json["columns"][0]["kind"], "dimension");
+
+        // Unknown columns can't be tagged
+        assert!(ElastiCubeBuilder::new("sales").tag_column("missing", "pii").is_err());
+    }
+}
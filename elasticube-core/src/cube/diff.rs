@@ -0,0 +1,240 @@
+//! Comparing two cubes row by row
+//!
+//! [`ElastiCube::diff`] matches the rows of two cubes with the same columns
+//! on key columns and sorts out the rows only one of them has and the rows
+//! whose values changed, e.g. to check a new pipeline run against the
+//! previous one before publishing it.
+
+use super::updates::concat_record_batches;
+use super::ElastiCube;
+use crate::error::{Error, Result};
+use crate::ingest::Coercion;
+use arrow::array::{ArrayRef, UInt32Array};
+use arrow::compute::take_record_batch;
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use arrow::row::{RowConverter, Rows, SortField};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Rows that differ between two cubes
+///
+/// All batches have the columns of the cube `diff` was called on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeDiff {
+    /// Columns the rows were matched on
+    pub keys: Vec<String>,
+
+    /// Rows of the other cube with a key this cube doesn't have
+    pub added: RecordBatch,
+
+    /// Rows of this cube with a key the other cube doesn't have
+    pub removed: RecordBatch,
+
+    /// Rows of the other cube whose values differ from this cube's row with
+    /// the same key
+    pub changed: RecordBatch,
+
+    /// This cube's rows for the keys in `changed`, in the same order
+    pub changed_before: RecordBatch,
+}
+
+impl CubeDiff {
+    /// Whether the cubes hold the same rows
+    pub fn is_empty(&self) -> bool {
+        self.added.num_rows() == 0 && self.removed.num_rows() == 0 && self.changed.num_rows() == 0
+    }
+}
+
+impl fmt::Display for CubeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} removed, {} changed",
+            self.added.num_rows(),
+            self.removed.num_rows(),
+            self.changed.num_rows()
+        )
+    }
+}
+
+impl ElastiCube {
+    /// Compare this cube's rows with `other`'s, matching them on `keys`
+    ///
+    /// Both cubes must have the same columns and each key must identify a
+    /// single row in either cube. Rows are changed when any column outside
+    /// the key differs; NULLs compare equal to each other.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let diff = previous_run.diff(&new_run, &["transaction_id"])?;
+    /// println!("{}", diff); // 12 added, 0 removed, 3 changed
+    /// arrow::util::pretty::print_batches(&[diff.changed_before, diff.changed])?;
+    /// ```
+    pub fn diff(&self, other: &ElastiCube, keys: &[impl AsRef<str>]) -> Result<CubeDiff> {
+        self.ensure_in_memory("diff")?;
+        let keys: Vec<String> = keys.iter().map(|key| key.as_ref().to_string()).collect();
+        if keys.is_empty() {
+            return Err(Error::schema("Diff needs at least one key column"));
+        }
+        let key_indices = keys
+            .iter()
+            .map(|key| {
+                self.arrow_schema.index_of(key).map_err(|_| {
+                    Error::schema(format!(
+                        "Key column '{}' not found in cube '{}'",
+                        key,
+                        self.schema.name()
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let value_indices: Vec<usize> = (0..self.arrow_schema.fields().len())
+            .filter(|index| !key_indices.contains(index))
+            .collect();
+
+        let before = single_batch(&self.arrow_schema, self.materialized_batches()?)?;
+        let conformed = self.conform(other, Coercion::Strict, false)?;
+        let after = single_batch(&self.arrow_schema, conformed)?;
+
+        // Both cubes are encoded by the same converters so their rows compare
+        let key_converter = converter(&self.arrow_schema, &key_indices)?;
+        let before_keys = key_converter.convert_columns(&columns(&before, &key_indices))?;
+        let after_keys = key_converter.convert_columns(&columns(&after, &key_indices))?;
+        let before_positions = positions(&before_keys, self.schema.name(), &keys)?;
+        let after_positions = positions(&after_keys, other.schema.name(), &keys)?;
+
+        let values = if value_indices.is_empty() {
+            None
+        } else {
+            let value_converter = converter(&self.arrow_schema, &value_indices)?;
+            Some((
+                value_converter.convert_columns(&columns(&before, &value_indices))?,
+                value_converter.convert_columns(&columns(&after, &value_indices))?,
+            ))
+        };
+
+        let removed: Vec<u32> = (0..before.num_rows())
+            .filter(|&row| !after_positions.contains_key(before_keys.row(row).as_ref()))
+            .map(|row| row as u32)
+            .collect();
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        let mut changed_before = Vec::new();
+        for row in 0..after.num_rows() {
+            match before_positions.get(after_keys.row(row).as_ref()) {
+                None => added.push(row as u32),
+                Some(&previous) => {
+                    let differs = values
+                        .as_ref()
+                        .is_some_and(|(old, new)| old.row(previous) != new.row(row));
+                    if differs {
+                        changed.push(row as u32);
+                        changed_before.push(previous as u32);
+                    }
+                }
+            }
+        }
+
+        let take = |batch: &RecordBatch, rows: Vec<u32>| {
+            Ok::<_, Error>(take_record_batch(batch, &UInt32Array::from(rows))?)
+        };
+        Ok(CubeDiff {
+            keys,
+            added: take(&after, added)?,
+            removed: take(&before, removed)?,
+            changed: take(&after, changed)?,
+            changed_before: take(&before, changed_before)?,
+        })
+    }
+}
+
+/// All of `batches` as one batch, empty if there are none
+fn single_batch(schema: &SchemaRef, batches: Vec<RecordBatch>) -> Result<RecordBatch> {
+    if batches.is_empty() {
+        return Ok(RecordBatch::new_empty(Arc::clone(schema)));
+    }
+    concat_record_batches(schema, &batches)
+}
+
+/// A converter encoding the columns at `indices` into comparable rows
+fn converter(schema: &SchemaRef, indices: &[usize]) -> Result<RowConverter> {
+    let fields = indices
+        .iter()
+        .map(|&i| SortField::new(schema.field(i).data_type().clone()))
+        .collect();
+    Ok(RowConverter::new(fields)?)
+}
+
+fn columns(batch: &RecordBatch, indices: &[usize]) -> Vec<ArrayRef> {
+    indices.iter().map(|&i| Arc::clone(batch.column(i))).collect()
+}
+
+/// Row position of every key, failing if a key is not unique
+fn positions(keys: &Rows, cube: &str, columns: &[String]) -> Result<HashMap<Vec<u8>, usize>> {
+    let mut positions = HashMap::with_capacity(keys.num_rows());
+    for row in 0..keys.num_rows() {
+        if positions.insert(keys.row(row).as_ref().to_vec(), row).is_some() {
+            return Err(Error::data(format!(
+                "Diff key ({}) is not unique in cube '{}'",
+                columns.join(", "),
+                cube
+            )));
+        }
+    }
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ElastiCubeBuilder;
+    use arrow::array::{AsArray, Float64Array, Int64Array};
+    use arrow::datatypes::{DataType, Field, Float64Type, Int64Type, Schema as ArrowSchema};
+
+    fn run(ids: Vec<i64>, amounts: Vec<f64>) -> ElastiCube {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("transaction_id", DataType::Int64, false),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![
+                Arc::new(Int64Array::from(ids)),
+                Arc::new(Float64Array::from(amounts)),
+            ],
+        )
+        .unwrap();
+        ElastiCubeBuilder::new("run")
+            .load_record_batches(schema, vec![batch])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = run(vec![1, 2, 3], vec![10.0, 20.0, 30.0]);
+        let current = run(vec![4, 3, 2], vec![40.0, 35.0, 20.0]);
+
+        let diff = previous.diff(&current, &["transaction_id"]).unwrap();
+        let ids = |batch: &RecordBatch| {
+            batch.column(0).as_primitive::<Int64Type>().values().to_vec()
+        };
+        assert_eq!(ids(&diff.added), vec![4]);
+        assert_eq!(ids(&diff.removed), vec![1]);
+        assert_eq!(ids(&diff.changed), vec![3]);
+        assert_eq!(ids(&diff.changed_before), vec![3]);
+        assert_eq!(diff.changed.column(1).as_primitive::<Float64Type>().value(0), 35.0);
+        assert_eq!(diff.changed_before.column(1).as_primitive::<Float64Type>().value(0), 30.0);
+        assert_eq!(diff.to_string(), "1 added, 1 removed, 1 changed");
+
+        assert!(previous.diff(&previous, &["transaction_id"]).unwrap().is_empty());
+        assert!(previous.diff(&current, &["order_id"]).is_err());
+
+        let duplicated = run(vec![1, 1], vec![10.0, 11.0]);
+        let err = previous.diff(&duplicated, &["transaction_id"]).unwrap_err();
+        assert!(err.to_string().contains("not unique in cube 'run'"));
+    }
+}
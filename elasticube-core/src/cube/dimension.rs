@@ -24,6 +24,22 @@ pub struct Dimension {
 
     /// User-provided description
     description: Option<String>,
+
+    /// Column identifying members when the dimension itself is a name
+    #[serde(default)]
+    key_column: Option<String>,
+
+    /// Column holding the members' display labels
+    #[serde(default)]
+    label_column: Option<String>,
+
+    /// Column ordering the members (e.g., month_number for month_name)
+    #[serde(default)]
+    sort_column: Option<String>,
+
+    /// Free-form labels for catalogs (e.g., "pii", "finance")
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
 impl Dimension {
@@ -35,6 +51,10 @@ impl Dimension {
             cardinality: None,
             nullable: true,
             description: None,
+            key_column: None,
+            label_column: None,
+            sort_column: None,
+            tags: Vec::new(),
         }
     }
 
@@ -52,6 +72,10 @@ impl Dimension {
             cardinality,
             nullable,
             description,
+            key_column: None,
+            label_column: None,
+            sort_column: None,
+            tags: Vec::new(),
         }
     }
 
@@ -80,11 +104,66 @@ impl Dimension {
         self.description.as_deref()
     }
 
+    /// Get the key column
+    pub fn key_column(&self) -> Option<&str> {
+        self.key_column.as_deref()
+    }
+
+    /// Get the label column
+    pub fn label_column(&self) -> Option<&str> {
+        self.label_column.as_deref()
+    }
+
+    /// Get the sort column
+    pub fn sort_column(&self) -> Option<&str> {
+        self.sort_column.as_deref()
+    }
+
+    /// Get the tags
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Add a tag, unless the dimension already has it
+    pub fn add_tag(&mut self, tag: impl Into<String>) {
+        let tag = tag.into();
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+    }
+
+    /// The key, label and sort columns that are set
+    pub fn attribute_columns(&self) -> impl Iterator<Item = &str> {
+        [&self.key_column, &self.label_column, &self.sort_column]
+            .into_iter()
+            .filter_map(|column| column.as_deref())
+    }
+
     /// Set the cardinality
     pub fn set_cardinality(&mut self, cardinality: usize) {
         self.cardinality = Some(cardinality);
     }
 
+    /// Change the data type, e.g. when the cube's timestamps are localized
+    pub(crate) fn set_data_type(&mut self, data_type: DataType) {
+        self.data_type = data_type;
+    }
+
+    /// Follow a column rename, whether of this dimension or an attribute
+    pub(crate) fn rename_column(&mut self, old: &str, new: &str) {
+        if self.name == old {
+            self.name = new.to_string();
+        }
+        for column in [&mut self.key_column, &mut self.label_column, &mut self.sort_column]
+            .into_iter()
+            .flatten()
+        {
+            if column == old {
+                *column = new.to_string();
+            }
+        }
+    }
+
     /// Set the description
     pub fn set_description(&mut self, description: impl Into<String>) {
         self.description = Some(description.into());
@@ -107,6 +186,32 @@ impl Dimension {
         self.description = Some(description.into());
         self
     }
+
+    /// Builder-style: add a tag
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.add_tag(tag);
+        self
+    }
+
+    /// Builder-style: set the key column
+    pub fn with_key_column(mut self, column: impl Into<String>) -> Self {
+        self.key_column = Some(column.into());
+        self
+    }
+
+    /// Builder-style: set the label column
+    pub fn with_label_column(mut self, column: impl Into<String>) -> Self {
+        self.label_column = Some(column.into());
+        self
+    }
+
+    /// Builder-style: set the sort column
+    ///
+    /// `order_by` on the dimension then sorts by this column instead.
+    pub fn with_sort_column(mut self, column: impl Into<String>) -> Self {
+        self.sort_column = Some(column.into());
+        self
+    }
 }
 
 #[cfg(test)]
@@ -127,11 +232,29 @@ mod tests {
         let dim = Dimension::new("country", DataType::Utf8)
             .with_cardinality(195)
             .with_nullable(false)
-            .with_description("ISO country code");
+            .with_description("ISO country code")
+            .with_tag("geo")
+            .with_tag("geo");
 
         assert_eq!(dim.name(), "country");
         assert_eq!(dim.cardinality(), Some(195));
         assert!(!dim.is_nullable());
         assert_eq!(dim.description(), Some("ISO country code"));
+        assert_eq!(dim.tags(), ["geo"]);
+    }
+
+    #[test]
+    fn test_dimension_attributes() {
+        let dim = Dimension::new("month_name", DataType::Utf8)
+            .with_key_column("month_key")
+            .with_sort_column("month_number");
+
+        assert_eq!(dim.key_column(), Some("month_key"));
+        assert_eq!(dim.label_column(), None);
+        assert_eq!(dim.sort_column(), Some("month_number"));
+        assert_eq!(
+            dim.attribute_columns().collect::<Vec<_>>(),
+            vec!["month_key", "month_number"]
+        );
     }
 }
@@ -0,0 +1,225 @@
+//! Display formatting for measure values
+//!
+//! Patterns follow the familiar spreadsheet notation: `#,##0` groups
+//! thousands, `.00` fixes two decimals (`.0#` keeps one to two), `%`
+//! multiplies by 100, and any other characters are copied around the
+//! number, so `"$#,##0.00"` renders 1234.5 as `$1,234.50`.
+
+use arrow::array::{Array, ArrayRef, Float64Array, StringArray};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::error::Result;
+
+/// How a measure's values are displayed in rendered results
+///
+/// # Example
+/// ```rust,ignore
+/// let currency = DisplayFormat::new("$#,##0.00");
+/// assert_eq!(currency.format_value(-1234.5), "-$1,234.50");
+///
+/// let weight = DisplayFormat::default().with_precision(1).with_unit("kg");
+/// assert_eq!(weight.format_value(12.345), "12.3 kg");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayFormat {
+    /// Number pattern such as `"$#,##0.00"` or `"0.0%"`
+    pub pattern: Option<String>,
+
+    /// Unit appended after the number, separated by a space
+    pub unit: Option<String>,
+
+    /// Number of decimals, overriding the pattern's
+    pub precision: Option<u8>,
+}
+
+impl DisplayFormat {
+    /// Create a format from a number pattern
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: Some(pattern.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Builder-style: set the unit
+    pub fn with_unit(mut self, unit: impl Into<String>) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+
+    /// Builder-style: set the number of decimals
+    pub fn with_precision(mut self, precision: u8) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    /// Check if the format changes anything
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_none() && self.unit.is_none() && self.precision.is_none()
+    }
+
+    /// Render one value
+    pub fn format_value(&self, value: f64) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+
+        let pattern = Pattern::parse(self.pattern.as_deref().unwrap_or(""));
+        let value = if pattern.percent { value * 100.0 } else { value };
+        let (min_decimals, max_decimals) = match self.precision {
+            Some(precision) => (precision as usize, precision as usize),
+            None => (pattern.min_decimals, pattern.max_decimals),
+        };
+
+        let digits = match (self.precision, pattern.has_number) {
+            (None, false) => value.abs().to_string(),
+            _ => format!("{:.*}", max_decimals, value.abs()),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+        let mut fraction = fraction.to_string();
+        while fraction.len() > min_decimals && fraction.ends_with('0') {
+            fraction.pop();
+        }
+        let integer = if pattern.grouping {
+            group_thousands(integer)
+        } else {
+            integer.to_string()
+        };
+
+        let mut out = String::new();
+        if value < 0.0 && digits.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            out.push('-');
+        }
+        out.push_str(pattern.prefix);
+        out.push_str(&integer);
+        if !fraction.is_empty() {
+            out.push('.');
+            out.push_str(&fraction);
+        }
+        out.push_str(pattern.suffix);
+        if let Some(unit) = &self.unit {
+            out.push(' ');
+            out.push_str(unit);
+        }
+        out
+    }
+
+    /// Render a numeric column as strings, keeping nulls
+    ///
+    /// Non-numeric columns are returned unchanged.
+    pub(crate) fn format_array(&self, array: &ArrayRef) -> Result<ArrayRef> {
+        if !array.data_type().is_numeric() {
+            return Ok(Arc::clone(array));
+        }
+        let values = cast(array, &DataType::Float64)?;
+        let values = values
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .expect("cast to Float64 yields a Float64Array");
+        let formatted: StringArray = values
+            .iter()
+            .map(|value| value.map(|v| self.format_value(v)))
+            .collect();
+        Ok(Arc::new(formatted))
+    }
+}
+
+/// A pattern split around its number placeholder
+struct Pattern<'a> {
+    prefix: &'a str,
+    suffix: &'a str,
+    has_number: bool,
+    grouping: bool,
+    percent: bool,
+    min_decimals: usize,
+    max_decimals: usize,
+}
+
+impl<'a> Pattern<'a> {
+    fn parse(pattern: &'a str) -> Self {
+        let is_placeholder = |c: char| matches!(c, '#' | '0' | ',' | '.');
+        let (prefix, number, suffix) = match pattern.find(['#', '0']) {
+            Some(start) => {
+                let end = pattern[start..]
+                    .find(|c: char| !is_placeholder(c))
+                    .map_or(pattern.len(), |len| start + len);
+                (&pattern[..start], &pattern[start..end], &pattern[end..])
+            }
+            // A lone "%" goes after the number, other text before it
+            None if pattern.starts_with('%') => ("", "", pattern),
+            None => (pattern, "", ""),
+        };
+
+        let decimals = number.split_once('.').map_or("", |(_, d)| d);
+        let min_decimals = decimals.chars().filter(|&c| c == '0').count();
+        Self {
+            prefix,
+            suffix,
+            has_number: !number.is_empty(),
+            grouping: number.contains(','),
+            percent: pattern.contains('%'),
+            min_decimals,
+            max_decimals: min_decimals + decimals.chars().filter(|&c| c == '#').count(),
+        }
+    }
+}
+
+fn group_thousands(integer: &str) -> String {
+    let mut out = String::with_capacity(integer.len() + integer.len() / 3);
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+
+    #[test]
+    fn test_patterns() {
+        let currency = DisplayFormat::new("$#,##0.00");
+        assert_eq!(currency.format_value(1234567.891), "$1,234,567.89");
+        assert_eq!(currency.format_value(-1234.5), "-$1,234.50");
+        assert_eq!(currency.format_value(0.0), "$0.00");
+
+        assert_eq!(DisplayFormat::new("0.0%").format_value(0.256), "25.6%");
+        assert_eq!(DisplayFormat::new("%").format_value(0.25), "25%");
+        assert_eq!(DisplayFormat::new("#,##0").format_value(999.6), "1,000");
+        assert_eq!(DisplayFormat::new("0.0#").format_value(1.5), "1.5");
+        assert_eq!(DisplayFormat::new("0.0#").format_value(1.2345), "1.23");
+    }
+
+    #[test]
+    fn test_precision_and_unit() {
+        let weight = DisplayFormat::default().with_precision(1).with_unit("kg");
+        assert_eq!(weight.format_value(12.345), "12.3 kg");
+
+        // Precision overrides the pattern's decimals
+        let rounded = DisplayFormat::new("$#,##0.00").with_precision(0);
+        assert_eq!(rounded.format_value(1234.4), "$1,234");
+
+        assert_eq!(DisplayFormat::default().with_unit("ms").format_value(2.5), "2.5 ms");
+        assert_eq!(DisplayFormat::new("0.00").format_value(-0.001), "0.00");
+    }
+
+    #[test]
+    fn test_format_array() {
+        let array: ArrayRef = Arc::new(Int64Array::from(vec![Some(1500), None]));
+        let formatted = DisplayFormat::new("#,##0").format_array(&array).unwrap();
+        let formatted = formatted.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(formatted.value(0), "1,500");
+        assert!(formatted.is_null(1));
+
+        let labels: ArrayRef = Arc::new(StringArray::from(vec!["a"]));
+        let unchanged = DisplayFormat::new("#,##0").format_array(&labels).unwrap();
+        assert_eq!(unchanged.data_type(), &DataType::Utf8);
+    }
+}
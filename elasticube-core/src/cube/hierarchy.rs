@@ -71,6 +71,15 @@ impl Hierarchy {
         self.description.as_deref()
     }
 
+    /// Follow a dimension rename
+    pub(crate) fn rename_level(&mut self, old: &str, new: &str) {
+        for level in &mut self.levels {
+            if level == old {
+                *level = new.to_string();
+            }
+        }
+    }
+
     /// Get the number of levels
     pub fn depth(&self) -> usize {
         self.levels.len()
@@ -0,0 +1,307 @@
+//! Primary key constraints
+//!
+//! A cube with a primary key holds at most one row per combination of key
+//! column values. The key is enforced when the cube is built and whenever
+//! rows are appended; the [`DuplicatePolicy`] decides what happens to rows
+//! whose key is already taken.
+
+use crate::error::{Error, Result};
+use arrow::array::{Array, ArrayRef, BooleanArray};
+use arrow::datatypes::Schema as ArrowSchema;
+use arrow::record_batch::RecordBatch;
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// What to do with a row whose key already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// Fail the build or append
+    #[default]
+    Reject,
+    /// Keep the existing row and drop the new one
+    Ignore,
+    /// Replace the existing row with the new one
+    Replace,
+}
+
+/// Key columns that identify a row, with the policy for duplicates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrimaryKey {
+    columns: Vec<String>,
+    #[serde(default)]
+    on_duplicate: DuplicatePolicy,
+}
+
+impl PrimaryKey {
+    /// Create a key over `columns` that rejects duplicates
+    pub fn new(columns: Vec<String>) -> Self {
+        Self {
+            columns,
+            on_duplicate: DuplicatePolicy::Reject,
+        }
+    }
+
+    /// Set how duplicate keys are handled
+    pub fn with_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.on_duplicate = policy;
+        self
+    }
+
+    /// Key column names
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// How duplicate keys are handled
+    pub fn on_duplicate(&self) -> DuplicatePolicy {
+        self.on_duplicate
+    }
+
+    /// Follow a column rename
+    pub(crate) fn rename_column(&mut self, old: &str, new: &str) {
+        for column in &mut self.columns {
+            if column == old {
+                *column = new.to_string();
+            }
+        }
+    }
+
+    /// Check that the key is non-empty and its columns exist in `schema`
+    pub(crate) fn validate(&self, schema: &ArrowSchema) -> Result<()> {
+        if self.columns.is_empty() {
+            return Err(Error::schema("Primary key needs at least one column"));
+        }
+        for column in &self.columns {
+            if schema.field_with_name(column).is_err() {
+                return Err(Error::schema(format!(
+                    "Primary key column '{}' not found in cube data",
+                    column
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove duplicate keys within `batches` according to the policy
+    ///
+    /// `Ignore` keeps the first row for each key and `Replace` the last one.
+    pub(crate) fn dedupe(
+        &self,
+        schema: &ArrowSchema,
+        batches: Vec<RecordBatch>,
+    ) -> Result<Vec<RecordBatch>> {
+        let encoder = KeyEncoder::new(self, schema)?;
+        let keys = batches
+            .iter()
+            .map(|batch| encoder.keys(batch))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut seen = HashSet::new();
+        let mut keep: Vec<Vec<bool>> = keys.iter().map(|k| vec![false; k.len()]).collect();
+
+        let mut positions: Vec<(usize, usize)> = keys
+            .iter()
+            .enumerate()
+            .flat_map(|(b, k)| (0..k.len()).map(move |r| (b, r)))
+            .collect();
+        // Walking backwards makes the last row written the one that is kept
+        if self.on_duplicate == DuplicatePolicy::Replace {
+            positions.reverse();
+        }
+
+        for (b, r) in positions {
+            if seen.insert(keys[b][r].clone()) {
+                keep[b][r] = true;
+            } else if self.on_duplicate == DuplicatePolicy::Reject {
+                return Err(self.duplicate_error(&batches[b], r));
+            }
+        }
+
+        batches
+            .iter()
+            .zip(keep)
+            .map(|(batch, mask)| filter(batch, mask))
+            .collect()
+    }
+
+    /// Merge `incoming` rows into `existing` data according to the policy
+    ///
+    /// Returns the existing batches (minus rows replaced by `Replace`) and
+    /// the incoming batches that should be appended.
+    pub(crate) fn merge(
+        &self,
+        schema: &ArrowSchema,
+        existing: &[RecordBatch],
+        incoming: Vec<RecordBatch>,
+    ) -> Result<(Vec<RecordBatch>, Vec<RecordBatch>)> {
+        let incoming = self.dedupe(schema, incoming)?;
+        let encoder = KeyEncoder::new(self, schema)?;
+
+        if self.on_duplicate == DuplicatePolicy::Replace {
+            let mut replacing = HashSet::new();
+            for batch in &incoming {
+                replacing.extend(encoder.keys(batch)?);
+            }
+            let kept = existing
+                .iter()
+                .map(|batch| {
+                    let mask = encoder
+                        .keys(batch)?
+                        .iter()
+                        .map(|key| !replacing.contains(key))
+                        .collect();
+                    filter(batch, mask)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            return Ok((kept, incoming));
+        }
+
+        let mut taken = HashSet::new();
+        for batch in existing {
+            taken.extend(encoder.keys(batch)?);
+        }
+        let appended = incoming
+            .iter()
+            .map(|batch| {
+                let keys = encoder.keys(batch)?;
+                if self.on_duplicate == DuplicatePolicy::Reject {
+                    if let Some(row) = keys.iter().position(|key| taken.contains(key)) {
+                        return Err(self.duplicate_error(batch, row));
+                    }
+                }
+                filter(batch, keys.iter().map(|key| !taken.contains(key)).collect())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok((existing.to_vec(), appended))
+    }
+
+    /// Apply keyed upserts and deletes to `existing` data
+    ///
+    /// `upserts` are full rows with unique keys; `deletes` hold only the key
+    /// columns, in key order. Existing rows whose key appears in either are
+    /// removed and the upserts appended. Returns the new data along with the
+    /// number of upserts that replaced a row and of deletes that removed one.
+    pub(crate) fn upsert(
+        &self,
+        schema: &ArrowSchema,
+        existing: &[RecordBatch],
+        upserts: Vec<RecordBatch>,
+        deletes: &[RecordBatch],
+    ) -> Result<(Vec<RecordBatch>, usize, usize)> {
+        let encoder = KeyEncoder::new(self, schema)?;
+        let mut taken = HashSet::new();
+        for batch in existing {
+            taken.extend(encoder.keys(batch)?);
+        }
+
+        let mut removing = HashSet::new();
+        let mut updated = 0;
+        for batch in &upserts {
+            for key in encoder.keys(batch)? {
+                updated += usize::from(taken.contains(&key));
+                removing.insert(key);
+            }
+        }
+        let mut deleted = 0;
+        for batch in deletes {
+            for key in encoder.encode(batch.columns())? {
+                deleted += usize::from(taken.contains(&key));
+                removing.insert(key);
+            }
+        }
+
+        let mut data = existing
+            .iter()
+            .map(|batch| {
+                let mask = encoder
+                    .keys(batch)?
+                    .iter()
+                    .map(|key| !removing.contains(key))
+                    .collect();
+                filter(batch, mask)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        data.extend(upserts);
+        Ok((data, updated, deleted))
+    }
+
+    fn duplicate_error(&self, batch: &RecordBatch, row: usize) -> Error {
+        let values: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| {
+                batch
+                    .column_by_name(column)
+                    .and_then(|array| {
+                        arrow::util::display::array_value_to_string(array, row).ok()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect();
+        Error::data(format!(
+            "Duplicate primary key ({}) = ({})",
+            self.columns.join(", "),
+            values.join(", ")
+        ))
+    }
+}
+
+/// Encodes key columns into comparable, hashable rows
+struct KeyEncoder {
+    converter: RowConverter,
+    indices: Vec<usize>,
+    columns: Vec<String>,
+}
+
+impl KeyEncoder {
+    fn new(key: &PrimaryKey, schema: &ArrowSchema) -> Result<Self> {
+        key.validate(schema)?;
+        let indices = key
+            .columns
+            .iter()
+            .map(|column| schema.index_of(column))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let fields = indices
+            .iter()
+            .map(|&i| SortField::new(schema.field(i).data_type().clone()))
+            .collect();
+        Ok(Self {
+            converter: RowConverter::new(fields)?,
+            indices,
+            columns: key.columns.clone(),
+        })
+    }
+
+    /// Key of every row of a batch with the cube's schema
+    fn keys(&self, batch: &RecordBatch) -> Result<Vec<OwnedRow>> {
+        let arrays: Vec<_> = self.indices.iter().map(|&i| batch.column(i).clone()).collect();
+        self.encode(&arrays)
+    }
+
+    /// Keys from arrays holding just the key columns, in key order
+    fn encode(&self, arrays: &[ArrayRef]) -> Result<Vec<OwnedRow>> {
+        for (array, column) in arrays.iter().zip(&self.columns) {
+            if array.null_count() > 0 {
+                return Err(Error::data(format!(
+                    "Primary key column '{}' contains NULL values",
+                    column
+                )));
+            }
+        }
+        let rows = self.converter.convert_columns(arrays)?;
+        Ok(rows.iter().map(|row| row.owned()).collect())
+    }
+}
+
+/// Keep the rows of `batch` where `mask` is true
+fn filter(batch: &RecordBatch, mask: Vec<bool>) -> Result<RecordBatch> {
+    if mask.iter().all(|&keep| keep) {
+        return Ok(batch.clone());
+    }
+    Ok(arrow::compute::filter_record_batch(
+        batch,
+        &BooleanArray::from(mask),
+    )?)
+}
@@ -3,8 +3,10 @@
 use arrow::datatypes::DataType;
 use serde::{Deserialize, Serialize};
 
+use super::format::DisplayFormat;
+
 /// Aggregation function for measures
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AggFunc {
     /// Sum of values
     Sum,
@@ -28,6 +30,20 @@ pub enum AggFunc {
     First,
     /// Last value
     Last,
+    /// Pearson correlation coefficient of two columns
+    Corr,
+    /// Sample covariance of two columns
+    CovarSamp,
+    /// Population covariance of two columns
+    CovarPop,
+    /// Sample skewness
+    Skewness,
+    /// Sample excess kurtosis
+    Kurtosis,
+    /// Collect values into a list
+    ArrayAgg,
+    /// Concatenate string values with the given separator
+    StringAgg(String),
 }
 
 impl AggFunc {
@@ -45,6 +61,28 @@ impl AggFunc {
             AggFunc::Variance => "VAR",
             AggFunc::First => "FIRST_VALUE",
             AggFunc::Last => "LAST_VALUE",
+            AggFunc::Corr => "CORR",
+            AggFunc::CovarSamp => "COVAR_SAMP",
+            AggFunc::CovarPop => "COVAR_POP",
+            AggFunc::Skewness => "SKEWNESS",
+            AggFunc::Kurtosis => "KURTOSIS",
+            AggFunc::ArrayAgg => "ARRAY_AGG",
+            AggFunc::StringAgg(_) => "STRING_AGG",
+        }
+    }
+
+    /// Render this aggregation applied to a SQL expression
+    ///
+    /// Unlike [`sql_name`](Self::sql_name), this distinguishes `COUNT(DISTINCT ...)`
+    /// from a plain `COUNT(...)`. Two-column aggregates such as `Corr` take
+    /// both arguments in `expr` (e.g. `"price, quantity"`).
+    pub fn to_sql(&self, expr: &str) -> String {
+        match self {
+            AggFunc::CountDistinct => format!("COUNT(DISTINCT {})", expr),
+            AggFunc::StringAgg(separator) => {
+                format!("STRING_AGG({}, '{}')", expr, separator.replace('\'', "''"))
+            }
+            _ => format!("{}({})", self.sql_name(), expr),
         }
     }
 
@@ -52,7 +90,15 @@ impl AggFunc {
     pub fn is_compatible_with(&self, data_type: &DataType) -> bool {
         use DataType::*;
         match self {
-            AggFunc::Sum | AggFunc::Avg | AggFunc::StdDev | AggFunc::Variance => {
+            AggFunc::Sum
+            | AggFunc::Avg
+            | AggFunc::StdDev
+            | AggFunc::Variance
+            | AggFunc::Corr
+            | AggFunc::CovarSamp
+            | AggFunc::CovarPop
+            | AggFunc::Skewness
+            | AggFunc::Kurtosis => {
                 matches!(
                     data_type,
                     Int8 | Int16
@@ -69,7 +115,8 @@ impl AggFunc {
                 )
             }
             AggFunc::Min | AggFunc::Max | AggFunc::First | AggFunc::Last => true,
-            AggFunc::Count | AggFunc::CountDistinct => true,
+            AggFunc::Count | AggFunc::CountDistinct | AggFunc::ArrayAgg => true,
+            AggFunc::StringAgg(_) => matches!(data_type, Utf8 | LargeUtf8 | Utf8View),
             AggFunc::Median => {
                 matches!(
                     data_type,
@@ -88,6 +135,39 @@ impl AggFunc {
     }
 }
 
+impl std::str::FromStr for AggFunc {
+    type Err = crate::error::Error;
+
+    /// Parse a case-insensitive aggregation name such as `sum`, `avg` or
+    /// `count_distinct`; `string_agg` uses `", "` as its separator
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "sum" => Ok(AggFunc::Sum),
+            "avg" | "average" | "mean" => Ok(AggFunc::Avg),
+            "min" => Ok(AggFunc::Min),
+            "max" => Ok(AggFunc::Max),
+            "count" => Ok(AggFunc::Count),
+            "count_distinct" | "countdistinct" => Ok(AggFunc::CountDistinct),
+            "median" => Ok(AggFunc::Median),
+            "stddev" | "std" => Ok(AggFunc::StdDev),
+            "variance" | "var" => Ok(AggFunc::Variance),
+            "first" => Ok(AggFunc::First),
+            "last" => Ok(AggFunc::Last),
+            "corr" | "correlation" => Ok(AggFunc::Corr),
+            "covar_samp" | "covar" | "covariance" => Ok(AggFunc::CovarSamp),
+            "covar_pop" => Ok(AggFunc::CovarPop),
+            "skewness" | "skew" => Ok(AggFunc::Skewness),
+            "kurtosis" | "kurt" => Ok(AggFunc::Kurtosis),
+            "array_agg" | "list" => Ok(AggFunc::ArrayAgg),
+            "string_agg" => Ok(AggFunc::StringAgg(", ".to_string())),
+            _ => Err(crate::error::Error::measure(format!(
+                "Unknown aggregation function: {}",
+                s
+            ))),
+        }
+    }
+}
+
 impl std::fmt::Display for AggFunc {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.sql_name())
@@ -115,8 +195,20 @@ pub struct Measure {
     /// User-provided description
     description: Option<String>,
 
-    /// Format string for display (e.g., "$,.2f" for currency)
+    /// Format string for display (e.g., "$#,##0.00" for currency)
     format: Option<String>,
+
+    /// Unit shown after values (e.g., "kg", "USD")
+    #[serde(default)]
+    unit: Option<String>,
+
+    /// Number of decimals shown, overriding the format string
+    #[serde(default)]
+    precision: Option<u8>,
+
+    /// Free-form labels for catalogs (e.g., "kpi", "finance")
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
 impl Measure {
@@ -129,6 +221,9 @@ impl Measure {
             nullable: true,
             description: None,
             format: None,
+            unit: None,
+            precision: None,
+            tags: Vec::new(),
         }
     }
 
@@ -148,6 +243,9 @@ impl Measure {
             nullable,
             description,
             format,
+            unit: None,
+            precision: None,
+            tags: Vec::new(),
         }
     }
 
@@ -163,7 +261,7 @@ impl Measure {
 
     /// Get the default aggregation function
     pub fn default_agg(&self) -> AggFunc {
-        self.default_agg
+        self.default_agg.clone()
     }
 
     /// Check if the measure is nullable
@@ -181,11 +279,61 @@ impl Measure {
         self.format.as_deref()
     }
 
+    /// Get the unit shown after values
+    pub fn unit(&self) -> Option<&str> {
+        self.unit.as_deref()
+    }
+
+    /// Get the number of decimals shown
+    pub fn precision(&self) -> Option<u8> {
+        self.precision
+    }
+
+    /// Get the tags
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Add a tag, unless the measure already has it
+    pub fn add_tag(&mut self, tag: impl Into<String>) {
+        let tag = tag.into();
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+    }
+
+    /// Get the display format, if any part of it is set
+    pub fn display_format(&self) -> Option<DisplayFormat> {
+        let format = DisplayFormat {
+            pattern: self.format.clone(),
+            unit: self.unit.clone(),
+            precision: self.precision,
+        };
+        (!format.is_empty()).then_some(format)
+    }
+
+    /// Set the format string, unit and precision at once
+    pub fn set_display_format(&mut self, format: DisplayFormat) {
+        self.format = format.pattern;
+        self.unit = format.unit;
+        self.precision = format.precision;
+    }
+
     /// Set the description
     pub fn set_description(&mut self, description: impl Into<String>) {
         self.description = Some(description.into());
     }
 
+    /// Change the data type, e.g. when the cube's timestamps are localized
+    pub(crate) fn set_data_type(&mut self, data_type: DataType) {
+        self.data_type = data_type;
+    }
+
+    /// Change the name, when the column is renamed
+    pub(crate) fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+
     /// Set the format
     pub fn set_format(&mut self, format: impl Into<String>) {
         self.format = Some(format.into());
@@ -197,6 +345,12 @@ impl Measure {
         self
     }
 
+    /// Builder-style: add a tag
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.add_tag(tag);
+        self
+    }
+
     /// Builder-style: set description
     pub fn with_description(mut self, description: impl Into<String>) -> Self {
         self.description = Some(description.into());
@@ -209,6 +363,18 @@ impl Measure {
         self
     }
 
+    /// Builder-style: set the unit
+    pub fn with_unit(mut self, unit: impl Into<String>) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+
+    /// Builder-style: set the number of decimals
+    pub fn with_precision(mut self, precision: u8) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
     /// Validate that the default aggregation is compatible with the data type
     pub fn validate(&self) -> Result<(), String> {
         if !self.default_agg.is_compatible_with(&self.data_type) {
@@ -251,6 +417,22 @@ mod tests {
 
         assert!(AggFunc::Count.is_compatible_with(&DataType::Utf8));
         assert!(AggFunc::Max.is_compatible_with(&DataType::Utf8));
+
+        let string_agg = AggFunc::StringAgg(", ".to_string());
+        assert!(string_agg.is_compatible_with(&DataType::Utf8));
+        assert!(!string_agg.is_compatible_with(&DataType::Int64));
+        assert_eq!(string_agg.to_sql("product"), "STRING_AGG(product, ', ')");
+    }
+
+    #[test]
+    fn test_agg_func_from_str() {
+        assert_eq!("SUM".parse::<AggFunc>().unwrap(), AggFunc::Sum);
+        assert_eq!("mean".parse::<AggFunc>().unwrap(), AggFunc::Avg);
+        assert_eq!(
+            "count_distinct".parse::<AggFunc>().unwrap(),
+            AggFunc::CountDistinct
+        );
+        assert!("bogus".parse::<AggFunc>().is_err());
     }
 
     #[test]
@@ -258,8 +440,10 @@ mod tests {
         let measure = Measure::new("sales", DataType::Float64, AggFunc::Sum)
             .with_nullable(false)
             .with_description("Total sales amount")
-            .with_format("$,.2f");
+            .with_format("$,.2f")
+            .with_tag("kpi");
 
+        assert_eq!(measure.tags(), ["kpi"]);
         assert_eq!(measure.name(), "sales");
         assert!(!measure.is_nullable());
         assert_eq!(measure.description(), Some("Total sales amount"));
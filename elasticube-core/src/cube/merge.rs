@@ -0,0 +1,303 @@
+//! Combining the data of several cubes
+//!
+//! [`ElastiCube::union`] stacks the rows of two cubes with the same columns,
+//! such as one month's cube onto the year so far. Cubes built from regional
+//! shards often differ a little: one region records a column the others
+//! don't. [`ElastiCube::merge_schemas`] combines any number of them into one
+//! cube with every column and definition of any shard, leaving NULL where a
+//! shard had no such column.
+
+use super::timezone::{localize_batch, localize_schema};
+use super::{CubeSchema, ElastiCube};
+use crate::drift::SchemaDrift;
+use crate::error::{Error, Result};
+use crate::ingest::{coerce_batches, Coercion};
+use arrow::array::new_null_array;
+use arrow::datatypes::{Field, Schema as ArrowSchema};
+use arrow::record_batch::RecordBatch;
+use std::sync::Arc;
+
+impl ElastiCube {
+    /// A cube with the rows of this cube followed by those of `other`
+    ///
+    /// Both cubes must have the same columns with the same types, in any
+    /// order. The result keeps this cube's definitions and settings, and a
+    /// primary key is enforced across both cubes' rows. Neither cube is
+    /// changed, and the result has no source to refresh from, since a
+    /// refresh would lose `other`'s rows.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let year_to_date = year_to_date.union(&march)?;
+    /// ```
+    pub fn union(&self, other: &ElastiCube) -> Result<ElastiCube> {
+        self.union_with(other, Coercion::Strict)
+    }
+
+    /// Like [`union`](Self::union), casting `other`'s columns to this cube's
+    /// types as the coercion mode allows
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // quantity is Int64 in the imported cube and Int32 in ours
+    /// let combined = ours.union_with(&imported, Coercion::Safe)?;
+    /// ```
+    pub fn union_with(&self, other: &ElastiCube, coercion: Coercion) -> Result<ElastiCube> {
+        self.ensure_in_memory("union")?;
+        let batches = self.conform(other, coercion, false)?;
+
+        let mut cube = self.clone();
+        cube.source = None;
+        cube.source_columns = None;
+        cube.push_batches(batches)?;
+        cube.expire_rows()?;
+        cube.record_version("union");
+        Ok(cube)
+    }
+
+    /// Combine cubes with overlapping columns into one cube named `name`
+    ///
+    /// The result has every column of every cube, in order of first
+    /// appearance, with the type it has in the first cube that has it; rows
+    /// from cubes without a column hold NULL in it. Dimensions, measures,
+    /// hierarchies and calculated fields are taken from the first cube
+    /// defining them, along with the first cube's primary key, time zone
+    /// and display settings. Columns typed differently in later cubes are
+    /// cast as `coercion` allows.
+    ///
+    /// The merged cube has no source to refresh from.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let shards = [&emea, &apac, &amer];
+    /// let global = ElastiCube::merge_schemas("global_sales", &shards, Coercion::Safe)?;
+    /// ```
+    pub fn merge_schemas(
+        name: impl Into<String>,
+        cubes: &[&ElastiCube],
+        coercion: Coercion,
+    ) -> Result<ElastiCube> {
+        let (first, rest) = cubes
+            .split_first()
+            .ok_or_else(|| Error::data("Cannot merge an empty list of cubes"))?;
+
+        let mut schema = first.schema.clone();
+        schema.set_name(name);
+        let mut fields: Vec<Field> =
+            first.arrow_schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+        for cube in rest {
+            for field in cube.arrow_schema.fields() {
+                if !fields.iter().any(|known| known.name() == field.name()) {
+                    fields.push(field.as_ref().clone());
+                }
+            }
+            absorb_definitions(&mut schema, &cube.schema)?;
+        }
+
+        // A column some cube lacks holds NULL in that cube's rows
+        for field in &mut fields {
+            if cubes.iter().any(|cube| cube.arrow_schema.field_with_name(field.name()).is_err()) {
+                *field = field.clone().with_nullable(true);
+            }
+        }
+
+        let arrow_schema = Arc::new(ArrowSchema::new(fields));
+        let mut merged = ElastiCube::new(schema, arrow_schema, Vec::new())?;
+        for cube in cubes {
+            let batches = merged.conform(cube, coercion, true)?;
+            merged.push_batches(batches)?;
+        }
+        Ok(merged)
+    }
+
+    /// `other`'s rows laid out in this cube's columns
+    ///
+    /// Timestamps are put in this cube's time zone and other types cast as
+    /// `coercion` allows. Columns this cube has and `other` lacks are filled
+    /// with NULL if `fill_missing` is set; otherwise they fail, as do
+    /// columns this cube doesn't have.
+    pub(super) fn conform(
+        &self,
+        other: &ElastiCube,
+        coercion: Coercion,
+        fill_missing: bool,
+    ) -> Result<Vec<RecordBatch>> {
+        other.ensure_in_memory("combine")?;
+        let mut schema = Arc::clone(&other.arrow_schema);
+        let mut batches = other.materialized_batches()?;
+        if let Some(tz) = self.schema.timezone() {
+            schema = localize_schema(&schema, tz);
+            batches = batches
+                .iter()
+                .map(|batch| localize_batch(batch, tz))
+                .collect::<Result<_>>()?;
+        }
+        let (schema, batches, _) = coerce_batches(&self.arrow_schema, schema, batches, coercion)?;
+
+        let drift = SchemaDrift::detect(&self.arrow_schema, &schema);
+        if !drift.added.is_empty()
+            || !drift.retyped.is_empty()
+            || (!fill_missing && !drift.missing.is_empty())
+        {
+            return Err(Error::schema(format!(
+                "Cube '{}' does not match the columns of '{}': {}",
+                other.schema.name(),
+                self.schema.name(),
+                drift
+            )));
+        }
+
+        batches
+            .iter()
+            .map(|batch| {
+                let columns = self
+                    .arrow_schema
+                    .fields()
+                    .iter()
+                    .map(|field| match batch.column_by_name(field.name()) {
+                        Some(column) => Arc::clone(column),
+                        None => new_null_array(field.data_type(), batch.num_rows()),
+                    })
+                    .collect();
+                Ok(RecordBatch::try_new(Arc::clone(&self.arrow_schema), columns)?)
+            })
+            .collect()
+    }
+}
+
+/// Add the definitions of `other` whose names `schema` doesn't use yet
+fn absorb_definitions(schema: &mut CubeSchema, other: &CubeSchema) -> Result<()> {
+    let known = |schema: &CubeSchema, name: &str| {
+        schema.has_dimension(name)
+            || schema.has_measure(name)
+            || schema.has_virtual_dimension(name)
+            || schema.has_calculated_measure(name)
+    };
+
+    for dimension in other.dimensions() {
+        if !known(schema, dimension.name()) {
+            schema.add_dimension(dimension.clone())?;
+        }
+    }
+    for measure in other.measures() {
+        if !known(schema, measure.name()) {
+            schema.add_measure(measure.clone())?;
+        }
+    }
+    for virtual_dim in other.virtual_dimensions() {
+        if !known(schema, virtual_dim.name()) {
+            schema.add_virtual_dimension(virtual_dim.clone())?;
+        }
+    }
+    for calc_measure in other.calculated_measures() {
+        if !known(schema, calc_measure.name()) {
+            schema.add_calculated_measure(calc_measure.clone())?;
+        }
+    }
+    for hierarchy in other.hierarchies() {
+        if !schema.has_hierarchy(hierarchy.name()) {
+            schema.add_hierarchy(hierarchy.clone())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AggFunc, ElastiCubeBuilder};
+    use arrow::array::{Array, Float64Array, Int64Array, StringArray};
+    use arrow::datatypes::DataType;
+
+    fn shard(name: &str, regions: Vec<&str>, sales: Vec<i64>, channel: Option<&str>) -> ElastiCube {
+        let mut fields = vec![
+            Field::new("sales", DataType::Int64, false),
+            Field::new("region", DataType::Utf8, false),
+        ];
+        let rows = regions.len();
+        let mut columns: Vec<Arc<dyn Array>> = vec![
+            Arc::new(Int64Array::from(sales)),
+            Arc::new(StringArray::from(regions)),
+        ];
+        let mut builder = ElastiCubeBuilder::new(name)
+            .add_measure("sales", DataType::Int64, AggFunc::Sum)
+            .unwrap()
+            .add_dimension("region", DataType::Utf8)
+            .unwrap();
+        if let Some(channel) = channel {
+            fields.push(Field::new("channel", DataType::Utf8, false));
+            columns.push(Arc::new(StringArray::from(vec![channel; rows])));
+            builder = builder.add_dimension("channel", DataType::Utf8).unwrap();
+        }
+        let schema = Arc::new(ArrowSchema::new(fields));
+        let batch = RecordBatch::try_new(Arc::clone(&schema), columns).unwrap();
+        builder.load_record_batches(schema, vec![batch]).unwrap().build().unwrap()
+    }
+
+    fn float_shard() -> ElastiCube {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("sales", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![
+                Arc::new(StringArray::from(vec!["North"])),
+                Arc::new(Float64Array::from(vec![12.0])),
+            ],
+        )
+        .unwrap();
+        ElastiCubeBuilder::new("floats")
+            .load_record_batches(schema, vec![batch])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_union() {
+        let emea = shard("emea", vec!["North", "South"], vec![10, 20], None);
+        let apac = shard("apac", vec!["East"], vec![30], None);
+
+        let combined = emea.union(&apac).unwrap();
+        assert_eq!(combined.row_count(), 3);
+        assert_eq!(combined.schema().name(), "emea");
+        assert_eq!(emea.row_count(), 2);
+
+        // Columns match by name, and types only through coercion
+        let floats = float_shard();
+        assert!(floats.union(&emea).is_err());
+        let cast = floats.union_with(&emea, Coercion::Safe).unwrap();
+        assert_eq!(cast.row_count(), 3);
+        assert_eq!(cast.arrow_schema().field(1).data_type(), &DataType::Float64);
+
+        let online = shard("online", vec!["West"], vec![40], Some("web"));
+        let err = emea.union(&online).unwrap_err();
+        assert!(err.to_string().contains("new columns channel"));
+    }
+
+    #[test]
+    fn test_merge_schemas() {
+        let emea = shard("emea", vec!["North", "South"], vec![10, 20], None);
+        let online = shard("online", vec!["West"], vec![40], Some("web"));
+
+        let global =
+            ElastiCube::merge_schemas("global", &[&emea, &online], Coercion::Strict).unwrap();
+        assert_eq!(global.schema().name(), "global");
+        assert_eq!(global.row_count(), 3);
+        assert!(global.get_dimension("channel").is_some());
+
+        let channel = global.arrow_schema().field_with_name("channel").unwrap();
+        assert!(channel.is_nullable());
+        let nulls: usize = global
+            .data()
+            .iter()
+            .map(|batch| batch.column_by_name("channel").unwrap().null_count())
+            .sum();
+        assert_eq!(nulls, 2);
+
+        assert!(ElastiCube::merge_schemas("none", &[], Coercion::Strict).is_err());
+        let floats = float_shard();
+        assert!(ElastiCube::merge_schemas("mixed", &[&emea, &floats], Coercion::Strict).is_err());
+    }
+}
@@ -1,23 +1,84 @@
 //! Core ElastiCube data structures
 
+mod aggregations;
+mod bridge;
 mod calculated;
+mod changes;
+mod cold;
+mod dates;
+mod describe;
+mod diff;
 mod dimension;
+mod format;
 mod hierarchy;
+mod keys;
 mod measure;
+mod merge;
+mod profile;
+mod rename;
+mod retention;
 mod schema;
+mod timezone;
+mod transaction;
 mod updates;
+mod versions;
 
+pub use bridge::BridgeDimension;
 pub use calculated::{CalculatedMeasure, VirtualDimension};
+pub use changes::{ChangeEvent, ChangeSummary};
+pub use cold::BatchCompression;
+pub use dates::DateParts;
+pub use describe::{ColumnDescription, ColumnKind, CubeDescription, HierarchyDescription};
+pub use diff::CubeDiff;
 pub use dimension::Dimension;
+pub use format::DisplayFormat;
 pub use hierarchy::Hierarchy;
+pub use keys::{DuplicatePolicy, PrimaryKey};
 pub use measure::{AggFunc, Measure};
-pub use schema::CubeSchema;
+pub use profile::{ColumnProfile, CubeProfile, HistogramBin, ProfileOptions};
+pub use schema::{CubeSchema, SCHEMA_FORMAT_VERSION};
+pub use transaction::Transaction;
+pub use versions::{AsOf, CubeVersion};
+pub(crate) use aggregations::Aggregation;
+pub(crate) use rename::{rename_batch_column, rename_field, rename_identifier};
+pub(crate) use timezone::{localize_batch, localize_schema};
 
+use crate::audit::AuditSink;
+use crate::cache::QueryCache;
+use crate::drift::{self, DriftPolicy, SchemaDrift};
 use crate::error::{Error, Result};
+use crate::frozen::FrozenCube;
+use crate::ingest::{coerce_batches, keep_columns, LoadReport, LoadSettings};
+use crate::optimization::{OptimizationConfig, SessionCache, StatisticsCache};
+use crate::predicate::Predicate;
 use crate::query::QueryBuilder;
-use arrow::datatypes::Schema as ArrowSchema;
-use arrow::record_batch::RecordBatch;
-use std::sync::Arc;
+use crate::query_log::{QueryLog, QueryRecord};
+use crate::sources::DataSource;
+use crate::storage::ParquetExportOptions;
+use cold::ColdStore;
+use retention::RetentionPolicy;
+use versions::VersionLog;
+use arrow::array::new_null_array;
+use arrow::datatypes::{DataType, Schema as ArrowSchema};
+use arrow::ffi_stream::FFI_ArrowArrayStream;
+use arrow::record_batch::{RecordBatch, RecordBatchIterator};
+use datafusion::catalog::TableProvider;
+use datafusion::datasource::MemTable;
+use datafusion::logical_expr::{create_udf, ColumnarValue, ScalarUDF, Volatility};
+use datafusion::prelude::{ident, SessionContext};
+use std::hash::Hasher;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::time::{Duration, Instant, SystemTime};
+
+/// Sequence the generations of every cube in the process are drawn from, so
+/// a cube and its clones never reach the same generation with different data
+static GENERATIONS: AtomicU64 = AtomicU64::new(0);
+
+fn next_generation() -> u64 {
+    GENERATIONS.fetch_add(1, Ordering::Relaxed) + 1
+}
 
 /// The main ElastiCube structure
 ///
@@ -37,6 +98,70 @@ pub struct ElastiCube {
 
     /// Total number of rows across all batches
     row_count: usize,
+
+    /// Source the cube was built from, kept for refreshes
+    source: Option<Arc<dyn DataSource>>,
+
+    /// Columns requested from the source (None = all columns)
+    source_columns: Option<Vec<String>>,
+
+    /// User-defined scalar functions available to queries
+    udfs: Vec<ScalarUDF>,
+
+    /// Snapshot history, when versioning is enabled
+    versions: Option<VersionLog>,
+
+    /// Maximum age of the rows kept, if a retention policy is set
+    retention: Option<RetentionPolicy>,
+
+    /// Column the batches are partitioned by; each batch holds one value
+    partition_column: Option<String>,
+
+    /// Statistics and zone maps of the current data, computed on demand
+    statistics_cache: Arc<StatisticsCache>,
+
+    /// Columns the data is kept sorted by, if declared
+    sort_order: Option<Vec<String>>,
+
+    /// Whether the data currently follows `sort_order`
+    sorted: bool,
+
+    /// Parquet file or directory queried in place instead of `data`
+    parquet_path: Option<String>,
+
+    /// Batches moved out of `data` and kept compressed
+    cold: ColdStore,
+
+    /// DataFusion session and table reused between queries
+    sessions: Arc<SessionCache>,
+
+    /// Pre-aggregations queries can be routed to
+    aggregations: Vec<Aggregation>,
+
+    /// Many-to-many dimensions joined through bridge tables
+    bridges: Vec<BridgeDimension>,
+
+    /// Earlier column names with the names they were renamed to
+    renamed_columns: Vec<(String, String)>,
+
+    /// How source columns are mapped, dropped and cast on refresh
+    load_settings: LoadSettings,
+
+    /// What happened to the data on its latest load
+    load_report: LoadReport,
+
+    /// Recent queries, shared with clones of the cube
+    query_log: Arc<QueryLog>,
+
+    /// Where every query is reported for auditing, if anywhere
+    audit_sink: Option<Arc<dyn AuditSink>>,
+
+    /// Changes to the data so far; replaced on every change
+    generation: u64,
+
+    /// Hash of the data and the generation it was computed at, shared with
+    /// clones
+    data_version: Arc<Mutex<Option<(u64, u64)>>>,
 }
 
 impl ElastiCube {
@@ -53,9 +178,74 @@ impl ElastiCube {
             arrow_schema,
             data,
             row_count,
+            source: None,
+            source_columns: None,
+            udfs: Vec::new(),
+            versions: None,
+            retention: None,
+            partition_column: None,
+            statistics_cache: Arc::new(StatisticsCache::default()),
+            sort_order: None,
+            sorted: false,
+            parquet_path: None,
+            cold: ColdStore::default(),
+            sessions: Arc::new(SessionCache::default()),
+            aggregations: Vec::new(),
+            bridges: Vec::new(),
+            renamed_columns: Vec::new(),
+            load_settings: LoadSettings::default(),
+            load_report: LoadReport::default(),
+            query_log: Arc::new(QueryLog::default()),
+            audit_sink: None,
+            generation: next_generation(),
+            data_version: Arc::new(Mutex::new(None)),
         })
     }
 
+    /// Create a cube that queries Parquet files in place
+    ///
+    /// No batches are kept in memory; `row_count` comes from the file footers.
+    pub(crate) fn new_lazy(
+        schema: CubeSchema,
+        arrow_schema: Arc<ArrowSchema>,
+        path: String,
+        row_count: usize,
+    ) -> Result<Self> {
+        let mut cube = Self::new(schema, arrow_schema, Vec::new())?;
+        cube.row_count = row_count;
+        cube.parquet_path = Some(path);
+        Ok(cube)
+    }
+
+    /// Remember the source the cube was loaded from so it can be refreshed
+    pub(crate) fn set_source(&mut self, source: Arc<dyn DataSource>, columns: Option<Vec<String>>) {
+        self.source = Some(source);
+        self.source_columns = columns;
+    }
+
+    /// Whether the cube was built from a source it can be refreshed from
+    pub fn has_source(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Reconcile source columns on refresh as the build did
+    pub(crate) fn set_load_settings(&mut self, settings: LoadSettings) {
+        self.load_settings = settings;
+    }
+
+    /// What happened to the data on its latest load from the source
+    ///
+    /// Covers the build, or the latest [`refresh`](Self::refresh) or
+    /// [`refresh_incremental`](Self::refresh_incremental).
+    pub fn load_report(&self) -> &LoadReport {
+        &self.load_report
+    }
+
+    /// Record what happened to the data on the build
+    pub(crate) fn set_load_report(&mut self, report: LoadReport) {
+        self.load_report = report;
+    }
+
     /// Get the cube schema
     pub fn schema(&self) -> &CubeSchema {
         &self.schema
@@ -67,10 +257,33 @@ impl ElastiCube {
     }
 
     /// Get the data batches
+    ///
+    /// Batches compressed by [`compress_cold_batches`](Self::compress_cold_batches)
+    /// are not included.
     pub fn data(&self) -> &[RecordBatch] {
         &self.data
     }
 
+    /// Parquet file or directory the cube reads lazily, if it was built
+    /// with `ElastiCubeBuilder::load_parquet_lazy`
+    ///
+    /// Lazy cubes hold no batches, so [`data`](Self::data) is empty and
+    /// updates, partitioning, sorting and saving are not available.
+    pub fn parquet_path(&self) -> Option<&str> {
+        self.parquet_path.as_deref()
+    }
+
+    /// Fail with a clear error if the cube's data isn't held in memory
+    fn ensure_in_memory(&self, operation: &str) -> Result<()> {
+        match &self.parquet_path {
+            Some(path) => Err(Error::data(format!(
+                "Cannot {} a cube that reads Parquet lazily from '{}'",
+                operation, path
+            ))),
+            None => Ok(()),
+        }
+    }
+
     /// Get the total number of rows
     pub fn row_count(&self) -> usize {
         self.row_count
@@ -142,6 +355,112 @@ impl ElastiCube {
         QueryBuilder::new(self)
     }
 
+    /// Turn the cube into a read-only handle
+    ///
+    /// The [`FrozenCube`] can be queried and cloned freely but offers no way
+    /// to modify the data, which the compiler enforces.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let frozen = cube.freeze();
+    /// serve(frozen.clone());
+    /// ```
+    pub fn freeze(self) -> FrozenCube {
+        FrozenCube::from(self)
+    }
+
+    /// Expose the cube's data as a DataFusion table
+    ///
+    /// Lets cubes be registered in an existing `SessionContext` next to other
+    /// tables and joined with plain SQL. The table holds the stored columns
+    /// only: calculated measures, virtual dimensions and UDFs are expanded by
+    /// [`QueryBuilder`] and are not visible through it. It is a snapshot, so
+    /// later updates to the cube need the table re-registered.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let ctx = SessionContext::new();
+    /// ctx.register_table("sales", cube.as_table_provider()?)?;
+    /// ctx.register_csv("targets", "targets.csv", CsvReadOptions::new()).await?;
+    ///
+    /// let df = ctx
+    ///     .sql("SELECT s.region, SUM(s.amount), MAX(t.target) FROM sales s JOIN targets t USING (region) GROUP BY s.region")
+    ///     .await?;
+    /// ```
+    pub fn as_table_provider(&self) -> Result<Arc<dyn TableProvider>> {
+        self.table_provider(None)
+    }
+
+    /// Save the cube to a directory so it can be reloaded with [`load`](Self::load)
+    ///
+    /// Writes the schema (including calculated measures, virtual dimensions
+    /// and hierarchies) as JSON and the data as an Arrow IPC file. The data
+    /// source and registered UDFs are not saved; re-register UDFs after
+    /// loading.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// cube.save("cubes/sales")?;
+    /// let cube = Arc::new(ElastiCube::load("cubes/sales")?);
+    /// ```
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.ensure_in_memory("save")?;
+        crate::storage::save_cube(self, path.as_ref())
+    }
+
+    /// Write the cube's data to a Parquet file with Snappy compression
+    ///
+    /// Only the data is written; use [`save`](Self::save) to keep the
+    /// schema as well.
+    pub fn export_parquet(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.export_parquet_with(path, &ParquetExportOptions::default())
+    }
+
+    /// Write the cube's data to a Parquet file with custom settings
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use parquet::basic::{Compression, ZstdLevel};
+    ///
+    /// let options = ParquetExportOptions::default()
+    ///     .with_compression(Compression::ZSTD(ZstdLevel::default()))
+    ///     .with_max_row_group_size(100_000);
+    /// cube.export_parquet_with("sales.parquet", &options)?;
+    /// ```
+    pub fn export_parquet_with(
+        &self,
+        path: impl AsRef<Path>,
+        options: &ParquetExportOptions,
+    ) -> Result<()> {
+        self.ensure_in_memory("export")?;
+        crate::storage::export_parquet(self, path.as_ref(), options)
+    }
+
+    /// Export the cube's data through the Arrow C stream interface
+    ///
+    /// The stream shares the cube's buffers instead of copying or
+    /// serializing them, so any Arrow implementation (pyarrow, Arrow C++,
+    /// nanoarrow, DuckDB, ...) can read the data in place. Hand the consumer
+    /// a pointer to the returned struct; it takes ownership by moving the
+    /// struct out and calling its `release` callback when done.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let stream = Box::into_raw(Box::new(cube.export_ffi()?));
+    /// unsafe { consume_arrow_stream(stream as *mut c_void) };
+    /// ```
+    pub fn export_ffi(&self) -> Result<FFI_ArrowArrayStream> {
+        self.ensure_in_memory("export")?;
+        let batches = self.materialized_batches()?;
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), self.arrow_schema.clone());
+        Ok(FFI_ArrowArrayStream::new(Box::new(reader)))
+    }
+
+    /// Load a cube written by [`save`](Self::save)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        crate::storage::load_cube(path.as_ref())
+    }
+
     /// Get cube statistics for performance analysis
     ///
     /// Returns statistics about the cube's data including row count,
@@ -152,8 +471,12 @@ impl ElastiCube {
     /// let stats = cube.statistics();
     /// println!("Cube: {}", stats.summary());
     /// ```
+    ///
+    /// Compressed cold batches are not included.
     pub fn statistics(&self) -> crate::optimization::CubeStatistics {
-        crate::optimization::CubeStatistics::from_batches(&self.data)
+        let mut statistics = (*self.statistics_cache.get(&self.data)).clone();
+        statistics.sort_order = self.current_sort_order().map(<[String]>::to_vec);
+        statistics
     }
 
     /// Create a query builder with custom optimization configuration
@@ -191,6 +514,150 @@ impl ElastiCube {
         QueryBuilder::with_config(self, config)
     }
 
+    /// The most recent queries on the cube, oldest first
+    ///
+    /// Records the SQL, duration, rows scanned and returned, and whether
+    /// the query cache answered, for the last
+    /// [`QueryLog::DEFAULT_CAPACITY`] queries by default. Use
+    /// [`query_log`](Self::query_log) to change how many are kept or to
+    /// find the slowest and most frequent ones.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// for record in cube.query_history() {
+    ///     if record.duration > Duration::from_secs(1) {
+    ///         println!("slow: {} ({} rows scanned)", record.sql, record.rows_scanned);
+    ///     }
+    /// }
+    /// ```
+    pub fn query_history(&self) -> Vec<QueryRecord> {
+        self.query_log.records()
+    }
+
+    /// The log behind [`query_history`](Self::query_history)
+    pub fn query_log(&self) -> &QueryLog {
+        &self.query_log
+    }
+
+    /// Report every query on the cube to `sink`
+    ///
+    /// Queries started from clones made earlier are not reported, so install
+    /// the sink before handing the cube out (or use
+    /// `ElastiCubeBuilder::with_audit_sink`). Tag queries with
+    /// [`QueryBuilder::as_principal`] to record who ran them.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let sink = Arc::new(MemoryAuditSink::new());
+    /// cube.set_audit_sink(sink.clone());
+    ///
+    /// let cube = Arc::new(cube);
+    /// cube.query()?.as_principal("alice").sql("SELECT * FROM cube").execute().await?;
+    /// assert_eq!(sink.events()[0].principal.as_deref(), Some("alice"));
+    /// ```
+    pub fn set_audit_sink(&mut self, sink: Arc<dyn AuditSink>) {
+        self.audit_sink = Some(sink);
+    }
+
+    /// Stop reporting queries for auditing
+    pub fn clear_audit_sink(&mut self) {
+        self.audit_sink = None;
+    }
+
+    /// The sink queries are reported to, if any
+    pub fn audit_sink(&self) -> Option<&Arc<dyn AuditSink>> {
+        self.audit_sink.as_ref()
+    }
+
+    /// Run queries ahead of time so their results are cached
+    ///
+    /// Meant for startup: executing the queries dashboards open with means
+    /// their first load is served from the query cache. The queries run
+    /// concurrently. Each query is cached under its own configuration, so
+    /// later queries must be built the same way to hit the warmed results.
+    /// Returns how many results are now cached; queries with caching
+    /// disabled or calling volatile functions run but aren't counted.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let cube = Arc::new(cube);
+    /// let by_region = cube.query()?
+    ///     .select(&["region", "SUM(sales) AS total"])
+    ///     .group_by(&["region"]);
+    /// let top_products = cube.query()?
+    ///     .sql("SELECT product, SUM(sales) AS total FROM cube GROUP BY product ORDER BY total DESC LIMIT 10");
+    /// cube.warm_cache(&[by_region, top_products]).await?;
+    /// ```
+    pub async fn warm_cache(&self, queries: &[QueryBuilder]) -> Result<usize> {
+        let runs = queries.iter().cloned().map(QueryBuilder::execute_cached);
+        let results = futures::future::try_join_all(runs).await?;
+        Ok(results.iter().filter(|(_, cached)| *cached).count())
+    }
+
+    // ============================================================
+    // User-Defined Functions
+    // ============================================================
+
+    /// Register a scalar user-defined function for use in queries
+    ///
+    /// The function becomes callable from SQL, the fluent API, calculated
+    /// measures and virtual dimensions. Unquoted function names are
+    /// lower-cased by the SQL parser, so use a lower-case `name`.
+    /// Registering a name again replaces the previous function.
+    ///
+    /// # Arguments
+    /// * `name` - SQL name of the function
+    /// * `input_types` - Argument types (arguments are coerced to these)
+    /// * `return_type` - Result type
+    /// * `fun` - Implementation, called with one value per argument
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use datafusion::common::cast::as_float64_array;
+    /// use datafusion::logical_expr::ColumnarValue;
+    ///
+    /// cube.register_udf("with_vat", vec![DataType::Float64], DataType::Float64, |args| {
+    ///     let arrays = ColumnarValue::values_to_arrays(args)?;
+    ///     let prices = as_float64_array(&arrays[0])?;
+    ///     let result: Float64Array = prices.iter().map(|p| p.map(|p| p * 1.2)).collect();
+    ///     Ok(ColumnarValue::Array(Arc::new(result)))
+    /// })?;
+    /// ```
+    pub fn register_udf<F>(
+        &mut self,
+        name: impl AsRef<str>,
+        input_types: Vec<DataType>,
+        return_type: DataType,
+        fun: F,
+    ) -> Result<()>
+    where
+        F: Fn(&[ColumnarValue]) -> datafusion::error::Result<ColumnarValue> + Send + Sync + 'static,
+    {
+        let name = name.as_ref();
+        if name.is_empty() {
+            return Err(Error::query("Function name cannot be empty"));
+        }
+
+        let udf = create_udf(name, input_types, return_type, Volatility::Immutable, Arc::new(fun));
+        self.register_scalar_udf(udf);
+        Ok(())
+    }
+
+    /// Register a fully specified DataFusion scalar UDF
+    ///
+    /// Use this instead of [`register_udf`](Self::register_udf) when the
+    /// function needs a custom signature, volatility or aliases.
+    pub fn register_scalar_udf(&mut self, udf: ScalarUDF) {
+        self.udfs.retain(|existing| existing.name() != udf.name());
+        self.udfs.push(udf);
+        self.mark_changed();
+    }
+
+    /// Get the user-defined functions registered on this cube
+    pub fn udfs(&self) -> &[ScalarUDF] {
+        &self.udfs
+    }
+
     // ============================================================
     // Data Update Operations
     // ============================================================
@@ -213,14 +680,15 @@ impl ElastiCube {
     /// println!("Added {} rows", rows_added);
     /// ```
     pub fn append_rows(&mut self, batch: RecordBatch) -> Result<usize> {
+        let batch = self.localize(vec![batch])?.remove(0);
+
         // Validate schema compatibility
         updates::validate_batch_schema(&self.arrow_schema, &batch.schema())?;
 
-        let rows_added = batch.num_rows();
-
         // Add the batch to our data
-        self.data.push(batch);
-        self.row_count += rows_added;
+        let rows_added = self.push_batches(vec![batch])?;
+        self.expire_rows()?;
+        self.record_version("append");
 
         Ok(rows_added)
     }
@@ -247,17 +715,17 @@ impl ElastiCube {
             return Ok(0);
         }
 
+        let batches = self.localize(batches)?;
+
         // Validate all batches first
         for batch in &batches {
             updates::validate_batch_schema(&self.arrow_schema, &batch.schema())?;
         }
 
-        // Count total rows
-        let rows_added: usize = batches.iter().map(|b| b.num_rows()).sum();
-
         // Append all batches
-        self.data.extend(batches);
-        self.row_count += rows_added;
+        let rows_added = self.push_batches(batches)?;
+        self.expire_rows()?;
+        self.record_version("append");
 
         Ok(rows_added)
     }
@@ -280,6 +748,20 @@ impl ElastiCube {
     /// println!("Deleted {} rows", deleted);
     /// ```
     pub async fn delete_rows(&mut self, filter_expr: &str) -> Result<usize> {
+        let rows_deleted = self.remove_matching_rows(filter_expr).await?;
+        self.record_version("delete");
+        Ok(rows_deleted)
+    }
+
+    /// Remove the rows matching a SQL predicate without recording a version
+    async fn remove_matching_rows(&mut self, filter_expr: &str) -> Result<usize> {
+        self.ensure_in_memory("delete rows from")?;
+        self.thaw()?;
+        self.mark_changed();
+        if let Some(rows_deleted) = self.remove_matching_partitions(filter_expr)? {
+            return Ok(rows_deleted);
+        }
+
         // We need to evaluate the filter using DataFusion to get a boolean mask
         // Then apply the inverse of that mask to keep only non-matching rows
 
@@ -317,13 +799,44 @@ impl ElastiCube {
         let new_row_count: usize = results.iter().map(|b| b.num_rows()).sum();
         let rows_deleted = self.row_count - new_row_count;
 
-        // Update the cube data
+        // Update the cube data; the query may have reordered rows
         self.data = results;
         self.row_count = new_row_count;
+        self.sorted = false;
+        self.repartition()?;
 
         Ok(rows_deleted)
     }
 
+    /// Drop whole partitions when the filter only reads the partition column
+    ///
+    /// Returns `None` if the cube isn't partitioned or the filter reads other
+    /// columns, in which case rows have to be matched one by one.
+    fn remove_matching_partitions(&mut self, filter_expr: &str) -> Result<Option<usize>> {
+        let Some(column) = &self.partition_column else {
+            return Ok(None);
+        };
+        let field = self.arrow_schema.field_with_name(column)?.clone();
+        let partition_schema = ArrowSchema::new(vec![field]);
+        let Ok(predicate) = Predicate::compile(filter_expr, &partition_schema) else {
+            return Ok(None);
+        };
+
+        let mut kept = Vec::with_capacity(self.data.len());
+        for batch in &self.data {
+            let partition = batch.project(&[batch.schema().index_of(column)?])?;
+            if !predicate.matches_first_row(&partition)? {
+                kept.push(batch.clone());
+            }
+        }
+
+        let new_row_count: usize = kept.iter().map(|b| b.num_rows()).sum();
+        let rows_deleted = self.row_count - new_row_count;
+        self.data = kept;
+        self.row_count = new_row_count;
+        Ok(Some(rows_deleted))
+    }
+
     /// Update rows in the cube based on a filter and replacement batch
     ///
     /// This method updates rows matching a filter expression by:
@@ -352,17 +865,63 @@ impl ElastiCube {
         replacement_batch: RecordBatch,
     ) -> Result<(usize, usize)> {
         // Validate the replacement batch schema
+        let replacement_batch = self.localize(vec![replacement_batch])?.remove(0);
         updates::validate_batch_schema(&self.arrow_schema, &replacement_batch.schema())?;
 
-        // Delete matching rows
-        let rows_deleted = self.delete_rows(filter_expr).await?;
+        // Delete matching rows, restoring them if the replacement is rejected
+        let previous = (self.data.clone(), self.row_count);
+        let rows_deleted = self.remove_matching_rows(filter_expr).await?;
 
         // Append the replacement batch
-        let rows_added = self.append_rows(replacement_batch)?;
+        let rows_added = match self.push_batches(vec![replacement_batch]) {
+            Ok(rows_added) => rows_added,
+            Err(e) => {
+                (self.data, self.row_count) = previous;
+                return Err(e);
+            }
+        };
+        self.record_version("update");
 
         Ok((rows_deleted, rows_added))
     }
 
+    /// Put incoming timestamps in the cube's time zone, if it has one
+    fn localize(&self, batches: Vec<RecordBatch>) -> Result<Vec<RecordBatch>> {
+        match self.schema.timezone() {
+            Some(tz) => batches.iter().map(|b| timezone::localize_batch(b, tz)).collect(),
+            None => Ok(batches),
+        }
+    }
+
+    /// Add validated batches to the data, enforcing the primary key
+    ///
+    /// Returns the number of rows appended, which is smaller than the input
+    /// when duplicate keys are ignored.
+    fn push_batches(&mut self, batches: Vec<RecordBatch>) -> Result<usize> {
+        self.ensure_in_memory("append rows to")?;
+        let batches = match self.schema.primary_key() {
+            Some(key) => {
+                let key = key.clone();
+                self.thaw()?;
+                let (existing, incoming) = key.merge(&self.arrow_schema, &self.data, batches)?;
+                self.data = existing;
+                incoming
+            }
+            None => batches,
+        };
+
+        let rows_added: usize = batches.iter().map(|b| b.num_rows()).sum();
+        if rows_added > 0 {
+            self.sorted = false;
+        }
+        self.data.extend(batches);
+        self.row_count =
+            self.data.iter().map(|b| b.num_rows()).sum::<usize>() + self.cold.row_count();
+        self.mark_changed();
+        self.repartition()?;
+        Ok(rows_added)
+    }
+
     /// Consolidate all data batches into a single batch
     ///
     /// This operation can improve query performance by reducing the number of
@@ -377,16 +936,24 @@ impl ElastiCube {
     /// println!("Consolidated from {} batches to 1 batch", old_batch_count);
     /// ```
     pub fn consolidate_batches(&mut self) -> Result<usize> {
+        self.ensure_in_memory("consolidate")?;
+        self.thaw()?;
         let old_batch_count = self.data.len();
 
-        if old_batch_count <= 1 {
+        if old_batch_count <= 1 && (self.sorted || self.sort_order.is_none()) {
             return Ok(old_batch_count);
         }
 
-        // Concatenate all batches into one
-        let consolidated = updates::concat_record_batches(&self.arrow_schema, &self.data)?;
+        // Concatenate all batches into one, restoring the sort order if declared
+        if self.sort_order.is_some() {
+            self.sort_data()?;
+        } else {
+            let consolidated = updates::concat_record_batches(&self.arrow_schema, &self.data)?;
+            self.data = vec![consolidated];
+        }
 
-        self.data = vec![consolidated];
+        // Partitioned cubes keep one batch per partition instead
+        self.repartition()?;
 
         Ok(old_batch_count)
     }
@@ -394,7 +961,735 @@ impl ElastiCube {
     /// Get the number of data batches in the cube
     ///
     /// Useful for monitoring fragmentation and deciding when to consolidate.
+    /// Compressed cold batches are counted separately by
+    /// [`compressed_batch_count`](Self::compressed_batch_count).
     pub fn batch_count(&self) -> usize {
         self.data.len()
     }
+
+    // ============================================================
+    // Cold Batch Compression
+    // ============================================================
+
+    /// Compress the batches no query has read for at least `idle`
+    ///
+    /// Compressed batches stay part of the cube: queries decompress the ones
+    /// they need on demand, skipping those whose zone maps rule them out, so
+    /// more history fits in memory at the cost of slower scans of old data.
+    /// Batches appended since the last call count as freshly read. Updates
+    /// other than plain appends decompress everything first.
+    ///
+    /// Returns the number of batches compressed.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // Run periodically, e.g. from a maintenance task
+    /// let compressed = cube.compress_cold_batches(Duration::from_secs(3600), BatchCompression::Zstd)?;
+    /// println!("{} batches, {} bytes compressed", cube.compressed_batch_count(), cube.compressed_bytes());
+    /// ```
+    pub fn compress_cold_batches(
+        &mut self,
+        idle: Duration,
+        compression: BatchCompression,
+    ) -> Result<usize> {
+        self.ensure_in_memory("compress")?;
+        if self.versions.is_some() {
+            // Snapshots hold the same buffers, so compressing would free nothing
+            return Err(Error::data("Cannot compress batches of a versioned cube"));
+        }
+
+        let now = Instant::now();
+        let (cold, hot): (Vec<_>, Vec<_>) = std::mem::take(&mut self.data)
+            .into_iter()
+            .partition(|batch| {
+                now.duration_since(self.statistics_cache.last_scan(batch, now)) >= idle
+            });
+        self.data = hot;
+
+        let compressed = cold.len();
+        if let Err(e) = self.cold.compress(cold.clone(), compression) {
+            self.data.extend(cold);
+            return Err(e);
+        }
+        Ok(compressed)
+    }
+
+    /// Decompress all cold batches back into memory
+    ///
+    /// Returns the number of batches decompressed.
+    pub fn decompress_batches(&mut self) -> Result<usize> {
+        let count = self.cold.len();
+        self.thaw()?;
+        Ok(count)
+    }
+
+    /// Number of batches held compressed
+    pub fn compressed_batch_count(&self) -> usize {
+        self.cold.len()
+    }
+
+    /// Memory used by the compressed batches, in bytes
+    pub fn compressed_bytes(&self) -> usize {
+        self.cold.compressed_bytes()
+    }
+
+    /// All batches, decompressing cold ones
+    pub(crate) fn materialized_batches(&self) -> Result<Vec<RecordBatch>> {
+        let mut batches = self.data.clone();
+        batches.extend(self.cold.scan(None, &self.arrow_schema)?);
+        Ok(batches)
+    }
+
+    /// Move the cold batches back into `data`
+    fn thaw(&mut self) -> Result<()> {
+        if !self.cold.is_empty() {
+            let batches = self.cold.thaw()?;
+            self.data.extend(batches);
+            self.sorted = false;
+        }
+        Ok(())
+    }
+
+    /// Start a transaction that applies several updates atomically
+    ///
+    /// Appends, deletes and updates made through the returned [`Transaction`]
+    /// only reach the cube on [`Transaction::commit`]. If any step fails, or
+    /// the transaction is rolled back or dropped, the cube is unchanged.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let mut tx = cube.begin_transaction();
+    /// tx.delete_rows("date = '2024-01-31'").await?;
+    /// tx.append_rows(corrected_batch)?;
+    /// tx.commit()?;
+    /// ```
+    pub fn begin_transaction(&mut self) -> Transaction<'_> {
+        Transaction::new(self)
+    }
+
+    // ============================================================
+    // Sort Order
+    // ============================================================
+
+    /// Sort the data by `columns` and keep that order declared
+    ///
+    /// All rows are sorted ascending (NULLs first) into one batch. Queries
+    /// on a sorted cube tell DataFusion about the ordering, so it can
+    /// aggregate sorted groups with less memory and skip sorts, and zone
+    /// maps on the leading column become tight. Appends and row-by-row
+    /// deletes break the order; [`consolidate_batches`](Self::consolidate_batches)
+    /// sorts again. Partitioned cubes sort within each partition only, so
+    /// the ordering is not advertised to queries.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// cube.sort_by(&["date", "region"])?;
+    /// assert!(cube.is_sorted());
+    /// ```
+    pub fn sort_by(&mut self, columns: &[impl AsRef<str>]) -> Result<()> {
+        if columns.is_empty() {
+            return Err(Error::config("Sort order needs at least one column"));
+        }
+        self.ensure_in_memory("sort")?;
+        self.thaw()?;
+        let columns: Vec<String> = columns.iter().map(|c| c.as_ref().to_string()).collect();
+        for column in &columns {
+            self.arrow_schema.field_with_name(column).map_err(|_| {
+                Error::schema(format!("Sort column '{}' not found in cube data", column))
+            })?;
+        }
+
+        self.sort_order = Some(columns);
+        self.sort_data()?;
+        self.repartition()
+    }
+
+    /// Declared sort columns, whether or not the data currently follows them
+    pub fn sort_order(&self) -> Option<&[String]> {
+        self.sort_order.as_deref()
+    }
+
+    /// Whether the data is currently in the declared sort order
+    pub fn is_sorted(&self) -> bool {
+        self.current_sort_order().is_some()
+    }
+
+    /// The ordering queries can rely on, if any
+    pub(crate) fn current_sort_order(&self) -> Option<&[String]> {
+        self.sort_order
+            .as_deref()
+            .filter(|_| self.sorted && self.partition_column.is_none() && self.cold.is_empty())
+    }
+
+    /// Sort the data by the declared sort order
+    fn sort_data(&mut self) -> Result<()> {
+        if let Some(columns) = &self.sort_order {
+            self.data = updates::sort_record_batches(&self.arrow_schema, &self.data, columns)?;
+            self.sorted = true;
+        }
+        Ok(())
+    }
+
+    // ============================================================
+    // Partitioning
+    // ============================================================
+
+    /// Physically partition the data by the values of `column`
+    ///
+    /// Afterwards every batch holds a single value of `column`, and new data
+    /// is split the same way as it arrives. Deletes whose filter only reads
+    /// `column` (e.g. `"month = '2024-01'"`) drop whole batches, and queries
+    /// filtering on it skip the batches that can't match. Partition by a
+    /// low-cardinality dimension such as a month or region; one batch per
+    /// distinct value is the minimum.
+    ///
+    /// Returns the number of partitions.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// cube.partition_by("month")?;
+    /// cube.delete_rows("month = '2024-01'").await?;
+    /// ```
+    pub fn partition_by(&mut self, column: &str) -> Result<usize> {
+        self.ensure_in_memory("partition")?;
+        self.thaw()?;
+        self.arrow_schema.field_with_name(column).map_err(|_| {
+            Error::schema(format!("Partition column '{}' not found in cube data", column))
+        })?;
+        self.partition_column = Some(column.to_string());
+        self.repartition()?;
+        self.partition_count()
+    }
+
+    /// Column the data is partitioned by, if any
+    pub fn partition_column(&self) -> Option<&str> {
+        self.partition_column.as_deref()
+    }
+
+    /// Number of distinct partition values (0 if the cube isn't partitioned)
+    pub fn partition_count(&self) -> Result<usize> {
+        match &self.partition_column {
+            Some(column) => crate::storage::count_partitions(&self.data, column),
+            None => Ok(0),
+        }
+    }
+
+    /// The batches a query with the given WHERE filter needs to scan
+    ///
+    /// Batches are left out when their partition value or their zone maps
+    /// (per-batch min/max, see [`ZoneMap`](crate::optimization::ZoneMap))
+    /// show that no row can satisfy the filter.
+    pub(crate) fn scan_batches(&self, filter: Option<&str>) -> Vec<RecordBatch> {
+        let Some(filter) = filter else {
+            return self.data.clone();
+        };
+        let mut keep = vec![true; self.data.len()];
+
+        if let Some(column) = &self.partition_column {
+            let columns = [column.as_str()];
+            let predicate = Predicate::compile_restricted(filter, &self.arrow_schema, &columns);
+            if let Some(predicate) = predicate {
+                for (keep, batch) in keep.iter_mut().zip(&self.data) {
+                    // Keep batches the predicate can't be evaluated on
+                    *keep = predicate.matches_first_row(batch).unwrap_or(true);
+                }
+            }
+        }
+
+        let columns: Vec<&str> = self
+            .arrow_schema
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect();
+        let predicate = Predicate::compile_restricted(filter, &self.arrow_schema, &columns);
+        if let Some(predicate) = predicate {
+            let statistics = self.statistics_cache.get(&self.data);
+            let schema = self.arrow_schema.clone();
+            if let Some(zones) = statistics.prune(predicate.physical_expr(), schema) {
+                for (keep, zone) in keep.iter_mut().zip(zones) {
+                    *keep &= zone;
+                }
+            }
+        }
+
+        self.data
+            .iter()
+            .zip(keep)
+            .filter(|(_, keep)| *keep)
+            .map(|(batch, _)| batch.clone())
+            .collect()
+    }
+
+    /// The table queries read the cube from
+    ///
+    /// In-memory cubes are served from the batches [`scan_batches`](Self::scan_batches)
+    /// keeps for `filter`, with the current sort order declared. Lazy cubes
+    /// get a listing table over their Parquet files, which DataFusion scans
+    /// with its own row group and page pruning.
+    pub(crate) fn table_provider(&self, filter: Option<&str>) -> Result<Arc<dyn TableProvider>> {
+        if let Some(path) = &self.parquet_path {
+            return crate::storage::parquet_table(path, self.arrow_schema.clone());
+        }
+
+        let mut batches = self.scan_batches(filter);
+        self.statistics_cache.record_scan(&batches);
+        if filter.is_none() && self.cold.is_empty() {
+            return self
+                .sessions
+                .table(&batches, || self.memory_table(batches.clone()));
+        }
+        batches.extend(self.cold.scan(filter, &self.arrow_schema)?);
+        self.memory_table(batches)
+    }
+
+    /// Fingerprint of the cube's name, columns and files
+    ///
+    /// Cached query results are keyed by it along with the
+    /// [data version](Self::data_version), so results kept by a disk cache
+    /// are never served for another cube or after its data changes. Files
+    /// read in place are not covered; see [`crate::cache::path_stamp`].
+    pub(crate) fn content_fingerprint(&self) -> u64 {
+        crate::cache::stable_hash(&format!(
+            "{}|{:?}|{:?}",
+            self.schema.name(),
+            self.arrow_schema,
+            self.parquet_path,
+        ))
+    }
+
+    /// Generation of the current data, which no other data in the process has
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Hash of the data, bridges included, the same in every process
+    /// holding the same data
+    ///
+    /// Computed once per generation, so a cube loaded again from unchanged
+    /// data, in this process or after a restart, finds the results a disk
+    /// cache kept for it.
+    pub(crate) fn data_version(&self) -> u64 {
+        let mut cached = self.data_version.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((generation, version)) = *cached {
+            if generation == self.generation {
+                return version;
+            }
+        }
+
+        let mut hasher = crate::cache::StableHasher::default();
+        crate::cache::hash_batches(&self.data, &mut hasher);
+        self.cold.hash(&mut hasher);
+        for bridge in &self.bridges {
+            hasher.write(bridge.name().as_bytes());
+            crate::cache::hash_batches(bridge.batches(), &mut hasher);
+        }
+        let version = hasher.finish();
+        *cached = Some((self.generation, version));
+        version
+    }
+
+    /// Record that the data changed, so results computed from it go stale
+    fn mark_changed(&mut self) {
+        self.generation = next_generation();
+    }
+
+    /// A DataFusion session for querying the cube, with its UDFs registered
+    ///
+    /// The session state is built once per configuration and reused.
+    pub(crate) fn session_context(&self, config: &OptimizationConfig) -> Result<SessionContext> {
+        self.sessions.context(config, &self.udfs)
+    }
+
+    /// The query result cache for `config`, shared by all queries on the cube
+    pub(crate) fn query_cache(
+        &self,
+        config: &OptimizationConfig,
+    ) -> Result<Option<Arc<QueryCache>>> {
+        self.sessions.query_cache(config)
+    }
+
+    fn memory_table(&self, batches: Vec<RecordBatch>) -> Result<Arc<dyn TableProvider>> {
+        // MemTable takes partitions; all batches go in a single one
+        let partitions = vec![batches];
+        let mut table = MemTable::try_new(self.arrow_schema.clone(), partitions)
+            .map_err(|e| Error::query(format!("Failed to create MemTable: {}", e)))?;
+        if let Some(columns) = self.current_sort_order() {
+            let ordering = columns.iter().map(|c| ident(c).sort(true, true)).collect();
+            table = table.with_sort_order(vec![ordering]);
+        }
+        Ok(Arc::new(table))
+    }
+
+    /// Split any batch holding several partition values
+    fn repartition(&mut self) -> Result<()> {
+        if let Some(column) = &self.partition_column {
+            self.data = crate::storage::partition_batches(&self.data, column)?;
+        }
+        Ok(())
+    }
+
+    // ============================================================
+    // Retention
+    // ============================================================
+
+    /// Keep only rows whose `column` is at most `max_age` old
+    ///
+    /// `column` must be a date or timestamp column. Expired rows are dropped
+    /// right away and again after every append, so a rolling-window cube
+    /// stays bounded; call [`prune_expired`](Self::prune_expired) to expire
+    /// rows as time passes without new data. Rows with a NULL time are kept.
+    ///
+    /// Returns the number of rows removed immediately.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// cube.set_retention("timestamp", Duration::from_secs(90 * 24 * 60 * 60))?;
+    /// ```
+    pub fn set_retention(&mut self, column: &str, max_age: Duration) -> Result<usize> {
+        self.ensure_in_memory("expire rows from")?;
+        self.retention = Some(RetentionPolicy::new(column, max_age, &self.arrow_schema)?);
+        self.prune_expired()
+    }
+
+    /// Remove the retention policy; no more rows are expired
+    pub fn clear_retention(&mut self) {
+        self.retention = None;
+    }
+
+    /// The retention column and maximum age, if a policy is set
+    pub fn retention(&self) -> Option<(&str, Duration)> {
+        self.retention
+            .as_ref()
+            .map(|policy| (policy.column.as_str(), policy.max_age))
+    }
+
+    /// Drop the rows older than the retention policy allows
+    ///
+    /// Returns the number of rows removed (0 without a policy). With
+    /// versioning enabled, a non-empty prune is recorded as an "expire"
+    /// version.
+    pub fn prune_expired(&mut self) -> Result<usize> {
+        let removed = self.expire_rows()?;
+        if removed > 0 {
+            self.record_version("expire");
+        }
+        Ok(removed)
+    }
+
+    /// Apply the retention policy without recording a version
+    fn expire_rows(&mut self) -> Result<usize> {
+        let Some(policy) = self.retention.clone() else {
+            return Ok(0);
+        };
+        let now = SystemTime::now();
+        // Compressed batches are only decompressed if their zone maps allow
+        // expired rows
+        let cold_expires = !self.cold.is_empty()
+            && self.cold.zone_maps(&policy.column).is_none_or(|zones| {
+                zones.iter().any(|zone| policy.may_expire(zone.min.as_ref(), now))
+            });
+        if cold_expires {
+            self.thaw()?;
+        }
+        let (data, removed) = policy.prune(&self.data, now)?;
+        if removed > 0 {
+            self.data = data;
+            self.row_count -= removed;
+            self.mark_changed();
+        }
+        Ok(removed)
+    }
+
+    // ============================================================
+    // Versioning
+    // ============================================================
+
+    /// Start recording a snapshot version on every data change
+    ///
+    /// The current data becomes version 0. Afterwards `append_rows`,
+    /// `append_batches`, `delete_rows`, `update_rows` and `refresh` each add
+    /// a version that [`as_of`](Self::as_of) can return. Snapshots share
+    /// unchanged Arrow buffers, but deleted rows stay in memory until pruned
+    /// with [`prune_history`](Self::prune_history). Enabling versioning again
+    /// has no effect. Compressed cold batches are decompressed first, which
+    /// fails if they can't be read back.
+    pub fn enable_versioning(&mut self) -> Result<()> {
+        if self.versions.is_none() {
+            self.thaw()?;
+            self.versions = Some(VersionLog::default());
+            self.record_version("initial");
+        }
+        Ok(())
+    }
+
+    /// Whether data changes are recorded as versions
+    pub fn is_versioned(&self) -> bool {
+        self.versions.is_some()
+    }
+
+    /// Recorded versions, oldest first (empty unless versioning is enabled)
+    pub fn history(&self) -> Vec<CubeVersion> {
+        self.versions
+            .as_ref()
+            .map(VersionLog::history)
+            .unwrap_or_default()
+    }
+
+    /// A read-only copy of the cube as it was at a past version
+    ///
+    /// Accepts a version number or a `SystemTime` (the latest version
+    /// created at or before it). The returned cube has no history of its own.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// cube.enable_versioning()?;
+    /// cube.delete_rows("region = 'North'").await?;
+    ///
+    /// let before = Arc::new(cube.as_of(0)?);
+    /// let result = before.query()?.select(&["COUNT(*)"]).execute().await?;
+    /// ```
+    pub fn as_of(&self, as_of: impl Into<AsOf>) -> Result<ElastiCube> {
+        let versions = self
+            .versions
+            .as_ref()
+            .ok_or_else(|| Error::data("Versioning is not enabled on this cube"))?;
+        let data = versions.data_as_of(as_of.into())?.to_vec();
+
+        let mut snapshot = self.clone();
+        snapshot.row_count = data.iter().map(|b| b.num_rows()).sum();
+        snapshot.data = data;
+        snapshot.mark_changed();
+        snapshot.versions = None;
+        snapshot.sorted = false;
+        snapshot.cold = ColdStore::default();
+        Ok(snapshot)
+    }
+
+    /// Keep only the newest `keep` versions, releasing older snapshots
+    pub fn prune_history(&mut self, keep: usize) {
+        if let Some(versions) = &mut self.versions {
+            versions.prune(keep);
+        }
+    }
+
+    /// Record the current data as a version if versioning is enabled
+    fn record_version(&mut self, operation: &str) {
+        if let Some(versions) = &mut self.versions {
+            versions.record(operation, &self.data, self.row_count);
+        }
+    }
+
+    // ============================================================
+    // Refresh Operations
+    // ============================================================
+
+    /// Reload all data from the source the cube was built from
+    ///
+    /// The reloaded data must still have the cube's columns; new source
+    /// columns are handled by the builder's drift policy and listed in
+    /// [`load_report`](Self::load_report) (see
+    /// `ElastiCubeBuilder::with_drift_policy`). The source is read on a
+    /// blocking thread, so this is safe to await from async code.
+    ///
+    /// # Returns
+    /// Number of rows in the cube after the refresh
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let mut cube = ElastiCubeBuilder::new("sales")
+    ///     .load_csv("sales.csv")
+    ///     .build()?;
+    ///
+    /// // ... later, after sales.csv has changed
+    /// let rows = cube.refresh().await?;
+    /// ```
+    pub async fn refresh(&mut self) -> Result<usize> {
+        // Lazy cubes read the files on every query; only the count can be stale
+        if let Some(path) = &self.parquet_path {
+            let files = crate::storage::parquet_files(Path::new(path))?;
+            let (_, row_count) = crate::storage::inspect_parquet(&files)?;
+            self.row_count = row_count;
+            self.mark_changed();
+            return Ok(row_count);
+        }
+
+        let (batches, report) = self.load_from_source().await?;
+        self.accept_drift(&report.drift)?;
+        self.load_report = report;
+        let batches = match self.schema.primary_key() {
+            Some(key) => key.dedupe(&self.arrow_schema, batches)?,
+            None => batches,
+        };
+
+        self.row_count = batches.iter().map(|b| b.num_rows()).sum();
+        self.data = batches;
+        self.cold = ColdStore::default();
+        self.mark_changed();
+        if self.sort_order.is_some() {
+            self.sort_data()?;
+        }
+        self.repartition()?;
+        self.record_version("refresh");
+
+        Ok(self.row_count)
+    }
+
+    /// Append only the new rows of the source the cube was built from
+    ///
+    /// `filter` is a SQL predicate selecting the new rows, in which `$last`
+    /// stands for the largest value of the compared column already in the
+    /// cube (e.g. `"updated_at > $last"` or `"id > $last"`). If the cube has
+    /// no value for that column yet, every row is appended.
+    ///
+    /// `$last` is the column's maximum at the time of the call, not a
+    /// watermark kept from the previous refresh: rows appended by other
+    /// means count towards it, and rows removed by deletes or retention no
+    /// longer do, so their source rows may be appended again.
+    ///
+    /// The source is still read in full; only matching rows are kept.
+    ///
+    /// # Returns
+    /// Number of rows appended
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let added = cube.refresh_incremental("updated_at > $last").await?;
+    /// println!("Appended {} new rows", added);
+    /// ```
+    pub async fn refresh_incremental(&mut self, filter: &str) -> Result<usize> {
+        self.thaw()?;
+        let resolved = updates::resolve_incremental_filter(&self.arrow_schema, &self.data, filter).await?;
+        let (batches, report) = self.load_from_source().await?;
+        self.accept_drift(&report.drift)?;
+        self.load_report = report;
+
+        let new_batches = match resolved {
+            Some(resolved) => {
+                let predicate = Predicate::compile(&resolved, &self.arrow_schema)?;
+                batches
+                    .iter()
+                    .map(|batch| predicate.filter(batch))
+                    .collect::<Result<Vec<_>>>()?
+            }
+            None => batches,
+        };
+
+        let new_batches = new_batches.into_iter().filter(|b| b.num_rows() > 0).collect();
+        self.append_batches(new_batches)
+    }
+
+    /// Load the cube's source on a blocking thread and validate its schema
+    async fn load_from_source(&self) -> Result<(Vec<RecordBatch>, LoadReport)> {
+        let source = self.source.clone().ok_or_else(|| {
+            Error::data("Cube has no source to refresh from (it was not created by ElastiCubeBuilder)")
+        })?;
+        let columns = self.source_columns.clone();
+
+        let settings = self.load_settings.clone();
+        let label = self.schema.name().to_string();
+        let load = move || settings.load(&label, source.as_ref(), columns.as_deref());
+
+        // Sources block (and some run their own runtime), so keep them off the async executor
+        #[cfg(not(target_arch = "wasm32"))]
+        let (_, batches, skipped_rows) = tokio::task::spawn_blocking(load)
+            .await
+            .map_err(|e| Error::data(format!("Source load task failed: {}", e)))??;
+
+        // The browser has no blocking threads to hand the load to
+        #[cfg(target_arch = "wasm32")]
+        let (_, batches, skipped_rows) = load()?;
+
+        let batches = batches
+            .iter()
+            .map(|batch| self.apply_renames(self.load_settings.map_batch(batch)?))
+            .collect::<Result<Vec<_>>>()?;
+        let batches = if self.load_settings.ignore_extra_columns {
+            let keep: Vec<String> =
+                self.arrow_schema.fields().iter().map(|f| f.name().clone()).collect();
+            batches
+                .iter()
+                .map(|batch| keep_columns(batch, &keep))
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            batches
+        };
+        let batches = match batches.first().map(|batch| batch.schema()) {
+            Some(schema) => self.load_settings.transform(schema, batches)?.1,
+            None => batches,
+        };
+        let batches = self.localize(batches)?;
+        let mut report = LoadReport {
+            skipped_rows,
+            ..LoadReport::default()
+        };
+        let batches = match batches.first().map(|batch| batch.schema()) {
+            Some(schema) => {
+                let (_, batches, coercions) = coerce_batches(
+                    &self.arrow_schema,
+                    schema,
+                    batches,
+                    self.load_settings.coercion,
+                )?;
+                report.coercions = coercions;
+                batches
+            }
+            None => batches,
+        };
+        let (schema, batches) = match batches.first().map(|batch| batch.schema()) {
+            Some(loaded) => {
+                report.drift = SchemaDrift::detect(&self.arrow_schema, &loaded);
+                let policy = self.load_settings.drift_policy;
+                drift::resolve(&report.drift, policy, &self.arrow_schema, batches)?
+            }
+            None => (Arc::clone(&self.arrow_schema), batches),
+        };
+        for batch in &batches {
+            updates::validate_batch_schema(&schema, &batch.schema())?;
+        }
+        report.violations = self.load_settings.check_constraints(&schema, &batches)?;
+
+        Ok((batches, report))
+    }
+
+    /// Add the new source columns of a refresh, if the drift policy says to
+    ///
+    /// New columns become dimensions or measures as automatic classification
+    /// would have it; rows already in the cube and its history hold NULL.
+    fn accept_drift(&mut self, drift: &SchemaDrift) -> Result<()> {
+        if self.load_settings.drift_policy != DriftPolicy::AutoAdd || drift.added.is_empty() {
+            return Ok(());
+        }
+        self.thaw()?;
+        for field in &drift.added {
+            let data_type = field.data_type().clone();
+            match crate::inference::suggest(field).aggregation {
+                Some(agg) => self.schema.add_measure(Measure::new(field.name(), data_type, agg))?,
+                None => self.schema.add_dimension(Dimension::new(field.name(), data_type))?,
+            }
+        }
+
+        let schema = Arc::new(drift::with_columns(&self.arrow_schema, &drift.added));
+        let widen = |batch: &RecordBatch| -> Result<RecordBatch> {
+            let mut columns = batch.columns().to_vec();
+            columns.extend(
+                drift
+                    .added
+                    .iter()
+                    .map(|field| new_null_array(field.data_type(), batch.num_rows())),
+            );
+            Ok(RecordBatch::try_new(Arc::clone(&schema), columns)?)
+        };
+        self.data = self.data.iter().map(&widen).collect::<Result<_>>()?;
+        if let Some(versions) = &mut self.versions {
+            versions.map_batches(widen)?;
+        }
+        self.arrow_schema = schema;
+        self.mark_changed();
+
+        // Cached statistics and tables don't know the new columns
+        self.statistics_cache = Arc::new(StatisticsCache::default());
+        self.sessions = Arc::new(SessionCache::default());
+        Ok(())
+    }
 }
@@ -0,0 +1,408 @@
+//! Data profiling
+//!
+//! [`ElastiCube::profile`] summarizes what is in each column: how many
+//! values are missing, how many distinct values there are, the range, the
+//! most frequent values and, for numbers, how they are spread. Null counts
+//! and ranges cover all rows; the value counts and histograms of a cube
+//! larger than [`ProfileOptions::sample_rows`] come from evenly spaced rows.
+
+use super::ElastiCube;
+use crate::error::Result;
+use arrow::array::{Array, ArrayRef, AsArray, Float64Array, StringArray, UInt32Array, UInt64Array};
+use arrow::compute::{cast, take_record_batch};
+use arrow::datatypes::{DataType, Field, Float64Type, Schema as ArrowSchema};
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::array_value_to_string;
+use datafusion::scalar::ScalarValue;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// How much work [`ElastiCube::profile_with`] does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileOptions {
+    /// Most rows read for distinct counts, top values and histograms
+    pub sample_rows: usize,
+
+    /// Number of most frequent values listed per column
+    pub top_k: usize,
+
+    /// Number of equal-width bins in a numeric column's histogram
+    pub histogram_bins: usize,
+}
+
+impl Default for ProfileOptions {
+    fn default() -> Self {
+        Self {
+            sample_rows: 100_000,
+            top_k: 10,
+            histogram_bins: 10,
+        }
+    }
+}
+
+impl ProfileOptions {
+    /// Set the most rows sampled
+    pub fn with_sample_rows(mut self, rows: usize) -> Self {
+        self.sample_rows = rows.max(1);
+        self
+    }
+
+    /// Set the number of most frequent values listed
+    pub fn with_top_k(mut self, k: usize) -> Self {
+        self.top_k = k;
+        self
+    }
+
+    /// Set the number of histogram bins
+    pub fn with_histogram_bins(mut self, bins: usize) -> Self {
+        self.histogram_bins = bins.max(1);
+        self
+    }
+}
+
+/// Profile of a cube's data
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeProfile {
+    /// Rows in the cube
+    pub row_count: usize,
+
+    /// Rows the value counts and histograms were computed from
+    pub sampled_rows: usize,
+
+    /// One profile per stored column, in schema order
+    pub columns: Vec<ColumnProfile>,
+}
+
+/// Profile of one column
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnProfile {
+    /// Column name
+    pub name: String,
+
+    /// Arrow data type
+    pub data_type: DataType,
+
+    /// NULL values in the column
+    pub null_count: usize,
+
+    /// Share of rows that are NULL, between 0 and 1
+    pub null_rate: f64,
+
+    /// Distinct non-NULL values, counted in the sample
+    pub distinct_count: usize,
+
+    /// Smallest value, if the type is ordered and not all values are NULL
+    pub min: Option<ScalarValue>,
+
+    /// Largest value, if the type is ordered and not all values are NULL
+    pub max: Option<ScalarValue>,
+
+    /// Most frequent values in the sample with their counts, most frequent
+    /// first
+    pub top_values: Vec<(String, usize)>,
+
+    /// Distribution of a numeric column over the sample
+    pub histogram: Option<Vec<HistogramBin>>,
+}
+
+/// Values falling in `[lower, upper)`, or `[lower, upper]` for the last bin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistogramBin {
+    /// Lower bound
+    pub lower: f64,
+
+    /// Upper bound
+    pub upper: f64,
+
+    /// Values in the bin
+    pub count: usize,
+}
+
+impl CubeProfile {
+    /// Look up the profile of a column
+    pub fn column(&self, name: &str) -> Option<&ColumnProfile> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// The profile as a table with one row per column, for display
+    ///
+    /// Ranges, top values and histograms are rendered as text.
+    pub fn to_record_batch(&self) -> Result<RecordBatch> {
+        let text = |value: &Option<ScalarValue>| value.as_ref().map(|v| v.to_string());
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("column", DataType::Utf8, false),
+            Field::new("data_type", DataType::Utf8, false),
+            Field::new("null_count", DataType::UInt64, false),
+            Field::new("null_rate", DataType::Float64, false),
+            Field::new("distinct_count", DataType::UInt64, false),
+            Field::new("min", DataType::Utf8, true),
+            Field::new("max", DataType::Utf8, true),
+            Field::new("top_values", DataType::Utf8, false),
+            Field::new("histogram", DataType::Utf8, true),
+        ]));
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from_iter_values(self.columns.iter().map(|c| &c.name))),
+            Arc::new(StringArray::from_iter_values(
+                self.columns.iter().map(|c| c.data_type.to_string()),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                self.columns.iter().map(|c| c.null_count as u64),
+            )),
+            Arc::new(Float64Array::from_iter_values(self.columns.iter().map(|c| c.null_rate))),
+            Arc::new(UInt64Array::from_iter_values(
+                self.columns.iter().map(|c| c.distinct_count as u64),
+            )),
+            Arc::new(StringArray::from_iter(self.columns.iter().map(|c| text(&c.min)))),
+            Arc::new(StringArray::from_iter(self.columns.iter().map(|c| text(&c.max)))),
+            Arc::new(StringArray::from_iter_values(self.columns.iter().map(|c| {
+                let values: Vec<String> = c
+                    .top_values
+                    .iter()
+                    .map(|(value, count)| format!("{} ({})", value, count))
+                    .collect();
+                values.join(", ")
+            }))),
+            Arc::new(StringArray::from_iter(self.columns.iter().map(|c| {
+                let bins = c.histogram.as_ref()?;
+                let bins: Vec<String> = bins
+                    .iter()
+                    .map(|bin| format!("[{}, {}): {}", bin.lower, bin.upper, bin.count))
+                    .collect();
+                Some(bins.join(", "))
+            }))),
+        ];
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+}
+
+impl ElastiCube {
+    /// Profile every stored column with the default options
+    ///
+    /// Batches compressed by
+    /// [`compress_cold_batches`](Self::compress_cold_batches) are not
+    /// included.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let profile = cube.profile()?;
+    /// let region = profile.column("region").unwrap();
+    /// println!("{:.1}% missing, {} distinct", region.null_rate * 100.0, region.distinct_count);
+    ///
+    /// // Or as a table
+    /// arrow::util::pretty::print_batches(&[profile.to_record_batch()?])?;
+    /// ```
+    pub fn profile(&self) -> Result<CubeProfile> {
+        self.profile_with(ProfileOptions::default())
+    }
+
+    /// Profile every stored column
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let options = ProfileOptions::default().with_sample_rows(10_000).with_top_k(5);
+    /// let profile = cube.profile_with(options)?;
+    /// ```
+    pub fn profile_with(&self, options: ProfileOptions) -> Result<CubeProfile> {
+        self.ensure_in_memory("profile")?;
+        let statistics = self.statistics();
+        let row_count = statistics.row_count;
+        let sample = sample_rows(&self.data, row_count, options.sample_rows)?;
+        let sampled_rows = sample.iter().map(|batch| batch.num_rows()).sum();
+
+        let columns = self
+            .arrow_schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let null_count = statistics
+                    .column_stats
+                    .get(index)
+                    .map_or(0, |stats| stats.null_count);
+                let zone_maps = statistics
+                    .column_stats
+                    .get(index)
+                    .map_or(&[][..], |stats| stats.zone_maps.as_slice());
+                let arrays: Vec<ArrayRef> =
+                    sample.iter().map(|batch| batch.column(index).clone()).collect();
+                let counts = value_counts(&arrays);
+
+                let mut top_values: Vec<(String, usize)> = counts.into_iter().collect();
+                let distinct_count = top_values.len();
+                top_values.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                top_values.truncate(options.top_k);
+
+                Ok(ColumnProfile {
+                    name: field.name().clone(),
+                    data_type: field.data_type().clone(),
+                    null_count,
+                    null_rate: if row_count > 0 {
+                        null_count as f64 / row_count as f64
+                    } else {
+                        0.0
+                    },
+                    distinct_count,
+                    min: extreme(zone_maps.iter().map(|zone| &zone.min), Ordering::Less),
+                    max: extreme(zone_maps.iter().map(|zone| &zone.max), Ordering::Greater),
+                    top_values,
+                    histogram: if field.data_type().is_numeric() {
+                        Some(histogram(&arrays, options.histogram_bins)?)
+                    } else {
+                        None
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(CubeProfile {
+            row_count,
+            sampled_rows,
+            columns,
+        })
+    }
+}
+
+/// Every `n`th row of `batches`, for at most `limit` rows
+fn sample_rows(batches: &[RecordBatch], rows: usize, limit: usize) -> Result<Vec<RecordBatch>> {
+    if rows <= limit {
+        return Ok(batches.to_vec());
+    }
+    let stride = rows.div_ceil(limit);
+    let mut offset = 0;
+    let mut sample = Vec::with_capacity(batches.len());
+    for batch in batches {
+        let first = (stride - offset % stride) % stride;
+        let rows = (first..batch.num_rows()).step_by(stride);
+        let indices = UInt32Array::from_iter_values(rows.map(|row| row as u32));
+        sample.push(take_record_batch(batch, &indices)?);
+        offset += batch.num_rows();
+    }
+    Ok(sample)
+}
+
+/// Occurrences of each non-NULL value, keyed by its display form
+fn value_counts(arrays: &[ArrayRef]) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for array in arrays {
+        for row in (0..array.len()).filter(|&row| array.is_valid(row)) {
+            if let Ok(value) = array_value_to_string(array, row) {
+                *counts.entry(value).or_insert(0) += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// The smallest (`Less`) or largest (`Greater`) of the non-NULL values
+fn extreme<'a>(
+    values: impl Iterator<Item = &'a Option<ScalarValue>>,
+    wanted: Ordering,
+) -> Option<ScalarValue> {
+    values
+        .flatten()
+        .filter(|value| !value.is_null())
+        .fold(None, |best: Option<&ScalarValue>, value| match best {
+            Some(best) if value.partial_cmp(best) != Some(wanted) => Some(best),
+            _ => Some(value),
+        })
+        .cloned()
+}
+
+/// Equal-width histogram of numeric arrays
+fn histogram(arrays: &[ArrayRef], bins: usize) -> Result<Vec<HistogramBin>> {
+    let mut values = Vec::new();
+    for array in arrays {
+        let array = cast(array, &DataType::Float64)?;
+        let finite = array.as_primitive::<Float64Type>().iter().flatten().filter(|v| v.is_finite());
+        values.extend(finite);
+    }
+    let Some(min) = values.iter().copied().reduce(f64::min) else {
+        return Ok(Vec::new());
+    };
+    let max = values.iter().copied().fold(min, f64::max);
+    if min == max {
+        return Ok(vec![HistogramBin {
+            lower: min,
+            upper: max,
+            count: values.len(),
+        }]);
+    }
+
+    let width = (max - min) / bins as f64;
+    let mut counts = vec![0; bins];
+    for value in values {
+        let bin = (((value - min) / width) as usize).min(bins - 1);
+        counts[bin] += 1;
+    }
+    Ok(counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, count)| HistogramBin {
+            lower: min + width * i as f64,
+            upper: if i + 1 == bins { max } else { min + width * (i + 1) as f64 },
+            count,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::{AggFunc, CubeSchema, Dimension, Measure};
+    use arrow::array::Int64Array;
+
+    #[test]
+    fn test_profile() {
+        let mut schema = CubeSchema::new("sales");
+        schema.add_dimension(Dimension::new("region", DataType::Utf8)).unwrap();
+        schema.add_measure(Measure::new("amount", DataType::Int64, AggFunc::Sum)).unwrap();
+        let arrow_schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, true),
+            Field::new("amount", DataType::Int64, false),
+        ]));
+        let batch = |regions: Vec<Option<&str>>, amounts: Vec<i64>| {
+            RecordBatch::try_new(
+                Arc::clone(&arrow_schema),
+                vec![
+                    Arc::new(StringArray::from(regions)),
+                    Arc::new(Int64Array::from(amounts)),
+                ],
+            )
+            .unwrap()
+        };
+        let data = vec![
+            batch(vec![Some("North"), Some("South"), None], vec![0, 10, 20]),
+            batch(vec![Some("North"), Some("North")], vec![30, 40]),
+        ];
+        let cube = ElastiCube::new(schema, Arc::clone(&arrow_schema), data).unwrap();
+
+        let options = ProfileOptions::default().with_top_k(1).with_histogram_bins(2);
+        let profile = cube.profile_with(options).unwrap();
+        assert_eq!((profile.row_count, profile.sampled_rows), (5, 5));
+
+        let region = profile.column("region").unwrap();
+        assert_eq!(region.null_count, 1);
+        assert_eq!(region.null_rate, 0.2);
+        assert_eq!(region.distinct_count, 2);
+        assert_eq!(region.top_values, vec![("North".to_string(), 3)]);
+        assert_eq!(region.max, Some(ScalarValue::from("South")));
+        assert!(region.histogram.is_none());
+
+        let amount = profile.column("amount").unwrap();
+        assert_eq!(amount.min, Some(ScalarValue::Int64(Some(0))));
+        assert_eq!(amount.max, Some(ScalarValue::Int64(Some(40))));
+        let bins = amount.histogram.as_ref().unwrap();
+        let counts: Vec<usize> = bins.iter().map(|bin| bin.count).collect();
+        assert_eq!(counts, vec![2, 3]);
+
+        let table = profile.to_record_batch().unwrap();
+        assert_eq!(table.num_rows(), 2);
+        assert_eq!(table.schema().field(7).name(), "top_values");
+
+        // Every other row of the five
+        let sampled = cube.profile_with(ProfileOptions::default().with_sample_rows(3)).unwrap();
+        assert_eq!(sampled.sampled_rows, 3);
+        assert_eq!(sampled.column("amount").unwrap().distinct_count, 3);
+    }
+}
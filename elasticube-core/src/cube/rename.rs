@@ -0,0 +1,208 @@
+//! Renaming dimensions and measures
+//!
+//! A rename relabels the Arrow column (in the data, the version history and
+//! any pre-aggregation) and rewrites whatever refers to the old name:
+//! calculated measure and virtual dimension expressions, hierarchy levels,
+//! dimension attribute columns, the primary key, the sort order and the
+//! partition and retention columns. The cube remembers its renames, so a
+//! refresh still reads the source's original columns and SQL written
+//! against the old names can be brought up to date with
+//! [`ElastiCube::rewrite_renamed_columns`].
+
+use super::ElastiCube;
+use crate::error::{Error, Result};
+use crate::optimization::{SessionCache, StatisticsCache};
+use crate::query::quote_ident;
+use arrow::datatypes::{Field, Schema as ArrowSchema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use std::sync::Arc;
+
+impl ElastiCube {
+    /// Rename a dimension, updating everything that refers to it
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// cube.rename_dimension("cust_region", "region")?;
+    /// ```
+    pub fn rename_dimension(&mut self, old: &str, new: &str) -> Result<()> {
+        if !self.schema.has_dimension(old) {
+            return Err(Error::dimension(format!("Dimension '{}' not found", old)));
+        }
+        self.rename_column(old, new)
+    }
+
+    /// Rename a measure, updating everything that refers to it
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// cube.rename_measure("sales", "revenue")?;
+    ///
+    /// // A calculated measure defined as "sales - cost" is now "revenue - cost"
+    /// let sql = cube.rewrite_renamed_columns("SELECT SUM(sales) FROM cube");
+    /// assert_eq!(sql, "SELECT SUM(revenue) FROM cube");
+    /// ```
+    pub fn rename_measure(&mut self, old: &str, new: &str) -> Result<()> {
+        if !self.schema.has_measure(old) {
+            return Err(Error::measure(format!("Measure '{}' not found", old)));
+        }
+        self.rename_column(old, new)
+    }
+
+    /// Rewrite SQL written against earlier column names to the current ones
+    ///
+    /// Useful for queries stored outside the cube, such as dashboard
+    /// definitions. Names inside string literals are left alone.
+    pub fn rewrite_renamed_columns(&self, sql: &str) -> String {
+        self.renamed_columns
+            .iter()
+            .filter(|(old, _)| self.arrow_schema.field_with_name(old).is_err())
+            .fold(sql.to_string(), |sql, (old, new)| rename_identifier(&sql, old, new))
+    }
+
+    /// `batch` from the source, with renamed columns under their current names
+    pub(crate) fn apply_renames(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        self.renamed_columns
+            .iter()
+            .try_fold(batch, |batch, (old, new)| rename_batch_column(&batch, old, new))
+    }
+
+    fn rename_column(&mut self, old: &str, new: &str) -> Result<()> {
+        self.ensure_in_memory("rename columns of")?;
+        if new.is_empty() {
+            return Err(Error::schema("Column name cannot be empty"));
+        }
+        if self.arrow_schema.field_with_name(new).is_ok()
+            || self.schema.has_dimension(new)
+            || self.schema.has_measure(new)
+            || self.schema.has_virtual_dimension(new)
+            || self.schema.has_calculated_measure(new)
+            || self.get_bridge(new).is_some()
+        {
+            return Err(Error::schema(format!(
+                "Cannot rename '{}' to '{}': a column named '{}' already exists",
+                old, new, new
+            )));
+        }
+        let generation = self.generation;
+
+        for bridge in self.bridges.iter_mut().filter(|bridge| bridge.key() == old) {
+            bridge.rename_key(new)?;
+        }
+
+        self.thaw()?;
+        self.arrow_schema = rename_field(&self.arrow_schema, old, new);
+        self.data = self
+            .data
+            .iter()
+            .map(|batch| rename_batch_column(batch, old, new))
+            .collect::<Result<_>>()?;
+        if let Some(versions) = &mut self.versions {
+            versions.map_batches(|batch| rename_batch_column(batch, old, new))?;
+        }
+        self.schema.rename_column(old, new);
+
+        let rename = |column: &mut String| {
+            if column == old {
+                *column = new.to_string();
+            }
+        };
+        self.sort_order.iter_mut().flatten().for_each(rename);
+        self.partition_column.iter_mut().for_each(rename);
+        self.retention.iter_mut().for_each(|policy| rename(&mut policy.column));
+        self.load_settings.rename_column(old, new);
+
+        for (_, current) in self.renamed_columns.iter_mut().filter(|(_, current)| current == old) {
+            *current = new.to_string();
+        }
+        self.renamed_columns.push((old.to_string(), new.to_string()));
+
+        // Cached statistics, zone maps and tables still name the old column
+        self.mark_changed();
+        self.statistics_cache = Arc::new(StatisticsCache::default());
+        self.sessions = Arc::new(SessionCache::default());
+        self.rename_in_aggregations(old, new, generation)
+    }
+}
+
+/// `expr` with every reference to the column `old` changed to `new`
+///
+/// Quoted and bare references are rewritten; string literals are not.
+pub(crate) fn rename_identifier(expr: &str, old: &str, new: &str) -> String {
+    let pattern = format!(
+        r#"'(?:[^']|'')*'|{}|\b{}\b"#,
+        regex::escape(&quote_ident(old)),
+        regex::escape(old)
+    );
+    let Ok(re) = regex::Regex::new(&pattern) else {
+        return expr.to_string();
+    };
+
+    // Unquoted identifiers are lowercased by the SQL parser
+    let is_plain = new
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !new.starts_with(|c: char| c.is_ascii_digit());
+    re.replace_all(expr, |caps: &regex::Captures| {
+        let matched = &caps[0];
+        if matched.starts_with('\'') {
+            matched.to_string()
+        } else if matched.starts_with('"') || !is_plain {
+            quote_ident(new)
+        } else {
+            new.to_string()
+        }
+    })
+    .into_owned()
+}
+
+/// `schema` with the field `old` named `new`
+pub(crate) fn rename_field(schema: &ArrowSchema, old: &str, new: &str) -> SchemaRef {
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            if field.name() == old {
+                field.as_ref().clone().with_name(new)
+            } else {
+                field.as_ref().clone()
+            }
+        })
+        .collect();
+    Arc::new(ArrowSchema::new_with_metadata(fields, schema.metadata().clone()))
+}
+
+/// `batch` with the column `old` named `new`, if it has such a column
+pub(crate) fn rename_batch_column(
+    batch: &RecordBatch,
+    old: &str,
+    new: &str,
+) -> Result<RecordBatch> {
+    let schema = batch.schema();
+    if schema.field_with_name(old).is_err() {
+        return Ok(batch.clone());
+    }
+    Ok(RecordBatch::try_new(rename_field(&schema, old, new), batch.columns().to_vec())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_identifier() {
+        assert_eq!(rename_identifier("sales - cost", "sales", "revenue"), "revenue - cost");
+        assert_eq!(rename_identifier("SUM(\"sales\")", "sales", "revenue"), "SUM(\"revenue\")");
+        assert_eq!(
+            rename_identifier("sales_tax + sales", "sales", "revenue"),
+            "sales_tax + revenue"
+        );
+        assert_eq!(
+            rename_identifier("CASE WHEN sales > 0 THEN 'sales' END", "sales", "revenue"),
+            "CASE WHEN revenue > 0 THEN 'sales' END"
+        );
+        assert_eq!(
+            rename_identifier("sales * 2", "sales", "Net Sales"),
+            "\"Net Sales\" * 2"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Retention policies for rolling-window cubes
+//!
+//! A retention policy names a date or timestamp column and a maximum age.
+//! Rows older than that are dropped by [`ElastiCube::prune_expired`] and on
+//! every append, so cubes fed continuously (logs, telemetry) stay bounded.
+//!
+//! [`ElastiCube::prune_expired`]: super::ElastiCube::prune_expired
+
+use crate::error::{Error, Result};
+use arrow::array::{Array, ArrayRef, BooleanArray, Int64Array};
+use arrow::datatypes::{DataType, Schema as ArrowSchema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use crate::time::{Duration, SystemTime, UNIX_EPOCH};
+use datafusion::common::ScalarValue;
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// Time column and maximum age of the rows kept in a cube
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RetentionPolicy {
+    pub(crate) column: String,
+    pub(crate) max_age: Duration,
+}
+
+impl RetentionPolicy {
+    /// Create a policy after checking the column holds dates or timestamps
+    pub(crate) fn new(column: &str, max_age: Duration, schema: &ArrowSchema) -> Result<Self> {
+        let field = schema.field_with_name(column).map_err(|_| {
+            Error::schema(format!("Retention column '{}' not found in cube data", column))
+        })?;
+        match field.data_type() {
+            DataType::Timestamp(_, _) | DataType::Date32 | DataType::Date64 => Ok(Self {
+                column: column.to_string(),
+                max_age,
+            }),
+            other => Err(Error::schema(format!(
+                "Retention column '{}' must be a date or timestamp, found {:?}",
+                column, other
+            ))),
+        }
+    }
+
+    /// Drop the rows older than the maximum age as of `now`
+    ///
+    /// Rows with a NULL time are kept. Returns the remaining batches and the
+    /// number of rows removed.
+    pub(crate) fn prune(
+        &self,
+        batches: &[RecordBatch],
+        now: SystemTime,
+    ) -> Result<(Vec<RecordBatch>, usize)> {
+        let cutoff = self.cutoff(now);
+        let mut removed = 0;
+        let mut kept = Vec::with_capacity(batches.len());
+        for batch in batches {
+            let column = batch.column_by_name(&self.column).ok_or_else(|| {
+                Error::data(format!("Retention column '{}' not found in batch", self.column))
+            })?;
+            let millis = self.epoch_millis(column)?;
+            let mask: BooleanArray = (0..millis.len())
+                .map(|row| Some(millis.is_null(row) || millis.value(row) >= cutoff))
+                .collect();
+            let expired = mask.false_count();
+            if expired == 0 {
+                kept.push(batch.clone());
+                continue;
+            }
+            removed += expired;
+            if expired < batch.num_rows() {
+                kept.push(arrow::compute::filter_record_batch(batch, &mask)?);
+            }
+        }
+        Ok((kept, removed))
+    }
+
+    /// Whether a batch whose smallest retention value is `min` may hold
+    /// rows that are expired as of `now`
+    ///
+    /// An unknown minimum may; a NULL one means the batch only holds NULLs,
+    /// which are kept.
+    pub(crate) fn may_expire(&self, min: Option<&ScalarValue>, now: SystemTime) -> bool {
+        let Some(millis) = min
+            .and_then(|min| min.to_array().ok())
+            .and_then(|array| self.epoch_millis(&array).ok())
+        else {
+            return true;
+        };
+        millis.is_valid(0) && millis.value(0) < self.cutoff(now)
+    }
+
+    /// Oldest time kept as of `now`, in milliseconds since the Unix epoch
+    fn cutoff(&self, now: SystemTime) -> i64 {
+        now.checked_sub(self.max_age)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(i64::MIN, |since_epoch| since_epoch.as_millis() as i64)
+    }
+
+    /// A retention column as milliseconds since the Unix epoch
+    fn epoch_millis(&self, column: &ArrayRef) -> Result<Int64Array> {
+        // Timestamps are stored as UTC offsets from the epoch whatever their
+        // time zone, so the raw values can be compared directly
+        let (raw_type, scale) = match column.data_type() {
+            DataType::Timestamp(TimeUnit::Second, _) => (DataType::Int64, Scale::Mul(1_000)),
+            DataType::Timestamp(TimeUnit::Millisecond, _) => (DataType::Int64, Scale::Mul(1)),
+            DataType::Timestamp(TimeUnit::Microsecond, _) => (DataType::Int64, Scale::Div(1_000)),
+            DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+                (DataType::Int64, Scale::Div(1_000_000))
+            }
+            DataType::Date32 => (DataType::Int32, Scale::Mul(MILLIS_PER_DAY)),
+            DataType::Date64 => (DataType::Int64, Scale::Mul(1)),
+            other => {
+                return Err(Error::data(format!(
+                    "Retention column '{}' must be a date or timestamp, found {:?}",
+                    self.column, other
+                )))
+            }
+        };
+
+        let raw = arrow::compute::cast(column, &raw_type)?;
+        let raw = arrow::compute::cast(&raw, &DataType::Int64)?;
+        let raw = raw
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .ok_or_else(|| Error::data("Failed to read retention column"))?;
+        Ok(raw.unary(|value| match scale {
+            Scale::Mul(factor) => value.saturating_mul(factor),
+            Scale::Div(divisor) => value.div_euclid(divisor),
+        }))
+    }
+}
+
+/// Conversion from a column's unit to milliseconds
+#[derive(Clone, Copy)]
+enum Scale {
+    Mul(i64),
+    Div(i64),
+}
@@ -1,6 +1,10 @@
 //! Schema metadata for ElastiCube
 
-use super::{CalculatedMeasure, Dimension, Hierarchy, Measure, VirtualDimension};
+use super::rename::rename_identifier;
+use super::timezone::{localized_type, validate_timezone};
+use super::{
+    CalculatedMeasure, Dimension, DisplayFormat, Hierarchy, Measure, PrimaryKey, VirtualDimension,
+};
 use crate::error::{Error, Result};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
@@ -31,6 +35,14 @@ pub struct CubeSchema {
 
     /// Optional description
     description: Option<String>,
+
+    /// Columns that uniquely identify a row, if declared
+    #[serde(default)]
+    primary_key: Option<PrimaryKey>,
+
+    /// Time zone the cube's timestamps are expressed in, if set
+    #[serde(default)]
+    timezone: Option<String>,
 }
 
 impl CubeSchema {
@@ -44,6 +56,8 @@ impl CubeSchema {
             calculated_measures: IndexMap::new(),
             virtual_dimensions: IndexMap::new(),
             description: None,
+            primary_key: None,
+            timezone: None,
         }
     }
 
@@ -52,6 +66,11 @@ impl CubeSchema {
         &self.name
     }
 
+    /// Change the cube name
+    pub(crate) fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+
     /// Get the description
     pub fn description(&self) -> Option<&str> {
         self.description.as_deref()
@@ -62,6 +81,49 @@ impl CubeSchema {
         self.description = Some(description.into());
     }
 
+    /// Get the primary key, if one is declared
+    pub fn primary_key(&self) -> Option<&PrimaryKey> {
+        self.primary_key.as_ref()
+    }
+
+    /// Declare the columns that uniquely identify a row
+    pub fn set_primary_key(&mut self, key: PrimaryKey) {
+        self.primary_key = Some(key);
+    }
+
+    /// Get the time zone of the cube's timestamps, if one is set
+    pub fn timezone(&self) -> Option<&str> {
+        self.timezone.as_deref()
+    }
+
+    /// Set the time zone of the cube's timestamps
+    ///
+    /// `timezone` is an IANA name such as `"America/New_York"` or a fixed
+    /// offset like `"+02:00"`.
+    pub fn set_timezone(&mut self, timezone: impl Into<String>) -> Result<()> {
+        let timezone = timezone.into();
+        validate_timezone(&timezone)?;
+        self.timezone = Some(timezone);
+        Ok(())
+    }
+
+    /// Put the timestamp dimensions and measures in the cube's time zone
+    pub(crate) fn localize_timestamps(&mut self) {
+        let Some(timezone) = self.timezone.clone() else {
+            return;
+        };
+        for dimension in self.dimensions.values_mut() {
+            if let Some(data_type) = localized_type(dimension.data_type(), &timezone) {
+                dimension.set_data_type(data_type);
+            }
+        }
+        for measure in self.measures.values_mut() {
+            if let Some(data_type) = localized_type(measure.data_type(), &timezone) {
+                measure.set_data_type(data_type);
+            }
+        }
+    }
+
     /// Add a dimension to the schema
     pub fn add_dimension(&mut self, dimension: Dimension) -> Result<()> {
         let name = dimension.name().to_string();
@@ -93,9 +155,10 @@ impl CubeSchema {
         // Validate the hierarchy
         hierarchy.validate().map_err(Error::hierarchy)?;
 
-        // Validate that all levels in the hierarchy reference existing dimensions
+        // Validate that all levels in the hierarchy reference existing
+        // dimensions or virtual dimensions
         for level in hierarchy.levels() {
-            if !self.dimensions.contains_key(level) {
+            if !self.dimensions.contains_key(level) && !self.virtual_dimensions.contains_key(level) {
                 return Err(Error::hierarchy(format!(
                     "Hierarchy '{}' references non-existent dimension '{}'",
                     hierarchy.name(),
@@ -291,11 +354,100 @@ impl CubeSchema {
         self.calculated_measures.get(name)
     }
 
+    /// Get a mutable calculated measure by name
+    pub fn get_calculated_measure_mut(&mut self, name: &str) -> Option<&mut CalculatedMeasure> {
+        self.calculated_measures.get_mut(name)
+    }
+
+    /// Get the display format of a measure or calculated measure
+    pub fn display_format(&self, name: &str) -> Option<DisplayFormat> {
+        match self.get_measure(name) {
+            Some(measure) => measure.display_format(),
+            None => self.get_calculated_measure(name)?.display_format(),
+        }
+    }
+
     /// Get a virtual dimension by name
     pub fn get_virtual_dimension(&self, name: &str) -> Option<&VirtualDimension> {
         self.virtual_dimensions.get(name)
     }
 
+    /// Get a mutable virtual dimension by name
+    pub fn get_virtual_dimension_mut(&mut self, name: &str) -> Option<&mut VirtualDimension> {
+        self.virtual_dimensions.get_mut(name)
+    }
+
+    /// Describe a dimension, measure, calculated measure or virtual dimension
+    pub fn set_column_description(
+        &mut self,
+        name: &str,
+        description: impl Into<String>,
+    ) -> Result<()> {
+        if let Some(dimension) = self.dimensions.get_mut(name) {
+            dimension.set_description(description);
+        } else if let Some(measure) = self.measures.get_mut(name) {
+            measure.set_description(description);
+        } else if let Some(measure) = self.calculated_measures.get_mut(name) {
+            measure.set_description(description);
+        } else if let Some(virtual_dim) = self.virtual_dimensions.get_mut(name) {
+            virtual_dim.set_description(description);
+        } else {
+            return Err(Error::schema(format!("Unknown column '{}'", name)));
+        }
+        Ok(())
+    }
+
+    /// Tag a dimension or measure
+    pub fn add_column_tag(&mut self, name: &str, tag: impl Into<String>) -> Result<()> {
+        if let Some(dimension) = self.dimensions.get_mut(name) {
+            dimension.add_tag(tag);
+        } else if let Some(measure) = self.measures.get_mut(name) {
+            measure.add_tag(tag);
+        } else {
+            return Err(Error::schema(format!(
+                "Only dimensions and measures can be tagged, and '{}' is neither",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rename a dimension or measure and the references to it
+    ///
+    /// Calculated measure and virtual dimension expressions, hierarchy
+    /// levels, dimension attribute columns and the primary key follow.
+    pub(crate) fn rename_column(&mut self, old: &str, new: &str) {
+        self.dimensions = std::mem::take(&mut self.dimensions)
+            .into_values()
+            .map(|mut dimension| {
+                dimension.rename_column(old, new);
+                (dimension.name().to_string(), dimension)
+            })
+            .collect();
+        self.measures = std::mem::take(&mut self.measures)
+            .into_values()
+            .map(|mut measure| {
+                if measure.name() == old {
+                    measure.set_name(new);
+                }
+                (measure.name().to_string(), measure)
+            })
+            .collect();
+
+        for measure in self.calculated_measures.values_mut() {
+            measure.set_expression(rename_identifier(measure.expression(), old, new));
+        }
+        for virtual_dim in self.virtual_dimensions.values_mut() {
+            virtual_dim.set_expression(rename_identifier(virtual_dim.expression(), old, new));
+        }
+        for hierarchy in self.hierarchies.values_mut() {
+            hierarchy.rename_level(old, new);
+        }
+        if let Some(key) = &mut self.primary_key {
+            key.rename_column(old, new);
+        }
+    }
+
     /// Remove a calculated measure
     pub fn remove_calculated_measure(&mut self, name: &str) -> Result<CalculatedMeasure> {
         self.calculated_measures.shift_remove(name).ok_or_else(|| {
@@ -359,6 +511,48 @@ impl CubeSchema {
 
         arrow::datatypes::Schema::new(fields)
     }
+
+    /// Serialize the schema to versioned JSON
+    ///
+    /// The output wraps the schema as `{"format_version": N, "schema": {...}}`
+    /// so readers can reject schemas written by a newer release.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(&VersionedSchemaRef {
+            format_version: SCHEMA_FORMAT_VERSION,
+            schema: self,
+        })
+        .map_err(|e| Error::schema(format!("Failed to serialize schema: {}", e)))
+    }
+
+    /// Parse a schema written by [`to_json`](Self::to_json)
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| Error::schema(format!("Invalid schema JSON: {}", e)))?;
+
+        let version = value
+            .get("format_version")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| Error::schema("Schema JSON has no format_version"))?;
+        if version > u64::from(SCHEMA_FORMAT_VERSION) {
+            return Err(Error::schema(format!(
+                "Schema format version {} is newer than the supported version {}",
+                version, SCHEMA_FORMAT_VERSION
+            )));
+        }
+
+        serde_json::from_value(value["schema"].take())
+            .map_err(|e| Error::schema(format!("Invalid schema JSON: {}", e)))
+    }
+}
+
+/// Version of the JSON produced by [`CubeSchema::to_json`]
+pub const SCHEMA_FORMAT_VERSION: u32 = 1;
+
+/// Envelope written by [`CubeSchema::to_json`]
+#[derive(Serialize)]
+struct VersionedSchemaRef<'a> {
+    format_version: u32,
+    schema: &'a CubeSchema,
 }
 
 #[cfg(test)]
@@ -594,4 +788,37 @@ mod tests {
         // Try to remove again - should fail
         assert!(schema.remove_calculated_measure("test").is_err());
     }
+
+    #[test]
+    fn test_schema_json_round_trip() {
+        let mut schema = CubeSchema::new("sales");
+        schema.set_description("Sales cube");
+        schema
+            .add_dimension(Dimension::new("region", DataType::Utf8))
+            .unwrap();
+        schema
+            .add_measure(Measure::new("revenue", DataType::Float64, AggFunc::Sum))
+            .unwrap();
+        schema
+            .add_hierarchy(Hierarchy::new("geo", vec!["region".to_string()]))
+            .unwrap();
+        schema
+            .add_calculated_measure(
+                CalculatedMeasure::new("double", "revenue * 2", DataType::Float64, AggFunc::Sum)
+                    .unwrap(),
+            )
+            .unwrap();
+
+        let json = schema.to_json().unwrap();
+        let parsed = CubeSchema::from_json(&json).unwrap();
+        assert_eq!(parsed.name(), "sales");
+        assert_eq!(parsed.description(), Some("Sales cube"));
+        assert_eq!(parsed.dimension_names(), vec!["region"]);
+        assert!(parsed.has_hierarchy("geo"));
+        assert!(parsed.has_calculated_measure("double"));
+
+        let newer = json.replacen("\"format_version\": 1", "\"format_version\": 99", 1);
+        assert!(CubeSchema::from_json(&newer).is_err());
+        assert!(CubeSchema::from_json("{}").is_err());
+    }
 }
@@ -0,0 +1,114 @@
+//! Local time zone of a cube's timestamps
+//!
+//! A cube with a time zone stores every timestamp column as
+//! `Timestamp(unit, Some(tz))`. Timestamps read without a zone (CSV text,
+//! naive Parquet columns) are taken as local wall-clock time; zoned ones
+//! keep their instant and are only displayed in the new zone. DataFusion
+//! then evaluates `EXTRACT`, `date_part` and `date_trunc` in that zone.
+
+use arrow::array::timezone::Tz;
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+use arrow::record_batch::RecordBatch;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+
+/// Check that `timezone` is an IANA name or a fixed offset like `+02:00`
+pub(crate) fn validate_timezone(timezone: &str) -> Result<()> {
+    timezone
+        .parse::<Tz>()
+        .map(|_| ())
+        .map_err(|e| Error::schema(format!("Invalid time zone '{}': {}", timezone, e)))
+}
+
+/// `data_type` in `timezone`, if it is a timestamp in another zone
+pub(crate) fn localized_type(data_type: &DataType, timezone: &str) -> Option<DataType> {
+    match data_type {
+        DataType::Timestamp(unit, tz) if tz.as_deref() != Some(timezone) => {
+            Some(DataType::Timestamp(*unit, Some(timezone.into())))
+        }
+        _ => None,
+    }
+}
+
+/// `schema` with every timestamp column in `timezone`
+pub(crate) fn localize_schema(schema: &ArrowSchema, timezone: &str) -> Arc<ArrowSchema> {
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(|field| match localized_type(field.data_type(), timezone) {
+            Some(data_type) => field.as_ref().clone().with_data_type(data_type),
+            None => field.as_ref().clone(),
+        })
+        .collect();
+    Arc::new(ArrowSchema::new_with_metadata(fields, schema.metadata().clone()))
+}
+
+/// `batch` with every timestamp column in `timezone`
+pub(crate) fn localize_batch(batch: &RecordBatch, timezone: &str) -> Result<RecordBatch> {
+    let schema = batch.schema();
+    if schema
+        .fields()
+        .iter()
+        .all(|field| localized_type(field.data_type(), timezone).is_none())
+    {
+        return Ok(batch.clone());
+    }
+
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(column, field)| match localized_type(field.data_type(), timezone) {
+            Some(data_type) => cast(column, &data_type),
+            None => Ok(Arc::clone(column)),
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(RecordBatch::try_new(localize_schema(&schema, timezone), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, TimestampSecondArray};
+    use arrow::datatypes::TimeUnit;
+
+    #[test]
+    fn test_localize_batch() {
+        assert!(validate_timezone("America/New_York").is_ok());
+        assert!(validate_timezone("+02:00").is_ok());
+        assert!(validate_timezone("Mars/Olympus_Mons").is_err());
+
+        // 2024-03-01 23:30:00, with and without a zone
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("naive", DataType::Timestamp(TimeUnit::Second, None), false),
+            Field::new("utc", DataType::Timestamp(TimeUnit::Second, Some("UTC".into())), false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(TimestampSecondArray::from(vec![1_709_335_800])),
+                Arc::new(TimestampSecondArray::from(vec![1_709_335_800]).with_timezone("UTC")),
+            ],
+        )
+        .unwrap();
+
+        let localized = localize_batch(&batch, "America/New_York").unwrap();
+        let zoned = DataType::Timestamp(TimeUnit::Second, Some("America/New_York".into()));
+        assert_eq!(localized.schema().field(0).data_type(), &zoned);
+        assert_eq!(localized.schema().field(1).data_type(), &zoned);
+
+        let value = |i: usize| {
+            localized
+                .column(i)
+                .as_any()
+                .downcast_ref::<TimestampSecondArray>()
+                .unwrap()
+                .value(0)
+        };
+        // Naive values are local wall time (EST is UTC-5); zoned ones keep their instant
+        assert_eq!(value(0), 1_709_335_800 + 5 * 3600);
+        assert_eq!(value(1), 1_709_335_800);
+    }
+}
@@ -0,0 +1,130 @@
+//! Transactional batch updates
+//!
+//! A [`Transaction`] stages appends, deletes and updates on a private copy of
+//! the cube's data. Nothing is visible on the cube until [`Transaction::commit`]
+//! swaps the staged data in at once; a failed step or a rollback leaves the
+//! cube exactly as it was. A failed step may have staged part of its change,
+//! so it poisons the transaction: every later step and the commit fail.
+
+use super::ElastiCube;
+use crate::error::{Error, Result};
+use arrow::record_batch::RecordBatch;
+
+/// Pending changes to an [`ElastiCube`], created by
+/// [`ElastiCube::begin_transaction`]
+///
+/// Dropping a transaction without committing discards its changes.
+#[derive(Debug)]
+pub struct Transaction<'a> {
+    /// Cube the changes are applied to on commit
+    cube: &'a mut ElastiCube,
+
+    /// Working copy the operations run against
+    staged: ElastiCube,
+
+    /// Number of operations staged so far
+    operations: usize,
+
+    /// Error of the step that failed, which poisons the transaction
+    failed: Option<String>,
+}
+
+impl<'a> Transaction<'a> {
+    pub(super) fn new(cube: &'a mut ElastiCube) -> Self {
+        let mut staged = cube.clone();
+        staged.versions = None;
+        Self {
+            cube,
+            staged,
+            operations: 0,
+            failed: None,
+        }
+    }
+
+    /// Fail if an earlier step failed
+    fn ensure_usable(&self) -> Result<()> {
+        match &self.failed {
+            Some(error) => Err(Error::data(format!(
+                "Transaction was poisoned by a failed step: {}",
+                error
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Count a successful step, or poison the transaction with a failed one
+    fn record<T>(&mut self, result: Result<T>) -> Result<T> {
+        match &result {
+            Ok(_) => self.operations += 1,
+            Err(e) => self.failed = Some(e.to_string()),
+        }
+        result
+    }
+
+    /// Stage appending a batch of rows
+    pub fn append_rows(&mut self, batch: RecordBatch) -> Result<usize> {
+        self.ensure_usable()?;
+        let result = self.staged.append_rows(batch);
+        self.record(result)
+    }
+
+    /// Stage appending several batches of rows
+    pub fn append_batches(&mut self, batches: Vec<RecordBatch>) -> Result<usize> {
+        self.ensure_usable()?;
+        let result = self.staged.append_batches(batches);
+        self.record(result)
+    }
+
+    /// Stage deleting the rows matching a SQL predicate
+    ///
+    /// The predicate sees the staged data, including rows appended earlier
+    /// in the transaction.
+    pub async fn delete_rows(&mut self, filter_expr: &str) -> Result<usize> {
+        self.ensure_usable()?;
+        let result = self.staged.delete_rows(filter_expr).await;
+        self.record(result)
+    }
+
+    /// Stage replacing the rows matching a SQL predicate
+    pub async fn update_rows(
+        &mut self,
+        filter_expr: &str,
+        replacement_batch: RecordBatch,
+    ) -> Result<(usize, usize)> {
+        self.ensure_usable()?;
+        let result = self.staged.update_rows(filter_expr, replacement_batch).await;
+        self.record(result)
+    }
+
+    /// Rows the cube will hold once the transaction commits
+    pub fn row_count(&self) -> usize {
+        self.staged.row_count
+    }
+
+    /// Number of operations staged so far
+    pub fn operation_count(&self) -> usize {
+        self.operations
+    }
+
+    /// Apply all staged changes to the cube at once
+    ///
+    /// With versioning enabled, the whole transaction is recorded as a single
+    /// "transaction" version. Committing an empty transaction changes nothing.
+    /// Fails, leaving the cube unchanged, if any step failed.
+    pub fn commit(self) -> Result<()> {
+        self.ensure_usable()?;
+        if self.operations == 0 {
+            return Ok(());
+        }
+        self.cube.data = self.staged.data;
+        self.cube.row_count = self.staged.row_count;
+        self.cube.cold = self.staged.cold;
+        self.cube.sorted = self.staged.sorted;
+        self.cube.generation = self.staged.generation;
+        self.cube.record_version("transaction");
+        Ok(())
+    }
+
+    /// Discard all staged changes
+    pub fn rollback(self) {}
+}
@@ -9,7 +9,7 @@
 //! and reconstruct the cube's internal data structure.
 
 use crate::error::{Error, Result};
-use arrow::array::BooleanArray;
+use arrow::array::{Array, BooleanArray};
 use arrow::compute;
 use arrow::datatypes::Schema as ArrowSchema;
 use arrow::record_batch::RecordBatch;
@@ -42,6 +42,36 @@ pub(crate) fn concat_record_batches(
     })
 }
 
+/// Sort all rows by `columns` (ascending, NULLs first) into a single batch
+///
+/// Returns no batches when there is no data.
+pub(crate) fn sort_record_batches(
+    schema: &Arc<ArrowSchema>,
+    batches: &[RecordBatch],
+    columns: &[String],
+) -> Result<Vec<RecordBatch>> {
+    if batches.iter().all(|b| b.num_rows() == 0) {
+        return Ok(Vec::new());
+    }
+    let batch = concat_record_batches(schema, batches)?;
+
+    let sort_columns = columns
+        .iter()
+        .map(|name| {
+            let values = batch.column_by_name(name).cloned().ok_or_else(|| {
+                Error::schema(format!("Sort column '{}' not found in cube data", name))
+            })?;
+            Ok(compute::SortColumn {
+                values,
+                options: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let indices = compute::lexsort_to_indices(&sort_columns, None)?;
+
+    Ok(vec![compute::take_record_batch(&batch, &indices)?])
+}
+
 /// Filter a RecordBatch based on a boolean array predicate
 ///
 /// # Arguments
@@ -104,12 +134,105 @@ pub(crate) fn validate_batch_schema(
     Ok(())
 }
 
+/// Placeholder in incremental refresh filters for the compared column's maximum
+pub(crate) const LAST_VALUE_PLACEHOLDER: &str = "$last";
+
+/// Resolve the `$last` placeholder of an incremental refresh filter
+///
+/// The filter must compare a column to `$last` (e.g. `"updated_at > $last"`);
+/// the placeholder is replaced with that column's current maximum as a SQL
+/// literal. Returns `None` when the cube has no non-NULL value for the column
+/// yet, meaning every row is new.
+pub(crate) async fn resolve_incremental_filter(
+    schema: &Arc<ArrowSchema>,
+    data: &[RecordBatch],
+    filter: &str,
+) -> Result<Option<String>> {
+    use datafusion::prelude::SessionContext;
+
+    let pattern = regex::Regex::new(r#"("[^"]+"|[A-Za-z_][A-Za-z0-9_]*)\s*(>=|>)\s*\$last\b"#)
+        .map_err(|e| Error::query(format!("Invalid placeholder pattern: {}", e)))?;
+    let captures = pattern.captures(filter).ok_or_else(|| {
+        Error::query(format!(
+            "Incremental filter '{}' must compare a column to {} (e.g. \"updated_at > $last\")",
+            filter, LAST_VALUE_PLACEHOLDER
+        ))
+    })?;
+    let column = captures[1].trim_matches('"').to_string();
+
+    let field = schema.field_with_name(&column).map_err(|_| {
+        Error::query(format!("Incremental filter column '{}' not found in cube", column))
+    })?;
+
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    // Compute the current maximum with DataFusion so any orderable type works
+    let ctx = SessionContext::new();
+    let table = datafusion::datasource::MemTable::try_new(schema.clone(), vec![data.to_vec()])
+        .map_err(|e| Error::query(format!("Failed to create temp table: {}", e)))?;
+    ctx.register_table("temp_table", Arc::new(table))
+        .map_err(|e| Error::query(format!("Failed to register table: {}", e)))?;
+
+    let query = format!(
+        "SELECT CAST(MAX(\"{}\") AS VARCHAR) FROM temp_table",
+        column.replace('"', "\"\"")
+    );
+    let results = ctx
+        .sql(&query)
+        .await
+        .map_err(|e| Error::query(format!("Failed to compute last value of '{}': {}", column, e)))?
+        .collect()
+        .await
+        .map_err(|e| Error::query(format!("Failed to compute last value of '{}': {}", column, e)))?;
+
+    let last = results.iter().find(|b| b.num_rows() > 0).and_then(|batch| {
+        let values = batch.column(0).as_any().downcast_ref::<arrow::array::StringArray>()?;
+        (!values.is_null(0)).then(|| values.value(0).to_string())
+    });
+
+    Ok(last.map(|value| {
+        let literal = if field.data_type().is_numeric() {
+            value
+        } else {
+            format!("'{}'", value.replace('\'', "''"))
+        };
+        filter.replace(LAST_VALUE_PLACEHOLDER, &literal)
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use arrow::array::{Float64Array, Int32Array};
     use arrow::datatypes::{DataType, Field};
 
+    #[tokio::test]
+    async fn test_resolve_incremental_filter() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("value", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![3, 7, 5])),
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0])),
+            ],
+        )
+        .unwrap();
+
+        let resolved = resolve_incremental_filter(&schema, &[batch], "id > $last").await.unwrap();
+        assert_eq!(resolved.as_deref(), Some("id > 7"));
+
+        // No data yet: everything is new
+        assert!(resolve_incremental_filter(&schema, &[], "id >= $last").await.unwrap().is_none());
+
+        assert!(resolve_incremental_filter(&schema, &[], "id > 10").await.is_err());
+        assert!(resolve_incremental_filter(&schema, &[], "missing > $last").await.is_err());
+    }
+
     #[test]
     fn test_concat_single_batch() {
         let schema = Arc::new(ArrowSchema::new(vec![
@@ -0,0 +1,101 @@
+//! Snapshot versions of cube data
+//!
+//! When versioning is enabled, every data change records the resulting
+//! batches as a new version. Arrow buffers are reference counted, so a
+//! snapshot only copies batch handles; rows kept across versions share memory.
+
+use crate::error::{Error, Result};
+use arrow::record_batch::RecordBatch;
+use crate::time::SystemTime;
+
+/// One entry of a cube's change history
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeVersion {
+    /// Sequential version number (0 = state when versioning was enabled)
+    pub version: u64,
+
+    /// When the version was created
+    pub timestamp: SystemTime,
+
+    /// Operation that produced the version (e.g. "append", "delete")
+    pub operation: String,
+
+    /// Rows in the cube at this version
+    pub row_count: usize,
+}
+
+/// Selects a historical version for [`ElastiCube::as_of`](super::ElastiCube::as_of)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AsOf {
+    /// An exact version number
+    Version(u64),
+    /// The latest version created at or before this time
+    Timestamp(SystemTime),
+}
+
+impl From<u64> for AsOf {
+    fn from(version: u64) -> Self {
+        AsOf::Version(version)
+    }
+}
+
+impl From<SystemTime> for AsOf {
+    fn from(timestamp: SystemTime) -> Self {
+        AsOf::Timestamp(timestamp)
+    }
+}
+
+/// Recorded versions with their data
+#[derive(Debug, Clone, Default)]
+pub(crate) struct VersionLog {
+    entries: Vec<(CubeVersion, Vec<RecordBatch>)>,
+}
+
+impl VersionLog {
+    /// Record the data resulting from `operation`
+    pub(crate) fn record(&mut self, operation: &str, data: &[RecordBatch], row_count: usize) {
+        let version = self.entries.last().map_or(0, |(v, _)| v.version + 1);
+        self.entries.push((
+            CubeVersion {
+                version,
+                timestamp: SystemTime::now(),
+                operation: operation.to_string(),
+                row_count,
+            },
+            data.to_vec(),
+        ));
+    }
+
+    /// History entries, oldest first
+    pub(crate) fn history(&self) -> Vec<CubeVersion> {
+        self.entries.iter().map(|(v, _)| v.clone()).collect()
+    }
+
+    /// Data of the selected version
+    pub(crate) fn data_as_of(&self, as_of: AsOf) -> Result<&[RecordBatch]> {
+        let entry = match as_of {
+            AsOf::Version(version) => self.entries.iter().find(|(v, _)| v.version == version),
+            AsOf::Timestamp(time) => self.entries.iter().rev().find(|(v, _)| v.timestamp <= time),
+        };
+        entry
+            .map(|(_, data)| data.as_slice())
+            .ok_or_else(|| Error::data(format!("No cube version matches {:?}", as_of)))
+    }
+
+    /// Apply `f` to the data of every version, e.g. to follow a column rename
+    pub(crate) fn map_batches(
+        &mut self,
+        mut f: impl FnMut(&RecordBatch) -> Result<RecordBatch>,
+    ) -> Result<()> {
+        for (_, data) in &mut self.entries {
+            *data = data.iter().map(&mut f).collect::<Result<_>>()?;
+        }
+        Ok(())
+    }
+
+    /// Drop all but the newest `keep` versions
+    pub(crate) fn prune(&mut self, keep: usize) {
+        let excess = self.entries.len().saturating_sub(keep);
+        self.entries.drain(..excess);
+    }
+}
@@ -350,4 +350,584 @@ mod tests {
         assert_eq!(cube.batch_count(), 1);
         assert_eq!(cube.row_count(), 4);
     }
+
+    #[tokio::test]
+    async fn test_refresh_from_source() {
+        use std::io::Write;
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "id,region,sales").unwrap();
+        writeln!(file, "1,North,100.0").unwrap();
+        writeln!(file, "2,South,200.0").unwrap();
+        file.flush().unwrap();
+
+        let mut cube = ElastiCubeBuilder::new("refresh")
+            .load_csv(file.path().to_str().unwrap())
+            .build()
+            .unwrap();
+        assert!(cube.has_source());
+        assert_eq!(cube.row_count(), 2);
+
+        // Nothing new yet
+        assert_eq!(cube.refresh_incremental("id > $last").await.unwrap(), 0);
+
+        writeln!(file, "3,East,300.0").unwrap();
+        writeln!(file, "4,West,400.0").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(cube.refresh_incremental("id > $last").await.unwrap(), 2);
+        assert_eq!(cube.row_count(), 4);
+
+        // A full refresh replaces the data instead of appending
+        assert_eq!(cube.refresh().await.unwrap(), 4);
+        assert_eq!(cube.row_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_schema_drift() {
+        use crate::DriftPolicy;
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap().to_string();
+        std::fs::write(&path, "id,region,sales\n1,North,100.0\n2,South,200.0\n").unwrap();
+        let build = |policy: DriftPolicy| {
+            ElastiCubeBuilder::new("drift")
+                .with_drift_policy(policy)
+                .load_csv(path.as_str())
+                .build()
+                .unwrap()
+        };
+        let mut strict = build(DriftPolicy::Fail);
+        let mut ignoring = build(DriftPolicy::IgnoreNew);
+        let mut adding = build(DriftPolicy::AutoAdd);
+        adding.enable_versioning().unwrap();
+
+        // A column is added upstream, ahead of the existing ones
+        std::fs::write(
+            &path,
+            "id,channel,region,sales\n1,web,North,100.0\n2,store,South,200.0\n3,web,East,300.0\n",
+        )
+        .unwrap();
+
+        let err = strict.refresh().await.unwrap_err();
+        assert!(err.to_string().contains("new columns channel"));
+        assert_eq!(strict.row_count(), 2);
+
+        assert_eq!(ignoring.refresh().await.unwrap(), 3);
+        assert_eq!(ignoring.arrow_schema().fields().len(), 3);
+        assert_eq!(ignoring.load_report().drift.added[0].name(), "channel");
+
+        assert_eq!(adding.refresh_incremental("id > $last").await.unwrap(), 1);
+        assert!(adding.get_dimension("channel").is_some());
+        assert_eq!(adding.arrow_schema().field(3).name(), "channel");
+        assert_eq!(adding.row_count(), 3);
+        assert_eq!(adding.as_of(0).unwrap().data()[0].num_columns(), 4);
+
+        // Once added, the column is part of the cube like any other
+        assert_eq!(adding.refresh().await.unwrap(), 3);
+        assert!(adding.load_report().drift.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_without_source_fails() {
+        let cube = create_test_cube();
+        let mut detached = crate::ElastiCube::new(
+            cube.schema().clone(),
+            cube.arrow_schema().clone(),
+            cube.data().to_vec(),
+        )
+        .unwrap();
+
+        assert!(!detached.has_source());
+        assert!(detached.refresh().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_versioning_as_of_and_history() {
+        let mut cube = (*create_test_cube()).clone();
+        assert!(cube.history().is_empty());
+        assert!(cube.as_of(0).is_err());
+
+        cube.enable_versioning().unwrap();
+        let before_changes = std::time::SystemTime::now();
+
+        let schema = cube.arrow_schema().clone();
+        let replacement = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["North"])),
+                Arc::new(StringArray::from(vec!["A"])),
+                Arc::new(Float64Array::from(vec![999.0])),
+                Arc::new(Int32Array::from(vec![99])),
+            ],
+        )
+        .unwrap();
+        cube.delete_rows("sales < 200").await.unwrap();
+        cube.update_rows("region = 'West'", replacement).await.unwrap();
+
+        let history = cube.history();
+        let operations: Vec<&str> = history.iter().map(|v| v.operation.as_str()).collect();
+        assert_eq!(operations, vec!["initial", "delete", "update"]);
+        assert_eq!(history[1].version, 1);
+        assert_eq!(history[1].row_count, 2);
+
+        // Historical views are queryable and leave the live cube untouched
+        let original = Arc::new(cube.as_of(0).unwrap());
+        assert_eq!(original.row_count(), 4);
+        let result = original
+            .query()
+            .unwrap()
+            .select(&["SUM(sales) AS total"])
+            .execute()
+            .await
+            .unwrap();
+        let total = result.batches()[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(total, 750.0);
+        assert!(original.history().is_empty());
+
+        assert_eq!(cube.as_of(before_changes).unwrap().row_count(), 4);
+        assert_eq!(cube.as_of(1).unwrap().row_count(), 2);
+        assert_eq!(cube.row_count(), 2);
+        assert!(cube.as_of(7).is_err());
+
+        cube.prune_history(1);
+        assert_eq!(cube.history().len(), 1);
+        assert!(cube.as_of(0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transaction_commit_and_rollback() {
+        let mut cube = (*create_test_cube()).clone();
+        cube.enable_versioning().unwrap();
+        let schema = cube.arrow_schema().clone();
+        let new_rows = || {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(StringArray::from(vec!["Central"])),
+                    Arc::new(StringArray::from(vec!["D"])),
+                    Arc::new(Float64Array::from(vec![50.0])),
+                    Arc::new(Int32Array::from(vec![5])),
+                ],
+            )
+            .unwrap()
+        };
+
+        // Rolled back changes never reach the cube
+        let mut tx = cube.begin_transaction();
+        tx.append_rows(new_rows()).unwrap();
+        assert_eq!(tx.delete_rows("sales >= 0").await.unwrap(), 5);
+        assert_eq!(tx.row_count(), 0);
+        tx.rollback();
+        assert_eq!(cube.row_count(), 4);
+
+        // A failing step leaves the cube untouched
+        let mut tx = cube.begin_transaction();
+        tx.delete_rows("region = 'North'").await.unwrap();
+        assert!(tx.delete_rows("no_such_column = 1").await.is_err());
+        drop(tx);
+        assert_eq!(cube.row_count(), 4);
+
+        // and poisons the transaction, so it can't be committed
+        let mut tx = cube.begin_transaction();
+        tx.delete_rows("region = 'North'").await.unwrap();
+        assert!(tx.delete_rows("no_such_column = 1").await.is_err());
+        assert!(tx.append_rows(new_rows()).is_err());
+        let err = tx.commit().unwrap_err();
+        assert!(err.to_string().contains("poisoned"));
+        assert_eq!(cube.row_count(), 4);
+        assert_eq!(cube.history().len(), 1);
+
+        // Committed changes are applied together as one version
+        let mut tx = cube.begin_transaction();
+        tx.delete_rows("region = 'North'").await.unwrap();
+        tx.append_rows(new_rows()).unwrap();
+        assert_eq!(tx.operation_count(), 2);
+        tx.commit().unwrap();
+
+        assert_eq!(cube.row_count(), 4);
+        let history = cube.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].operation, "transaction");
+    }
+
+    #[test]
+    fn test_apply_changes() {
+        use crate::{ChangeEvent, ChangeSummary};
+        use serde_json::json;
+
+        let batch = create_test_cube().data()[0].clone();
+        let mut cube = ElastiCubeBuilder::new("test_sales")
+            .with_primary_key(&["region"])
+            .load_record_batches(batch.schema(), vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let messages = [
+            json!({"payload": {"op": "u", "before": null,
+                "after": {"region": "North", "product": "A", "sales": 110.0, "quantity": 11}}}),
+            json!({"op": "c",
+                "after": {"region": "Central", "product": "D", "sales": 50.0, "quantity": 5}}),
+            json!({"op": "d",
+                "before": {"region": "West", "product": "C", "sales": 300.0, "quantity": 30}}),
+            json!({"op": "d", "before": {"region": "Nowhere"}}),
+            json!(null),
+        ];
+        let events: Vec<ChangeEvent> = messages
+            .iter()
+            .filter_map(|m| ChangeEvent::from_debezium(m).transpose())
+            .collect::<crate::Result<_>>()
+            .unwrap();
+        assert_eq!(events.len(), 4);
+
+        let summary = cube.apply_changes(events).unwrap();
+        assert_eq!(
+            summary,
+            ChangeSummary {
+                inserted: 1,
+                updated: 1,
+                deleted: 1
+            }
+        );
+        assert_eq!(cube.row_count(), 4);
+
+        let sales: f64 = cube
+            .data()
+            .iter()
+            .map(|b| {
+                let column = b.column(2).as_any().downcast_ref::<Float64Array>().unwrap();
+                column.values().iter().sum::<f64>()
+            })
+            .sum();
+        assert_eq!(sales, 110.0 + 200.0 + 150.0 + 50.0);
+
+        // Events without a key are rejected and leave the cube unchanged
+        let bad = vec![
+            ChangeEvent::Delete(json!({"region": "South"}).as_object().unwrap().clone()),
+            ChangeEvent::Insert(json!({"product": "E"}).as_object().unwrap().clone()),
+        ];
+        assert!(cube.apply_changes(bad).is_err());
+        assert_eq!(cube.row_count(), 4);
+
+        // A cube without a primary key can't match change events
+        let mut unkeyed = (*create_test_cube()).clone();
+        assert!(unkeyed.apply_changes(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_retention_prunes_expired_rows() {
+        use arrow::array::TimestampMillisecondArray;
+        use arrow::datatypes::TimeUnit;
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+        let millis = |age: Duration| {
+            (SystemTime::now() - age).duration_since(UNIX_EPOCH).unwrap().as_millis() as i64
+        };
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("timestamp", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+            Field::new("sales", DataType::Float64, false),
+        ]));
+        let batch = |times: Vec<Option<i64>>| {
+            let sales = vec![1.0; times.len()];
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(TimestampMillisecondArray::from(times)),
+                    Arc::new(Float64Array::from(sales)),
+                ],
+            )
+            .unwrap()
+        };
+
+        let mut cube = ElastiCubeBuilder::new("events")
+            .load_record_batches(
+                schema.clone(),
+                vec![batch(vec![
+                    Some(millis(DAY)),
+                    Some(millis(DAY * 100)),
+                    None,
+                ])],
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert!(cube.set_retention("sales", DAY).is_err());
+        assert_eq!(cube.set_retention("timestamp", DAY * 90).unwrap(), 1);
+        assert_eq!(cube.row_count(), 2);
+        assert_eq!(cube.retention(), Some(("timestamp", DAY * 90)));
+
+        // Appends expire rows that are already too old
+        cube.append_rows(batch(vec![Some(millis(DAY * 91)), Some(millis(DAY * 2))]))
+            .unwrap();
+        assert_eq!(cube.row_count(), 3);
+
+        // A shorter window applies on the next prune
+        cube.set_retention("timestamp", DAY * 30).unwrap();
+        assert_eq!(cube.prune_expired().unwrap(), 0);
+        cube.set_retention("timestamp", Duration::from_secs(60)).unwrap();
+        assert_eq!(cube.row_count(), 1);
+
+        // Compressed batches are only decompressed when they may hold
+        // expired rows
+        cube.set_retention("timestamp", DAY * 30).unwrap();
+        cube.append_rows(batch(vec![Some(millis(DAY))])).unwrap();
+        let compressed = cube
+            .compress_cold_batches(Duration::ZERO, crate::BatchCompression::Lz4)
+            .unwrap();
+        assert_eq!(cube.compressed_batch_count(), compressed);
+        cube.append_rows(batch(vec![Some(millis(DAY * 2))])).unwrap();
+        assert_eq!(cube.compressed_batch_count(), compressed);
+        assert_eq!(cube.row_count(), 3);
+        cube.set_retention("timestamp", DAY / 2).unwrap();
+        assert_eq!(cube.compressed_batch_count(), 0);
+        assert_eq!(cube.row_count(), 1);
+
+        cube.clear_retention();
+        assert_eq!(cube.prune_expired().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_partitioned_cube() {
+        let mut cube = (*create_test_cube()).clone();
+        assert_eq!(cube.partition_count().unwrap(), 0);
+        assert!(cube.partition_by("missing").is_err());
+
+        assert_eq!(cube.partition_by("product").unwrap(), 3);
+        assert_eq!(cube.batch_count(), 3);
+        for batch in cube.data() {
+            let products = batch.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+            assert!(products.iter().all(|p| p == products.iter().next().unwrap()));
+        }
+
+        // Appended rows are split into their partitions
+        let schema = cube.arrow_schema().clone();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["Central", "North"])),
+                Arc::new(StringArray::from(vec!["A", "D"])),
+                Arc::new(Float64Array::from(vec![50.0, 60.0])),
+                Arc::new(Int32Array::from(vec![5, 6])),
+            ],
+        )
+        .unwrap();
+        cube.append_rows(batch).unwrap();
+        assert_eq!(cube.batch_count(), 5);
+        assert_eq!(cube.partition_count().unwrap(), 4);
+
+        // Queries filtering on the partition column skip other partitions
+        let scanned = cube.scan_batches(Some("product = 'A' AND sales > 0"));
+        assert_eq!(scanned.iter().map(|b| b.num_rows()).sum::<usize>(), 3);
+        let shared = Arc::new(cube.clone());
+        let result = shared
+            .query()
+            .unwrap()
+            .select(&["SUM(sales) AS total"])
+            .filter("product = 'A' AND sales > 0")
+            .execute()
+            .await
+            .unwrap();
+        let total = result.batches()[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(total, 300.0);
+
+        // Deletes on the partition column drop whole partitions
+        assert_eq!(cube.delete_rows("product IN ('A', 'D')").await.unwrap(), 4);
+        assert_eq!(cube.row_count(), 2);
+        assert_eq!(cube.partition_count().unwrap(), 2);
+
+        // Other deletes still work row by row and keep the partitioning
+        assert_eq!(cube.delete_rows("sales > 250").await.unwrap(), 1);
+        assert_eq!(cube.partition_count().unwrap(), 1);
+        cube.consolidate_batches().unwrap();
+        assert_eq!(cube.batch_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_zone_maps_skip_batches() {
+        let mut cube = (*create_test_cube()).clone();
+        let schema = cube.arrow_schema().clone();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["North", "South"])),
+                Arc::new(StringArray::from(vec!["A", "B"])),
+                Arc::new(Float64Array::from(vec![1000.0, 1200.0])),
+                Arc::new(Int32Array::from(vec![1, 2])),
+            ],
+        )
+        .unwrap();
+        cube.append_rows(batch).unwrap();
+
+        let scanned = cube.scan_batches(Some("sales >= 1000"));
+        assert_eq!(scanned.len(), 1);
+        assert_eq!(scanned[0].num_rows(), 2);
+        assert_eq!(cube.scan_batches(Some("sales < 0")).len(), 0);
+        assert_eq!(cube.scan_batches(None).len(), 2);
+
+        let zones = &cube.statistics().column_stats[2].zone_maps;
+        assert_eq!(zones.len(), 2);
+        assert_eq!(zones[1].min, Some(datafusion::scalar::ScalarValue::Float64(Some(1000.0))));
+
+        let result = Arc::new(cube)
+            .query()
+            .unwrap()
+            .select(&["COUNT(*) AS n"])
+            .filter("sales >= 150")
+            .execute()
+            .await
+            .unwrap();
+        let n = result.batches()[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<arrow::array::Int64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(n, 5);
+    }
+
+    #[tokio::test]
+    async fn test_compress_cold_batches() {
+        use crate::BatchCompression;
+        use std::time::Duration;
+
+        let mut cube = (*create_test_cube()).clone();
+        let schema = cube.arrow_schema().clone();
+        let batch = |region: Vec<&str>, sales: Vec<f64>| {
+            let n = region.len();
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(StringArray::from(region)),
+                    Arc::new(StringArray::from(vec!["A"; n])),
+                    Arc::new(Float64Array::from(sales)),
+                    Arc::new(Int32Array::from(vec![1; n])),
+                ],
+            )
+            .unwrap()
+        };
+        cube.append_rows(batch(vec!["North", "South"], vec![1000.0, 1200.0]))
+            .unwrap();
+
+        // Batches just seen count as recently read
+        let compressed = cube
+            .compress_cold_batches(Duration::from_secs(3600), BatchCompression::Lz4)
+            .unwrap();
+        assert_eq!(compressed, 0);
+
+        let compressed = cube
+            .compress_cold_batches(Duration::ZERO, BatchCompression::Zstd)
+            .unwrap();
+        assert_eq!(compressed, 2);
+        assert_eq!(cube.batch_count(), 0);
+        assert_eq!(cube.compressed_batch_count(), 2);
+        assert!(cube.compressed_bytes() > 0);
+        assert_eq!(cube.row_count(), 6);
+
+        // Queries decompress what they need
+        let count = |cube: &crate::ElastiCube, filter: &str| {
+            let cube = Arc::new(cube.clone());
+            let filter = filter.to_string();
+            async move {
+                let result = cube
+                    .query()
+                    .unwrap()
+                    .select(&["COUNT(*) AS n"])
+                    .filter(&filter)
+                    .execute()
+                    .await
+                    .unwrap();
+                result.batches()[0]
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<arrow::array::Int64Array>()
+                    .unwrap()
+                    .value(0)
+            }
+        };
+        assert_eq!(count(&cube, "sales >= 150").await, 5);
+        assert_eq!(count(&cube, "sales < 0").await, 0);
+
+        // Plain appends leave cold batches compressed; deletes decompress them
+        cube.append_rows(batch(vec!["East"], vec![5.0])).unwrap();
+        assert_eq!(cube.compressed_batch_count(), 2);
+        assert_eq!(cube.row_count(), 7);
+        assert_eq!(count(&cube, "sales > 0").await, 7);
+
+        let deleted = cube.delete_rows("region = 'North'").await.unwrap();
+        assert_eq!(deleted, 2);
+        assert_eq!(cube.compressed_batch_count(), 0);
+        assert_eq!(cube.row_count(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_rename_measure_rewrites_references() {
+        let mut cube = (*create_test_cube()).clone();
+        let mut schema = cube.schema().clone();
+        schema
+            .add_calculated_measure(
+                crate::CalculatedMeasure::new(
+                    "avg_price",
+                    "sales / quantity",
+                    DataType::Float64,
+                    AggFunc::Avg,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        cube = crate::ElastiCube::new(schema, cube.arrow_schema().clone(), cube.data().to_vec())
+            .unwrap();
+        cube.sort_by(&["sales"]).unwrap();
+
+        cube.rename_measure("sales", "revenue").unwrap();
+        assert!(cube.get_measure("sales").is_none());
+        assert_eq!(cube.schema().measure_names(), vec!["revenue", "quantity"]);
+        assert_eq!(cube.arrow_schema().field(2).name(), "revenue");
+        assert_eq!(cube.data()[0].schema().field(2).name(), "revenue");
+        assert_eq!(
+            cube.schema().get_calculated_measure("avg_price").unwrap().expression(),
+            "revenue / quantity"
+        );
+        assert_eq!(cube.sort_order(), Some(&["revenue".to_string()][..]));
+        assert_eq!(
+            cube.rewrite_renamed_columns("SELECT SUM(sales) FROM cube WHERE product = 'sales'"),
+            "SELECT SUM(revenue) FROM cube WHERE product = 'sales'"
+        );
+
+        let cube = Arc::new(cube);
+        let result = cube
+            .query()
+            .unwrap()
+            .select(&["SUM(revenue) AS total", "SUM(avg_price) AS prices"])
+            .execute()
+            .await
+            .unwrap();
+        let total = result.batches()[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(total, 750.0);
+
+        let mut cube = (*cube).clone();
+        assert!(cube.rename_measure("sales", "total").is_err());
+        assert!(cube.rename_measure("revenue", "quantity").is_err());
+        assert!(cube.rename_dimension("revenue", "amount").is_err());
+        cube.rename_dimension("region", "area").unwrap();
+        assert!(cube.schema().has_dimension("area"));
+    }
 }
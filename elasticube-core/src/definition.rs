@@ -0,0 +1,437 @@
+//! Cube definition files
+//!
+//! A definition file declares a cube's dimensions, measures, calculated
+//! fields, hierarchies and data source in JSON (or YAML with the `yaml`
+//! feature), so the semantic layer can be edited without touching Rust:
+//!
+//! ```yaml
+//! name: sales
+//! description: Daily sales by region
+//! timezone: America/New_York
+//! source:
+//!   type: csv
+//!   path: sales.csv
+//! dimensions:
+//!   - { name: region, type: utf8 }
+//!   - { name: date, type: date32 }
+//! measures:
+//!   - name: revenue
+//!     type: float64
+//!     agg: sum
+//!     format: "$#,##0.00"
+//!     description: Net revenue after discounts
+//!     tags: [finance]
+//!   - { name: cost, type: float64, agg: sum }
+//! calculated_measures:
+//!   - { name: profit, expression: revenue - cost, type: float64, agg: sum }
+//!   - { name: margin, expression: SUM(profit) / SUM(revenue), type: float64, aggregate: true }
+//! virtual_dimensions:
+//!   - { name: year, expression: "EXTRACT(YEAR FROM date)", type: int32 }
+//! hierarchies:
+//!   - { name: time, levels: [year, date] }
+//! ```
+//!
+//! Relative source paths are resolved against the definition file's directory.
+
+use crate::builder::ElastiCubeBuilder;
+use crate::cube::{AggFunc, Dimension, DisplayFormat, Measure};
+use crate::error::{Error, Result};
+use crate::sources::{CsvSource, JsonSource, ParquetSource, PartitionedDatasetSource};
+use arrow::datatypes::DataType;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+/// A cube declared in a definition file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CubeDefinition {
+    /// Cube name
+    pub name: String,
+
+    /// Optional cube description
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Time zone of the cube's timestamps, e.g. `America/New_York`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+
+    /// Where the data comes from (None = supply data on the builder)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<SourceDefinition>,
+
+    /// Dimension columns
+    #[serde(default)]
+    pub dimensions: Vec<DimensionDefinition>,
+
+    /// Measure columns
+    #[serde(default)]
+    pub measures: Vec<MeasureDefinition>,
+
+    /// Measures computed from expressions
+    #[serde(default)]
+    pub calculated_measures: Vec<CalculatedMeasureDefinition>,
+
+    /// Dimensions computed from expressions
+    #[serde(default)]
+    pub virtual_dimensions: Vec<VirtualDimensionDefinition>,
+
+    /// Drill paths over dimensions
+    #[serde(default)]
+    pub hierarchies: Vec<HierarchyDefinition>,
+}
+
+/// Data source of a [`CubeDefinition`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum SourceDefinition {
+    /// A CSV file
+    Csv {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        has_header: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        delimiter: Option<char>,
+    },
+    /// A Parquet file
+    Parquet { path: String },
+    /// A JSON file
+    Json { path: String },
+    /// A Hive-style partitioned directory of Parquet files
+    Partitioned { path: String },
+}
+
+/// A dimension column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DimensionDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub data_type: String,
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Column that `order_by` on this dimension sorts by
+    #[serde(default)]
+    pub sort: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A measure column with its default aggregation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MeasureDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub data_type: String,
+    pub agg: String,
+    /// Display pattern such as `"$#,##0.00"`
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub precision: Option<u8>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A measure computed from a SQL expression
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CalculatedMeasureDefinition {
+    pub name: String,
+    pub expression: String,
+    #[serde(rename = "type")]
+    pub data_type: String,
+    /// Aggregation function; not used when `aggregate` is set
+    #[serde(default)]
+    pub agg: String,
+    /// Whether `expression` is made of aggregates, evaluated per group
+    #[serde(default)]
+    pub aggregate: bool,
+}
+
+/// A dimension computed from a SQL expression
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VirtualDimensionDefinition {
+    pub name: String,
+    pub expression: String,
+    #[serde(rename = "type")]
+    pub data_type: String,
+}
+
+/// An ordered list of dimension levels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HierarchyDefinition {
+    pub name: String,
+    pub levels: Vec<String>,
+}
+
+impl CubeDefinition {
+    /// Read a definition file; `.yaml`/`.yml` files are parsed as YAML,
+    /// everything else as JSON
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            Error::config(format!(
+                "Failed to read cube definition '{}': {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let mut definition = match extension.as_deref() {
+            Some("yaml") | Some("yml") => Self::from_yaml_str(&contents)?,
+            _ => Self::from_json_str(&contents)?,
+        };
+
+        if let (Some(source), Some(dir)) = (definition.source.as_mut(), path.parent()) {
+            source.resolve_relative_to(dir);
+        }
+        Ok(definition)
+    }
+
+    /// Parse a JSON definition
+    pub fn from_json_str(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| Error::config(format!("Invalid cube definition: {}", e)))
+    }
+
+    /// Parse a YAML definition
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str(yaml: &str) -> Result<Self> {
+        serde_yaml::from_str(yaml)
+            .map_err(|e| Error::config(format!("Invalid cube definition: {}", e)))
+    }
+
+    /// Parse a YAML definition (requires the `yaml` feature)
+    #[cfg(not(feature = "yaml"))]
+    pub fn from_yaml_str(_yaml: &str) -> Result<Self> {
+        Err(Error::config("YAML cube definitions require the `yaml` feature"))
+    }
+
+    /// Create a builder with everything the definition declares
+    pub fn into_builder(self) -> Result<ElastiCubeBuilder> {
+        let mut builder = ElastiCubeBuilder::new(self.name);
+        if let Some(description) = self.description {
+            builder = builder.with_description(description);
+        }
+        if let Some(timezone) = self.timezone {
+            builder = builder.with_timezone(timezone)?;
+        }
+
+        for definition in self.dimensions {
+            let data_type = parse_data_type(&definition.data_type)?;
+            let mut dimension = Dimension::new(definition.name, data_type);
+            if let Some(key) = definition.key {
+                dimension = dimension.with_key_column(key);
+            }
+            if let Some(label) = definition.label {
+                dimension = dimension.with_label_column(label);
+            }
+            if let Some(sort) = definition.sort {
+                dimension = dimension.with_sort_column(sort);
+            }
+            if let Some(description) = definition.description {
+                dimension = dimension.with_description(description);
+            }
+            for tag in definition.tags {
+                dimension = dimension.with_tag(tag);
+            }
+            builder = builder.add_dimension_with(dimension)?;
+        }
+        for measure in self.measures {
+            let data_type = parse_data_type(&measure.data_type)?;
+            let format = DisplayFormat {
+                pattern: measure.format,
+                unit: measure.unit,
+                precision: measure.precision,
+            };
+            let mut configured = Measure::new(&measure.name, data_type, measure.agg.parse()?);
+            if let Some(description) = measure.description {
+                configured = configured.with_description(description);
+            }
+            for tag in measure.tags {
+                configured = configured.with_tag(tag);
+            }
+            builder = builder.add_measure_with(configured)?;
+            if !format.is_empty() {
+                builder = builder.format_measure(&measure.name, format)?;
+            }
+        }
+        for virtual_dim in self.virtual_dimensions {
+            let data_type = parse_data_type(&virtual_dim.data_type)?;
+            builder =
+                builder.add_virtual_dimension(virtual_dim.name, virtual_dim.expression, data_type)?;
+        }
+        for calc in self.calculated_measures {
+            let data_type = parse_data_type(&calc.data_type)?;
+            if calc.aggregate {
+                builder = builder.add_aggregate_measure(calc.name, calc.expression, data_type)?;
+                continue;
+            }
+            let agg: AggFunc = calc.agg.parse()?;
+            builder = builder.add_calculated_measure(calc.name, calc.expression, data_type, agg)?;
+        }
+        for hierarchy in self.hierarchies {
+            builder = builder.add_hierarchy(hierarchy.name, hierarchy.levels)?;
+        }
+
+        Ok(match self.source {
+            None => builder,
+            Some(SourceDefinition::Csv {
+                path,
+                has_header,
+                delimiter,
+            }) => {
+                let mut source = CsvSource::new(path);
+                if let Some(has_header) = has_header {
+                    source = source.with_header(has_header);
+                }
+                if let Some(delimiter) = delimiter {
+                    let delimiter = u8::try_from(delimiter).map_err(|_| {
+                        Error::config(format!("CSV delimiter '{}' is not a single byte", delimiter))
+                    })?;
+                    source = source.with_delimiter(delimiter);
+                }
+                builder.load_csv_with(source)
+            }
+            Some(SourceDefinition::Parquet { path }) => {
+                builder.load_parquet_with(ParquetSource::new(path))
+            }
+            Some(SourceDefinition::Json { path }) => builder.load_json_with(JsonSource::new(path)),
+            Some(SourceDefinition::Partitioned { path }) => {
+                builder.load_partitioned_with(PartitionedDatasetSource::new(path))
+            }
+        })
+    }
+}
+
+impl SourceDefinition {
+    fn path_mut(&mut self) -> &mut String {
+        match self {
+            SourceDefinition::Csv { path, .. }
+            | SourceDefinition::Parquet { path }
+            | SourceDefinition::Json { path }
+            | SourceDefinition::Partitioned { path } => path,
+        }
+    }
+
+    /// Make a relative local path relative to `dir` instead of the working directory
+    fn resolve_relative_to(&mut self, dir: &Path) {
+        let path = self.path_mut();
+        if path.contains("://") || Path::new(path.as_str()).is_absolute() {
+            return;
+        }
+        *path = dir.join(path.as_str()).to_string_lossy().into_owned();
+    }
+}
+
+/// Parse a data type name such as `utf8`, `float64` or `Timestamp(Millisecond, None)`
+///
+/// Common lowercase aliases are accepted; anything else uses Arrow's own
+/// `DataType` syntax.
+fn parse_data_type(name: &str) -> Result<DataType> {
+    let data_type = match name.trim().to_ascii_lowercase().as_str() {
+        "string" | "utf8" | "text" => DataType::Utf8,
+        "bool" | "boolean" => DataType::Boolean,
+        "int8" => DataType::Int8,
+        "int16" => DataType::Int16,
+        "int32" | "int" => DataType::Int32,
+        "int64" | "bigint" => DataType::Int64,
+        "uint8" => DataType::UInt8,
+        "uint16" => DataType::UInt16,
+        "uint32" => DataType::UInt32,
+        "uint64" => DataType::UInt64,
+        "float32" | "float" => DataType::Float32,
+        "float64" | "double" => DataType::Float64,
+        "date" | "date32" => DataType::Date32,
+        "date64" => DataType::Date64,
+        _ => DataType::from_str(name.trim())
+            .map_err(|_| Error::config(format!("Unknown data type '{}'", name)))?,
+    };
+    Ok(data_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_definition_file_with_csv_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut csv = std::fs::File::create(dir.path().join("sales.csv")).unwrap();
+        writeln!(csv, "region,revenue,cost").unwrap();
+        writeln!(csv, "North,100.0,60.0").unwrap();
+        writeln!(csv, "South,80.0,50.0").unwrap();
+
+        let definition_path = dir.path().join("sales.json");
+        std::fs::write(
+            &definition_path,
+            r#"{
+                "name": "sales",
+                "source": { "type": "csv", "path": "sales.csv" },
+                "dimensions": [{ "name": "region", "type": "utf8" }],
+                "measures": [
+                    { "name": "revenue", "type": "float64", "agg": "sum" },
+                    { "name": "cost", "type": "Float64", "agg": "sum" }
+                ],
+                "calculated_measures": [
+                    { "name": "profit", "expression": "revenue - cost", "type": "double", "agg": "sum" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let cube = Arc::new(
+            ElastiCubeBuilder::from_definition_file(&definition_path)
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+        assert_eq!(cube.row_count(), 2);
+        assert!(cube.schema().get_calculated_measure("profit").is_some());
+
+        let result = cube
+            .query()
+            .unwrap()
+            .select(&["SUM(profit) AS profit"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 1);
+    }
+
+    #[test]
+    fn test_definition_errors() {
+        let unknown_type = r#"{ "name": "c", "dimensions": [{ "name": "d", "type": "nope" }] }"#;
+        assert!(CubeDefinition::from_json_str(unknown_type)
+            .unwrap()
+            .into_builder()
+            .is_err());
+
+        let unknown_field = r#"{ "name": "c", "dimension": [] }"#;
+        assert!(CubeDefinition::from_json_str(unknown_field).is_err());
+
+        assert_eq!(
+            parse_data_type("Timestamp(Millisecond, None)").unwrap(),
+            DataType::Timestamp(arrow::datatypes::TimeUnit::Millisecond, None)
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! Detecting changes to a source's columns on refresh
+//!
+//! Sources change under a cube: a column is added upstream, another is
+//! dropped, or a number starts arriving as text. Every refresh compares
+//! the loaded columns, after mapping, transforms and coercion, with the
+//! cube's and records the differences as a [`SchemaDrift`] in
+//! [`ElastiCube::load_report`](crate::ElastiCube::load_report). The
+//! [`DriftPolicy`] decides what new columns do; missing and retyped columns
+//! always fail the refresh, since the cube's own columns can't be filled.
+//!
+//! A cube that declares its dimensions and measures reads only those
+//! columns from the source, so new columns only show up for cubes whose
+//! schema was inferred from the data.
+
+use crate::error::{Error, Result};
+use arrow::datatypes::{DataType, Field, Schema as ArrowSchema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use std::fmt;
+use std::sync::Arc;
+
+/// A column whose loaded type differs from the cube's
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetypedColumn {
+    /// Column name
+    pub column: String,
+
+    /// Type of the column in the cube
+    pub expected: DataType,
+
+    /// Type of the loaded data
+    pub found: DataType,
+}
+
+/// Differences between the columns of loaded data and a cube
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaDrift {
+    /// Loaded columns the cube doesn't have
+    pub added: Vec<Field>,
+
+    /// Cube columns missing from the loaded data
+    pub missing: Vec<String>,
+
+    /// Columns loaded with a different type that coercion didn't resolve
+    pub retyped: Vec<RetypedColumn>,
+}
+
+impl SchemaDrift {
+    /// Compare the loaded schema `loaded` with the cube's `expected`
+    ///
+    /// Columns are matched by name, so a change of column order is not drift.
+    pub fn detect(expected: &ArrowSchema, loaded: &ArrowSchema) -> Self {
+        let mut drift = SchemaDrift::default();
+        for field in expected.fields() {
+            match loaded.field_with_name(field.name()) {
+                Ok(found) if found.data_type() != field.data_type() => {
+                    drift.retyped.push(RetypedColumn {
+                        column: field.name().clone(),
+                        expected: field.data_type().clone(),
+                        found: found.data_type().clone(),
+                    })
+                }
+                Ok(_) => {}
+                Err(_) => drift.missing.push(field.name().clone()),
+            }
+        }
+        drift.added = loaded
+            .fields()
+            .iter()
+            .filter(|field| expected.field_with_name(field.name()).is_err())
+            .map(|field| field.as_ref().clone().with_nullable(true))
+            .collect();
+        drift
+    }
+
+    /// Whether the loaded columns match the cube's
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.missing.is_empty() && self.retyped.is_empty()
+    }
+}
+
+impl fmt::Display for SchemaDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if !self.added.is_empty() {
+            let names: Vec<&str> = self.added.iter().map(|field| field.name().as_str()).collect();
+            parts.push(format!("new columns {}", names.join(", ")));
+        }
+        if !self.missing.is_empty() {
+            parts.push(format!("missing columns {}", self.missing.join(", ")));
+        }
+        if !self.retyped.is_empty() {
+            let changes: Vec<String> = self
+                .retyped
+                .iter()
+                .map(|r| format!("{} ({} -> {})", r.column, r.expected, r.found))
+                .collect();
+            parts.push(format!("retyped columns {}", changes.join(", ")));
+        }
+        if parts.is_empty() {
+            return f.write_str("no drift");
+        }
+        f.write_str(&parts.join("; "))
+    }
+}
+
+/// What a refresh does with source columns the cube doesn't have
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DriftPolicy {
+    /// Fail the refresh
+    #[default]
+    Fail,
+    /// Leave the new columns out of the cube
+    IgnoreNew,
+    /// Add the new columns to the cube as dimensions or measures, chosen
+    /// as for automatic classification; existing rows hold NULL in them
+    AutoAdd,
+}
+
+/// Reconcile `batches` with the cube's `expected` schema under `policy`
+///
+/// Returns the schema the cube will have and the batches in its column
+/// order, with new columns last under [`DriftPolicy::AutoAdd`] and dropped
+/// under [`DriftPolicy::IgnoreNew`]. Fails on drift the policy doesn't
+/// accept.
+pub(crate) fn resolve(
+    drift: &SchemaDrift,
+    policy: DriftPolicy,
+    expected: &SchemaRef,
+    batches: Vec<RecordBatch>,
+) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+    let accepted = drift.missing.is_empty()
+        && drift.retyped.is_empty()
+        && (drift.added.is_empty() || policy != DriftPolicy::Fail);
+    if !accepted {
+        return Err(Error::schema(format!(
+            "Source schema no longer matches the cube: {}",
+            drift
+        )));
+    }
+
+    let schema = match policy {
+        DriftPolicy::AutoAdd if !drift.added.is_empty() => {
+            Arc::new(with_columns(expected, &drift.added))
+        }
+        _ => Arc::clone(expected),
+    };
+    let batches = batches
+        .iter()
+        .map(|batch| {
+            let loaded = batch.schema();
+            let indices = schema
+                .fields()
+                .iter()
+                .map(|field| loaded.index_of(field.name()))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Ok(batch.project(&indices)?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((schema, batches))
+}
+
+/// `schema` with `fields` appended
+pub(crate) fn with_columns(schema: &ArrowSchema, fields: &[Field]) -> ArrowSchema {
+    let mut all: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    all.extend(fields.iter().cloned());
+    ArrowSchema::new_with_metadata(all, schema.metadata().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Float64Array, Int64Array, StringArray};
+
+    #[test]
+    fn test_detect_and_resolve() {
+        let expected = Arc::new(ArrowSchema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        let loaded = Arc::new(ArrowSchema::new(vec![
+            Field::new("amount", DataType::Float64, false),
+            Field::new("channel", DataType::Utf8, false),
+            Field::new("id", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&loaded),
+            vec![
+                Arc::new(Float64Array::from(vec![1.5, 2.5])),
+                Arc::new(StringArray::from(vec!["web", "store"])),
+                Arc::new(Int64Array::from(vec![1, 2])),
+            ],
+        )
+        .unwrap();
+
+        let drift = SchemaDrift::detect(&expected, &loaded);
+        assert_eq!(drift.added, vec![Field::new("channel", DataType::Utf8, true)]);
+        assert!(drift.missing.is_empty() && drift.retyped.is_empty());
+        assert_eq!(drift.to_string(), "new columns channel");
+
+        let batches = vec![batch];
+        assert!(resolve(&drift, DriftPolicy::Fail, &expected, batches.clone()).is_err());
+
+        let (schema, ignored) =
+            resolve(&drift, DriftPolicy::IgnoreNew, &expected, batches.clone()).unwrap();
+        assert_eq!(schema, expected);
+        assert_eq!(ignored[0].schema().field(0).name(), "id");
+        assert_eq!(ignored[0].num_columns(), 2);
+
+        let (schema, added) = resolve(&drift, DriftPolicy::AutoAdd, &expected, batches).unwrap();
+        assert_eq!(schema.fields().len(), 3);
+        assert_eq!(added[0].schema().field(2).name(), "channel");
+
+        // Missing and retyped columns fail whatever the policy
+        let narrowed = ArrowSchema::new(vec![Field::new("id", DataType::Utf8, false)]);
+        let drift = SchemaDrift::detect(&expected, &narrowed);
+        assert_eq!(
+            drift.to_string(),
+            "missing columns amount; retyped columns id (Int64 -> Utf8)"
+        );
+        assert!(resolve(&drift, DriftPolicy::AutoAdd, &expected, Vec::new()).is_err());
+    }
+}
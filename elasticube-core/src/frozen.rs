@@ -0,0 +1,122 @@
+//! Read-only cube handle
+//!
+//! A [`FrozenCube`] can be queried and inspected like an [`ElastiCube`],
+//! but it never hands out `&mut ElastiCube`, so appends, deletes, updates
+//! and other mutations do not compile against it. Give one to code that
+//! serves a published cube and must never change it.
+
+use crate::cube::ElastiCube;
+use crate::error::Result;
+use crate::optimization::OptimizationConfig;
+use crate::query::QueryBuilder;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Immutable, cheaply cloneable handle to a cube
+///
+/// Dereferences to [`ElastiCube`] for the read-only methods (schema,
+/// statistics, query history, exports). To change the data, copy it out
+/// with `(*frozen).clone()` and build a new cube from the copy.
+///
+/// ```compile_fail
+/// # fn demo(frozen: elasticube_core::FrozenCube, batch: arrow::record_batch::RecordBatch) {
+/// frozen.append_rows(batch); // cannot borrow data in dereference as mutable
+/// # }
+/// ```
+///
+/// # Example
+/// ```rust,ignore
+/// let frozen = cube.freeze();
+/// let result = frozen.query()?.select(&["region", "SUM(sales)"]).execute().await?;
+/// println!("{} rows", frozen.row_count());
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrozenCube {
+    cube: Arc<ElastiCube>,
+}
+
+impl FrozenCube {
+    /// Start a query against the cube
+    pub fn query(&self) -> Result<QueryBuilder> {
+        Arc::clone(&self.cube).query()
+    }
+
+    /// Start a query with custom optimization settings
+    pub fn query_with_config(&self, config: OptimizationConfig) -> Result<QueryBuilder> {
+        Arc::clone(&self.cube).query_with_config(config)
+    }
+}
+
+impl Deref for FrozenCube {
+    type Target = ElastiCube;
+
+    fn deref(&self) -> &ElastiCube {
+        &self.cube
+    }
+}
+
+impl From<ElastiCube> for FrozenCube {
+    fn from(cube: ElastiCube) -> Self {
+        Self {
+            cube: Arc::new(cube),
+        }
+    }
+}
+
+impl From<Arc<ElastiCube>> for FrozenCube {
+    fn from(cube: Arc<ElastiCube>) -> Self {
+        Self { cube }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ElastiCubeBuilder;
+    use crate::shared::SharedCube;
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+    use arrow::record_batch::RecordBatch;
+
+    fn sales_cube() -> ElastiCube {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("sales", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["North", "South"])),
+                Arc::new(Float64Array::from(vec![1.0, 2.0])),
+            ],
+        )
+        .unwrap();
+        ElastiCubeBuilder::new("sales")
+            .load_record_batches(schema, vec![batch])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_frozen_cube_queries_published_version() {
+        let shared = SharedCube::new(sales_cube());
+        let frozen = shared.freeze();
+
+        let batch = sales_cube().data()[0].clone();
+        shared.update(|cube| cube.append_rows(batch)).await.unwrap();
+        assert_eq!(shared.row_count(), 4);
+
+        // The handle keeps the version it was frozen from
+        assert_eq!(frozen.row_count(), 2);
+        let result = frozen
+            .query()
+            .unwrap()
+            .sql("SELECT COUNT(*) FROM cube")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 1);
+        assert_eq!(frozen.clone().schema().name(), "sales");
+    }
+}
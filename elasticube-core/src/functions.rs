@@ -0,0 +1,184 @@
+//! Additional SQL functions registered on query sessions
+//!
+//! DataFusion ships `CORR`, `COVAR_SAMP` and `COVAR_POP`, but has no
+//! higher-moment aggregates. This module provides `SKEWNESS` and `KURTOSIS`
+//! as user-defined aggregates so they can be used from SQL and the fluent API.
+
+use datafusion::arrow::array::ArrayRef;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::cast::as_float64_array;
+use datafusion::common::ScalarValue;
+use datafusion::error::Result as DFResult;
+use datafusion::logical_expr::function::AccumulatorArgs;
+use datafusion::logical_expr::{create_udaf, Accumulator, AggregateUDF, Volatility};
+use datafusion::prelude::SessionContext;
+use std::sync::Arc;
+
+/// Register the statistical aggregates on a session
+pub(crate) fn register_statistical_functions(ctx: &SessionContext) {
+    ctx.register_udaf(moment_udaf("skewness", Moment::Skewness));
+    ctx.register_udaf(moment_udaf("kurtosis", Moment::Kurtosis));
+}
+
+/// Build a single-argument aggregate over the first four power sums
+fn moment_udaf(name: &str, moment: Moment) -> AggregateUDF {
+    create_udaf(
+        name,
+        vec![DataType::Float64],
+        Arc::new(DataType::Float64),
+        Volatility::Immutable,
+        Arc::new(move |_: AccumulatorArgs| {
+            Ok(Box::new(MomentAccumulator::new(moment)) as Box<dyn Accumulator>)
+        }),
+        Arc::new(vec![DataType::Float64; 5]),
+    )
+}
+
+/// Which standardized moment to report
+#[derive(Debug, Clone, Copy)]
+enum Moment {
+    /// Adjusted Fisher-Pearson sample skewness (needs at least 3 values)
+    Skewness,
+    /// Sample excess kurtosis (needs at least 4 values)
+    Kurtosis,
+}
+
+/// Accumulates count and power sums; the moments are derived at the end
+#[derive(Debug)]
+struct MomentAccumulator {
+    moment: Moment,
+    count: f64,
+    sums: [f64; 4],
+}
+
+impl MomentAccumulator {
+    fn new(moment: Moment) -> Self {
+        Self {
+            moment,
+            count: 0.0,
+            sums: [0.0; 4],
+        }
+    }
+
+    fn compute(&self) -> Option<f64> {
+        let n = self.count;
+        let mean = self.sums[0] / n;
+        let s2 = self.sums[1] / n;
+        let s3 = self.sums[2] / n;
+        let s4 = self.sums[3] / n;
+
+        // Central moments from raw moments
+        let m2 = s2 - mean * mean;
+        if m2 <= 0.0 {
+            return None;
+        }
+
+        match self.moment {
+            Moment::Skewness => {
+                if n < 3.0 {
+                    return None;
+                }
+                let m3 = s3 - 3.0 * mean * s2 + 2.0 * mean.powi(3);
+                let g1 = m3 / m2.powf(1.5);
+                Some((n * (n - 1.0)).sqrt() / (n - 2.0) * g1)
+            }
+            Moment::Kurtosis => {
+                if n < 4.0 {
+                    return None;
+                }
+                let m4 = s4 - 4.0 * mean * s3 + 6.0 * mean * mean * s2 - 3.0 * mean.powi(4);
+                let g2 = m4 / (m2 * m2) - 3.0;
+                Some(((n + 1.0) * g2 + 6.0) * (n - 1.0) / ((n - 2.0) * (n - 3.0)))
+            }
+        }
+    }
+}
+
+impl Accumulator for MomentAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> DFResult<()> {
+        for value in as_float64_array(&values[0])?.iter().flatten() {
+            self.count += 1.0;
+            self.sums[0] += value;
+            self.sums[1] += value * value;
+            self.sums[2] += value.powi(3);
+            self.sums[3] += value.powi(4);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> DFResult<()> {
+        let counts = as_float64_array(&states[0])?;
+        for row in 0..counts.len() {
+            self.count += counts.value(row);
+            for (i, sum) in self.sums.iter_mut().enumerate() {
+                *sum += as_float64_array(&states[i + 1])?.value(row);
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> DFResult<Vec<ScalarValue>> {
+        let mut state = vec![ScalarValue::Float64(Some(self.count))];
+        state.extend(self.sums.iter().map(|s| ScalarValue::Float64(Some(*s))));
+        Ok(state)
+    }
+
+    fn evaluate(&mut self) -> DFResult<ScalarValue> {
+        Ok(ScalarValue::Float64(self.compute()))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Array, Float64Array};
+
+    #[tokio::test]
+    async fn test_skewness_and_kurtosis() {
+        let ctx = SessionContext::new();
+        register_statistical_functions(&ctx);
+
+        let batches = ctx
+            .sql(
+                "SELECT SKEWNESS(x) AS s, KURTOSIS(x) AS k, SKEWNESS(CAST(x AS INT)) AS si \
+                 FROM (VALUES (1.0), (2.0), (3.0), (4.0), (10.0)) AS t(x)",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+
+        let value = |i: usize| {
+            batches[0]
+                .column(i)
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap()
+                .value(0)
+        };
+        assert!((value(0) - 1.697_056).abs() < 1e-5);
+        assert!((value(1) - 3.152).abs() < 1e-6);
+        assert!((value(2) - 1.697_056).abs() < 1e-5);
+    }
+
+    #[tokio::test]
+    async fn test_moments_need_enough_values() {
+        let ctx = SessionContext::new();
+        register_statistical_functions(&ctx);
+
+        let batches = ctx
+            .sql("SELECT SKEWNESS(x), KURTOSIS(x) FROM (VALUES (1.0), (2.0)) AS t(x)")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert!(batches[0].column(0).is_null(0));
+        assert!(batches[0].column(1).is_null(0));
+    }
+}
@@ -0,0 +1,247 @@
+//! Suggesting how to model a source before building a cube
+//!
+//! [`ElastiCubeBuilder::infer_schema`](crate::ElastiCubeBuilder::infer_schema)
+//! samples the configured source and suggests a role for every column:
+//! numbers become measures summed by default, while strings, dates,
+//! booleans and identifier-like integers (`id`, `customer_id`) become
+//! dimensions. With
+//! [`with_auto_classification`](crate::ElastiCubeBuilder::with_auto_classification)
+//! a build without declared columns applies the same rules to the loaded
+//! data, and also leaves out text columns with too many distinct values to
+//! group by, such as free-text comments.
+
+use crate::cube::AggFunc;
+use arrow::array::{Array, AsArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use std::collections::HashSet;
+
+/// Number of rows `infer_schema` reads from the source
+pub(crate) const SAMPLE_ROWS: usize = 1000;
+
+/// Distinct values a text column may have and still be a dimension, unless
+/// it has at least twice as many rows
+const MAX_DIMENSION_VALUES: usize = 1000;
+
+/// Role suggested for a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnRole {
+    /// Group and filter by the column
+    Dimension,
+    /// Aggregate the column
+    Measure,
+    /// Keep the column out of the cube's schema
+    Skipped,
+}
+
+/// Suggested modelling of one source column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSuggestion {
+    /// Column name
+    pub name: String,
+
+    /// Arrow data type in the source
+    pub data_type: DataType,
+
+    /// Suggested role
+    pub role: ColumnRole,
+
+    /// Default aggregation of a suggested measure
+    pub aggregation: Option<AggFunc>,
+
+    /// Why the role was suggested
+    pub reason: String,
+}
+
+/// Inferred schema of a source with a suggested role for each column
+///
+/// # Example
+/// ```rust,ignore
+/// let builder = ElastiCubeBuilder::new("sales").load_csv("sales.csv");
+/// let preview = builder.infer_schema()?;
+/// for column in &preview.columns {
+///     println!("{}: {:?} ({})", column.name, column.role, column.reason);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SchemaPreview {
+    /// Arrow schema of the sampled data
+    pub schema: SchemaRef,
+
+    /// Number of rows sampled
+    pub sample_rows: usize,
+
+    /// Suggestions in schema order
+    pub columns: Vec<ColumnSuggestion>,
+}
+
+impl SchemaPreview {
+    /// Suggest roles for the columns of a sample
+    pub(crate) fn from_sample(schema: SchemaRef, batches: &[RecordBatch]) -> Self {
+        let columns = schema.fields().iter().map(|field| suggest(field)).collect();
+        Self {
+            sample_rows: batches.iter().map(|batch| batch.num_rows()).sum(),
+            schema,
+            columns,
+        }
+    }
+
+    /// Look up the suggestion for a column
+    pub fn column(&self, name: &str) -> Option<&ColumnSuggestion> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// Names of the columns suggested as dimensions
+    pub fn dimensions(&self) -> Vec<&str> {
+        self.names_with_role(ColumnRole::Dimension)
+    }
+
+    /// Names of the columns suggested as measures
+    pub fn measures(&self) -> Vec<&str> {
+        self.names_with_role(ColumnRole::Measure)
+    }
+
+    fn names_with_role(&self, role: ColumnRole) -> Vec<&str> {
+        self.columns
+            .iter()
+            .filter(|column| column.role == role)
+            .map(|column| column.name.as_str())
+            .collect()
+    }
+}
+
+/// Classify every column of loaded data, using its values where it helps
+pub(crate) fn classify_columns(
+    schema: &SchemaRef,
+    batches: &[RecordBatch],
+) -> Vec<ColumnSuggestion> {
+    let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+    let limit = MAX_DIMENSION_VALUES.max(rows / 2);
+
+    schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let suggestion = suggest(field);
+            if !is_text(field.data_type()) || distinct_strings(batches, index, limit) <= limit {
+                return suggestion;
+            }
+            ColumnSuggestion {
+                role: ColumnRole::Skipped,
+                reason: format!("text with more than {} distinct values", limit),
+                ..suggestion
+            }
+        })
+        .collect()
+}
+
+/// Distinct values of the string column `index`, counting up to `limit + 1`
+fn distinct_strings(batches: &[RecordBatch], index: usize, limit: usize) -> usize {
+    let mut seen: HashSet<String> = HashSet::new();
+    for batch in batches {
+        let Ok(column) = cast(batch.column(index), &DataType::Utf8) else {
+            return 0;
+        };
+        for value in column.as_string::<i32>().iter().flatten() {
+            if seen.len() > limit {
+                return seen.len();
+            }
+            if !seen.contains(value) {
+                seen.insert(value.to_string());
+            }
+        }
+    }
+    seen.len()
+}
+
+/// Suggest a role for `field` from its name and type
+pub(crate) fn suggest(field: &Field) -> ColumnSuggestion {
+    let data_type = field.data_type();
+    let (role, reason) = if data_type.is_integer() && looks_like_key(field.name()) {
+        (ColumnRole::Dimension, "integer identifier")
+    } else if data_type.is_numeric() {
+        (ColumnRole::Measure, "numeric")
+    } else if data_type.is_temporal() {
+        (ColumnRole::Dimension, "date or time")
+    } else if is_text(data_type) {
+        (ColumnRole::Dimension, "text")
+    } else {
+        (ColumnRole::Dimension, "not numeric")
+    };
+
+    ColumnSuggestion {
+        name: field.name().clone(),
+        data_type: data_type.clone(),
+        role,
+        aggregation: (role == ColumnRole::Measure).then_some(AggFunc::Sum),
+        reason: reason.to_string(),
+    }
+}
+
+fn is_text(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View)
+}
+
+/// Whether a column name looks like a key rather than a quantity
+fn looks_like_key(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    lower == "id"
+        || ["_id", "_key", "_code"].iter().any(|suffix| lower.ends_with(suffix))
+        || (name.ends_with("Id") && name.len() > 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::Schema as ArrowSchema;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_suggestions() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("customer_id", DataType::Int64, false),
+            Field::new("region", DataType::Utf8, false),
+            Field::new("order_date", DataType::Date32, false),
+            Field::new("quantity", DataType::Int64, false),
+            Field::new("price", DataType::Float64, false),
+            Field::new("productId", DataType::Int32, false),
+        ]));
+        let preview = SchemaPreview::from_sample(schema, &[]);
+
+        assert_eq!(preview.dimensions(), vec!["customer_id", "region", "order_date", "productId"]);
+        assert_eq!(preview.measures(), vec!["quantity", "price"]);
+        assert_eq!(preview.column("price").unwrap().aggregation, Some(AggFunc::Sum));
+        assert_eq!(preview.column("customer_id").unwrap().reason, "integer identifier");
+        assert_eq!(preview.sample_rows, 0);
+    }
+
+    #[test]
+    fn test_classify_columns() {
+        use arrow::array::{Float64Array, StringArray};
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("comment", DataType::Utf8, true),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        let rows = 3000;
+        let regions = (0..rows).map(|i| ["North", "South"][i % 2]);
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![
+                Arc::new(StringArray::from_iter_values(regions)),
+                Arc::new(StringArray::from_iter_values((0..rows).map(|i| format!("note {}", i)))),
+                Arc::new(Float64Array::from_iter_values((0..rows).map(|i| i as f64))),
+            ],
+        )
+        .unwrap();
+
+        let decisions = classify_columns(&schema, &[batch]);
+        let roles: Vec<ColumnRole> = decisions.iter().map(|d| d.role).collect();
+        assert_eq!(roles, vec![ColumnRole::Dimension, ColumnRole::Skipped, ColumnRole::Measure]);
+        assert_eq!(decisions[1].reason, "text with more than 1500 distinct values");
+        assert_eq!(decisions[2].aggregation, Some(AggFunc::Sum));
+    }
+}
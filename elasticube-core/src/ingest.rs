@@ -0,0 +1,660 @@
+//! Reconciling loaded data with a cube's declared schema
+//!
+//! Sources rarely match the cube exactly. Headers are named differently
+//! (`"Sales Amount"` for `sales`), files carry columns the cube doesn't use,
+//! CSV inference picks `Int64` where the cube says `Int32`, and dates arrive
+//! as strings. The builder can map source columns to cube columns, drop the
+//! extra ones, clean or derive columns with load-time transforms and, with
+//! a [`Coercion`] mode, cast loaded columns to the declared types; the
+//! [`LoadReport`] of the built cube lists the casts.
+
+use crate::constraints::{self, Constraint, ConstraintViolation, ViolationPolicy};
+use crate::cube::{rename_batch_column, rename_field, rename_identifier};
+use crate::drift::{DriftPolicy, SchemaDrift};
+use crate::error::{Error, Result};
+use crate::inference::ColumnSuggestion;
+use crate::progress::{self, ProgressCallback};
+use crate::sources::DataSource;
+use arrow::array::Array;
+use arrow::compute::{can_cast_types, cast_with_options, CastOptions};
+use arrow::datatypes::{DataType, Field, Schema as ArrowSchema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use datafusion::common::DFSchema;
+use datafusion::logical_expr::Expr;
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::prelude::{ident, SessionContext};
+use std::fmt;
+use std::sync::Arc;
+
+/// How loaded columns whose type differs from the declared one are handled
+///
+/// # Example
+/// ```rust,ignore
+/// // quantity is inferred as Int64 and order_date as Utf8 from the CSV
+/// let cube = ElastiCubeBuilder::new("orders")
+///     .add_dimension("order_date", DataType::Date32)?
+///     .add_measure("quantity", DataType::Int32, AggFunc::Sum)?
+///     .with_type_coercion(Coercion::Safe)
+///     .load_csv("orders.csv")
+///     .build()?;
+///
+/// for coercion in &cube.load_report().coercions {
+///     println!("{}: {} -> {}", coercion.column, coercion.from, coercion.to);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Coercion {
+    /// Types must match exactly
+    #[default]
+    Strict,
+
+    /// Cast to the declared type, failing on any value that doesn't convert
+    ///
+    /// Strings are parsed (so `"2024-03-01"` becomes a date) and integers
+    /// must fit the declared width. Casts that always lose information,
+    /// such as floats to integers or timestamps to dates, are rejected.
+    Safe,
+
+    /// Cast whenever Arrow can; values that don't convert become NULL
+    Lenient,
+}
+
+impl std::str::FromStr for Coercion {
+    type Err = Error;
+
+    /// Parse `strict`, `safe` or `lenient`, ignoring case
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "strict" | "none" => Ok(Coercion::Strict),
+            "safe" => Ok(Coercion::Safe),
+            "lenient" => Ok(Coercion::Lenient),
+            _ => Err(Error::config(format!(
+                "Unknown type coercion '{}'; expected strict, safe or lenient",
+                s
+            ))),
+        }
+    }
+}
+
+/// A loaded column that was cast to its declared type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnCoercion {
+    /// Column name
+    pub column: String,
+
+    /// Type of the loaded data
+    pub from: DataType,
+
+    /// Declared type the column was cast to
+    pub to: DataType,
+
+    /// Non-null values that became NULL because they didn't convert
+    /// (only with [`Coercion::Lenient`])
+    pub nulled: usize,
+}
+
+/// How rows that fail to parse are handled while loading
+///
+/// # Example
+/// ```rust,ignore
+/// let cube = ElastiCubeBuilder::new("events")
+///     .with_error_policy(ErrorPolicy::SkipBadRows { max: 1000 })
+///     .load_csv("events.csv")
+///     .build()?;
+///
+/// for row in &cube.load_report().skipped_rows {
+///     eprintln!("skipped line {}: {}", row.row, row.reason);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// The first bad row fails the load
+    #[default]
+    Fail,
+
+    /// Skip bad rows, failing only once more than `max` have been skipped
+    ///
+    /// Supported by CSV sources; other sources fail on the first bad row.
+    SkipBadRows {
+        /// Most rows that may be skipped
+        max: usize,
+    },
+}
+
+/// A source row left out of a load because it didn't parse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadRow {
+    /// 1-based position of the row in the source (the line it starts on
+    /// for CSV files)
+    pub row: usize,
+
+    /// Why the row couldn't be read
+    pub reason: String,
+}
+
+/// What happened to the data on its way into a cube
+///
+/// Describes the most recent load: the build, or the latest refresh.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// Columns cast to their declared types
+    pub coercions: Vec<ColumnCoercion>,
+
+    /// Role chosen for every loaded column, when the build classified them
+    /// automatically (see `ElastiCubeBuilder::with_auto_classification`)
+    pub classifications: Vec<ColumnSuggestion>,
+
+    /// Rows skipped under [`ErrorPolicy::SkipBadRows`]
+    pub skipped_rows: Vec<BadRow>,
+
+    /// Constraints the loaded data breaks, under [`ViolationPolicy::Report`]
+    pub violations: Vec<ConstraintViolation>,
+
+    /// How the source's columns differed from the cube's on a refresh
+    pub drift: SchemaDrift,
+}
+
+impl LoadReport {
+    /// Whether there is nothing to report
+    pub fn is_empty(&self) -> bool {
+        self.coercions.is_empty()
+            && self.classifications.is_empty()
+            && self.skipped_rows.is_empty()
+            && self.violations.is_empty()
+            && self.drift.is_empty()
+    }
+}
+
+/// How the builder reconciles source data with the cube, kept for refreshes
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LoadSettings {
+    pub(crate) coercion: Coercion,
+
+    /// Source column names with the cube columns they load into
+    pub(crate) column_map: Vec<(String, String)>,
+
+    /// Whether source columns the cube doesn't need are dropped
+    pub(crate) ignore_extra_columns: bool,
+
+    /// Rewrites applied to the loaded columns, in order
+    pub(crate) transforms: Vec<ColumnTransform>,
+
+    pub(crate) error_policy: ErrorPolicy,
+
+    /// Called as sources are read
+    pub(crate) progress: Option<ProgressCallback>,
+
+    /// Rules the loaded data must satisfy
+    pub(crate) constraints: Vec<Constraint>,
+    pub(crate) violation_policy: ViolationPolicy,
+
+    /// What refreshes do with new source columns
+    pub(crate) drift_policy: DriftPolicy,
+}
+
+impl LoadSettings {
+    /// Load `source`, reading only `columns` if given
+    ///
+    /// Also returns the rows the error policy skipped. Progress is reported
+    /// under the name `label`.
+    pub(crate) fn load(
+        &self,
+        label: &str,
+        source: &dyn DataSource,
+        columns: Option<&[String]>,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>, Vec<BadRow>)> {
+        let load = || self.load_untracked(source, columns);
+        match &self.progress {
+            Some(callback) => {
+                progress::track(callback, label, source.estimated_size(columns), load)
+            }
+            None => load(),
+        }
+    }
+
+    fn load_untracked(
+        &self,
+        source: &dyn DataSource,
+        columns: Option<&[String]>,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>, Vec<BadRow>)> {
+        match (self.error_policy, columns) {
+            (ErrorPolicy::SkipBadRows { max }, _) => source.load_skipping_bad_rows(columns, max),
+            (ErrorPolicy::Fail, Some(columns)) => {
+                let (schema, batches) = source.load_projected(columns)?;
+                Ok((schema, batches, Vec::new()))
+            }
+            (ErrorPolicy::Fail, None) => {
+                let (schema, batches) = source.load()?;
+                Ok((schema, batches, Vec::new()))
+            }
+        }
+    }
+
+    /// Load source column `source` into the cube column `column`
+    pub(crate) fn map_column(&mut self, source: String, column: String) {
+        self.column_map.retain(|(mapped, _)| *mapped != source);
+        self.column_map.push((source, column));
+    }
+
+    /// The source names of the cube's `columns`
+    pub(crate) fn source_columns(&self, columns: &[String]) -> Vec<String> {
+        columns
+            .iter()
+            .map(|column| {
+                self.column_map
+                    .iter()
+                    .find(|(_, mapped)| mapped == column)
+                    .map_or_else(|| column.clone(), |(source, _)| source.clone())
+            })
+            .collect()
+    }
+
+    /// `batch` with mapped source columns under their cube names
+    pub(crate) fn map_batch(&self, batch: &RecordBatch) -> Result<RecordBatch> {
+        self.column_map
+            .iter()
+            .try_fold(batch.clone(), |batch, (source, column)| {
+                rename_batch_column(&batch, source, column)
+            })
+    }
+
+    /// Map the source columns of a load and drop those not in `keep`
+    ///
+    /// Extra columns are only dropped with `ignore_extra_columns` and when
+    /// the cube declares the columns it needs.
+    pub(crate) fn reconcile(
+        &self,
+        keep: Option<&[String]>,
+        schema: &ArrowSchema,
+        batches: Vec<RecordBatch>,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+        let schema = self
+            .column_map
+            .iter()
+            .fold(Arc::new(schema.clone()), |schema, (source, column)| {
+                rename_field(&schema, source, column)
+            });
+        let batches = batches
+            .iter()
+            .map(|batch| self.map_batch(batch))
+            .collect::<Result<Vec<_>>>()?;
+
+        match keep.filter(|_| self.ignore_extra_columns) {
+            Some(keep) => {
+                let schema = Arc::new(keep_columns_of_schema(&schema, keep)?);
+                let batches = batches
+                    .iter()
+                    .map(|batch| keep_columns(batch, keep))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok((schema, batches))
+            }
+            None => Ok((schema, batches)),
+        }
+    }
+
+    /// Apply the transforms to loaded data
+    pub(crate) fn transform(
+        &self,
+        schema: SchemaRef,
+        batches: Vec<RecordBatch>,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+        self.transforms
+            .iter()
+            .try_fold((schema, batches), |(schema, batches), transform| {
+                transform.apply(&schema, &batches)
+            })
+    }
+
+    /// Check the constraints against the loaded data
+    ///
+    /// Fails on violations unless the policy only reports them.
+    pub(crate) fn check_constraints(
+        &self,
+        schema: &SchemaRef,
+        batches: &[RecordBatch],
+    ) -> Result<Vec<ConstraintViolation>> {
+        constraints::enforce(&self.constraints, self.violation_policy, schema, batches)
+    }
+
+    /// Follow a rename of the cube column `old`
+    pub(crate) fn rename_column(&mut self, old: &str, new: &str) {
+        for constraint in &mut self.constraints {
+            constraint.rename_column(old, new);
+        }
+        for transform in &mut self.transforms {
+            if transform.column == old {
+                transform.column = new.to_string();
+            }
+            if let TransformExpr::Sql(sql) = &mut transform.expression {
+                *sql = rename_identifier(sql, old, new);
+            }
+        }
+    }
+}
+
+/// A load-time rewrite of a column, or a new column derived from others
+#[derive(Clone)]
+pub(crate) struct ColumnTransform {
+    /// Column the result is stored in, replacing any loaded column
+    column: String,
+    expression: TransformExpr,
+}
+
+#[derive(Clone)]
+enum TransformExpr {
+    /// SQL expression over the loaded columns
+    Sql(String),
+
+    /// Builds the expression from a reference to the column itself
+    Function(Arc<dyn Fn(Expr) -> Expr + Send + Sync>),
+}
+
+impl fmt::Debug for ColumnTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expression = match &self.expression {
+            TransformExpr::Sql(sql) => sql.as_str(),
+            TransformExpr::Function(_) => "<fn>",
+        };
+        f.debug_struct("ColumnTransform")
+            .field("column", &self.column)
+            .field("expression", &expression)
+            .finish()
+    }
+}
+
+impl ColumnTransform {
+    /// Store the value of the SQL expression `sql` in `column`
+    pub(crate) fn sql(column: impl Into<String>, sql: impl Into<String>) -> Self {
+        Self {
+            column: column.into(),
+            expression: TransformExpr::Sql(sql.into()),
+        }
+    }
+
+    /// Store the expression `function` builds from the column in `column`
+    pub(crate) fn function(
+        column: impl Into<String>,
+        function: impl Fn(Expr) -> Expr + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            column: column.into(),
+            expression: TransformExpr::Function(Arc::new(function)),
+        }
+    }
+
+    /// Columns the transform reads
+    pub(crate) fn inputs(&self) -> Vec<String> {
+        match &self.expression {
+            TransformExpr::Sql(sql) => crate::builder::expression_identifiers(sql),
+            TransformExpr::Function(function) => function(ident(&self.column))
+                .column_refs()
+                .into_iter()
+                .map(|column| column.name.clone())
+                .collect(),
+        }
+    }
+
+    fn compile(&self, schema: &ArrowSchema) -> Result<Arc<dyn PhysicalExpr>> {
+        let df_schema = DFSchema::try_from(schema.clone())?;
+        let ctx = SessionContext::new();
+        let logical = match &self.expression {
+            TransformExpr::Sql(sql) => ctx.parse_sql_expr(sql, &df_schema).map_err(|e| {
+                Error::data(format!(
+                    "Invalid transform of '{}' ('{}'): {}",
+                    self.column, sql, e
+                ))
+            })?,
+            TransformExpr::Function(function) => function(ident(&self.column)),
+        };
+        ctx.create_physical_expr(logical, &df_schema).map_err(|e| {
+            Error::data(format!("Failed to plan transform of '{}': {}", self.column, e))
+        })
+    }
+
+    /// `batches` with the transformed column replaced, or appended if new
+    fn apply(
+        &self,
+        schema: &SchemaRef,
+        batches: &[RecordBatch],
+    ) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+        let expr = self.compile(schema)?;
+        let field = Field::new(&self.column, expr.data_type(schema)?, expr.nullable(schema)?);
+
+        let index = schema.index_of(&self.column).ok();
+        let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+        match index {
+            Some(index) => fields[index] = field,
+            None => fields.push(field),
+        }
+        let output = Arc::new(ArrowSchema::new_with_metadata(fields, schema.metadata().clone()));
+
+        let batches = batches
+            .iter()
+            .map(|batch| {
+                let values = expr.evaluate(batch)?.into_array(batch.num_rows())?;
+                let mut columns = batch.columns().to_vec();
+                match index {
+                    Some(index) => columns[index] = values,
+                    None => columns.push(values),
+                }
+                Ok(RecordBatch::try_new(Arc::clone(&output), columns)?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok((output, batches))
+    }
+}
+
+/// `batch` without the columns that aren't in `keep`
+pub(crate) fn keep_columns(batch: &RecordBatch, keep: &[String]) -> Result<RecordBatch> {
+    let indices = kept_indices(&batch.schema(), keep);
+    Ok(batch.project(&indices)?)
+}
+
+fn keep_columns_of_schema(schema: &ArrowSchema, keep: &[String]) -> Result<ArrowSchema> {
+    Ok(schema.project(&kept_indices(schema, keep))?)
+}
+
+fn kept_indices(schema: &ArrowSchema, keep: &[String]) -> Vec<usize> {
+    schema
+        .fields()
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| keep.contains(field.name()))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+impl Coercion {
+    /// Fail unless this mode may cast `column` from `from` to `to`
+    fn check(self, column: &str, from: &DataType, to: &DataType) -> Result<()> {
+        let lossy = (from.is_floating() && to.is_integer())
+            || (matches!(from, DataType::Timestamp(_, _))
+                && matches!(to, DataType::Date32 | DataType::Date64));
+        let allowed = match self {
+            Coercion::Strict => false,
+            Coercion::Safe => !lossy && can_cast_types(from, to),
+            Coercion::Lenient => can_cast_types(from, to),
+        };
+        if allowed {
+            return Ok(());
+        }
+        Err(Error::schema(format!(
+            "Field '{}' has incompatible type: expected {:?}, found {:?}",
+            column, to, from
+        )))
+    }
+}
+
+/// Cast the columns of `batches` whose type differs from `expected`
+///
+/// Columns missing from `expected` are left alone. Returns the coerced
+/// schema and batches with a record of every cast column.
+pub(crate) fn coerce_batches(
+    expected: &ArrowSchema,
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+    coercion: Coercion,
+) -> Result<(SchemaRef, Vec<RecordBatch>, Vec<ColumnCoercion>)> {
+    if coercion == Coercion::Strict {
+        return Ok((schema, batches, Vec::new()));
+    }
+
+    let mut coercions = Vec::new();
+    let mut targets = Vec::new();
+    for (index, field) in schema.fields().iter().enumerate() {
+        let Ok(declared) = expected.field_with_name(field.name()) else {
+            continue;
+        };
+        if field.data_type() == declared.data_type() {
+            continue;
+        }
+        coercion.check(field.name(), field.data_type(), declared.data_type())?;
+        targets.push(index);
+        coercions.push(ColumnCoercion {
+            column: field.name().clone(),
+            from: field.data_type().clone(),
+            to: declared.data_type().clone(),
+            nulled: 0,
+        });
+    }
+    if targets.is_empty() {
+        return Ok((schema, batches, coercions));
+    }
+
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    for (&index, coerced) in targets.iter().zip(&coercions) {
+        fields[index] = fields[index].clone().with_data_type(coerced.to.clone());
+    }
+    let coerced_schema = Arc::new(ArrowSchema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let options = CastOptions {
+        safe: coercion == Coercion::Lenient,
+        ..CastOptions::default()
+    };
+    let mut coerced_batches = Vec::with_capacity(batches.len());
+    for batch in &batches {
+        let mut columns = batch.columns().to_vec();
+        for (&index, coerced) in targets.iter().zip(coercions.iter_mut()) {
+            let column = &columns[index];
+            let cast = cast_with_options(column, &coerced.to, &options).map_err(|e| {
+                Error::data(format!(
+                    "Cannot coerce column '{}' from {:?} to {:?}: {}",
+                    coerced.column, coerced.from, coerced.to, e
+                ))
+            })?;
+            coerced.nulled += cast.null_count() - column.null_count();
+            columns[index] = cast;
+        }
+        coerced_batches.push(RecordBatch::try_new(Arc::clone(&coerced_schema), columns)?);
+    }
+
+    Ok((coerced_schema, coerced_batches, coercions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Date32Array, Float64Array, Int32Array, Int64Array, StringArray};
+
+    fn batch() -> RecordBatch {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("order_date", DataType::Utf8, true),
+            Field::new("quantity", DataType::Int64, true),
+            Field::new("price", DataType::Float64, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["2024-03-01", "not a date"])),
+                Arc::new(Int64Array::from(vec![3, 4])),
+                Arc::new(Float64Array::from(vec![9.5, 10.0])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn expected(price: DataType) -> ArrowSchema {
+        ArrowSchema::new(vec![
+            Field::new("order_date", DataType::Date32, true),
+            Field::new("quantity", DataType::Int32, true),
+            Field::new("price", price, true),
+        ])
+    }
+
+    #[test]
+    fn test_coerce_batches() {
+        let batch = batch();
+        let expected = expected(DataType::Float64);
+
+        // Strict leaves the data for schema validation to reject
+        let (schema, _, coercions) =
+            coerce_batches(&expected, batch.schema(), vec![batch.clone()], Coercion::Strict)
+                .unwrap();
+        assert_eq!(schema.field(0).data_type(), &DataType::Utf8);
+        assert!(coercions.is_empty());
+
+        // Safe fails on the unparseable date
+        assert!(
+            coerce_batches(&expected, batch.schema(), vec![batch.clone()], Coercion::Safe)
+                .is_err()
+        );
+
+        let (schema, batches, coercions) =
+            coerce_batches(&expected, batch.schema(), vec![batch.clone()], Coercion::Lenient)
+                .unwrap();
+        assert_eq!(schema.field(0).data_type(), &DataType::Date32);
+        assert_eq!(coercions.len(), 2);
+        assert_eq!(coercions[0].nulled, 1);
+        assert_eq!(coercions[1].from, DataType::Int64);
+        let dates = batches[0].column(0).as_any().downcast_ref::<Date32Array>().unwrap();
+        assert_eq!(dates.value(0), 19783);
+        assert!(dates.is_null(1));
+        let quantities = batches[0].column(1).as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(quantities.value(1), 4);
+    }
+
+    #[test]
+    fn test_safe_rejects_lossy_casts() {
+        let batch = batch().project(&[1, 2]).unwrap();
+        let expected = expected(DataType::Int64);
+        assert!(
+            coerce_batches(&expected, batch.schema(), vec![batch.clone()], Coercion::Safe)
+                .is_err()
+        );
+        assert!(
+            coerce_batches(&expected, batch.schema(), vec![batch], Coercion::Lenient).is_ok()
+        );
+        assert_eq!("Safe".parse::<Coercion>().unwrap(), Coercion::Safe);
+        assert!("loose".parse::<Coercion>().is_err());
+    }
+
+    #[test]
+    fn test_transforms() {
+        let mut settings = LoadSettings::default();
+        settings.transforms.push(ColumnTransform::sql("order_date", "trim(order_date)"));
+        settings.transforms.push(ColumnTransform::sql("revenue", "quantity * price"));
+        settings.transforms.push(ColumnTransform::function("quantity", |expr| {
+            expr * datafusion::prelude::lit(10_i64)
+        }));
+        assert_eq!(settings.transforms[1].inputs(), vec!["quantity", "price"]);
+        assert_eq!(settings.transforms[2].inputs(), vec!["quantity"]);
+
+        let batch = batch();
+        let (schema, batches) = settings.transform(batch.schema(), vec![batch]).unwrap();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, vec!["order_date", "quantity", "price", "revenue"]);
+
+        let revenue = batches[0].column(3).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(revenue.value(1), 40.0);
+        let quantities = batches[0].column(1).as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(quantities.value(0), 30);
+
+        // Renames reach SQL transforms
+        settings.rename_column("price", "unit_price");
+        assert_eq!(settings.transforms[1].inputs(), vec!["quantity", "unit_price"]);
+
+        let mut settings = LoadSettings::default();
+        settings.transforms.push(ColumnTransform::sql("total", "missing + 1"));
+        assert!(settings.transform(batch().schema(), vec![batch()]).is_err());
+    }
+}
@@ -32,14 +32,33 @@
 //! }
 //! ```
 
+pub mod audit;
 pub mod builder;
 pub mod cache;
+pub mod constraints;
+pub mod context;
 pub mod cube;
+pub mod definition;
+pub mod drift;
 pub mod error;
+pub mod frozen;
+mod functions;
+pub mod inference;
+pub mod ingest;
 pub mod optimization;
+#[cfg(feature = "polars")]
+mod polars_interop;
+mod predicate;
+pub mod progress;
 pub mod query;
+pub mod query_log;
+pub mod render;
+pub mod server;
+pub mod shared;
 pub mod storage;
 pub mod sources;
+mod time;
+pub mod viz;
 
 #[cfg(test)]
 mod query_materialization_tests;
@@ -48,16 +67,39 @@ mod query_materialization_tests;
 mod cube_update_tests;
 
 // Re-export commonly used types
-pub use builder::ElastiCubeBuilder;
+pub use audit::{AuditEvent, AuditSink, MemoryAuditSink};
+pub use builder::{CubeTemplate, ElastiCubeBuilder};
 pub use cache::{CacheStats, QueryCache, QueryCacheKey};
+pub use constraints::{Constraint, ConstraintViolation, ViolationPolicy};
+pub use context::{ContextQuery, CubeContext};
 pub use cube::{
-    AggFunc, CalculatedMeasure, CubeSchema, Dimension, ElastiCube, Hierarchy, Measure,
-    VirtualDimension,
+    AggFunc, AsOf, BatchCompression, BridgeDimension, CalculatedMeasure, ChangeEvent,
+    ChangeSummary, ColumnDescription, ColumnKind, ColumnProfile, CubeDescription, CubeDiff,
+    CubeProfile, CubeSchema, CubeVersion, DateParts, Dimension, DisplayFormat, DuplicatePolicy,
+    ElastiCube, Hierarchy, HierarchyDescription, HistogramBin, Measure, PrimaryKey,
+    ProfileOptions, Transaction, VirtualDimension,
 };
+pub use definition::CubeDefinition;
+pub use drift::{DriftPolicy, RetypedColumn, SchemaDrift};
 pub use error::{Error, Result};
-pub use optimization::{ColumnStatistics, CubeStatistics, OptimizationConfig};
-pub use query::{QueryBuilder, QueryResult};
-pub use sources::{CsvSource, DataSource, JsonSource, ParquetSource, RecordBatchSource};
+pub use frozen::FrozenCube;
+pub use inference::{ColumnRole, ColumnSuggestion, SchemaPreview};
+pub use ingest::{BadRow, Coercion, ColumnCoercion, ErrorPolicy, LoadReport};
+pub use progress::LoadProgress;
+pub use optimization::{ColumnStatistics, CubeStatistics, OptimizationConfig, ZoneMap};
+pub use query::{
+    FillStrategy, Granularity, Histogram, Paginator, PreparedQuery, QueryBuilder, QueryPlan,
+    QueryResult, QueryStream,
+};
+pub use query_log::{QueryLog, QueryRecord};
+pub use render::RenderOptions;
+pub use server::{CubeCatalog, QueryRequest, PRINCIPAL_HEADER};
+pub use shared::{CubeWriter, SharedCube};
+pub use storage::ParquetExportOptions;
+pub use sources::{
+    CsvSource, DataSource, JsonSource, ParquetSource, PartitionFileFormat,
+    PartitionedDatasetSource, RecordBatchSource, UnionSource,
+};
 
 // Re-export database sources when feature is enabled
 /// Database source connectors (PostgreSQL, MySQL, SQL Server, etc.)
@@ -73,6 +115,19 @@ pub use sources::{CsvSource, DataSource, JsonSource, ParquetSource, RecordBatchS
 #[cfg(feature = "database")]
 pub use sources::database::{MySqlSource, OdbcSource, PostgresSource};
 
+// Re-export the native MySQL source when feature is enabled
+/// Pure-Rust MySQL / MariaDB source connector (no ODBC driver required)
+///
+/// This type is only available when the `mysql-native` feature is enabled:
+/// ```toml
+/// [dependencies]
+/// elasticube-core = { version = "0.2", features = ["mysql-native"] }
+/// ```
+///
+/// See [`ElastiCubeBuilder::load_mysql_native`] for usage examples.
+#[cfg(feature = "mysql-native")]
+pub use sources::mysql::MySqlNativeSource;
+
 // Re-export REST API sources when feature is enabled
 /// REST API data source connector
 ///
@@ -84,7 +139,7 @@ pub use sources::database::{MySqlSource, OdbcSource, PostgresSource};
 ///
 /// See [`ElastiCubeBuilder::load_rest_api`] for usage examples.
 #[cfg(feature = "rest-api")]
-pub use sources::rest::{HttpMethod, RestApiSource};
+pub use sources::rest::{HttpMethod, OAuth2ClientCredentials, RestApiSource, StaticToken, TokenProvider};
 
 // Re-export object storage sources when feature is enabled
 /// Object storage source connectors (AWS S3, Google Cloud Storage, Azure Blob Storage)
@@ -105,3 +160,115 @@ pub use sources::rest::{HttpMethod, RestApiSource};
 /// and [`ElastiCubeBuilder::load_azure`] for usage examples.
 #[cfg(feature = "object-storage")]
 pub use sources::object_storage::{AzureSource, GcsSource, ObjectStorageSource, S3Source, StorageFileFormat};
+
+// Re-export Iceberg sources when feature is enabled
+/// Apache Iceberg table source
+///
+/// These types are only available when the `iceberg` feature is enabled:
+/// ```toml
+/// [dependencies]
+/// elasticube-core = { version = "0.2", features = ["iceberg"] }
+/// ```
+///
+/// See [`ElastiCubeBuilder::load_iceberg_with`] for usage examples.
+#[cfg(feature = "iceberg")]
+pub use sources::iceberg::{IcebergCatalog, IcebergSnapshot, IcebergSource};
+
+// Re-export Excel sources when feature is enabled
+/// Excel workbook source
+///
+/// This type is only available when the `excel` feature is enabled:
+/// ```toml
+/// [dependencies]
+/// elasticube-core = { version = "0.2", features = ["excel"] }
+/// ```
+///
+/// See [`ElastiCubeBuilder::load_excel`] for usage examples.
+#[cfg(feature = "excel")]
+pub use sources::excel::ExcelSource;
+
+// Re-export MongoDB sources when feature is enabled
+/// MongoDB collection source with document flattening
+///
+/// These types are only available when the `mongodb` feature is enabled:
+/// ```toml
+/// [dependencies]
+/// elasticube-core = { version = "0.2", features = ["mongodb"] }
+/// ```
+///
+/// See [`ElastiCubeBuilder::load_mongo_with`] for usage examples.
+#[cfg(feature = "mongodb")]
+pub use sources::mongo::{MongoQuery, MongoSource};
+
+// Re-export Lance sources when feature is enabled
+/// Lance columnar dataset source with version time travel
+///
+/// These types are only available when the `lance` feature is enabled:
+/// ```toml
+/// [dependencies]
+/// elasticube-core = { version = "0.2", features = ["lance"] }
+/// ```
+///
+/// See [`ElastiCubeBuilder::load_lance_with`] for usage examples.
+#[cfg(feature = "lance")]
+pub use sources::lance::{LanceSource, LanceVersion};
+
+// Re-export Kafka sources when feature is enabled
+/// Kafka topic source with Confluent Schema Registry decoding (Avro, Protobuf, JSON)
+///
+/// These types are only available when the `kafka` feature is enabled:
+/// ```toml
+/// [dependencies]
+/// elasticube-core = { version = "0.2", features = ["kafka"] }
+/// ```
+///
+/// See [`ElastiCubeBuilder::load_kafka_with`] for usage examples.
+#[cfg(feature = "kafka")]
+pub use sources::kafka::{KafkaSource, MessageFormat, SchemaRegistry};
+
+// Re-export the Flight SQL server when feature is enabled
+/// Arrow Flight SQL server for querying cubes over the network
+///
+/// This type is only available when the `flight-sql` feature is enabled:
+/// ```toml
+/// [dependencies]
+/// elasticube-core = { version = "0.2", features = ["flight-sql"] }
+/// ```
+///
+/// Together with the `server` feature, the `elasticube-server` binary also
+/// serves cube definition files over Flight SQL.
+#[cfg(feature = "flight-sql")]
+pub use server::flight::FlightSqlServer;
+
+// Re-export the HTTP server when feature is enabled
+/// HTTP server with JSON and Arrow IPC query endpoints
+///
+/// This type is only available when the `server` feature is enabled:
+/// ```toml
+/// [dependencies]
+/// elasticube-core = { version = "0.2", features = ["server"] }
+/// ```
+#[cfg(feature = "server")]
+pub use server::rest::RestServer;
+
+// Re-export the PostgreSQL wire-protocol server when feature is enabled
+/// Server letting Postgres clients (psql, Grafana, Metabase) query cubes
+///
+/// This type is only available when the `pgwire` feature is enabled:
+/// ```toml
+/// [dependencies]
+/// elasticube-core = { version = "0.2", features = ["pgwire"] }
+/// ```
+#[cfg(feature = "pgwire")]
+pub use server::postgres::PostgresServer;
+
+// Re-export the gRPC server when feature is enabled
+/// gRPC service streaming query results as Arrow IPC
+///
+/// This type is only available when the `grpc` feature is enabled:
+/// ```toml
+/// [dependencies]
+/// elasticube-core = { version = "0.2", features = ["grpc"] }
+/// ```
+#[cfg(feature = "grpc")]
+pub use server::grpc::GrpcServer;
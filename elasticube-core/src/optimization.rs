@@ -3,9 +3,31 @@
 //! Provides configuration for query optimization, storage optimization,
 //! and caching to improve analytical query performance.
 
+use crate::cache::QueryCache;
+use crate::error::{Error, Result};
+use arrow::array::{Array, ArrayRef, BooleanArray, UInt64Array};
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use datafusion::common::pruning::PruningStatistics;
+use datafusion::catalog::{
+    CatalogProvider, CatalogProviderList, MemoryCatalogProvider, MemoryCatalogProviderList,
+    MemorySchemaProvider, TableProvider,
+};
+use datafusion::common::{Column, ScalarValue};
 use datafusion::execution::config::SessionConfig;
-use datafusion::execution::runtime_env::RuntimeEnv;
-use std::sync::Arc;
+use datafusion::execution::disk_manager::{DiskManagerBuilder, DiskManagerMode};
+use datafusion::execution::memory_pool::FairSpillPool;
+use datafusion::execution::runtime_env::{RuntimeEnv, RuntimeEnvBuilder};
+use datafusion::execution::session_state::{SessionState, SessionStateBuilder};
+use datafusion::logical_expr::ScalarUDF;
+use datafusion::prelude::SessionContext;
+use datafusion::functions_aggregate::min_max::{max_batch, min_batch};
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::physical_optimizer::pruning::PruningPredicate;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+use crate::time::{Duration, Instant};
 
 /// Configuration for query optimization
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -47,10 +69,53 @@ pub struct OptimizationConfig {
     /// Default: 100
     pub max_cache_entries: usize,
 
+    /// How long a cached query result stays valid
+    /// None keeps results until they are evicted
+    /// Default: None
+    pub cache_ttl: Option<Duration>,
+
+    /// Maximum total in-memory size of cached query results (in bytes)
+    /// None bounds the cache by entry count only
+    /// Default: None
+    pub max_cache_bytes: Option<usize>,
+
+    /// Directory query results are also cached in, across restarts
+    /// None keeps the cache in memory only
+    /// Default: None
+    pub query_cache_dir: Option<PathBuf>,
+
+    /// Maximum total size of the on-disk query cache (in bytes)
+    /// Default: 1 GiB
+    pub max_disk_cache_bytes: u64,
+
     /// Memory limit for query execution (in bytes)
+    /// Sorts, joins and aggregations spill to disk once it is reached
     /// None means unlimited
     /// Default: None
     pub memory_limit: Option<usize>,
+
+    /// Directory for spill files written under the memory limit
+    /// None means the OS temp directory
+    /// Default: None
+    pub spill_path: Option<PathBuf>,
+
+    /// Answer queries from the cube's pre-aggregations when they cover them
+    /// Default: true
+    pub enable_aggregate_routing: bool,
+
+    /// Maximum number of rows a query may return
+    /// None means unlimited
+    /// Default: None
+    pub max_result_rows: Option<usize>,
+
+    /// Maximum in-memory size of a query result (in bytes)
+    /// None means unlimited
+    /// Default: None
+    pub max_result_bytes: Option<usize>,
+
+    /// Truncate results that exceed a limit instead of failing the query
+    /// Default: false
+    pub truncate_oversized_results: bool,
 }
 
 impl Default for OptimizationConfig {
@@ -64,7 +129,16 @@ impl Default for OptimizationConfig {
             batch_size: 8192,
             enable_query_cache: true,
             max_cache_entries: 100,
+            cache_ttl: None,
+            max_cache_bytes: None,
+            query_cache_dir: None,
+            max_disk_cache_bytes: 1 << 30,
             memory_limit: None,
+            spill_path: None,
+            enable_aggregate_routing: true,
+            max_result_rows: None,
+            max_result_bytes: None,
+            truncate_oversized_results: false,
         }
     }
 }
@@ -106,11 +180,31 @@ impl OptimizationConfig {
     }
 
     /// Set memory limit for query execution
+    ///
+    /// Memory is shared fairly between the operators of a query. When it
+    /// runs out, large GROUP BYs, sorts and joins spill intermediate state
+    /// to disk instead of failing; operators that can't spill return a
+    /// "Resources exhausted" error rather than growing without bound.
     pub fn with_memory_limit(mut self, limit: usize) -> Self {
         self.memory_limit = Some(limit);
         self
     }
 
+    /// Set the directory spill files are written to
+    ///
+    /// The directory must exist. Only used with a memory limit.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let config = OptimizationConfig::new()
+    ///     .with_memory_limit(512 * 1024 * 1024)
+    ///     .with_spill_path("/mnt/scratch");
+    /// ```
+    pub fn with_spill_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.spill_path = Some(path.into());
+        self
+    }
+
     /// Enable or disable query result caching
     pub fn with_query_cache(mut self, enabled: bool) -> Self {
         self.enable_query_cache = enabled;
@@ -123,6 +217,68 @@ impl OptimizationConfig {
         self
     }
 
+    /// Expire cached query results a fixed time after they were cached
+    ///
+    /// Useful when the cube is rebuilt from sources that change outside
+    /// ElastiCube's control, so results go stale without the cube noticing.
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Bound the total in-memory size of cached query results (in bytes)
+    ///
+    /// Least recently used results are evicted first. Applies alongside
+    /// the entry limit of [`with_max_cache_entries`](Self::with_max_cache_entries).
+    pub fn with_max_cache_bytes(mut self, bytes: usize) -> Self {
+        self.max_cache_bytes = Some(bytes);
+        self
+    }
+
+    /// Persist cached query results in a directory, up to `max_bytes`
+    ///
+    /// Results are keyed by the query and a hash of the cube's data, so any
+    /// cube loaded with the same data, including after a restart, reuses
+    /// them, and changed data never does. The directory is created if
+    /// needed.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let config = OptimizationConfig::new()
+    ///     .with_disk_cache("/var/cache/elasticube", 10 * 1024 * 1024 * 1024);
+    /// ```
+    pub fn with_disk_cache(mut self, dir: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        self.query_cache_dir = Some(dir.into());
+        self.max_disk_cache_bytes = max_bytes;
+        self
+    }
+
+    /// Enable or disable routing queries to pre-aggregations
+    ///
+    /// See `ElastiCube::add_aggregation`.
+    pub fn with_aggregate_routing(mut self, enabled: bool) -> Self {
+        self.enable_aggregate_routing = enabled;
+        self
+    }
+
+    /// Limit the number of rows a query may return
+    pub fn with_max_result_rows(mut self, rows: usize) -> Self {
+        self.max_result_rows = Some(rows);
+        self
+    }
+
+    /// Limit the in-memory size of a query result (in bytes)
+    pub fn with_max_result_bytes(mut self, bytes: usize) -> Self {
+        self.max_result_bytes = Some(bytes);
+        self
+    }
+
+    /// Truncate oversized results instead of returning an error
+    pub fn with_truncate_oversized_results(mut self, truncate: bool) -> Self {
+        self.truncate_oversized_results = truncate;
+        self
+    }
+
     /// Create a DataFusion SessionConfig from this optimization config
     pub fn to_session_config(&self) -> SessionConfig {
         let config = SessionConfig::new()
@@ -137,11 +293,24 @@ impl OptimizationConfig {
     }
 
     /// Create a DataFusion RuntimeEnv from this optimization config
-    pub fn to_runtime_env(&self) -> Arc<RuntimeEnv> {
-        // DataFusion 50+ uses RuntimeEnv::default() or RuntimeEnv::new()
-        // Memory limits are configured differently in newer versions
-        // For now, we'll use the default RuntimeEnv
-        Arc::new(RuntimeEnv::default())
+    ///
+    /// With a memory limit, queries run against a spilling memory pool and
+    /// a disk manager writing to the spill path. Fails if the spill path
+    /// can't be used.
+    pub fn to_runtime_env(&self) -> Result<Arc<RuntimeEnv>> {
+        let Some(limit) = self.memory_limit else {
+            return Ok(Arc::new(RuntimeEnv::default()));
+        };
+
+        let mode = match &self.spill_path {
+            Some(path) => DiskManagerMode::Directories(vec![path.clone()]),
+            None => DiskManagerMode::OsTmpDirectory,
+        };
+        RuntimeEnvBuilder::new()
+            .with_memory_pool(Arc::new(FairSpillPool::new(limit)))
+            .with_disk_manager_builder(DiskManagerBuilder::default().with_mode(mode))
+            .build_arc()
+            .map_err(|e| Error::config(format!("Failed to set up query spilling: {}", e)))
     }
 }
 
@@ -162,6 +331,9 @@ pub struct CubeStatistics {
 
     /// Per-column statistics
     pub column_stats: Vec<ColumnStatistics>,
+
+    /// Columns the data is sorted by (ascending), if the cube keeps an order
+    pub sort_order: Option<Vec<String>>,
 }
 
 impl CubeStatistics {
@@ -197,6 +369,32 @@ impl CubeStatistics {
             avg_rows_per_partition,
             memory_bytes,
             column_stats,
+            sort_order: None,
+        }
+    }
+
+    /// Add the statistics of more batches with the same schema, as if they
+    /// followed the batches already described
+    pub(crate) fn extend(&mut self, other: CubeStatistics) {
+        self.row_count += other.row_count;
+        self.partition_count += other.partition_count;
+        self.avg_rows_per_partition = self.row_count.checked_div(self.partition_count).unwrap_or(0);
+        self.memory_bytes += other.memory_bytes;
+
+        if self.column_stats.is_empty() {
+            self.column_stats = other.column_stats;
+            return;
+        }
+        for (column, more) in self.column_stats.iter_mut().zip(other.column_stats) {
+            column.null_count += more.null_count;
+            column.zone_maps.extend(more.zone_maps);
+            let rows: usize = column.zone_maps.iter().map(|z| z.row_count).sum();
+            column.null_percentage = if rows > 0 {
+                (column.null_count as f64 / rows as f64) * 100.0
+            } else {
+                0.0
+            };
+            column.distinct_count = None;
         }
     }
 
@@ -209,6 +407,269 @@ impl CubeStatistics {
             self.memory_bytes as f64 / 1_048_576.0
         )
     }
+
+    /// Which batches may contain rows matching `predicate`, going by the
+    /// zone maps
+    ///
+    /// Returns one flag per batch; `false` means the batch can be skipped.
+    /// Returns `None` if the predicate can't be checked against min/max
+    /// values.
+    pub(crate) fn prune(
+        &self,
+        predicate: &Arc<dyn PhysicalExpr>,
+        schema: SchemaRef,
+    ) -> Option<Vec<bool>> {
+        let pruning = PruningPredicate::try_new(Arc::clone(predicate), schema).ok()?;
+        pruning.prune(self).ok()
+    }
+
+    fn column(&self, column: &Column) -> Option<&ColumnStatistics> {
+        self.column_stats.iter().find(|c| c.column_name == column.name)
+    }
+
+    fn zone_values(
+        &self,
+        column: &Column,
+        value: impl Fn(&ZoneMap) -> Option<ScalarValue>,
+    ) -> Option<ArrayRef> {
+        let values = self
+            .column(column)?
+            .zone_maps
+            .iter()
+            .map(value)
+            .collect::<Option<Vec<_>>>()?;
+        ScalarValue::iter_to_array(values).ok()
+    }
+}
+
+impl PruningStatistics for CubeStatistics {
+    fn min_values(&self, column: &Column) -> Option<ArrayRef> {
+        self.zone_values(column, |zone| zone.min.clone())
+    }
+
+    fn max_values(&self, column: &Column) -> Option<ArrayRef> {
+        self.zone_values(column, |zone| zone.max.clone())
+    }
+
+    fn num_containers(&self) -> usize {
+        self.partition_count
+    }
+
+    fn null_counts(&self, column: &Column) -> Option<ArrayRef> {
+        let counts = self.column(column)?.zone_maps.iter().map(|z| z.null_count as u64);
+        Some(Arc::new(UInt64Array::from_iter_values(counts)))
+    }
+
+    fn row_counts(&self, column: &Column) -> Option<ArrayRef> {
+        let counts = self.column(column)?.zone_maps.iter().map(|z| z.row_count as u64);
+        Some(Arc::new(UInt64Array::from_iter_values(counts)))
+    }
+
+    fn contained(&self, _column: &Column, _values: &HashSet<ScalarValue>) -> Option<BooleanArray> {
+        None
+    }
+}
+
+/// Cached statistics of a cube's data, and when each batch was last scanned
+///
+/// The statistics are recomputed only when the batches change, which is
+/// detected by identity so that any data update invalidates them.
+#[derive(Debug, Default)]
+pub(crate) struct StatisticsCache {
+    entry: Mutex<Option<(Vec<ArrayRef>, Arc<CubeStatistics>)>>,
+
+    /// Last scan of each batch, keyed by its first column; the weak
+    /// reference keeps the address from being reused without holding data
+    scans: Mutex<Vec<(Weak<dyn Array>, Instant)>>,
+}
+
+impl StatisticsCache {
+    /// Statistics for `batches`, computing them if they changed
+    pub(crate) fn get(&self, batches: &[RecordBatch]) -> Arc<CubeStatistics> {
+        let fingerprint = fingerprint(batches);
+        let mut entry = self.entry.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((cached, statistics)) = entry.as_ref() {
+            if cached.len() == batches.len() && same_fingerprint(cached, &fingerprint) {
+                return Arc::clone(statistics);
+            }
+        }
+
+        let statistics = Arc::new(CubeStatistics::from_batches(batches));
+        *entry = Some((fingerprint, Arc::clone(&statistics)));
+        statistics
+    }
+
+    /// Note that queries are reading `batches`
+    pub(crate) fn record_scan(&self, batches: &[RecordBatch]) {
+        let now = Instant::now();
+        let mut scans = self.scans.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        scans.retain(|(array, _)| array.strong_count() > 0);
+        for column in batches.iter().filter_map(|batch| batch.columns().first()) {
+            match scans.iter_mut().find(|(array, _)| is_same_array(array, column)) {
+                Some((_, time)) => *time = now,
+                None => scans.push((Arc::downgrade(column), now)),
+            }
+        }
+    }
+
+    /// When `batch` was last scanned
+    ///
+    /// A batch never seen before counts as scanned at `now`, so new data
+    /// isn't mistaken for cold data.
+    pub(crate) fn last_scan(&self, batch: &RecordBatch, now: Instant) -> Instant {
+        let Some(column) = batch.columns().first() else {
+            return now;
+        };
+        let mut scans = self.scans.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((_, time)) = scans.iter().find(|(array, _)| is_same_array(array, column)) {
+            return *time;
+        }
+        scans.push((Arc::downgrade(column), now));
+        now
+    }
+}
+
+/// Session state and table provider reused across a cube's queries
+///
+/// Building a DataFusion session (function registry, runtime, spill
+/// directories) and the cube's table on every query is wasted work when
+/// neither the configuration nor the data changed.
+#[derive(Debug, Default)]
+pub(crate) struct SessionCache {
+    state: Mutex<Option<CachedState>>,
+    table: Mutex<Option<(Vec<ArrayRef>, Arc<dyn TableProvider>)>>,
+    results: Mutex<Option<(CacheSettings, Arc<QueryCache>)>>,
+}
+
+/// The options of an [`OptimizationConfig`] that shape its query cache
+type CacheSettings = (usize, Option<Duration>, Option<usize>, Option<PathBuf>, u64);
+
+fn cache_settings(config: &OptimizationConfig) -> CacheSettings {
+    (
+        config.max_cache_entries,
+        config.cache_ttl,
+        config.max_cache_bytes,
+        config.query_cache_dir.clone(),
+        config.max_disk_cache_bytes,
+    )
+}
+
+#[derive(Debug)]
+struct CachedState {
+    config: OptimizationConfig,
+    udfs: Vec<ScalarUDF>,
+    state: SessionState,
+}
+
+impl SessionCache {
+    /// A session for one query, set up for `config` with `udfs` registered
+    ///
+    /// Functions and the runtime come from the cached state; the catalog is
+    /// new, so tables a query registers don't leak into the next one.
+    pub(crate) fn context(
+        &self,
+        config: &OptimizationConfig,
+        udfs: &[ScalarUDF],
+    ) -> Result<SessionContext> {
+        let mut entry = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let cached = entry
+            .as_ref()
+            .filter(|cached| cached.config == *config && cached.udfs == udfs);
+        let state = match cached {
+            Some(cached) => cached.state.clone(),
+            None => {
+                let ctx = SessionContext::new_with_config_rt(
+                    config.to_session_config(),
+                    config.to_runtime_env()?,
+                );
+                crate::functions::register_statistical_functions(&ctx);
+                for udf in udfs {
+                    ctx.register_udf(udf.clone());
+                }
+                let state = ctx.state();
+                *entry = Some(CachedState {
+                    config: config.clone(),
+                    udfs: udfs.to_vec(),
+                    state: state.clone(),
+                });
+                state
+            }
+        };
+        drop(entry);
+
+        let options = state.config().options();
+        let schema = Arc::new(MemorySchemaProvider::new());
+        let catalog = Arc::new(MemoryCatalogProvider::new());
+        catalog.register_schema(&options.catalog.default_schema, schema)?;
+        let catalogs = Arc::new(MemoryCatalogProviderList::new());
+        catalogs.register_catalog(options.catalog.default_catalog.clone(), catalog);
+
+        let state = SessionStateBuilder::new_from_existing(state)
+            .with_catalog_list(catalogs)
+            .build();
+        Ok(SessionContext::new_with_state(state))
+    }
+
+    /// The unfiltered table over `batches`, creating it if they changed
+    pub(crate) fn table(
+        &self,
+        batches: &[RecordBatch],
+        create: impl FnOnce() -> Result<Arc<dyn TableProvider>>,
+    ) -> Result<Arc<dyn TableProvider>> {
+        let fingerprint = fingerprint(batches);
+        let mut entry = self.table.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((cached, table)) = entry.as_ref() {
+            if same_fingerprint(cached, &fingerprint) {
+                return Ok(Arc::clone(table));
+            }
+        }
+        let table = create()?;
+        *entry = Some((fingerprint, Arc::clone(&table)));
+        Ok(table)
+    }
+
+    /// The result cache shared by queries using `config`
+    ///
+    /// Results are keyed by the cube's contents, so the cache can outlive
+    /// updates to the cube. Changing the cache settings starts a new one.
+    pub(crate) fn query_cache(
+        &self,
+        config: &OptimizationConfig,
+    ) -> Result<Option<Arc<QueryCache>>> {
+        if !config.enable_query_cache {
+            return Ok(None);
+        }
+        let settings = cache_settings(config);
+        let mut entry = self.results.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((cached, cache)) = entry.as_ref() {
+            if *cached == settings {
+                return Ok(Some(Arc::clone(cache)));
+            }
+        }
+        let cache = Arc::new(QueryCache::from_config(config)?);
+        *entry = Some((settings, Arc::clone(&cache)));
+        Ok(Some(cache))
+    }
+}
+
+/// Identity of a list of batches: the first column of each
+///
+/// Updates always create new arrays, and holding them prevents address
+/// reuse, so an unchanged fingerprint means unchanged data.
+fn fingerprint(batches: &[RecordBatch]) -> Vec<ArrayRef> {
+    batches
+        .iter()
+        .filter_map(|batch| batch.columns().first().cloned())
+        .collect()
+}
+
+fn same_fingerprint(a: &[ArrayRef], b: &[ArrayRef]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| Arc::ptr_eq(a, b))
+}
+
+fn is_same_array(tracked: &Weak<dyn Array>, array: &ArrayRef) -> bool {
+    std::ptr::addr_eq(tracked.as_ptr(), Arc::as_ptr(array))
+}
 }
 
 /// Statistics for a single column
@@ -229,6 +690,39 @@ pub struct ColumnStatistics {
     /// Estimated distinct values (cardinality)
     /// None if not computed
     pub distinct_count: Option<usize>,
+
+    /// Min/max of the column in each batch, in batch order
+    pub zone_maps: Vec<ZoneMap>,
+}
+
+/// Value range of a column within one RecordBatch
+///
+/// Queries use zone maps to skip batches whose range can't satisfy a
+/// filter, which pays off on data appended in time order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneMap {
+    /// Smallest value (None if the type has no ordering)
+    pub min: Option<ScalarValue>,
+
+    /// Largest value (None if the type has no ordering)
+    pub max: Option<ScalarValue>,
+
+    /// Number of NULL values in the batch
+    pub null_count: usize,
+
+    /// Number of rows in the batch
+    pub row_count: usize,
+}
+
+impl ZoneMap {
+    fn from_array(array: &ArrayRef) -> Self {
+        Self {
+            min: min_batch(array).ok(),
+            max: max_batch(array).ok(),
+            null_count: array.null_count(),
+            row_count: array.len(),
+        }
+    }
 }
 
 impl ColumnStatistics {
@@ -239,11 +733,13 @@ impl ColumnStatistics {
 
         let mut total_nulls = 0;
         let mut total_rows = 0;
+        let mut zone_maps = Vec::with_capacity(batches.len());
 
         for batch in batches {
             let array = batch.column(col_idx);
             total_nulls += array.null_count();
             total_rows += array.len();
+            zone_maps.push(ZoneMap::from_array(array));
         }
 
         let null_percentage = if total_rows > 0 {
@@ -258,6 +754,7 @@ impl ColumnStatistics {
             null_count: total_nulls,
             null_percentage,
             distinct_count: None, // Computing distinct count is expensive, skip for now
+            zone_maps,
         }
     }
 }
@@ -299,4 +796,55 @@ mod tests {
         assert_eq!(session_config.target_partitions(), 4);
         assert_eq!(session_config.batch_size(), 1024);
     }
+
+    #[test]
+    fn test_runtime_env_spill_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = OptimizationConfig::new()
+            .with_memory_limit(1024 * 1024)
+            .with_spill_path(dir.path());
+        let runtime = config.to_runtime_env().unwrap();
+        assert!(runtime.disk_manager.tmp_files_enabled());
+
+        let missing = config.with_spill_path(dir.path().join("missing"));
+        assert!(missing.to_runtime_env().is_err());
+    }
+
+    #[test]
+    fn test_zone_maps_prune_batches() {
+        use arrow::array::Int64Array;
+        use arrow::datatypes::{DataType, Field, Schema};
+        use datafusion::physical_expr::expressions::{col, lit, BinaryExpr};
+        use datafusion::logical_expr::Operator;
+
+        let schema = Arc::new(Schema::new(vec![Field::new("day", DataType::Int64, true)]));
+        let batch = |days: Vec<Option<i64>>| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(days))]).unwrap()
+        };
+        let batches = vec![
+            batch(vec![Some(1), Some(5)]),
+            batch(vec![Some(6), None, Some(10)]),
+            batch(vec![None]),
+        ];
+
+        let stats = CubeStatistics::from_batches(&batches);
+        let zones = &stats.column_stats[0].zone_maps;
+        assert_eq!(zones.len(), 3);
+        assert_eq!(zones[1].min, Some(ScalarValue::Int64(Some(6))));
+        assert_eq!(zones[1].max, Some(ScalarValue::Int64(Some(10))));
+        assert_eq!(zones[1].null_count, 1);
+
+        let predicate: Arc<dyn PhysicalExpr> = Arc::new(BinaryExpr::new(
+            col("day", &schema).unwrap(),
+            Operator::Gt,
+            lit(5i64),
+        ));
+        let keep = stats.prune(&predicate, schema.clone()).unwrap();
+        assert_eq!(keep, vec![false, true, false]);
+
+        let cache = StatisticsCache::default();
+        let first = cache.get(&batches);
+        assert!(Arc::ptr_eq(&first, &cache.get(&batches)));
+        assert!(!Arc::ptr_eq(&first, &cache.get(&batches[..2])));
+    }
 }
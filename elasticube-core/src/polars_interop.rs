@@ -0,0 +1,154 @@
+//! Conversion between polars DataFrames and Arrow RecordBatches
+//!
+//! polars keeps its data in `polars-arrow` arrays, which share the Arrow
+//! memory layout with arrow-rs but are distinct Rust types. Columns are
+//! handed across through the Arrow C data interface, so buffers move between
+//! the two libraries without being copied.
+
+use crate::error::{Error, Result};
+use arrow::array::{make_array, ArrayRef};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Schema as ArrowSchema, SchemaRef};
+use arrow::ffi::{FFI_ArrowArray, FFI_ArrowSchema};
+use arrow::record_batch::RecordBatch;
+use polars::prelude::{Column, CompatLevel, DataFrame, PolarsError, Series};
+use std::sync::Arc;
+
+/// Convert a DataFrame into a single RecordBatch
+///
+/// polars strings and binaries arrive as view (or large) arrays; they are
+/// cast to plain `Utf8`/`Binary` so the batch matches cube schemas declared
+/// with those types.
+pub(crate) fn dataframe_to_batch(df: &DataFrame) -> Result<RecordBatch> {
+    let mut fields = Vec::with_capacity(df.width());
+    let mut columns = Vec::with_capacity(df.width());
+    for column in df.get_columns() {
+        let series = column.as_materialized_series().rechunk();
+        let field = series.field().to_arrow(CompatLevel::newest());
+        let array = series.to_arrow(0, CompatLevel::newest());
+        let (field, array) = import_column(&field, array)?;
+        let array = match field.data_type() {
+            DataType::Utf8View | DataType::LargeUtf8 => cast(&array, &DataType::Utf8)?,
+            DataType::BinaryView | DataType::LargeBinary => cast(&array, &DataType::Binary)?,
+            _ => array,
+        };
+        fields.push(field.with_data_type(array.data_type().clone()));
+        columns.push(array);
+    }
+
+    let schema = Arc::new(ArrowSchema::new(fields));
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+/// Convert RecordBatches into a DataFrame, one column per schema field
+pub(crate) fn batches_to_dataframe(
+    schema: &SchemaRef,
+    batches: &[RecordBatch],
+) -> Result<DataFrame> {
+    let mut columns = Vec::with_capacity(schema.fields().len());
+    for (i, field) in schema.fields().iter().enumerate() {
+        let chunks: Vec<&dyn arrow::array::Array> =
+            batches.iter().map(|b| b.column(i).as_ref()).collect();
+        let array = match chunks.as_slice() {
+            [] => arrow::array::new_empty_array(field.data_type()),
+            [single] => make_array(single.to_data()),
+            _ => arrow::compute::concat(&chunks)?,
+        };
+        columns.push(Column::from(export_column(field, &array)?));
+    }
+
+    DataFrame::new(columns).map_err(polars_error)
+}
+
+/// Move a polars column into arrow-rs
+fn import_column(
+    field: &polars_arrow::datatypes::Field,
+    array: Box<dyn polars_arrow::array::Array>,
+) -> Result<(Field, ArrayRef)> {
+    let schema = polars_arrow::ffi::export_field_to_c(field);
+    let array = polars_arrow::ffi::export_array_to_c(array);
+
+    // SAFETY: both structs are the C data interface's ArrowSchema and
+    // ArrowArray, laid out identically by the two crates; ownership passes
+    // to arrow-rs, which releases them on drop
+    let (schema, array) = unsafe {
+        (
+            std::mem::transmute::<polars_arrow::ffi::ArrowSchema, FFI_ArrowSchema>(schema),
+            std::mem::transmute::<polars_arrow::ffi::ArrowArray, FFI_ArrowArray>(array),
+        )
+    };
+
+    let field = Field::try_from(&schema)?;
+    // SAFETY: the array was just exported together with this schema
+    let data = unsafe { arrow::ffi::from_ffi(array, &schema)? };
+    Ok((field, make_array(data)))
+}
+
+/// Move an arrow-rs column into polars
+fn export_column(field: &Field, array: &ArrayRef) -> Result<Series> {
+    let schema = FFI_ArrowSchema::try_from(field)?;
+    let array = FFI_ArrowArray::new(&array.to_data());
+
+    // SAFETY: see import_column
+    let (schema, array) = unsafe {
+        (
+            std::mem::transmute::<FFI_ArrowSchema, polars_arrow::ffi::ArrowSchema>(schema),
+            std::mem::transmute::<FFI_ArrowArray, polars_arrow::ffi::ArrowArray>(array),
+        )
+    };
+
+    // SAFETY: the array was just exported together with this schema
+    let array = unsafe {
+        let field = polars_arrow::ffi::import_field_from_c(&schema).map_err(polars_error)?;
+        polars_arrow::ffi::import_array_from_c(array, field.dtype().clone()).map_err(polars_error)?
+    };
+    Series::from_arrow(field.name().as_str().into(), array).map_err(polars_error)
+}
+
+fn polars_error(e: PolarsError) -> Error {
+    Error::data(format!("polars conversion failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Float64Array, StringArray};
+    use polars::prelude::{df, NamedFrom};
+
+    #[test]
+    fn test_round_trip() {
+        let df = df!(
+            "region" => ["North", "South", "North"],
+            "sales" => [100.0, 200.0, 150.0],
+        )
+        .unwrap();
+
+        let batch = dataframe_to_batch(&df).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.schema().field(0).data_type(), &DataType::Utf8);
+        assert_eq!(batch.schema().field(1).data_type(), &DataType::Float64);
+        let sales = batch.column(1).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(sales.value(1), 200.0);
+
+        // Split into two batches to exercise concatenation
+        let batches = vec![batch.slice(0, 1), batch.slice(1, 2)];
+        let back = batches_to_dataframe(&batch.schema(), &batches).unwrap();
+        assert!(back.equals(&df));
+
+        let empty = batches_to_dataframe(&batch.schema(), &[]).unwrap();
+        assert_eq!(empty.shape(), (0, 2));
+    }
+
+    #[test]
+    fn test_utf8_columns_convert() {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new("name", DataType::Utf8, true)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from(vec![Some("a"), None]))],
+        )
+        .unwrap();
+
+        let df = batches_to_dataframe(&schema, &[batch]).unwrap();
+        assert_eq!(df.column("name").unwrap().null_count(), 1);
+    }
+}
@@ -0,0 +1,194 @@
+//! Row-level predicates compiled from SQL filter expressions
+//!
+//! Several parts of the library need to evaluate a user-supplied SQL filter
+//! (e.g. `"year >= 2024 AND region = 'North'"`) against in-memory batches
+//! without going through a full DataFusion query. This module compiles the
+//! expression once into a DataFusion physical expression and evaluates it
+//! synchronously, batch by batch.
+
+use crate::error::{Error, Result};
+use arrow::array::{Array, BooleanArray};
+use arrow::compute;
+use arrow::datatypes::Schema as ArrowSchema;
+use arrow::record_batch::RecordBatch;
+use datafusion::common::DFSchema;
+use datafusion::logical_expr::utils::{conjunction, split_conjunction};
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::prelude::SessionContext;
+use std::sync::Arc;
+
+/// A compiled SQL predicate bound to a specific Arrow schema
+#[derive(Debug, Clone)]
+pub(crate) struct Predicate {
+    /// The original SQL text (kept for error messages)
+    sql: String,
+
+    /// The compiled physical expression
+    expr: Arc<dyn PhysicalExpr>,
+}
+
+impl Predicate {
+    /// Compile a SQL boolean expression against the given schema
+    ///
+    /// # Arguments
+    /// * `sql` - SQL expression (e.g., "year = 2024 AND month >= 6")
+    /// * `schema` - Schema of the batches the predicate will be evaluated on
+    pub(crate) fn compile(sql: &str, schema: &ArrowSchema) -> Result<Self> {
+        let df_schema = DFSchema::try_from(schema.clone())?;
+        let ctx = SessionContext::new();
+
+        let logical = ctx.parse_sql_expr(sql, &df_schema).map_err(|e| {
+            Error::query(format!("Invalid filter expression '{}': {}", sql, e))
+        })?;
+        let expr = ctx.create_physical_expr(logical, &df_schema).map_err(|e| {
+            Error::query(format!("Failed to plan filter expression '{}': {}", sql, e))
+        })?;
+
+        Ok(Self {
+            sql: sql.to_string(),
+            expr,
+        })
+    }
+
+    /// Compile the top-level `AND` terms of `sql` that only read `columns`
+    ///
+    /// The result matches a superset of the rows `sql` matches, so batches it
+    /// rejects can be skipped. Returns `None` when no term qualifies or the
+    /// expression can't be planned against `schema` (e.g. it names a
+    /// calculated field).
+    pub(crate) fn compile_restricted(
+        sql: &str,
+        schema: &ArrowSchema,
+        columns: &[&str],
+    ) -> Option<Self> {
+        let df_schema = DFSchema::try_from(schema.clone()).ok()?;
+        let ctx = SessionContext::new();
+        let logical = ctx.parse_sql_expr(sql, &df_schema).ok()?;
+
+        let terms = split_conjunction(&logical).into_iter().filter(|term| {
+            let refs = term.column_refs();
+            !refs.is_empty() && refs.iter().all(|c| columns.contains(&c.name.as_str()))
+        });
+        let restricted = conjunction(terms.cloned())?;
+        let expr = ctx.create_physical_expr(restricted, &df_schema).ok()?;
+
+        Some(Self {
+            sql: sql.to_string(),
+            expr,
+        })
+    }
+
+    /// The compiled physical expression
+    pub(crate) fn physical_expr(&self) -> &Arc<dyn PhysicalExpr> {
+        &self.expr
+    }
+
+    /// Whether the first row of `batch` matches (false for an empty batch)
+    ///
+    /// Decides for the whole batch when every column the predicate reads
+    /// holds a single value in it, as in a partitioned cube.
+    pub(crate) fn matches_first_row(&self, batch: &RecordBatch) -> Result<bool> {
+        if batch.num_rows() == 0 {
+            return Ok(false);
+        }
+        Ok(self.evaluate(&batch.slice(0, 1))?.value(0))
+    }
+
+    /// Evaluate the predicate, returning a mask where `true` means the row matches
+    ///
+    /// NULL results are treated as `false`, matching SQL `WHERE` semantics.
+    pub(crate) fn evaluate(&self, batch: &RecordBatch) -> Result<BooleanArray> {
+        let value = self.expr.evaluate(batch)?;
+        let array = value.into_array(batch.num_rows())?;
+
+        let mask = array.as_any().downcast_ref::<BooleanArray>().ok_or_else(|| {
+            Error::query(format!(
+                "Filter expression '{}' does not evaluate to a boolean (got {:?})",
+                self.sql,
+                array.data_type()
+            ))
+        })?;
+
+        if mask.null_count() > 0 {
+            Ok(compute::prep_null_mask_filter(mask))
+        } else {
+            Ok(mask.clone())
+        }
+    }
+
+    /// Keep only the rows of `batch` that match the predicate
+    pub(crate) fn filter(&self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let mask = self.evaluate(batch)?;
+        compute::filter_record_batch(batch, &mask).map_err(|e| {
+            Error::arrow(format!("Failed to filter record batch: {}", e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field};
+
+    fn test_batch() -> RecordBatch {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("year", DataType::Int64, true),
+            Field::new("region", DataType::Utf8, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![Some(2023), Some(2024), None])),
+                Arc::new(StringArray::from(vec!["North", "South", "North"])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_predicate_filters_rows() {
+        let batch = test_batch();
+        let predicate = Predicate::compile("year >= 2024 OR region = 'North'", &batch.schema()).unwrap();
+
+        let filtered = predicate.filter(&batch).unwrap();
+        assert_eq!(filtered.num_rows(), 3);
+
+        let predicate = Predicate::compile("year = 2024", &batch.schema()).unwrap();
+        let mask = predicate.evaluate(&batch).unwrap();
+        // NULL year must not match
+        assert_eq!(mask, BooleanArray::from(vec![false, true, false]));
+    }
+
+    #[test]
+    fn test_compile_restricted_keeps_matching_terms() {
+        let batch = test_batch();
+        let schema = batch.schema();
+
+        let predicate = Predicate::compile_restricted(
+            "region = 'South' AND (year > 2000 OR region = 'North')",
+            &schema,
+            &["region"],
+        )
+        .unwrap();
+        let mask = predicate.evaluate(&batch).unwrap();
+        assert_eq!(mask, BooleanArray::from(vec![false, true, false]));
+        assert!(!predicate.matches_first_row(&batch).unwrap());
+
+        assert!(Predicate::compile_restricted("year > 2000", &schema, &["region"]).is_none());
+        assert!(Predicate::compile_restricted("missing = 1", &schema, &["region"]).is_none());
+    }
+
+    #[test]
+    fn test_predicate_rejects_unknown_column() {
+        let batch = test_batch();
+        assert!(Predicate::compile("missing = 1", &batch.schema()).is_err());
+    }
+
+    #[test]
+    fn test_predicate_requires_boolean() {
+        let batch = test_batch();
+        let predicate = Predicate::compile("year + 1", &batch.schema()).unwrap();
+        assert!(predicate.evaluate(&batch).is_err());
+    }
+}
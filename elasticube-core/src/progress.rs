@@ -0,0 +1,258 @@
+//! Progress of long source loads
+//!
+//! A callback registered with
+//! [`ElastiCubeBuilder::on_progress`](crate::ElastiCubeBuilder::on_progress)
+//! is called as a source reads its data. Sources stay unaware of who is
+//! listening: while a load is tracked, the reading thread holds a tracker
+//! that the CSV and Parquet readers report each decoded batch to. Sources
+//! that don't report are covered by a single update when they finish.
+//!
+//! Bytes are counted in the unit of the source's
+//! [`estimated_size`](crate::DataSource::estimated_size), so that the share
+//! done compares like with like: the file bytes consumed for CSV, and the
+//! uncompressed column bytes decoded for Parquet.
+
+use crate::error::Result;
+use crate::time::{Duration, Instant};
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// How far a source load has got
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadProgress {
+    /// What is being loaded: the cube, or a bridge table
+    pub source: String,
+
+    /// Batches read so far
+    pub batches: usize,
+
+    /// Rows read so far
+    pub rows: usize,
+
+    /// Input read so far, in bytes, counted like `total_bytes`
+    pub bytes: usize,
+
+    /// Estimated size of the whole input, if the source can tell
+    pub total_bytes: Option<usize>,
+
+    /// Time since the load started
+    pub elapsed: Duration,
+
+    /// Whether the source has finished reading
+    pub done: bool,
+}
+
+impl LoadProgress {
+    /// Share of the load done, between 0 and 1, if the total is known
+    pub fn fraction(&self) -> Option<f64> {
+        if self.done {
+            return Some(1.0);
+        }
+        let total = self.total_bytes.filter(|&total| total > 0)?;
+        Some((self.bytes as f64 / total as f64).min(1.0))
+    }
+
+    /// Estimated time left, extrapolated from the pace so far
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction().filter(|&fraction| fraction > 0.0)?;
+        Some(self.elapsed.mul_f64((1.0 - fraction) / fraction))
+    }
+}
+
+/// A function called with each progress update
+#[derive(Clone)]
+pub(crate) struct ProgressCallback(Arc<dyn Fn(&LoadProgress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub(crate) fn new(callback: impl Fn(&LoadProgress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+struct Tracker {
+    callback: ProgressCallback,
+    started: Instant,
+    progress: LoadProgress,
+}
+
+impl Tracker {
+    fn advance(&mut self, batch: &RecordBatch, bytes: usize) {
+        self.progress.batches += 1;
+        self.progress.rows += batch.num_rows();
+        self.progress.bytes += bytes;
+        self.notify();
+    }
+
+    fn notify(&mut self) {
+        self.progress.elapsed = self.started.elapsed();
+        (self.callback.0)(&self.progress);
+    }
+}
+
+thread_local! {
+    static TRACKER: RefCell<Option<Tracker>> = const { RefCell::new(None) };
+}
+
+/// Run `load`, reporting the batches it reads to `callback`
+///
+/// `load` must read on the calling thread.
+pub(crate) fn track<T>(
+    callback: &ProgressCallback,
+    source: &str,
+    total_bytes: Option<usize>,
+    load: impl FnOnce() -> Result<(SchemaRef, Vec<RecordBatch>, T)>,
+) -> Result<(SchemaRef, Vec<RecordBatch>, T)> {
+    let tracker = Tracker {
+        callback: callback.clone(),
+        started: Instant::now(),
+        progress: LoadProgress {
+            source: source.to_string(),
+            batches: 0,
+            rows: 0,
+            bytes: 0,
+            total_bytes,
+            elapsed: Duration::ZERO,
+            done: false,
+        },
+    };
+    let outer = TRACKER.with(|current| current.replace(Some(tracker)));
+    let result = load();
+    let tracker = TRACKER.with(|current| current.replace(outer));
+    let (schema, batches, extra) = result?;
+
+    if let Some(mut tracker) = tracker {
+        // Sources that don't report as they read are reported once, when done
+        if tracker.progress.batches == 0 {
+            tracker.progress.batches = batches.len();
+            tracker.progress.rows = batches.iter().map(|batch| batch.num_rows()).sum();
+            tracker.progress.bytes = tracker.progress.total_bytes.unwrap_or_else(|| {
+                batches.iter().map(|batch| batch.get_array_memory_size()).sum()
+            });
+        }
+        tracker.progress.done = true;
+        tracker.notify();
+    }
+    Ok((schema, batches, extra))
+}
+
+/// Report a batch read by the source being loaded, if the load is tracked
+///
+/// `bytes` is the input consumed since the previous batch.
+pub(crate) fn report_batch(batch: &RecordBatch, bytes: usize) {
+    // The tracker is taken out while the callback runs, so a callback that
+    // loads something itself isn't tracked as part of this load
+    let Some(mut tracker) = TRACKER.with(|current| current.borrow_mut().take()) else {
+        return;
+    };
+    tracker.advance(batch, bytes);
+    TRACKER.with(|current| *current.borrow_mut() = Some(tracker));
+}
+
+/// Bytes a source has read from its input
+#[derive(Debug, Default)]
+pub(crate) struct InputBytes {
+    read: Arc<AtomicUsize>,
+    reported: usize,
+}
+
+impl InputBytes {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// `reader`, counting what is read through it
+    pub(crate) fn counted<R: Read>(&self, reader: R) -> CountingReader<R> {
+        CountingReader {
+            inner: reader,
+            read: Arc::clone(&self.read),
+        }
+    }
+
+    /// Bytes read since the last call
+    pub(crate) fn take(&mut self) -> usize {
+        let read = self.read.load(Ordering::Relaxed);
+        let new = read - self.reported;
+        self.reported = read;
+        new
+    }
+}
+
+/// A reader counting the bytes read into an [`InputBytes`]
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    read: Arc<AtomicUsize>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read.fetch_add(read, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_track_reports_batches() {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new("n", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap();
+
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&updates);
+        let callback = ProgressCallback::new(move |progress| {
+            seen.lock().unwrap().push(progress.clone());
+        });
+
+        track(&callback, "sales", Some(200), || {
+            report_batch(&batch, 100);
+            report_batch(&batch, 100);
+            Ok((Arc::clone(&schema), vec![batch.clone(), batch.clone()], ()))
+        })
+        .unwrap();
+
+        let updates = updates.lock().unwrap();
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates[0].rows, 3);
+        assert_eq!(updates[0].fraction(), Some(0.5));
+        assert!(updates[0].eta().is_some());
+        assert_eq!(updates[2].rows, 6);
+        assert!(updates[2].done);
+
+        // Nothing is tracked outside a load
+        report_batch(&batch, 100);
+        assert_eq!(updates.len(), 3);
+    }
+
+    #[test]
+    fn test_input_bytes() {
+        let mut input = InputBytes::new();
+        let mut reader = input.counted(&b"region,amount\nNorth,1\n"[..]);
+        let mut buf = [0; 8];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(input.take(), 8);
+        assert_eq!(input.take(), 0);
+        std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+        assert_eq!(input.take(), 14);
+    }
+}
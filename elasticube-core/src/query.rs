@@ -3,14 +3,36 @@
 //! Provides a fluent API for building and executing analytical queries
 //! against ElastiCube data using Apache DataFusion.
 
-use crate::cache::{QueryCache, QueryCacheKey};
-use crate::cube::ElastiCube;
+use crate::audit::AuditEvent;
+use crate::cache::{Flight, QueryCache, QueryCacheKey};
+use crate::cube::{AggFunc, Aggregation, BridgeDimension, DisplayFormat, ElastiCube};
 use crate::error::{Error, Result};
 use crate::optimization::OptimizationConfig;
-use arrow::record_batch::RecordBatch;
+use crate::query_log::QueryRecord;
+use arrow::array::{Array, Float64Array, Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+use arrow::ffi_stream::FFI_ArrowArrayStream;
+use arrow::record_batch::{RecordBatch, RecordBatchIterator};
+use arrow::datatypes::SchemaRef;
+use datafusion::catalog::TableProvider;
+use datafusion::common::ScalarValue;
 use datafusion::datasource::MemTable;
+use datafusion::execution::context::SQLOptions;
+use datafusion::execution::SendableRecordBatchStream;
+use datafusion::physical_plan::ExecutionPlan;
 use datafusion::prelude::*;
-use std::sync::Arc;
+use futures::{Stream, StreamExt};
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock};
+use std::task::{Context, Poll};
+use crate::time::{Instant, SystemTime};
 
 /// Query builder for ElastiCube queries
 ///
@@ -36,6 +58,7 @@ use std::sync::Arc;
 ///     .execute()
 ///     .await?;
 /// ```
+#[derive(Clone)]
 pub struct QueryBuilder {
     /// Reference to the parent cube
     cube: Arc<ElastiCube>,
@@ -44,12 +67,14 @@ pub struct QueryBuilder {
     ctx: SessionContext,
 
     /// Optimization configuration
-    #[allow(dead_code)] // Used for creating SessionContext, may be used in future features
     config: OptimizationConfig,
 
     /// Optional query cache
     cache: Option<Arc<QueryCache>>,
 
+    /// Pre-aggregation registered as the cube table when the query is routed
+    aggregate_table: Option<Arc<dyn TableProvider>>,
+
     /// Optional SQL query string (takes precedence over fluent API)
     sql_query: Option<String>,
 
@@ -62,6 +87,36 @@ pub struct QueryBuilder {
     /// GROUP BY columns
     group_by_exprs: Vec<String>,
 
+    /// How the GROUP BY columns are combined into groups
+    grouping: GroupingMode,
+
+    /// Time buckets selected and grouped ahead of the other columns
+    time_buckets: Vec<TimeBucket>,
+
+    /// Running-total window columns appended to the select list
+    cumulatives: Vec<CumulativeSpec>,
+
+    /// Optional gap filling over a dense time axis
+    resample: Option<ResampleSpec>,
+
+    /// External files registered as extra tables, as (name, path) pairs
+    external_tables: Vec<(String, String)>,
+
+    /// Common table expressions as (name, SQL) pairs
+    ctes: Vec<(String, String)>,
+
+    /// SQL of another query used as the source instead of the cube table
+    source_query: Option<String>,
+
+    /// Optional unpivot turning columns into rows
+    unpivot: Option<UnpivotSpec>,
+
+    /// Optional random sample of the input rows
+    sample: Option<SampleSpec>,
+
+    /// Optional pivot turning dimension values into columns
+    pivot: Option<PivotSpec>,
+
     /// ORDER BY expressions
     order_by_exprs: Vec<String>,
 
@@ -70,6 +125,9 @@ pub struct QueryBuilder {
 
     /// OFFSET clause
     offset_count: Option<usize>,
+
+    /// Who the query runs as, for the audit trail
+    principal: Option<String>,
 }
 
 impl QueryBuilder {
@@ -80,30 +138,36 @@ impl QueryBuilder {
 
     /// Create a new query builder with custom optimization configuration
     pub(crate) fn with_config(cube: Arc<ElastiCube>, config: OptimizationConfig) -> Result<Self> {
-        // Create SessionContext with optimization settings
-        let session_config = config.to_session_config();
-        let runtime_env = config.to_runtime_env();
-        let ctx = SessionContext::new_with_config_rt(session_config, runtime_env);
-
-        // Create query cache if enabled
-        let cache = if config.enable_query_cache {
-            Some(Arc::new(QueryCache::new(config.max_cache_entries)))
-        } else {
-            None
-        };
+        // Reuse the cube's session state for these optimization settings
+        let ctx = cube.session_context(&config)?;
+
+        // Share the cube's result cache between queries
+        let cache = cube.query_cache(&config)?;
 
         Ok(Self {
             cube,
             ctx,
             config,
             cache,
+            aggregate_table: None,
             sql_query: None,
             select_exprs: Vec::new(),
             filter_expr: None,
             group_by_exprs: Vec::new(),
+            grouping: GroupingMode::Plain,
+            time_buckets: Vec::new(),
+            cumulatives: Vec::new(),
+            resample: None,
+            external_tables: Vec::new(),
+            ctes: Vec::new(),
+            source_query: None,
+            unpivot: None,
+            sample: None,
+            pivot: None,
             order_by_exprs: Vec::new(),
             limit_count: None,
             offset_count: None,
+            principal: None,
         })
     }
 
@@ -168,6 +232,225 @@ impl QueryBuilder {
     /// ```
     pub fn group_by(mut self, columns: &[impl AsRef<str>]) -> Self {
         self.group_by_exprs = columns.iter().map(|c| c.as_ref().to_string()).collect();
+        self.grouping = GroupingMode::Plain;
+        self
+    }
+
+    /// Group by every selected column that is not an aggregate
+    ///
+    /// Looks at the columns selected so far, so call it after
+    /// [`select`](Self::select). Expressions containing an aggregate or
+    /// window function (including calculated measures that expand to one)
+    /// are left out; the rest are grouped by their expression, not alias.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // Same as .group_by(&["region", "product"])
+    /// .select(&["region", "product", "SUM(sales) AS total"])
+    /// .group_by_all()
+    /// ```
+    pub fn group_by_all(mut self) -> Self {
+        let state = self.ctx.state();
+        let functions: Vec<String> = state
+            .aggregate_functions()
+            .keys()
+            .chain(state.window_functions().keys())
+            .map(|name| regex::escape(name))
+            .collect();
+        let aggregate = regex::Regex::new(&format!(r"(?i)\b({})\s*\(", functions.join("|")))
+            .expect("escaped function names form a valid regex");
+        let alias = regex::Regex::new(r#"(?is)^(.*?)\s+AS\s+(?:"[^"]*"|\w+)\s*$"#)
+            .expect("alias pattern is a valid regex");
+
+        self.group_by_exprs = self
+            .select_exprs
+            .iter()
+            .filter(|expr| expr.trim() != "*")
+            .filter(|expr| !aggregate.is_match(&self.expand_calculated_fields(expr)))
+            .map(|expr| match alias.captures(expr) {
+                Some(captures) => captures[1].trim().to_string(),
+                None => expr.trim().to_string(),
+            })
+            .collect();
+        self.grouping = GroupingMode::Plain;
+        self
+    }
+
+    /// Group by columns with ROLLUP, adding subtotal and grand-total rows
+    ///
+    /// Produces groups for every prefix of `columns`: `(year, quarter, month)`,
+    /// `(year, quarter)`, `(year)` and the grand total `()`. Rolled-up columns
+    /// are NULL in subtotal rows; select `GROUPING(column)` to tell them apart
+    /// from genuine NULLs.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// .select(&["year", "quarter", "SUM(sales) as total"])
+    /// .group_by_rollup(&["year", "quarter"])
+    /// ```
+    pub fn group_by_rollup(mut self, columns: &[impl AsRef<str>]) -> Self {
+        self.group_by_exprs = columns.iter().map(|c| c.as_ref().to_string()).collect();
+        self.grouping = GroupingMode::Rollup;
+        self
+    }
+
+    /// Group by columns with CUBE, adding subtotals for every combination
+    ///
+    /// Produces groups for all subsets of `columns`, including the grand total.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// .select(&["region", "product", "SUM(sales) as total"])
+    /// .group_by_cube(&["region", "product"])
+    /// ```
+    pub fn group_by_cube(mut self, columns: &[impl AsRef<str>]) -> Self {
+        self.group_by_exprs = columns.iter().map(|c| c.as_ref().to_string()).collect();
+        self.grouping = GroupingMode::Cube;
+        self
+    }
+
+    /// Group by explicit grouping sets
+    ///
+    /// Each set is a list of columns to group by; an empty set yields the
+    /// grand total.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // Totals per region, per product, and overall
+    /// .select(&["region", "product", "SUM(sales) as total"])
+    /// .grouping_sets(&[&["region"][..], &["product"], &[]])
+    /// ```
+    pub fn grouping_sets(mut self, sets: &[&[&str]]) -> Self {
+        let sets: Vec<Vec<String>> = sets
+            .iter()
+            .map(|set| set.iter().map(|c| c.to_string()).collect())
+            .collect();
+
+        // Keep the distinct columns so roll_up/drill_down still see them
+        self.group_by_exprs.clear();
+        for column in sets.iter().flatten() {
+            if !self.group_by_exprs.contains(column) {
+                self.group_by_exprs.push(column.clone());
+            }
+        }
+        self.grouping = GroupingMode::Sets(sets);
+        self
+    }
+
+    /// Group by a timestamp truncated to a calendar granularity
+    ///
+    /// Adds `date_trunc(granularity, column)` as the first selected and
+    /// grouped column, aliased `<column>_<granularity>` (e.g.
+    /// `timestamp_week`), so order by that name. String and date columns are
+    /// converted to timestamps. Weeks start on Monday.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let weekly = cube.query()?
+    ///     .bucket_time("timestamp", Granularity::Week)
+    ///     .select(&["SUM(sales) AS total"])
+    ///     .order_by(&["timestamp_week"])
+    ///     .execute()
+    ///     .await?;
+    /// ```
+    pub fn bucket_time(self, column: impl Into<String>, granularity: Granularity) -> Self {
+        self.push_time_bucket(column.into(), granularity, None)
+    }
+
+    /// Like [`bucket_time`](Self::bucket_time), but buckets in a time zone
+    ///
+    /// Timestamps are converted to `timezone` (an IANA name such as
+    /// `"America/New_York"` or an offset like `"+02:00"`) before truncation,
+    /// so days and weeks follow local midnight. Timestamps without a time
+    /// zone are treated as UTC.
+    pub fn bucket_time_tz(
+        self,
+        column: impl Into<String>,
+        granularity: Granularity,
+        timezone: impl Into<String>,
+    ) -> Self {
+        self.push_time_bucket(column.into(), granularity, Some(timezone.into()))
+    }
+
+    fn push_time_bucket(
+        mut self,
+        column: String,
+        granularity: Granularity,
+        timezone: Option<String>,
+    ) -> Self {
+        self.time_buckets.retain(|b| b.column != column);
+        self.time_buckets.push(TimeBucket {
+            column,
+            granularity,
+            timezone,
+        });
+        self
+    }
+
+    /// Aggregate into time buckets and fill in the missing periods
+    ///
+    /// Buckets `column` like [`bucket_time`](Self::bucket_time), then joins
+    /// the result onto a dense axis running from the first to the last
+    /// bucket so every period appears exactly once. Any other `group_by`
+    /// columns are kept as series keys: each series gets the full axis.
+    /// Periods without data are filled according to `fill`.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let readings = cube.query()?
+    ///     .select(&["sensor", "AVG(value) AS value"])
+    ///     .group_by(&["sensor"])
+    ///     .resample("timestamp", Granularity::Hour, FillStrategy::ForwardFill)
+    ///     .order_by(&["sensor", "timestamp_hour"])
+    ///     .execute()
+    ///     .await?;
+    /// ```
+    pub fn resample(
+        self,
+        column: impl Into<String>,
+        granularity: Granularity,
+        fill: FillStrategy,
+    ) -> Self {
+        let column = column.into();
+        let mut query = self.push_time_bucket(column.clone(), granularity, None);
+        query.resample = Some(ResampleSpec {
+            column,
+            granularity,
+            fill,
+            columns: None,
+        });
+        query
+    }
+
+    /// Add a running total of an aggregate as an extra column
+    ///
+    /// Emits `expr` as a window function accumulated over `order_by`,
+    /// restarting for each combination of `partition_by` columns. The column
+    /// is named after the expression, e.g. `SUM(sales)` becomes
+    /// `cumulative_sum_sales`.
+    ///
+    /// In a grouped query the running total is taken over the group results:
+    /// `MIN`/`MAX` keep a running minimum/maximum and every other aggregate is
+    /// summed, so `COUNT(*)` gives a running count. `order_by` and
+    /// `partition_by` may name time bucket columns such as `date_month`.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// .select(&["region", "date", "SUM(sales) AS sales"])
+    /// .group_by(&["region", "date"])
+    /// .cumulative("SUM(sales)", "date", &["region"])
+    /// ```
+    pub fn cumulative(
+        mut self,
+        expr: impl Into<String>,
+        order_by: impl Into<String>,
+        partition_by: &[impl AsRef<str>],
+    ) -> Self {
+        self.cumulatives.push(CumulativeSpec {
+            expr: expr.into(),
+            order_by: order_by.into(),
+            partition_by: partition_by.iter().map(|c| c.as_ref().to_string()).collect(),
+        });
         self
     }
 
@@ -207,6 +490,49 @@ impl QueryBuilder {
         self
     }
 
+    /// Record the query as run by `principal` in the cube's audit trail
+    ///
+    /// Has no effect on the result; see [`ElastiCube::set_audit_sink`].
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// .as_principal("svc-dashboard")
+    /// ```
+    pub fn as_principal(mut self, principal: impl Into<String>) -> Self {
+        self.principal = Some(principal.into());
+        self
+    }
+
+    /// Page through the results `page_size` rows at a time using OFFSET
+    ///
+    /// Any `offset()` already set is the starting point and any `limit()`
+    /// caps the total number of rows returned across all pages. Combine with
+    /// `order_by()` so pages are stable.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let mut pages = cube.query()?
+    ///     .order_by(&["order_id"])
+    ///     .paginate(500);
+    /// while let Some(page) = pages.next_page().await? {
+    ///     render(&page);
+    /// }
+    /// ```
+    pub fn paginate(self, page_size: usize) -> Paginator {
+        Paginator::new(self, page_size, None)
+    }
+
+    /// Page through the results using keyset (seek) pagination
+    ///
+    /// Rows are ordered by `key_column`, and each page after the first is
+    /// fetched with `key_column > <last key seen>` instead of an OFFSET, so
+    /// deep pages stay cheap. The key must be unique and non-null, and must
+    /// be a column of the cube (or a GROUP BY column) that also appears in
+    /// the output.
+    pub fn paginate_by_key(self, page_size: usize, key_column: impl Into<String>) -> Paginator {
+        Paginator::new(self, page_size, Some(key_column.into()))
+    }
+
     /// OLAP Operation: Slice - filter on a single dimension
     ///
     /// # Example
@@ -233,6 +559,50 @@ impl QueryBuilder {
         self.filter(combined)
     }
 
+    /// OLAP Operation: Drill-through - fetch the detail rows behind a cell
+    ///
+    /// Restricts the query to the fact rows matching every `(dimension, value)`
+    /// pair of an aggregated cell (combined with any existing filter) and
+    /// drops grouping, pivoting and the select list, so raw rows are returned.
+    /// Call `select()` and `limit()` afterwards to choose columns and cap the
+    /// number of rows.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let details = cube.query()?
+    ///     .drill_through(&[("region", "North"), ("quarter", "1")])
+    ///     .select(&["order_id", "product", "sales"])
+    ///     .limit(100)
+    ///     .execute()
+    ///     .await?;
+    /// ```
+    pub fn drill_through(mut self, cell: &[(impl AsRef<str>, impl AsRef<str>)]) -> Self {
+        let mut conditions: Vec<String> = self
+            .filter_expr
+            .take()
+            .map(|filter| format!("({})", filter))
+            .into_iter()
+            .collect();
+        let schema = self.cube.schema();
+        conditions.extend(cell.iter().map(|(dim, val)| {
+            // Virtual dimensions are compared through their expression
+            let column = match schema.get_virtual_dimension(dim.as_ref()) {
+                Some(virtual_dim) => format!("({})", virtual_dim.expression()),
+                None => quote_ident(dim.as_ref()),
+            };
+            format!("{} = '{}'", column, val.as_ref().replace('\'', "''"))
+        }));
+        if !conditions.is_empty() {
+            self.filter_expr = Some(conditions.join(" AND "));
+        }
+
+        self.select_exprs.clear();
+        self.group_by_exprs.clear();
+        self.grouping = GroupingMode::Plain;
+        self.pivot = None;
+        self
+    }
+
     /// OLAP Operation: Drill-down - navigate down a hierarchy
     ///
     /// This selects data at a more granular level by including a lower-level dimension.
@@ -272,449 +642,4727 @@ impl QueryBuilder {
         self
     }
 
-    /// Execute the query and return results
+    /// Pivot a dimension into columns
     ///
-    /// # Returns
-    /// A QueryResult containing the data and metadata
-    pub async fn execute(mut self) -> Result<QueryResult> {
-        // Build the query SQL string for caching
-        let query_sql = if let Some(sql) = &self.sql_query {
-            sql.clone()
-        } else {
-            self.build_sql_query()
-        };
+    /// Each distinct value of `dimension` becomes its own column holding
+    /// `agg(value_measure)` for that value. Rows are formed by the GROUP BY
+    /// columns, so the result is a wide grid suitable for reports (e.g.
+    /// months across, regions down). The pivot replaces any `select()` list.
+    ///
+    /// Column values are discovered when the query executes, after applying
+    /// the filter. NULL dimension values are skipped. The query fails if it
+    /// has no GROUP BY columns or the filter leaves no values to pivot.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // One row per region, one column per month
+    /// let grid = cube.query()?
+    ///     .group_by(&["region"])
+    ///     .pivot("month", "sales", AggFunc::Sum)
+    ///     .order_by(&["region"])
+    ///     .execute()
+    ///     .await?;
+    /// ```
+    pub fn pivot(
+        mut self,
+        dimension: impl Into<String>,
+        value_measure: impl Into<String>,
+        agg: AggFunc,
+    ) -> Self {
+        self.pivot = Some(PivotSpec {
+            dimension: dimension.into(),
+            value_measure: value_measure.into(),
+            agg,
+            values: None,
+        });
+        self
+    }
 
-        // Check cache if enabled
-        if let Some(cache) = &self.cache {
-            let cache_key = QueryCacheKey::new(&query_sql);
-            if let Some(cached_result) = cache.get(&cache_key) {
-                return Ok(cached_result);
-            }
+    /// Drill down one level along a hierarchy
+    ///
+    /// Groups by every level of the named hierarchy from the top down to the
+    /// child of `current_level`, replacing any levels of that hierarchy
+    /// already present in the GROUP BY and SELECT lists. Other grouping
+    /// columns are left untouched.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // time = year > quarter > month; now grouped by year, quarter
+    /// .select(&["year", "SUM(sales) AS total"])
+    /// .group_by(&["year"])
+    /// .drill_down_hierarchy("time", "year")?
+    /// ```
+    pub fn drill_down_hierarchy(
+        mut self,
+        hierarchy: impl AsRef<str>,
+        current_level: impl AsRef<str>,
+    ) -> Result<Self> {
+        let levels = self.hierarchy_levels(hierarchy.as_ref())?;
+        let current_level = current_level.as_ref();
+
+        let index = levels
+            .iter()
+            .position(|l| l == current_level)
+            .ok_or_else(|| {
+                Error::hierarchy(format!(
+                    "Level '{}' is not part of hierarchy '{}'",
+                    current_level,
+                    hierarchy.as_ref()
+                ))
+            })?;
+        if index + 1 >= levels.len() {
+            return Err(Error::hierarchy(format!(
+                "Cannot drill down below '{}', the finest level of hierarchy '{}'",
+                current_level,
+                hierarchy.as_ref()
+            )));
         }
 
-        // Register the cube data as a MemTable
-        self.register_cube_data().await?;
+        self.set_hierarchy_depth(&levels, index + 2);
+        Ok(self)
+    }
 
-        // Execute the query
-        let dataframe = if let Some(sql) = &self.sql_query {
-            // Execute raw SQL query
-            self.execute_sql(sql).await?
-        } else {
-            // Build and execute fluent API query
-            self.execute_fluent_query().await?
-        };
+    /// Roll up one level along a hierarchy
+    ///
+    /// Removes the finest level of the named hierarchy currently in the
+    /// GROUP BY (and SELECT) list. Rolling up from the top level removes the
+    /// hierarchy entirely, aggregating across it.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // grouped by year, quarter -> grouped by year
+    /// .roll_up_hierarchy("time")?
+    /// ```
+    pub fn roll_up_hierarchy(mut self, hierarchy: impl AsRef<str>) -> Result<Self> {
+        let levels = self.hierarchy_levels(hierarchy.as_ref())?;
 
-        // Collect results
-        let batches = dataframe
-            .collect()
-            .await
-            .map_err(|e| Error::query(format!("Failed to collect query results: {}", e)))?;
+        let current = levels
+            .iter()
+            .rposition(|l| self.group_by_exprs.contains(l))
+            .ok_or_else(|| {
+                Error::hierarchy(format!(
+                    "Query is not grouped by any level of hierarchy '{}'",
+                    hierarchy.as_ref()
+                ))
+            })?;
 
-        let row_count = batches.iter().map(|b| b.num_rows()).sum();
+        self.set_hierarchy_depth(&levels, current);
+        Ok(self)
+    }
 
-        let result = QueryResult {
-            batches,
-            row_count,
+    /// Look up the levels of a hierarchy in the cube schema
+    fn hierarchy_levels(&self, name: &str) -> Result<Vec<String>> {
+        self.cube
+            .schema()
+            .get_hierarchy(name)
+            .map(|h| h.levels().to_vec())
+            .ok_or_else(|| Error::hierarchy(format!("Hierarchy '{}' not found", name)))
+    }
+
+    /// Group by the first `depth` levels of a hierarchy
+    ///
+    /// Levels are placed where the hierarchy previously appeared in the
+    /// GROUP BY and SELECT lists; ORDER BY entries on dropped levels are removed.
+    fn set_hierarchy_depth(&mut self, levels: &[String], depth: usize) {
+        let keep = &levels[..depth];
+
+        let replace = |exprs: &mut Vec<String>| {
+            let position = exprs.iter().position(|e| levels.contains(e)).unwrap_or(0);
+            exprs.retain(|e| !levels.contains(e));
+            let position = position.min(exprs.len());
+            exprs.splice(position..position, keep.iter().cloned());
         };
 
-        // Cache the result if caching is enabled
-        if let Some(cache) = &self.cache {
-            let cache_key = QueryCacheKey::new(&query_sql);
-            cache.put(cache_key, result.clone());
+        replace(&mut self.group_by_exprs);
+        if !self.select_exprs.is_empty() {
+            replace(&mut self.select_exprs);
         }
 
-        Ok(result)
+        self.order_by_exprs.retain(|expr| {
+            let column = expr.split_whitespace().next().unwrap_or_default();
+            !levels.iter().any(|l| l == column) || keep.iter().any(|l| l == column)
+        });
     }
 
-    /// Register cube data as a DataFusion MemTable
-    async fn register_cube_data(&mut self) -> Result<()> {
-        let schema = self.cube.arrow_schema().clone();
-        let data = self.cube.data().to_vec();
+    /// Unpivot (melt) columns into rows
+    ///
+    /// Converts wide data into long format: every input row becomes one row
+    /// per column in `columns`, with the column's name in `name_column` and
+    /// its value in `value_column`. All other columns are carried over.
+    /// The rest of the query (filter, grouping, ordering) runs against the
+    /// unpivoted rows.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // q1..q4 columns -> (quarter, sales) rows
+    /// let long = cube.query()?
+    ///     .unpivot(&["q1", "q2", "q3", "q4"], "quarter", "sales")
+    ///     .select(&["region", "quarter", "sales"])
+    ///     .execute()
+    ///     .await?;
+    /// ```
+    pub fn unpivot(
+        mut self,
+        columns: &[impl AsRef<str>],
+        name_column: impl Into<String>,
+        value_column: impl Into<String>,
+    ) -> Self {
+        self.unpivot = Some(UnpivotSpec {
+            columns: columns.iter().map(|c| c.as_ref().to_string()).collect(),
+            name_column: name_column.into(),
+            value_column: value_column.into(),
+        });
+        self
+    }
 
-        // MemTable expects Vec<Vec<RecordBatch>> (partitions)
-        // We'll use a single partition with all our batches
-        let partitions = vec![data];
+    /// Register an external Parquet, CSV or NDJSON file as an extra table
+    ///
+    /// The file is read in place when the query runs, so cubes can be joined
+    /// against reference data without rebuilding them. The format is taken
+    /// from the file extension (`.parquet`, `.csv`, `.json`/`.ndjson`);
+    /// directories of Parquet files are also accepted.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let result = cube.query()?
+    ///     .with_external_table("ref", "lookup.parquet")
+    ///     .sql("SELECT r.category, SUM(c.sales) FROM cube c \
+    ///           JOIN ref r ON c.product = r.product GROUP BY r.category")
+    ///     .execute()
+    ///     .await?;
+    /// ```
+    pub fn with_external_table(mut self, name: impl Into<String>, path: impl Into<String>) -> Self {
+        let name = name.into();
+        self.external_tables.retain(|(existing, _)| existing != &name);
+        self.external_tables.push((name, path.into()));
+        self
+    }
 
-        let mem_table = MemTable::try_new(schema, partitions)
-            .map_err(|e| Error::query(format!("Failed to create MemTable: {}", e)))?;
+    /// Add a common table expression built from another query
+    ///
+    /// The inner query becomes `WITH name AS (...)` and can be referenced by
+    /// name in filters, subqueries and raw SQL of this query. Both queries
+    /// must come from the same cube; pivot queries cannot be used because
+    /// their columns are only known at execution time.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let big_customers = cube.clone().query()?
+    ///     .select(&["customer_id"])
+    ///     .filter("sales > 10000");
+    ///
+    /// let result = cube.query()?
+    ///     .with_cte("big_customers", big_customers)?
+    ///     .filter("customer_id IN (SELECT customer_id FROM big_customers)")
+    ///     .select(&["region", "SUM(sales) AS total"])
+    ///     .group_by(&["region"])
+    ///     .execute()
+    ///     .await?;
+    /// ```
+    pub fn with_cte(self, name: impl Into<String>, inner: QueryBuilder) -> Result<Self> {
+        let sql = self.inner_query_sql(&inner)?;
+        Ok(self.with_cte_sql(name, sql))
+    }
 
-        self.ctx
-            .register_table("cube", Arc::new(mem_table))
-            .map_err(|e| Error::query(format!("Failed to register table: {}", e)))?;
+    /// Add a common table expression from raw SQL
+    pub fn with_cte_sql(mut self, name: impl Into<String>, sql: impl Into<String>) -> Self {
+        let name = name.into();
+        self.ctes.retain(|(existing, _)| existing != &name);
+        self.ctes.push((name, sql.into()));
+        self
+    }
 
-        Ok(())
+    /// Query the results of another query instead of the raw cube rows
+    ///
+    /// The inner query's output is exposed to this query under the name
+    /// `cube`, so multi-step analyses (e.g. aggregate per customer, then
+    /// bucket the totals) stay in the fluent API.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let per_region = cube.clone().query()?
+    ///     .select(&["region", "SUM(sales) AS total"])
+    ///     .group_by(&["region"]);
+    ///
+    /// let result = cube.query()?
+    ///     .from_query(per_region)?
+    ///     .filter("total > 1000")
+    ///     .execute()
+    ///     .await?;
+    /// ```
+    pub fn from_query(mut self, inner: QueryBuilder) -> Result<Self> {
+        let sql = self.inner_query_sql(&inner)?;
+        self.source_query = Some(sql);
+        Ok(self)
     }
 
-    /// Execute a raw SQL query
-    async fn execute_sql(&self, query: &str) -> Result<DataFrame> {
-        self.ctx
-            .sql(query)
-            .await
-            .map_err(|e| Error::query(format!("SQL execution failed: {}", e)))
+    /// SQL of a query nested inside this one
+    fn inner_query_sql(&self, inner: &QueryBuilder) -> Result<String> {
+        if !Arc::ptr_eq(&self.cube, &inner.cube) {
+            return Err(Error::query(
+                "Nested queries must be built from the same cube as the outer query",
+            ));
+        }
+        if inner.pivot.is_some() {
+            return Err(Error::query(
+                "Pivot queries cannot be nested because their columns are resolved at execution",
+            ));
+        }
+        Ok(inner.query_sql())
     }
 
-    /// Expand calculated fields in an expression
+    /// Run the query over a random sample of the rows
     ///
-    /// Replaces references to calculated measures and virtual dimensions
-    /// with their underlying expressions. Performs recursive expansion
-    /// to handle nested calculated fields.
-    fn expand_calculated_fields(&self, expr: &str) -> String {
-        let mut expanded = expr.to_string();
-        let schema = self.cube.schema();
+    /// Each input row is kept with probability `fraction` (0 < fraction <= 1)
+    /// before filters and grouping apply, giving quick approximate answers
+    /// for exploratory queries. The sample differs between executions.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // Roughly 1% of the rows
+    /// .sample(0.01)
+    /// ```
+    pub fn sample(mut self, fraction: f64) -> Self {
+        self.sample = Some(SampleSpec {
+            fraction,
+            stratify_by: None,
+        });
+        self
+    }
 
-        // Keep expanding until no more changes occur (handles nested calculated fields)
-        // Use a maximum iteration count to prevent infinite loops
-        const MAX_ITERATIONS: usize = 10;
-        for _ in 0..MAX_ITERATIONS {
-            let before = expanded.clone();
+    /// Run the query over a stratified random sample
+    ///
+    /// Samples `fraction` of the rows of every distinct value of `by_column`
+    /// (rounded up, so each group keeps at least one row). Unlike
+    /// [`sample`](Self::sample), small groups are never dropped entirely.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // 10% of each region's rows
+    /// .sample_stratified(0.1, "region")
+    /// ```
+    pub fn sample_stratified(mut self, fraction: f64, by_column: impl Into<String>) -> Self {
+        self.sample = Some(SampleSpec {
+            fraction,
+            stratify_by: Some(by_column.into()),
+        });
+        self
+    }
 
-            // Expand virtual dimensions first (they can be used in calculated measures)
-            for vdim in schema.virtual_dimensions() {
-                let pattern = vdim.name();
-                // Use word boundaries to avoid partial matches
-                // e.g., don't replace "year" in "yearly_sales"
-                let regex_pattern = format!(r"\b{}\b", regex::escape(pattern));
-                if let Ok(re) = regex::Regex::new(&regex_pattern) {
-                    let replacement = format!("({})", vdim.expression());
-                    expanded = re.replace_all(&expanded, replacement.as_str()).to_string();
-                }
-            }
+    /// Execute the query and return results
+    ///
+    /// # Returns
+    /// A QueryResult containing the data and metadata
+    pub async fn execute(self) -> Result<QueryResult> {
+        self.execute_cached().await.map(|(result, _)| result)
+    }
 
-            // Expand calculated measures
-            for calc_measure in schema.calculated_measures() {
-                let pattern = calc_measure.name();
-                let regex_pattern = format!(r"\b{}\b", regex::escape(pattern));
-                if let Ok(re) = regex::Regex::new(&regex_pattern) {
-                    let replacement = format!("({})", calc_measure.expression());
-                    expanded = re.replace_all(&expanded, replacement.as_str()).to_string();
-                }
+    /// Execute the query, also reporting whether its result is now cached
+    pub(crate) async fn execute_cached(mut self) -> Result<(QueryResult, bool)> {
+        let started_at = SystemTime::now();
+        let start = Instant::now();
+
+        // Build the query SQL string for caching
+        let query_sql = match self.resolve_sql().await {
+            Ok(sql) => sql,
+            Err(e) => {
+                self.log_query(self.query_sql(), started_at, start, Err(&e));
+                return Err(e);
             }
+        };
 
-            // If no changes were made, we're done
-            if expanded == before {
-                break;
+        let execution = self.execute_resolved(&query_sql).await;
+        self.log_query(query_sql, started_at, start, execution.as_ref());
+        execution.map(|execution| (self.with_display_formats(execution.result), execution.cached))
+    }
+
+    /// Attach the display formats of the measures shown in the result
+    ///
+    /// A column takes its measure's format when it is named after the
+    /// measure or is DataFusion's name for a plain aggregate of it, like
+    /// `sum(cube.revenue)`. Counts are left alone: they count rows, not
+    /// the measure's unit.
+    fn with_display_formats(&self, mut result: QueryResult) -> QueryResult {
+        let Some(batch) = result.batches.first() else {
+            return result;
+        };
+
+        let schema = self.cube.schema();
+        let mut formats = HashMap::new();
+        for field in batch.schema().fields() {
+            let name = field.name();
+            let format = schema.display_format(name).or_else(|| {
+                let captures = AGGREGATE_COLUMN.captures(name)?;
+                schema.display_format(&captures[1])
+            });
+            if let Some(format) = format {
+                formats.insert(name.clone(), format);
             }
         }
+        result.display_formats = formats;
+        result
+    }
 
-        expanded
+    /// Answer resolved SQL from the cache or by running it
+    async fn execute_resolved(&mut self, query_sql: &str) -> Result<Execution> {
+        // Check cache if enabled
+        let cache_key = self.cache.as_ref().and_then(|_| self.cache_key(query_sql));
+        let (Some(cache), Some(cache_key)) = (self.cache.clone(), cache_key) else {
+            let (result, rows_scanned) = self.run(query_sql).await?;
+            return Ok(Execution::ran(result, rows_scanned, false));
+        };
+        if let Some(cached_result) = cache.get(&cache_key) {
+            return Ok(Execution::cache_hit(cached_result));
+        }
+
+        // Wait for an identical query that is already running
+        let flight = match cache.join_flight(&cache_key) {
+            Flight::Leader(flight) => Some(flight),
+            Flight::Follower(receiver) => match Flight::wait(receiver).await {
+                Some(result) => return Ok(Execution::cache_hit(result)),
+                None => None,
+            },
+        };
+
+        let (result, rows_scanned) = self.run(query_sql).await?;
+        cache.put(cache_key, result.clone());
+        if let Some(flight) = flight {
+            flight.finish(&result);
+        }
+        Ok(Execution::ran(result, rows_scanned, true))
     }
 
-    /// Build SQL query string from fluent API parameters
-    fn build_sql_query(&self) -> String {
-        let mut query_str = String::from("SELECT ");
+    /// Register the cube data and run the resolved SQL
+    async fn run(&mut self, sql: &str) -> Result<(QueryResult, usize)> {
+        self.register_cube_data().await?;
+        let dataframe = self.execute_sql(sql).await?;
+        self.collect_result(dataframe).await
+    }
 
-        // SELECT clause - expand calculated fields
-        if self.select_exprs.is_empty() {
-            query_str.push('*');
-        } else {
-            let expanded_selects: Vec<String> = self
-                .select_exprs
-                .iter()
-                .map(|expr| self.expand_calculated_fields(expr))
-                .collect();
-            query_str.push_str(&expanded_selects.join(", "));
+    /// Add an execution to the cube's query history
+    fn log_query(
+        &self,
+        sql: String,
+        started_at: SystemTime,
+        start: Instant,
+        outcome: std::result::Result<&Execution, &Error>,
+    ) {
+        let (rows_scanned, rows_returned, cache_hit, error) = match outcome {
+            Ok(execution) => (
+                execution.rows_scanned,
+                execution.result.row_count(),
+                execution.cache_hit,
+                None,
+            ),
+            Err(e) => (0, 0, false, Some(e.to_string())),
+        };
+        self.recorder(sql, started_at, start)
+            .finish(rows_scanned, rows_returned, cache_hit, error);
+    }
+
+    fn recorder(&self, sql: String, started_at: SystemTime, start: Instant) -> QueryRecorder {
+        QueryRecorder {
+            cubes: vec![Arc::clone(&self.cube)],
+            principal: self.principal.clone(),
+            sql,
+            started_at,
+            start,
         }
+    }
 
-        query_str.push_str(" FROM cube");
+    /// Cache key for this query's SQL against the cube's current data
+    ///
+    /// Queries calling volatile functions (sampling, the current time) give
+    /// a different answer on each run, so they get no key and aren't cached.
+    /// Files read in place, by a lazy cube or as external tables, are keyed
+    /// by their sizes and modification times; queries reading files that
+    /// can't be inspected aren't cached either.
+    fn cache_key(&self, sql: &str) -> Option<QueryCacheKey> {
+        const VOLATILE: [&str; 5] = ["random(", "now(", "current_date", "current_time", "uuid("];
+        let lower = sql.to_lowercase();
+        if VOLATILE.iter().any(|function| lower.contains(function)) {
+            return None;
+        }
+        let mut files = Vec::new();
+        if let Some(path) = self.cube.parquet_path() {
+            files.push((String::from("cube"), crate::cache::path_stamp(path)?));
+        }
+        for (name, path) in &self.external_tables {
+            files.push((name.clone(), crate::cache::path_stamp(path)?));
+        }
+        let key = QueryCacheKey::new(&format!(
+            "{}\n-- cube {:016x} files {:?}",
+            sql,
+            self.cube.content_fingerprint(),
+            files
+        ));
+        Some(key.with_data_version(self.cube.data_version()))
+    }
 
-        // WHERE clause - expand calculated fields
-        if let Some(filter) = &self.filter_expr {
-            query_str.push_str(" WHERE ");
-            let expanded_filter = self.expand_calculated_fields(filter);
-            query_str.push_str(&expanded_filter);
+    /// Count the values of a numeric column in equal-width bins
+    ///
+    /// The bins span the column's minimum to maximum after filters (and any
+    /// sample) apply; grouping, ordering and limits on the builder are
+    /// ignored. NULLs are not counted. Empty bins are reported with a count
+    /// of zero.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let histogram = cube.query()?
+    ///     .filter("region = 'North'")
+    ///     .histogram("revenue", 20)
+    ///     .await?;
+    /// for (lower, upper, count) in histogram.bins() {
+    ///     println!("{:>8.1} - {:>8.1}: {}", lower, upper, count);
+    /// }
+    /// ```
+    pub async fn histogram(self, column: &str, bins: usize) -> Result<Histogram> {
+        if bins == 0 {
+            return Err(Error::query("Histogram needs at least one bin"));
         }
 
-        // GROUP BY clause - expand calculated fields
-        if !self.group_by_exprs.is_empty() {
-            query_str.push_str(" GROUP BY ");
-            let expanded_groups: Vec<String> = self
-                .group_by_exprs
-                .iter()
-                .map(|expr| self.expand_calculated_fields(expr))
-                .collect();
-            query_str.push_str(&expanded_groups.join(", "));
+        let mut values = self.histogram_values(column);
+        let sql = values.resolve_sql().await?;
+        values.register_cube_data().await?;
+
+        let bounds = values
+            .collect_sql(&format!(
+                "SELECT MIN(__value), MAX(__value) FROM ({}) AS histogram",
+                sql
+            ))
+            .await?;
+        let bound = |i: usize| {
+            bounds
+                .first()
+                .and_then(|batch| batch.column(i).as_any().downcast_ref::<Float64Array>())
+                .filter(|array| !array.is_empty() && array.is_valid(0))
+                .map(|array| array.value(0))
+        };
+        let (Some(min), Some(max)) = (bound(0), bound(1)) else {
+            return Ok(Histogram {
+                edges: Vec::new(),
+                counts: Vec::new(),
+            });
+        };
+
+        let width = (max - min) / bins as f64;
+        let mut edges: Vec<f64> = (0..bins).map(|i| min + width * i as f64).collect();
+        edges.push(max);
+        values.count_bins(&sql, edges).await
+    }
+
+    /// Count the values of a numeric column between explicit bin edges
+    ///
+    /// `edges` must be increasing; bin `i` covers `[edges[i], edges[i + 1])`
+    /// and the last bin also includes its upper edge. Values outside the
+    /// edges are not counted.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // Cold / normal / warm / hot
+    /// let histogram = cube.query()?
+    ///     .histogram_with_edges("temperature", &[-50.0, 20.0, 25.0, 30.0, 60.0])
+    ///     .await?;
+    /// ```
+    pub async fn histogram_with_edges(self, column: &str, edges: &[f64]) -> Result<Histogram> {
+        if edges.len() < 2 {
+            return Err(Error::query("Histogram needs at least two bin edges"));
+        }
+        if edges.iter().any(|e| !e.is_finite()) || edges.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(Error::query(
+                "Histogram bin edges must be finite and strictly increasing",
+            ));
         }
 
-        // ORDER BY clause - expand calculated fields
-        if !self.order_by_exprs.is_empty() {
-            query_str.push_str(" ORDER BY ");
-            let expanded_orders: Vec<String> = self
-                .order_by_exprs
-                .iter()
-                .map(|expr| self.expand_calculated_fields(expr))
-                .collect();
-            query_str.push_str(&expanded_orders.join(", "));
+        let mut values = self.histogram_values(column);
+        let sql = values.resolve_sql().await?;
+        values.register_cube_data().await?;
+        values.count_bins(&sql, edges.to_vec()).await
+    }
+
+    /// Reduce the builder to the filtered values of one column as `__value`
+    fn histogram_values(mut self, column: &str) -> Self {
+        self.select_exprs = vec![format!("CAST({} AS DOUBLE) AS __value", column)];
+        self.group_by_exprs.clear();
+        self.grouping = GroupingMode::Plain;
+        self.time_buckets.clear();
+        self.cumulatives.clear();
+        self.resample = None;
+        self.pivot = None;
+        self.order_by_exprs.clear();
+        self.limit_count = None;
+        self.offset_count = None;
+        self
+    }
+
+    /// Count the `__value`s of `sql` falling into each bin
+    async fn count_bins(&self, sql: &str, edges: Vec<f64>) -> Result<Histogram> {
+        let last = edges.len() - 2;
+        let mut case = String::from("CASE");
+        for (i, upper) in edges.iter().enumerate().skip(1) {
+            let op = if i - 1 == last { "<=" } else { "<" };
+            case.push_str(&format!(" WHEN __value {} {:?} THEN {}", op, upper, i - 1));
         }
+        case.push_str(" END");
 
-        // LIMIT clause
-        if let Some(limit) = self.limit_count {
-            query_str.push_str(&format!(" LIMIT {}", limit));
+        let batches = self
+            .collect_sql(&format!(
+                "SELECT {case} AS bin, COUNT(*) AS count FROM ({sql}) AS histogram \
+                 WHERE __value >= {min:?} GROUP BY bin",
+                case = case,
+                sql = sql,
+                min = edges[0]
+            ))
+            .await?;
+
+        let mut counts = vec![0u64; edges.len() - 1];
+        for batch in &batches {
+            let bins = arrow::compute::cast(batch.column(0), &DataType::Int64)?;
+            let bins = bins
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .ok_or_else(|| Error::query("Histogram bins are not integers"))?;
+            let bin_counts = batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .ok_or_else(|| Error::query("Histogram counts are not integers"))?;
+            for row in 0..batch.num_rows() {
+                if bins.is_valid(row) {
+                    counts[bins.value(row) as usize] = bin_counts.value(row) as u64;
+                }
+            }
         }
 
-        // OFFSET clause
-        if let Some(offset) = self.offset_count {
-            query_str.push_str(&format!(" OFFSET {}", offset));
+        Ok(Histogram { edges, counts })
+    }
+
+    /// Run SQL on this builder's session and collect the batches
+    async fn collect_sql(&self, sql: &str) -> Result<Vec<RecordBatch>> {
+        self.execute_sql(sql)
+            .await?
+            .collect()
+            .await
+            .map_err(|e| Error::query(format!("Failed to collect query results: {}", e)))
+    }
+
+    /// Execute the query and stream the results batch by batch
+    ///
+    /// Unlike [`execute`](Self::execute), batches are produced as they are
+    /// computed instead of being collected in memory, so large results can
+    /// be consumed incrementally or written straight to a file. Streamed
+    /// results bypass the query cache. The query reaches the cube's history
+    /// and audit sink once the stream ends, fails or is dropped.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use futures::StreamExt;
+    ///
+    /// let mut stream = cube.query()?
+    ///     .filter("year = 2024")
+    ///     .execute_stream()
+    ///     .await?;
+    /// while let Some(batch) = stream.next().await {
+    ///     writer.write(&batch?)?;
+    /// }
+    /// ```
+    pub async fn execute_stream(mut self) -> Result<QueryStream> {
+        let started_at = SystemTime::now();
+        let start = Instant::now();
+        let sql = match self.resolve_sql().await {
+            Ok(sql) => sql,
+            Err(e) => {
+                self.log_query(self.query_sql(), started_at, start, Err(&e));
+                return Err(e);
+            }
+        };
+
+        match self.start_stream(&sql).await {
+            Ok(inner) => {
+                let recorder = self.recorder(sql, started_at, start);
+                Ok(QueryStream::new(inner).recorded(recorder))
+            }
+            Err(e) => {
+                self.log_query(sql, started_at, start, Err(&e));
+                Err(e)
+            }
         }
+    }
 
-        query_str
+    async fn start_stream(&mut self, sql: &str) -> Result<SendableRecordBatchStream> {
+        self.register_cube_data().await?;
+        self.execute_sql(sql)
+            .await?
+            .execute_stream()
+            .await
+            .map_err(|e| Error::query(format!("Failed to start query stream: {}", e)))
     }
 
-    /// Build and execute a fluent API query
-    async fn execute_fluent_query(&self) -> Result<DataFrame> {
-        let query_str = self.build_sql_query();
-        self.execute_sql(&query_str).await
+    /// Plan the query once for repeated execution with different parameters
+    ///
+    /// Filters and expressions may contain positional placeholders (`$1`,
+    /// `$2`, ...) whose values are supplied to [`PreparedQuery::execute`].
+    /// The SQL is parsed and planned here, so each execution only binds the
+    /// parameters and runs the plan. The plan reads the cube's data as of
+    /// this call; prepare again after updating the cube.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let by_region = cube.query()?
+    ///     .select(&["product", "SUM(sales) AS total"])
+    ///     .filter("region = $1")
+    ///     .group_by(&["product"])
+    ///     .prepare()
+    ///     .await?;
+    ///
+    /// let north = by_region.execute(["North"]).await?;
+    /// let south = by_region.execute(["South"]).await?;
+    /// ```
+    pub async fn prepare(mut self) -> Result<PreparedQuery> {
+        // Parameters change between executions, so no batches can be pruned
+        let table = self.cube.table_provider(None)?;
+        self.ctx
+            .register_table("cube", table)
+            .map_err(|e| Error::query(format!("Failed to register table: {}", e)))?;
+
+        let sql = self.resolve_sql().await?;
+        self.register_cube_data().await?;
+        let dataframe = self.execute_sql(&sql).await?;
+        Ok(PreparedQuery {
+            query: self,
+            dataframe,
+            sql,
+        })
     }
-}
 
-/// Query result containing the executed query data
-#[derive(Debug, Clone)]
-pub struct QueryResult {
-    /// Result data as Arrow RecordBatches
-    batches: Vec<RecordBatch>,
+    /// Collect results, enforcing the result-size limits if any are set
+    ///
+    /// Also returns the number of rows the plan read from its sources.
+    async fn collect_result(&self, dataframe: DataFrame) -> Result<(QueryResult, usize)> {
+        let task_ctx = Arc::new(dataframe.task_ctx());
+        let plan = dataframe
+            .create_physical_plan()
+            .await
+            .map_err(|e| Error::query(format!("Failed to plan query: {}", e)))?;
 
-    /// Total number of rows in the result
-    row_count: usize,
-}
+        let result = if self.config.max_result_rows.is_some()
+            || self.config.max_result_bytes.is_some()
+        {
+            let stream = datafusion::physical_plan::execute_stream(Arc::clone(&plan), task_ctx)
+                .map_err(|e| Error::query(format!("Failed to collect query results: {}", e)))?;
+            self.collect_bounded(stream).await?
+        } else {
+            let batches = datafusion::physical_plan::collect(Arc::clone(&plan), task_ctx)
+                .await
+                .map_err(|e| Error::query(format!("Failed to collect query results: {}", e)))?;
+            QueryResult::from_batches(batches)
+        };
+        Ok((result, rows_scanned(plan.as_ref())))
+    }
 
-impl QueryResult {
-    /// Create a new QueryResult (for testing purposes)
-    #[cfg(test)]
-    pub(crate) fn new_for_testing(batches: Vec<RecordBatch>, row_count: usize) -> Self {
-        Self {
-            batches,
-            row_count,
+    /// Collect results while enforcing the configured result-size limits
+    ///
+    /// Stops reading as soon as a limit is crossed, so an oversized query
+    /// never materializes in full.
+    async fn collect_bounded(&self, mut stream: SendableRecordBatchStream) -> Result<QueryResult> {
+        let max_rows = self.config.max_result_rows.unwrap_or(usize::MAX);
+        let max_bytes = self.config.max_result_bytes.unwrap_or(usize::MAX);
+        let truncate = self.config.truncate_oversized_results;
+
+        let mut batches = Vec::new();
+        let mut rows = 0;
+        let mut bytes = 0;
+        let mut truncated = false;
+        while let Some(batch) = stream.next().await {
+            let batch = batch?;
+
+            if rows + batch.num_rows() > max_rows {
+                if !truncate {
+                    return Err(Error::query(format!(
+                        "Query result exceeds the limit of {} rows",
+                        max_rows
+                    )));
+                }
+                let batch = batch.slice(0, max_rows - rows);
+                rows += batch.num_rows();
+                if batch.num_rows() > 0 {
+                    batches.push(batch);
+                }
+                truncated = true;
+                break;
+            }
+
+            let batch_bytes = batch.get_array_memory_size();
+            if bytes + batch_bytes > max_bytes {
+                if !truncate {
+                    return Err(Error::query(format!(
+                        "Query result exceeds the limit of {} bytes",
+                        max_bytes
+                    )));
+                }
+                truncated = true;
+                break;
+            }
+
+            rows += batch.num_rows();
+            bytes += batch_bytes;
+            batches.push(batch);
         }
+
+        let mut result = QueryResult::from_batches(batches);
+        result.truncated = truncated;
+        Ok(result)
     }
 
-    /// Get the result batches
-    pub fn batches(&self) -> &[RecordBatch] {
-        &self.batches
+    /// Show how DataFusion plans the query without running it
+    ///
+    /// The returned [`QueryPlan`] holds the final SQL (after calculated
+    /// fields are expanded) and the logical and physical plans.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let plan = cube.query()?
+    ///     .select(&["region", "SUM(profit)"])
+    ///     .group_by(&["region"])
+    ///     .explain()
+    ///     .await?;
+    /// println!("{}", plan);
+    /// ```
+    pub async fn explain(self) -> Result<QueryPlan> {
+        self.explain_plan(false).await
     }
 
-    /// Get the total number of rows
-    pub fn row_count(&self) -> usize {
-        self.row_count
+    /// Run the query and return the physical plan annotated with runtime metrics
+    ///
+    /// Useful for finding which operator of a slow query takes the time.
+    pub async fn explain_analyze(self) -> Result<QueryPlan> {
+        self.explain_plan(true).await
     }
 
-    /// Check if the result is empty
-    pub fn is_empty(&self) -> bool {
-        self.row_count == 0
+    async fn explain_plan(mut self, analyze: bool) -> Result<QueryPlan> {
+        let sql = self.resolve_sql().await?;
+        self.register_cube_data().await?;
+
+        let batches = self
+            .execute_sql(&sql)
+            .await?
+            .explain(false, analyze)?
+            .collect()
+            .await
+            .map_err(|e| Error::query(format!("Failed to explain query: {}", e)))?;
+
+        let mut steps = Vec::new();
+        for batch in &batches {
+            let plan_types = arrow::compute::cast(batch.column(0), &DataType::Utf8)?;
+            let plans = arrow::compute::cast(batch.column(1), &DataType::Utf8)?;
+            let (Some(plan_types), Some(plans)) = (
+                plan_types.as_any().downcast_ref::<StringArray>(),
+                plans.as_any().downcast_ref::<StringArray>(),
+            ) else {
+                return Err(Error::query("Unexpected EXPLAIN output"));
+            };
+            for row in 0..batch.num_rows() {
+                steps.push((plan_types.value(row).to_string(), plans.value(row).to_string()));
+            }
+        }
+
+        Ok(QueryPlan { sql, steps })
     }
 
-    /// Get a pretty-printed string representation of the results
+    /// Validate the query, resolve anything that depends on the data and
+    /// return the SQL to run
+    async fn resolve_sql(&mut self) -> Result<String> {
+        if self.sql_query.is_none() {
+            self.validate_bridges()?;
+        }
+        if let Some(unpivot) = &self.unpivot {
+            self.validate_unpivot(unpivot)?;
+        }
+        if let Some(sample) = &self.sample {
+            if !(sample.fraction > 0.0 && sample.fraction <= 1.0) {
+                return Err(Error::query(format!(
+                    "Sample fraction must be in (0, 1], got {}",
+                    sample.fraction
+                )));
+            }
+        }
+
+        // Pivot columns depend on the data, so resolve them before building the SQL
+        if self.sql_query.is_none() && self.pivot.is_some() {
+            if !self.is_grouped() {
+                return Err(Error::query("Pivot requires at least one group_by column"));
+            }
+            self.register_cube_data().await?;
+            self.resolve_pivot_values().await?;
+        }
+        if self.sql_query.is_none() && self.resample.is_some() {
+            self.register_cube_data().await?;
+            self.resolve_resample_columns().await?;
+        }
+        self.route_to_aggregation().await?;
+
+        Ok(self.query_sql())
+    }
+
+    /// Answer the query from one of the cube's pre-aggregations if it can
     ///
-    /// Useful for debugging and testing
-    pub fn pretty_print(&self) -> Result<String> {
-        use arrow::util::pretty::pretty_format_batches;
+    /// Only plain fluent queries qualify: every selected expression must be
+    /// a GROUP BY column or SUM, MIN, MAX, COUNT or AVG of a measure, the
+    /// filter may only use the aggregation's dimensions, and ORDER BY may
+    /// only name output columns. Among the aggregations that fit, the one
+    /// with the fewest rows is used. Other queries run unchanged.
+    async fn route_to_aggregation(&mut self) -> Result<()> {
+        let plain = self.config.enable_aggregate_routing
+            && self.sql_query.is_none()
+            && self.ctes.is_empty()
+            && self.source_query.is_none()
+            && self.external_tables.is_empty()
+            && self.unpivot.is_none()
+            && self.sample.is_none()
+            && self.pivot.is_none()
+            && self.resample.is_none()
+            && self.time_buckets.is_empty()
+            && self.cumulatives.is_empty()
+            && matches!(self.grouping, GroupingMode::Plain)
+            && !self.select_exprs.is_empty()
+            && self.aggregate_table.is_none()
+            && !self.uses_bridges();
+        if !plain || self.ctx.table_exist("cube")? {
+            return Ok(());
+        }
+        let cube = Arc::clone(&self.cube);
+        let mut candidates = cube.current_aggregations();
+        if candidates.is_empty() {
+            return Ok(());
+        }
+        candidates.sort_by_key(|aggregation| aggregation.row_count());
 
-        pretty_format_batches(&self.batches)
-            .map(|display| display.to_string())
-            .map_err(|e| Error::query(format!("Failed to format results: {}", e)))
+        if !self.group_by_exprs.iter().all(|column| is_identifier(column)) {
+            return Ok(());
+        }
+        let Some(selects) = self
+            .select_exprs
+            .iter()
+            .map(|expr| RoutableSelect::parse(expr, &self.group_by_exprs))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Ok(());
+        };
+
+        // Sorting on anything but an output column could need raw rows
+        let outputs: Vec<&str> = self
+            .group_by_exprs
+            .iter()
+            .map(String::as_str)
+            .chain(selects.iter().filter_map(RoutableSelect::alias))
+            .collect();
+        let schema = cube.schema();
+        let sorts_outputs = self.order_by_exprs.iter().all(|expr| {
+            expr.split_whitespace().next().is_some_and(|first| {
+                let first = first.trim_matches('"');
+                let sorted_by_attribute = schema
+                    .get_dimension(first)
+                    .is_some_and(|dimension| dimension.sort_column().is_some());
+                !sorted_by_attribute
+                    && outputs.iter().any(|output| output.trim_matches('"') == first)
+            })
+        });
+        if !sorts_outputs {
+            return Ok(());
+        }
+
+        for aggregation in candidates {
+            let covered = self
+                .group_by_exprs
+                .iter()
+                .all(|column| aggregation.dimensions.contains(column));
+            let Some(rewritten) = selects
+                .iter()
+                .map(|select| select.rewrite(&aggregation.measures))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            if covered && self.filter_fits(&aggregation.dimension_schema).await? {
+                self.select_exprs = rewritten;
+                self.aggregate_table = Some(aggregation.table()?);
+                return Ok(());
+            }
+        }
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::builder::ElastiCubeBuilder;
-    use crate::cube::AggFunc;
-    use arrow::array::{Float64Array, Int32Array, StringArray};
-    use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+    /// Whether the WHERE filter only uses columns of `schema`
+    async fn filter_fits(&self, schema: &SchemaRef) -> Result<bool> {
+        let Some(filter) = &self.filter_expr else {
+            return Ok(true);
+        };
+        let ctx = self.cube.session_context(&self.config)?;
+        ctx.register_table("cube", Arc::new(MemTable::try_new(schema.clone(), vec![vec![]])?))?;
+        let sql = format!(
+            "SELECT 1 FROM cube WHERE {}",
+            self.expand_calculated_fields(filter)
+        );
+        Ok(ctx.sql(&sql).await.is_ok())
+    }
 
-    fn create_test_cube() -> Result<ElastiCube> {
-        // Create test data
-        let schema = Arc::new(ArrowSchema::new(vec![
-            Field::new("region", DataType::Utf8, false),
-            Field::new("product", DataType::Utf8, false),
+    /// Register cube data as a DataFusion table, plus any external tables
+    async fn register_cube_data(&mut self) -> Result<()> {
+        self.register_external_tables().await?;
+        for bridge in self.cube.bridges() {
+            if !self.ctx.table_exist(bridge.table_name().as_str())? {
+                self.ctx.register_table(bridge.table_name().as_str(), bridge.table()?)?;
+            }
+        }
+        if self.ctx.table_exist("cube")? {
+            return Ok(());
+        }
+
+        let table = match &self.aggregate_table {
+            Some(aggregation) => Arc::clone(aggregation),
+            None => self.cube.table_provider(self.prunable_filter())?,
+        };
+        self.ctx
+            .register_table("cube", table)
+            .map_err(|e| Error::query(format!("Failed to register table: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// The WHERE filter, if batches it rules out can be left out of the scan
+    ///
+    /// Only plain queries over the cube qualify: raw SQL, CTEs, nested
+    /// sources, samples and unpivots may read the cube table unfiltered.
+    fn prunable_filter(&self) -> Option<&str> {
+        let plain = self.sql_query.is_none()
+            && self.ctes.is_empty()
+            && self.source_query.is_none()
+            && self.sample.is_none()
+            && self.unpivot.is_none();
+        self.filter_expr.as_deref().filter(|_| plain)
+    }
+
+    /// Register the external files as tables on the session
+    async fn register_external_tables(&self) -> Result<()> {
+        for (name, path) in &self.external_tables {
+            if name == "cube" {
+                return Err(Error::query("External table name 'cube' is reserved"));
+            }
+            if self.ctx.table_exist(name.as_str())? {
+                continue;
+            }
+
+            let extension = Path::new(path)
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.to_ascii_lowercase());
+            let registered = match extension.as_deref() {
+                Some("csv") => {
+                    self.ctx
+                        .register_csv(name.as_str(), path.as_str(), CsvReadOptions::new())
+                        .await
+                }
+                Some("json") | Some("ndjson") | Some("jsonl") => {
+                    self.ctx
+                        .register_json(
+                            name.as_str(),
+                            path.as_str(),
+                            NdJsonReadOptions::default().file_extension(""),
+                        )
+                        .await
+                }
+                Some("parquet") | None => {
+                    self.ctx
+                        .register_parquet(name.as_str(), path.as_str(), ParquetReadOptions::default())
+                        .await
+                }
+                Some(other) => {
+                    return Err(Error::query(format!(
+                        "Unsupported external table format '.{}' for '{}'",
+                        other, path
+                    )))
+                }
+            };
+            registered.map_err(|e| {
+                Error::query(format!(
+                    "Failed to register external table '{}' from '{}': {}",
+                    name, path, e
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Check that an unpivot refers to existing columns
+    fn validate_unpivot(&self, unpivot: &UnpivotSpec) -> Result<()> {
+        if self.source_query.is_some() {
+            return Err(Error::query("Unpivot cannot be combined with from_query"));
+        }
+        if unpivot.columns.is_empty() {
+            return Err(Error::query("Unpivot requires at least one column"));
+        }
+
+        let schema = self.cube.arrow_schema();
+        for column in &unpivot.columns {
+            if schema.field_with_name(column).is_err() {
+                return Err(Error::query(format!(
+                    "Cannot unpivot unknown column '{}'",
+                    column
+                )));
+            }
+        }
+
+        for name in [&unpivot.name_column, &unpivot.value_column] {
+            if schema.field_with_name(name).is_ok() && !unpivot.columns.contains(name) {
+                return Err(Error::query(format!(
+                    "Unpivot output column '{}' conflicts with an existing column",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The relation queried by the fluent API
+    ///
+    /// Sampled if requested, then joined to the bridge tables of the bridge
+    /// dimensions the query groups by.
+    fn from_clause(&self) -> String {
+        self.joined_bridges()
+            .into_iter()
+            .fold(self.sampled_relation(), |relation, bridge| bridge.join_sql(&relation))
+    }
+
+    /// The relation queried by the fluent API, sampled if requested
+    fn sampled_relation(&self) -> String {
+        let base = self.base_relation();
+        let Some(sample) = &self.sample else {
+            return base;
+        };
+
+        match &sample.stratify_by {
+            None => format!(
+                "(SELECT * FROM {} WHERE random() < {}) AS cube",
+                base, sample.fraction
+            ),
+            Some(column) => {
+                let column = self.expand_calculated_fields(column);
+                format!(
+                    "(SELECT * EXCLUDE (__sample_rank, __sample_size) FROM \
+                     (SELECT *, ROW_NUMBER() OVER (PARTITION BY {col} ORDER BY random()) AS __sample_rank, \
+                     COUNT(*) OVER (PARTITION BY {col}) AS __sample_size FROM {base}) \
+                     WHERE __sample_rank <= CEIL(__sample_size * {fraction})) AS cube",
+                    col = column,
+                    base = base,
+                    fraction = sample.fraction
+                )
+            }
+        }
+    }
+
+    /// The unsampled relation queried by the fluent API
+    ///
+    /// Normally the registered `cube` table; with an unpivot, a derived
+    /// table named `cube` holding the long-format rows.
+    fn base_relation(&self) -> String {
+        if let Some(source) = &self.source_query {
+            return format!("({}) AS cube", source);
+        }
+        let Some(unpivot) = &self.unpivot else {
+            return "cube".to_string();
+        };
+
+        let kept: Vec<String> = self
+            .cube
+            .arrow_schema()
+            .fields()
+            .iter()
+            .map(|f| f.name())
+            .filter(|name| !unpivot.columns.contains(*name))
+            .map(|name| quote_ident(name))
+            .collect();
+
+        let branches: Vec<String> = unpivot
+            .columns
+            .iter()
+            .map(|column| {
+                let mut exprs = kept.clone();
+                exprs.push(format!(
+                    "'{}' AS {}",
+                    column.replace('\'', "''"),
+                    quote_ident(&unpivot.name_column)
+                ));
+                exprs.push(format!(
+                    "{} AS {}",
+                    quote_ident(column),
+                    quote_ident(&unpivot.value_column)
+                ));
+                format!("SELECT {} FROM cube", exprs.join(", "))
+            })
+            .collect();
+
+        format!("({}) AS cube", branches.join(" UNION ALL "))
+    }
+
+    /// Look up the output columns of the aggregation being resampled
+    async fn resolve_resample_columns(&mut self) -> Result<()> {
+        let Some(resample) = self.resample.as_mut() else {
+            return Ok(());
+        };
+        // Build the plain aggregation first to learn its output columns
+        resample.columns = None;
+
+        let sql = self.build_sql_query();
+        let frame = self.execute_sql(&sql).await?;
+        let columns = frame
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| (field.name().clone(), field.data_type().is_numeric()))
+            .collect();
+
+        if let Some(resample) = self.resample.as_mut() {
+            resample.columns = Some(columns);
+        }
+        Ok(())
+    }
+
+    /// Wrap an aggregation in a join against a dense time axis
+    fn resample_sql(
+        &self,
+        resample: &ResampleSpec,
+        body: &str,
+        columns: &[(String, bool)],
+    ) -> String {
+        let time = quote_ident(&format!(
+            "{}_{}",
+            resample.column,
+            resample.granularity.as_str()
+        ));
+        let keys: Vec<String> = columns
+            .iter()
+            .filter(|(name, _)| self.group_by_exprs.contains(name))
+            .map(|(name, _)| quote_ident(name))
+            .collect();
+
+        let mut from = format!(
+            "(SELECT unnest(generate_series(MIN({t}), MAX({t}), INTERVAL '{step}')) AS {t} \
+             FROM ({body}) AS __bounds) AS __axis",
+            t = time,
+            step = resample.granularity.interval(),
+            body = body
+        );
+        let mut join = vec![format!("__data.{t} = __axis.{t}", t = time)];
+        if !keys.is_empty() {
+            from.push_str(&format!(
+                " CROSS JOIN (SELECT DISTINCT {} FROM ({}) AS __series) AS __keys",
+                keys.join(", "),
+                body
+            ));
+            join.extend(
+                keys.iter()
+                    .map(|k| format!("__data.{k} IS NOT DISTINCT FROM __keys.{k}", k = k)),
+            );
+        }
+
+        let partition = if keys.is_empty() {
+            String::new()
+        } else {
+            let keys: Vec<String> = keys.iter().map(|k| format!("__keys.{}", k)).collect();
+            format!("PARTITION BY {} ", keys.join(", "))
+        };
+        let selects: Vec<String> = columns
+            .iter()
+            .map(|(name, numeric)| {
+                let ident = quote_ident(name);
+                if ident == time {
+                    return format!("__axis.{t} AS {t}", t = time);
+                }
+                if keys.contains(&ident) {
+                    return format!("__keys.{c} AS {c}", c = ident);
+                }
+                let value = format!("__data.{}", ident);
+                let filled = match resample.fill {
+                    FillStrategy::Null => value,
+                    FillStrategy::Zero if *numeric => format!("COALESCE({}, 0)", value),
+                    FillStrategy::Zero => value,
+                    FillStrategy::ForwardFill => format!(
+                        "LAST_VALUE({}) IGNORE NULLS OVER ({}ORDER BY __axis.{} \
+                         ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW)",
+                        value, partition, time
+                    ),
+                };
+                format!("{} AS {}", filled, ident)
+            })
+            .collect();
+
+        format!(
+            "SELECT * FROM (SELECT {} FROM {} LEFT JOIN ({}) AS __data ON {}) AS resampled",
+            selects.join(", "),
+            from,
+            body,
+            join.join(" AND ")
+        )
+    }
+
+    /// Look up the distinct values of the pivot dimension
+    async fn resolve_pivot_values(&mut self) -> Result<()> {
+        let Some(pivot) = &self.pivot else {
+            return Ok(());
+        };
+
+        let dimension = self.expand_calculated_fields(&pivot.dimension);
+        let mut query = format!(
+            "{with}SELECT DISTINCT CAST({dim} AS VARCHAR) AS value FROM {from} WHERE {dim} IS NOT NULL",
+            with = self.with_clause(),
+            dim = dimension,
+            from = self.from_clause()
+        );
+        if let Some(filter) = &self.filter_expr {
+            query.push_str(&format!(" AND ({})", self.expand_filter(filter)));
+        }
+        query.push_str(" ORDER BY value");
+
+        let batches = self
+            .execute_sql(&query)
+            .await?
+            .collect()
+            .await
+            .map_err(|e| Error::query(format!("Failed to collect pivot values: {}", e)))?;
+
+        let mut values = Vec::new();
+        for batch in &batches {
+            let column = arrow::compute::cast(batch.column(0), &DataType::Utf8)?;
+            let strings = column
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .ok_or_else(|| Error::query("Pivot values are not strings"))?;
+            values.extend(strings.iter().flatten().map(str::to_string));
+        }
+
+        if values.is_empty() {
+            return Err(Error::query(format!(
+                "Pivot on '{}' found no values to turn into columns",
+                pivot.dimension
+            )));
+        }
+        if values.len() > MAX_PIVOT_COLUMNS {
+            return Err(Error::query(format!(
+                "Pivot on '{}' would produce {} columns (maximum is {})",
+                pivot.dimension,
+                values.len(),
+                MAX_PIVOT_COLUMNS
+            )));
+        }
+
+        if let Some(pivot) = &mut self.pivot {
+            pivot.values = Some(values);
+        }
+        Ok(())
+    }
+
+    /// Plan a raw SQL query, refusing anything but queries
+    async fn execute_sql(&self, query: &str) -> Result<DataFrame> {
+        self.ctx
+            .sql_with_options(query, read_only_sql())
+            .await
+            .map_err(|e| Error::query(format!("SQL execution failed: {}", e)))
+    }
+
+    /// Expand calculated fields in an expression
+    ///
+    /// Replaces references to calculated measures and virtual dimensions
+    /// with their underlying expressions. Performs recursive expansion
+    /// to handle nested calculated fields.
+    fn expand_calculated_fields(&self, expr: &str) -> String {
+        let mut expanded = expr.to_string();
+        let schema = self.cube.schema();
+
+        // Keep expanding until no more changes occur (handles nested calculated fields)
+        // Use a maximum iteration count to prevent infinite loops
+        const MAX_ITERATIONS: usize = 10;
+        for _ in 0..MAX_ITERATIONS {
+            let before = expanded.clone();
+
+            // Expand virtual dimensions first (they can be used in calculated measures)
+            for vdim in schema.virtual_dimensions() {
+                let pattern = vdim.name();
+                // Use word boundaries to avoid partial matches
+                // e.g., don't replace "year" in "yearly_sales"
+                let regex_pattern = format!(r"\b{}\b", regex::escape(pattern));
+                if let Ok(re) = regex::Regex::new(&regex_pattern) {
+                    let replacement = format!("({})", vdim.expression());
+                    expanded = re.replace_all(&expanded, replacement.as_str()).to_string();
+                }
+            }
+
+            // Expand calculated measures
+            for calc_measure in schema.calculated_measures() {
+                let pattern = calc_measure.name();
+                let regex_pattern = format!(r"\b{}\b", regex::escape(pattern));
+                if let Ok(re) = regex::Regex::new(&regex_pattern) {
+                    let replacement = format!("({})", calc_measure.expression());
+                    expanded = re.replace_all(&expanded, replacement.as_str()).to_string();
+                }
+            }
+
+            // If no changes were made, we're done
+            if expanded == before {
+                break;
+            }
+        }
+
+        expanded
+    }
+
+    /// Whether the query is aggregated into groups
+    fn is_grouped(&self) -> bool {
+        !self.group_by_exprs.is_empty()
+            || !self.time_buckets.is_empty()
+            || matches!(self.grouping, GroupingMode::Sets(_))
+    }
+
+    /// Bridge dimensions mentioned by any of `exprs`
+    fn bridges_in<'a>(&'a self, exprs: &[&String]) -> Vec<&'a BridgeDimension> {
+        let expanded: Vec<String> = exprs
+            .iter()
+            .map(|expr| self.expand_calculated_fields(expr))
+            .collect();
+        self.cube
+            .bridges()
+            .iter()
+            .filter(|bridge| expanded.iter().any(|expr| mentions(expr, bridge.name())))
+            .collect()
+    }
+
+    /// Bridge dimensions whose bridge table is joined onto the cube rows
+    ///
+    /// Those a grouped query groups by, or an ungrouped one selects or
+    /// sorts by; each row then appears once per distinct member.
+    fn joined_bridges(&self) -> Vec<&BridgeDimension> {
+        if self.sql_query.is_some() {
+            return Vec::new();
+        }
+        let pivoted = self.pivot.iter().map(|pivot| &pivot.dimension);
+        let exprs: Vec<&String> = if self.is_grouped() {
+            self.group_by_exprs.iter().chain(pivoted).collect()
+        } else {
+            self.select_exprs
+                .iter()
+                .chain(&self.order_by_exprs)
+                .chain(pivoted)
+                .collect()
+        };
+        self.bridges_in(&exprs)
+    }
+
+    /// Whether the fluent query mentions any bridge dimension
+    fn uses_bridges(&self) -> bool {
+        let exprs: Vec<&String> = self
+            .select_exprs
+            .iter()
+            .chain(&self.group_by_exprs)
+            .chain(&self.order_by_exprs)
+            .chain(&self.filter_expr)
+            .collect();
+        !self.bridges_in(&exprs).is_empty()
+    }
+
+    /// Reject uses of bridge dimensions that would count rows twice
+    ///
+    /// A grouped query must group by a bridge dimension to select or sort
+    /// by it, and cannot roll it up into subtotals. Each top-level `AND`
+    /// condition of the filter may name one bridge dimension that is not
+    /// joined.
+    fn validate_bridges(&self) -> Result<()> {
+        if self.cube.bridges().is_empty() {
+            return Ok(());
+        }
+        let joined: Vec<&str> = self.joined_bridges().iter().map(|b| b.name()).collect();
+
+        if self.is_grouped() {
+            if let Some(bridge) = joined.first() {
+                if !matches!(self.grouping, GroupingMode::Plain) {
+                    return Err(Error::query(format!(
+                        "Bridge dimension '{}' cannot be rolled up; subtotals over it would \
+                         count rows once per member",
+                        bridge
+                    )));
+                }
+            }
+            let outputs: Vec<&String> =
+                self.select_exprs.iter().chain(&self.order_by_exprs).collect();
+            if let Some(bridge) = self
+                .bridges_in(&outputs)
+                .into_iter()
+                .find(|bridge| !joined.contains(&bridge.name()))
+            {
+                return Err(Error::query(format!(
+                    "Bridge dimension '{}' must be grouped by to be selected or sorted in \
+                     a grouped query",
+                    bridge.name()
+                )));
+            }
+        }
+
+        if let Some(filter) = &self.filter_expr {
+            let expanded = self.expand_calculated_fields(filter);
+            for condition in split_conjuncts(&expanded) {
+                let named: Vec<&str> = self
+                    .cube
+                    .bridges()
+                    .iter()
+                    .map(|bridge| bridge.name())
+                    .filter(|name| !joined.contains(name) && mentions(condition, name))
+                    .collect();
+                if named.len() > 1 {
+                    return Err(Error::query(format!(
+                        "Filter condition '{}' names several bridge dimensions ({}); \
+                         combine separate conditions with AND",
+                        condition,
+                        named.join(", ")
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand a WHERE filter
+    ///
+    /// Top-level conditions on a bridge dimension the query does not join
+    /// become semi-joins on its bridge table, keeping each matching row
+    /// once.
+    fn expand_filter(&self, filter: &str) -> String {
+        let expanded = self.expand_calculated_fields(filter);
+        let joined: Vec<&str> = self.joined_bridges().iter().map(|b| b.name()).collect();
+        let filtered: Vec<&BridgeDimension> = self
+            .cube
+            .bridges()
+            .iter()
+            .filter(|bridge| !joined.contains(&bridge.name()) && mentions(&expanded, bridge.name()))
+            .collect();
+        if filtered.is_empty() {
+            return expanded;
+        }
+
+        split_conjuncts(&expanded)
+            .into_iter()
+            .map(|condition| {
+                match filtered.iter().find(|bridge| mentions(condition, bridge.name())) {
+                    Some(bridge) => bridge.semi_join_sql(condition),
+                    None => condition.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+
+    /// Expand an ORDER BY entry, sorting a dimension by its sort column
+    ///
+    /// In grouped queries the sort column is wrapped in `MIN`, which is
+    /// valid whether or not it is grouped and, for a sort column that
+    /// follows the dimension, equal to its value in every group.
+    fn expand_order(&self, expr: &str, grouped: bool) -> String {
+        let expr = expr.trim();
+        let (column, direction) = expr.split_once(char::is_whitespace).unwrap_or((expr, ""));
+        let schema = self.cube.schema();
+        let Some(sort) = schema
+            .get_dimension(column.trim_matches('"'))
+            .and_then(|dimension| dimension.sort_column())
+        else {
+            return self.expand_calculated_fields(expr);
+        };
+
+        let sort = match schema.get_virtual_dimension(sort) {
+            Some(_) => self.expand_calculated_fields(sort),
+            None => quote_ident(sort),
+        };
+        let key = if grouped { format!("MIN({})", sort) } else { sort };
+        format!("{} {}", key, direction).trim_end().to_string()
+    }
+
+    /// Expand a SELECT item, naming a bare aggregate measure after itself
+    ///
+    /// Aggregate measures are selected by name alone, and the result column
+    /// would otherwise be named after the expanded expression.
+    fn expand_select(&self, expr: &str) -> String {
+        let expanded = self.expand_calculated_fields(expr);
+        match self.cube.schema().get_calculated_measure(expr.trim()) {
+            Some(measure) if measure.is_aggregate() => {
+                format!("{} AS {}", expanded, quote_ident(measure.name()))
+            }
+            _ => expanded,
+        }
+    }
+
+    /// The SQL this builder runs: the raw SQL query or the fluent query
+    ///
+    /// Common table expressions are prepended to raw SQL too, merging with
+    /// a `WITH` clause the raw SQL may already have.
+    fn query_sql(&self) -> String {
+        let Some(sql) = &self.sql_query else {
+            return self.build_sql_query();
+        };
+        if self.ctes.is_empty() {
+            return sql.clone();
+        }
+
+        let trimmed = sql.trim_start();
+        match trimmed.get(..5) {
+            Some(keyword) if keyword.eq_ignore_ascii_case("WITH ") => {
+                format!("{}, {}", self.with_clause().trim_end(), &trimmed[5..])
+            }
+            _ => format!("{}{}", self.with_clause(), sql),
+        }
+    }
+
+    /// `WITH ...` prefix for the registered CTEs (empty when there are none)
+    fn with_clause(&self) -> String {
+        if self.ctes.is_empty() {
+            return String::new();
+        }
+        let ctes: Vec<String> = self
+            .ctes
+            .iter()
+            .map(|(name, sql)| format!("{} AS ({})", quote_ident(name), sql))
+            .collect();
+        format!("WITH {} ", ctes.join(", "))
+    }
+
+    /// Build SQL query string from fluent API parameters
+    fn build_sql_query(&self) -> String {
+        let mut query_str = self.with_clause();
+        let body_start = query_str.len();
+        query_str.push_str("SELECT ");
+
+        let buckets: Vec<(String, String)> = self
+            .time_buckets
+            .iter()
+            .map(|bucket| {
+                let column = self.expand_calculated_fields(&bucket.column);
+                (bucket.sql(&column), bucket.alias())
+            })
+            .collect();
+        let bucket_selects = buckets
+            .iter()
+            .map(|(expr, alias)| format!("{} AS {}", expr, quote_ident(alias)));
+
+        let grouped = !self.group_by_exprs.is_empty()
+            || !buckets.is_empty()
+            || matches!(self.grouping, GroupingMode::Sets(_));
+        let window_selects = self.cumulatives.iter().map(|cumulative| {
+            // Bucket aliases are not visible inside the window definition
+            let resolve = |column: &str| {
+                buckets
+                    .iter()
+                    .find(|(_, alias)| alias == column)
+                    .map(|(expr, _)| expr.clone())
+                    .unwrap_or_else(|| self.expand_calculated_fields(column))
+            };
+            let partitions: Vec<String> =
+                cumulative.partition_by.iter().map(|c| resolve(c)).collect();
+            format!(
+                "{} AS {}",
+                cumulative.sql(
+                    &self.expand_calculated_fields(&cumulative.expr),
+                    grouped,
+                    &partitions,
+                    &resolve(&cumulative.order_by),
+                ),
+                quote_ident(&cumulative.alias())
+            )
+        });
+
+        // SELECT clause - expand calculated fields
+        if let Some(PivotSpec {
+            dimension,
+            value_measure,
+            agg,
+            values: Some(values),
+        }) = &self.pivot
+        {
+            let dimension = self.expand_calculated_fields(dimension);
+            let measure = self.expand_calculated_fields(value_measure);
+            let mut columns: Vec<String> = bucket_selects.collect();
+            columns.extend(
+                self.group_by_exprs
+                    .iter()
+                    .map(|expr| self.expand_calculated_fields(expr)),
+            );
+            columns.extend(values.iter().map(|value| {
+                let case = format!(
+                    "CASE WHEN CAST({} AS VARCHAR) = '{}' THEN {} END",
+                    dimension,
+                    value.replace('\'', "''"),
+                    measure
+                );
+                format!("{} AS {}", agg.to_sql(&case), quote_ident(value))
+            }));
+            query_str.push_str(&columns.join(", "));
+        } else if self.select_exprs.is_empty() && buckets.is_empty() {
+            let columns: Vec<String> = std::iter::once("*".to_string())
+                .chain(window_selects)
+                .collect();
+            query_str.push_str(&columns.join(", "));
+        } else {
+            let expanded_selects: Vec<String> = bucket_selects
+                .chain(self.select_exprs.iter().map(|expr| self.expand_select(expr)))
+                .chain(window_selects)
+                .collect();
+            query_str.push_str(&expanded_selects.join(", "));
+        }
+
+        query_str.push_str(" FROM ");
+        query_str.push_str(&self.from_clause());
+
+        // WHERE clause - expand calculated fields
+        if let Some(filter) = &self.filter_expr {
+            query_str.push_str(" WHERE ");
+            let expanded_filter = self.expand_filter(filter);
+            query_str.push_str(&expanded_filter);
+        }
+
+        // GROUP BY clause - expand calculated fields
+        if let GroupingMode::Sets(sets) = &self.grouping {
+            let expanded_sets: Vec<String> = sets
+                .iter()
+                .map(|set| {
+                    let expanded: Vec<String> = buckets
+                        .iter()
+                        .map(|(expr, _)| expr.clone())
+                        .chain(set.iter().map(|expr| self.expand_calculated_fields(expr)))
+                        .collect();
+                    format!("({})", expanded.join(", "))
+                })
+                .collect();
+            query_str.push_str(&format!(" GROUP BY GROUPING SETS ({})", expanded_sets.join(", ")));
+        } else if !self.group_by_exprs.is_empty() || !buckets.is_empty() {
+            query_str.push_str(" GROUP BY ");
+            let expanded_groups: Vec<String> = buckets
+                .iter()
+                .map(|(expr, _)| expr.clone())
+                .chain(
+                    self.group_by_exprs
+                        .iter()
+                        .map(|expr| self.expand_calculated_fields(expr)),
+                )
+                .collect();
+            let groups = expanded_groups.join(", ");
+            match self.grouping {
+                GroupingMode::Rollup => query_str.push_str(&format!("ROLLUP ({})", groups)),
+                GroupingMode::Cube => query_str.push_str(&format!("CUBE ({})", groups)),
+                _ => query_str.push_str(&groups),
+            }
+        }
+
+        // Gap filling wraps the aggregation; ordering and limits apply after it
+        if let Some(resample) = &self.resample {
+            if let Some(columns) = &resample.columns {
+                let body = query_str.split_off(body_start);
+                query_str.push_str(&self.resample_sql(resample, &body, columns));
+            }
+        }
+
+        // ORDER BY clause - expand calculated fields and dimension sort columns
+        if !self.order_by_exprs.is_empty() {
+            query_str.push_str(" ORDER BY ");
+            let expanded_orders: Vec<String> = self
+                .order_by_exprs
+                .iter()
+                .map(|expr| match self.resample {
+                    // Gap filling leaves only the output columns to sort on
+                    Some(_) => self.expand_calculated_fields(expr),
+                    None => self.expand_order(expr, grouped),
+                })
+                .collect();
+            query_str.push_str(&expanded_orders.join(", "));
+        }
+
+        // LIMIT clause
+        if let Some(limit) = self.limit_count {
+            query_str.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        // OFFSET clause
+        if let Some(offset) = self.offset_count {
+            query_str.push_str(&format!(" OFFSET {}", offset));
+        }
+
+        query_str
+    }
+}
+
+/// Iterates over the pages of a query result
+///
+/// Created by [`QueryBuilder::paginate`] or [`QueryBuilder::paginate_by_key`].
+/// Each call to [`next_page`](Self::next_page) runs the query for one page.
+pub struct Paginator {
+    /// Query template every page is derived from
+    query: QueryBuilder,
+
+    /// Maximum rows per page
+    page_size: usize,
+
+    /// Column used for keyset pagination (None = OFFSET pagination)
+    key_column: Option<String>,
+
+    /// Offset of the next page (OFFSET pagination)
+    next_offset: usize,
+
+    /// SQL literal of the last key returned (keyset pagination)
+    last_key: Option<String>,
+
+    /// Rows left under the query's LIMIT, if any
+    remaining: Option<usize>,
+
+    /// Number of pages returned so far
+    pages: usize,
+
+    /// Set once the final page has been returned
+    done: bool,
+}
+
+impl Paginator {
+    fn new(mut query: QueryBuilder, page_size: usize, key_column: Option<String>) -> Self {
+        let next_offset = query.offset_count.unwrap_or(0);
+        let remaining = query.limit_count.take();
+        Self {
+            query,
+            page_size: page_size.max(1),
+            key_column,
+            next_offset,
+            last_key: None,
+            remaining,
+            pages: 0,
+            done: false,
+        }
+    }
+
+    /// Maximum number of rows per page
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Number of pages returned so far
+    pub fn pages_fetched(&self) -> usize {
+        self.pages
+    }
+
+    /// Fetch the next page, or `None` once all rows have been returned
+    pub async fn next_page(&mut self) -> Result<Option<QueryResult>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let limit = self
+            .remaining
+            .map_or(self.page_size, |remaining| remaining.min(self.page_size));
+        if limit == 0 {
+            self.done = true;
+            return Ok(None);
+        }
+
+        let mut query = self.query.clone();
+        query.limit_count = Some(limit);
+        match &self.key_column {
+            Some(key) => {
+                if let Some(last_key) = &self.last_key {
+                    let condition = format!("{} > {}", key, last_key);
+                    query.filter_expr = Some(match query.filter_expr.take() {
+                        Some(filter) => format!("({}) AND {}", filter, condition),
+                        None => condition,
+                    });
+                    query.offset_count = None;
+                }
+                query.order_by_exprs = vec![key.clone()];
+            }
+            None => query.offset_count = Some(self.next_offset),
+        }
+
+        let result = query.execute().await?;
+        let rows = result.row_count();
+        if rows < limit {
+            self.done = true;
+        }
+        if rows == 0 {
+            return Ok(None);
+        }
+
+        self.next_offset += rows;
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= rows;
+        }
+        if let Some(key) = &self.key_column {
+            self.last_key = last_key_literal(&result, key)?;
+            if self.last_key.is_none() {
+                self.done = true;
+            }
+        }
+
+        self.pages += 1;
+        Ok(Some(result))
+    }
+}
+
+/// Render the key of the last row of a page as a SQL literal
+fn last_key_literal(result: &QueryResult, key: &str) -> Result<Option<String>> {
+    let Some(batch) = result.batches().iter().rev().find(|b| b.num_rows() > 0) else {
+        return Ok(None);
+    };
+
+    let column = batch.column_by_name(key).ok_or_else(|| {
+        Error::query(format!(
+            "Pagination key '{}' is not a column of the query result",
+            key
+        ))
+    })?;
+    let row = batch.num_rows() - 1;
+    if column.is_null(row) {
+        return Ok(None);
+    }
+
+    let value = arrow::util::display::array_value_to_string(column, row)?;
+    let literal = if column.data_type().is_numeric() {
+        value
+    } else {
+        format!("'{}'", value.replace('\'', "''"))
+    };
+    Ok(Some(literal))
+}
+
+/// A planned query that can be executed repeatedly with new parameters
+///
+/// Created by [`QueryBuilder::prepare`]. Executions bypass the query cache.
+#[derive(Clone)]
+pub struct PreparedQuery {
+    /// Builder the plan was made with, for its session and settings
+    query: QueryBuilder,
+
+    /// Planned query, still holding its placeholders
+    dataframe: DataFrame,
+
+    /// SQL that was planned
+    sql: String,
+}
+
+impl PreparedQuery {
+    /// The SQL that was planned, after calculated-field expansion
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Run the query with `params` bound to `$1`, `$2`, ... in order
+    pub async fn execute<P: Into<ScalarValue>>(
+        &self,
+        params: impl IntoIterator<Item = P>,
+    ) -> Result<QueryResult> {
+        let params: Vec<ScalarValue> = params.into_iter().map(Into::into).collect();
+        let dataframe = self
+            .dataframe
+            .clone()
+            .with_param_values(params)
+            .map_err(|e| Error::query(format!("Failed to bind query parameters: {}", e)))?;
+
+        let started_at = SystemTime::now();
+        let start = Instant::now();
+        let execution = self
+            .query
+            .collect_result(dataframe)
+            .await
+            .map(|(result, rows_scanned)| Execution::ran(result, rows_scanned, false));
+        self.query
+            .log_query(self.sql.clone(), started_at, start, execution.as_ref());
+        execution.map(|execution| self.query.with_display_formats(execution.result))
+    }
+}
+
+impl std::fmt::Debug for PreparedQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreparedQuery").field("sql", &self.sql).finish()
+    }
+}
+
+/// Outcome of executing a query
+struct Execution {
+    result: QueryResult,
+    rows_scanned: usize,
+    /// Whether the result is now held in the query cache
+    cached: bool,
+    /// Whether the result came from the cache or an identical running query
+    cache_hit: bool,
+}
+
+impl Execution {
+    fn ran(result: QueryResult, rows_scanned: usize, cached: bool) -> Self {
+        Self {
+            result,
+            rows_scanned,
+            cached,
+            cache_hit: false,
+        }
+    }
+
+    fn cache_hit(result: QueryResult) -> Self {
+        Self {
+            result,
+            rows_scanned: 0,
+            cached: true,
+            cache_hit: true,
+        }
+    }
+}
+
+/// Rows a physical plan read from its sources
+///
+/// Sums the output of the plan's leaves, taken from their execution metrics
+/// when they record any and from their statistics otherwise.
+fn rows_scanned(plan: &dyn ExecutionPlan) -> usize {
+    let children = plan.children();
+    if children.is_empty() {
+        return plan
+            .metrics()
+            .and_then(|metrics| metrics.output_rows())
+            .or_else(|| {
+                let statistics = plan.partition_statistics(None).ok()?;
+                statistics.num_rows.get_value().copied()
+            })
+            .unwrap_or(0);
+    }
+    children.into_iter().map(|child| rows_scanned(child.as_ref())).sum()
+}
+
+/// A query whose outcome is still to be added to the history and audit
+/// trail of the cubes it read
+pub(crate) struct QueryRecorder {
+    pub(crate) cubes: Vec<Arc<ElastiCube>>,
+    pub(crate) principal: Option<String>,
+    pub(crate) sql: String,
+    pub(crate) started_at: SystemTime,
+    pub(crate) start: Instant,
+}
+
+impl QueryRecorder {
+    /// Record the outcome with every cube
+    pub(crate) fn finish(
+        self,
+        rows_scanned: usize,
+        rows_returned: usize,
+        cache_hit: bool,
+        error: Option<String>,
+    ) {
+        let duration = self.start.elapsed();
+        for cube in &self.cubes {
+            if let Some(sink) = cube.audit_sink() {
+                sink.record(&AuditEvent {
+                    principal: self.principal.clone(),
+                    cube: cube.schema().name().to_string(),
+                    sql: self.sql.clone(),
+                    started_at: self.started_at,
+                    duration,
+                    rows_returned,
+                    error: error.clone(),
+                });
+            }
+            cube.query_log().record(QueryRecord {
+                sql: self.sql.clone(),
+                started_at: self.started_at,
+                duration,
+                rows_scanned,
+                rows_returned,
+                cache_hit,
+                error: error.clone(),
+            });
+        }
+    }
+}
+
+/// Stream of result batches returned by [`QueryBuilder::execute_stream`]
+pub struct QueryStream {
+    /// Underlying DataFusion stream
+    inner: SendableRecordBatchStream,
+
+    /// Where the query is recorded once the stream is over
+    recorder: Option<QueryRecorder>,
+
+    /// Rows produced so far
+    rows_returned: usize,
+}
+
+impl QueryStream {
+    pub(crate) fn new(inner: SendableRecordBatchStream) -> Self {
+        Self {
+            inner,
+            recorder: None,
+            rows_returned: 0,
+        }
+    }
+
+    /// Record the query once the stream ends, fails or is dropped
+    pub(crate) fn recorded(mut self, recorder: QueryRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    fn finish(&mut self, error: Option<String>) {
+        if let Some(recorder) = self.recorder.take() {
+            // Rows scanned are only known to the plan's metrics
+            recorder.finish(0, self.rows_returned, false, error);
+        }
+    }
+
+    /// Schema of the batches produced by the stream
+    pub fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+}
+
+impl Stream for QueryStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let polled = self
+            .inner
+            .poll_next_unpin(cx)
+            .map(|batch| batch.map(|batch| batch.map_err(Error::from)));
+        match &polled {
+            Poll::Ready(Some(Ok(batch))) => self.rows_returned += batch.num_rows(),
+            Poll::Ready(Some(Err(e))) => self.finish(Some(e.to_string())),
+            Poll::Ready(None) => self.finish(None),
+            Poll::Pending => {}
+        }
+        polled
+    }
+}
+
+impl Drop for QueryStream {
+    fn drop(&mut self) {
+        self.finish(Some("Stream dropped before the end of the result".to_string()));
+    }
+}
+
+/// The plan DataFusion chose for a query
+///
+/// Returned by [`QueryBuilder::explain`] and [`QueryBuilder::explain_analyze`].
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+    /// SQL that was planned, after calculated-field expansion
+    sql: String,
+
+    /// (plan type, plan text) pairs as reported by EXPLAIN
+    steps: Vec<(String, String)>,
+}
+
+impl QueryPlan {
+    /// The SQL that was planned, after calculated-field expansion
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// All plan sections as (plan type, plan text) pairs
+    pub fn steps(&self) -> &[(String, String)] {
+        &self.steps
+    }
+
+    /// The optimized logical plan, if reported
+    pub fn logical_plan(&self) -> Option<&str> {
+        self.step("logical_plan")
+    }
+
+    /// The physical plan (with runtime metrics for `explain_analyze`)
+    pub fn physical_plan(&self) -> Option<&str> {
+        self.step("physical_plan")
+            .or_else(|| self.step("Plan with Metrics"))
+    }
+
+    fn step(&self, plan_type: &str) -> Option<&str> {
+        self.steps
+            .iter()
+            .find(|(t, _)| t == plan_type)
+            .map(|(_, plan)| plan.as_str())
+    }
+}
+
+impl std::fmt::Display for QueryPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "sql: {}", self.sql)?;
+        for (plan_type, plan) in &self.steps {
+            writeln!(f, "\n{}:\n{}", plan_type, plan)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts of a numeric column per bin, from [`QueryBuilder::histogram`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Bin boundaries; bin `i` spans `edges[i]..edges[i + 1]`
+    edges: Vec<f64>,
+
+    /// Number of values per bin
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    /// Bin boundaries (one more than the number of bins)
+    pub fn edges(&self) -> &[f64] {
+        &self.edges
+    }
+
+    /// Number of values in each bin
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Bins as (lower edge, upper edge, count)
+    pub fn bins(&self) -> impl Iterator<Item = (f64, f64, u64)> + '_ {
+        self.edges
+            .windows(2)
+            .zip(&self.counts)
+            .map(|(edges, count)| (edges[0], edges[1], *count))
+    }
+
+    /// Total number of values counted
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+impl std::fmt::Display for Histogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (lower, upper, count) in self.bins() {
+            writeln!(f, "[{}, {}): {}", lower, upper, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options admitting only queries
+///
+/// SQL reaches the engine from API callers and network clients, so DDL,
+/// writes such as `COPY ... TO` and `INSERT`, and statements such as `SET`
+/// are rejected before they are planned.
+pub(crate) fn read_only_sql() -> SQLOptions {
+    SQLOptions::new()
+        .with_allow_ddl(false)
+        .with_allow_dml(false)
+        .with_allow_statements(false)
+}
+
+/// Quote a column name as a SQL identifier
+pub(crate) fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Whether `expr` refers to `name` as a whole word
+///
+/// Quoted identifiers count; string literals are skipped, so `tag` is not
+/// mentioned by `label = 'tag'`.
+fn mentions(expr: &str, name: &str) -> bool {
+    SQL_TOKEN.captures_iter(expr).any(|token| {
+        if let Some(quoted) = token.get(1) {
+            quoted.as_str().replace("\"\"", "\"") == name
+        } else {
+            token.get(2).is_some_and(|word| word.as_str() == name)
+        }
+    })
+}
+
+/// Split a filter into its top-level `AND` conditions
+///
+/// `AND`s inside parentheses, string literals, quoted identifiers and
+/// `BETWEEN ... AND ...` don't split.
+fn split_conjuncts(filter: &str) -> Vec<&str> {
+    let bytes = filter.as_bytes();
+    let mut conditions = Vec::new();
+    let (mut start, mut depth, mut quote, mut between) = (0, 0usize, None, false);
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if let Some(open) = quote {
+            if c == open {
+                quote = None;
+            }
+            i += 1;
+            continue;
+        }
+        match c {
+            b'\'' | b'"' => quote = Some(c),
+            b'(' => depth += 1,
+            b')' => depth = depth.saturating_sub(1),
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|b| !(b.is_ascii_alphanumeric() || *b == b'_'))
+                    .map_or(bytes.len(), |len| i + len);
+                let word = &filter[i..end];
+                if depth == 0 && word.eq_ignore_ascii_case("between") {
+                    between = true;
+                } else if depth == 0 && word.eq_ignore_ascii_case("and") {
+                    if between {
+                        between = false;
+                    } else {
+                        conditions.push(filter[start..i].trim());
+                        start = end;
+                    }
+                }
+                i = end;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    conditions.push(filter[start..].trim());
+    conditions
+}
+
+/// Whether `name` is a plain unquoted SQL identifier
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A selected expression that can be answered from a pre-aggregation
+#[derive(Debug, Clone)]
+enum RoutableSelect {
+    /// A GROUP BY column, selected as written
+    Column { expr: String, alias: Option<String> },
+    /// An aggregate of a measure, or `COUNT(*)` when `measure` is None
+    Aggregate {
+        expr: String,
+        function: String,
+        measure: Option<String>,
+        alias: Option<String>,
+    },
+}
+
+impl RoutableSelect {
+    fn parse(expr: &str, group_by: &[String]) -> Option<Self> {
+        let captures = ROUTABLE_SELECT.captures(expr)?;
+        let alias = captures.get(4).map(|m| m.as_str().to_string());
+
+        if let Some(column) = captures.get(3) {
+            return group_by
+                .iter()
+                .any(|g| g == column.as_str())
+                .then(|| RoutableSelect::Column {
+                    expr: expr.to_string(),
+                    alias,
+                });
+        }
+        let function = captures.get(1)?.as_str().to_ascii_lowercase();
+        let argument = captures.get(2)?.as_str();
+        let measure = match argument {
+            "*" if function == "count" => None,
+            "*" => return None,
+            column => Some(column.to_string()),
+        };
+        Some(RoutableSelect::Aggregate {
+            expr: expr.to_string(),
+            function,
+            measure,
+            alias,
+        })
+    }
+
+    fn alias(&self) -> Option<&str> {
+        match self {
+            RoutableSelect::Column { alias, .. } | RoutableSelect::Aggregate { alias, .. } => {
+                alias.as_deref()
+            }
+        }
+    }
+
+    /// The expression over an aggregation holding partials of `measures`
+    ///
+    /// Unaliased aggregates keep the column name DataFusion gives the
+    /// original expression, so routed results look the same.
+    fn rewrite(&self, measures: &[String]) -> Option<String> {
+        let (expr, function, measure, alias) = match self {
+            RoutableSelect::Column { expr, .. } => return Some(expr.clone()),
+            RoutableSelect::Aggregate {
+                expr,
+                function,
+                measure,
+                alias,
+            } => (expr, function.as_str(), measure.as_deref(), alias),
+        };
+        let name = |default: String| alias.clone().unwrap_or_else(|| quote_ident(&default));
+
+        let Some(measure) = measure else {
+            return Some(format!(
+                "SUM({}) AS {}",
+                quote_ident(Aggregation::COUNT_COLUMN),
+                name("count(*)".to_string())
+            ));
+        };
+        if !measures.iter().any(|m| m == measure) {
+            return None;
+        }
+        let default = format!("{}(cube.{})", function, measure);
+        let rewritten = match function {
+            // The aggregation stores the sum under the measure's own name
+            "sum" => return Some(expr.clone()),
+            "min" => format!("MIN({})", quote_ident(&Aggregation::min_column(measure))),
+            "max" => format!("MAX({})", quote_ident(&Aggregation::max_column(measure))),
+            "count" => format!("SUM({})", quote_ident(&Aggregation::count_column(measure))),
+            "avg" => format!(
+                "CAST(SUM({}) AS DOUBLE) / SUM({})",
+                quote_ident(measure),
+                quote_ident(&Aggregation::count_column(measure))
+            ),
+            _ => return None,
+        };
+        Some(format!("{} AS {}", rewritten, name(default)))
+    }
+}
+
+/// A pending unpivot of columns into rows
+#[derive(Debug, Clone)]
+struct UnpivotSpec {
+    /// Columns folded into rows
+    columns: Vec<String>,
+    /// Output column holding the original column name
+    name_column: String,
+    /// Output column holding the value
+    value_column: String,
+}
+
+/// Calendar granularity for [`QueryBuilder::bucket_time`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Granularity {
+    /// Truncate to the second
+    Second,
+    /// Truncate to the minute
+    Minute,
+    /// Truncate to the hour
+    Hour,
+    /// Truncate to the day
+    Day,
+    /// Truncate to the week (starting Monday)
+    Week,
+    /// Truncate to the month
+    Month,
+    /// Truncate to the quarter
+    Quarter,
+    /// Truncate to the year
+    Year,
+}
+
+impl Granularity {
+    /// The `date_trunc` unit name for this granularity
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Granularity::Second => "second",
+            Granularity::Minute => "minute",
+            Granularity::Hour => "hour",
+            Granularity::Day => "day",
+            Granularity::Week => "week",
+            Granularity::Month => "month",
+            Granularity::Quarter => "quarter",
+            Granularity::Year => "year",
+        }
+    }
+
+    /// Length of one bucket as a SQL interval literal
+    fn interval(&self) -> &'static str {
+        match self {
+            Granularity::Second => "1 second",
+            Granularity::Minute => "1 minute",
+            Granularity::Hour => "1 hour",
+            Granularity::Day => "1 day",
+            Granularity::Week => "7 days",
+            Granularity::Month => "1 month",
+            Granularity::Quarter => "3 months",
+            Granularity::Year => "1 year",
+        }
+    }
+}
+
+/// How [`QueryBuilder::resample`] fills periods that have no data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FillStrategy {
+    /// Leave the measures NULL
+    Null,
+    /// Use 0 for numeric columns (others stay NULL)
+    Zero,
+    /// Repeat the last value seen in the same series
+    ForwardFill,
+}
+
+/// A timestamp column truncated to a granularity
+#[derive(Debug, Clone)]
+struct TimeBucket {
+    /// Column (or virtual dimension) holding the timestamp
+    column: String,
+    /// Bucket size
+    granularity: Granularity,
+    /// Time zone to bucket in (None = the column's own time zone)
+    timezone: Option<String>,
+}
+
+impl TimeBucket {
+    /// SQL expression computing the bucket from the (expanded) column
+    fn sql(&self, column: &str) -> String {
+        let unit = self.granularity.as_str();
+        match &self.timezone {
+            // Going through UTC first makes naive timestamps count as UTC
+            Some(tz) => format!(
+                "date_trunc('{}', ({} AT TIME ZONE 'UTC') AT TIME ZONE '{}')",
+                unit,
+                column,
+                tz.replace('\'', "''")
+            ),
+            None => format!("date_trunc('{}', {})", unit, column),
+        }
+    }
+
+    /// Output column name
+    fn alias(&self) -> String {
+        format!("{}_{}", self.column, self.granularity.as_str())
+    }
+}
+
+/// A running total added by [`QueryBuilder::cumulative`]
+#[derive(Debug, Clone)]
+struct CumulativeSpec {
+    /// Aggregate expression to accumulate, e.g. `SUM(sales)`
+    expr: String,
+    /// Column the total runs along
+    order_by: String,
+    /// Columns that restart the total
+    partition_by: Vec<String>,
+}
+
+impl CumulativeSpec {
+    /// Window expression over already expanded/resolved SQL fragments
+    fn sql(&self, expr: &str, grouped: bool, partitions: &[String], order_by: &str) -> String {
+        let function = if grouped {
+            // Window over the per-group aggregate results
+            let outer = match expr.split('(').next().map(|f| f.trim().to_uppercase()) {
+                Some(f) if f == "MIN" || f == "MAX" => f,
+                _ => "SUM".to_string(),
+            };
+            format!("{}({})", outer, expr)
+        } else {
+            expr.to_string()
+        };
+
+        let mut window = String::new();
+        if !partitions.is_empty() {
+            window.push_str(&format!("PARTITION BY {} ", partitions.join(", ")));
+        }
+        window.push_str(&format!(
+            "ORDER BY {} ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW",
+            order_by
+        ));
+        format!("{} OVER ({})", function, window)
+    }
+
+    /// Output column name derived from the expression
+    fn alias(&self) -> String {
+        let mut alias = String::from("cumulative");
+        for word in self
+            .expr
+            .to_lowercase()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|w| !w.is_empty())
+        {
+            alias.push('_');
+            alias.push_str(word);
+        }
+        alias
+    }
+}
+
+/// A pending gap fill over a dense time axis
+#[derive(Debug, Clone)]
+struct ResampleSpec {
+    /// Time column being bucketed
+    column: String,
+    /// Bucket size and axis step
+    granularity: Granularity,
+    /// Fill for missing periods
+    fill: FillStrategy,
+    /// Output columns of the aggregation as (name, is numeric), resolved at execution
+    columns: Option<Vec<(String, bool)>>,
+}
+
+/// A pending random sample of the input rows
+#[derive(Debug, Clone)]
+struct SampleSpec {
+    /// Fraction of rows to keep
+    fraction: f64,
+    /// Column to stratify by (None = simple random sample)
+    stratify_by: Option<String>,
+}
+
+/// Upper bound on the number of columns a pivot may generate
+const MAX_PIVOT_COLUMNS: usize = 1000;
+
+/// A string literal, a quoted identifier (group 1) or a word (group 2)
+static SQL_TOKEN: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r#"'(?:[^']|'')*'|"((?:[^"]|"")*)"|(\w+)"#)
+        .expect("SQL token pattern is a valid regex")
+});
+
+/// DataFusion's name for a plain aggregate of a column, like `sum(cube.revenue)`
+static AGGREGATE_COLUMN: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(
+        r#"(?i)^(?:sum|min|max|avg|mean|median|first_value|last_value)\((?:cube\.)?"?([^()"]+)"?\)$"#,
+    )
+    .expect("aggregate column pattern is a valid regex")
+});
+
+/// A select expression a pre-aggregation can answer: an aggregate of a
+/// column or `*` (groups 1 and 2) or a bare column (group 3), with an
+/// optional alias (group 4)
+static ROUTABLE_SELECT: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(
+        r#"(?is)^\s*(?:(sum|min|max|count|avg)\s*\(\s*(\*|[A-Za-z_][A-Za-z0-9_]*)\s*\)|([A-Za-z_][A-Za-z0-9_]*))\s*(?:\s+AS\s+("[^"]*"|[A-Za-z_][A-Za-z0-9_]*))?\s*$"#,
+    )
+    .expect("routable select pattern is a valid regex")
+});
+
+/// A pending pivot of a dimension into columns
+#[derive(Debug, Clone)]
+struct PivotSpec {
+    /// Dimension whose values become columns
+    dimension: String,
+    /// Measure aggregated into each cell
+    value_measure: String,
+    /// Aggregation applied per cell
+    agg: AggFunc,
+    /// Distinct dimension values, resolved at execution time
+    values: Option<Vec<String>>,
+}
+
+/// How GROUP BY columns are combined into groups
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GroupingMode {
+    /// Plain GROUP BY
+    Plain,
+    /// GROUP BY ROLLUP (...)
+    Rollup,
+    /// GROUP BY CUBE (...)
+    Cube,
+    /// GROUP BY GROUPING SETS (...)
+    Sets(Vec<Vec<String>>),
+}
+
+/// Query result containing the executed query data
+#[derive(Debug, Clone)]
+pub struct QueryResult {
+    /// Result data as Arrow RecordBatches
+    batches: Vec<RecordBatch>,
+
+    /// Total number of rows in the result
+    row_count: usize,
+
+    /// Whether rows were dropped to honor a result-size limit
+    truncated: bool,
+
+    /// Display formats of measure columns, by column name
+    display_formats: HashMap<String, DisplayFormat>,
+}
+
+impl QueryResult {
+    /// Create a new QueryResult (for testing purposes)
+    #[cfg(test)]
+    pub(crate) fn new_for_testing(batches: Vec<RecordBatch>, row_count: usize) -> Self {
+        Self {
+            batches,
+            row_count,
+            truncated: false,
+            display_formats: HashMap::new(),
+        }
+    }
+
+    /// Create a QueryResult from collected batches
+    pub(crate) fn from_batches(batches: Vec<RecordBatch>) -> Self {
+        let row_count = batches.iter().map(|b| b.num_rows()).sum();
+        Self {
+            batches,
+            row_count,
+            truncated: false,
+            display_formats: HashMap::new(),
+        }
+    }
+
+    /// Mark the result as truncated by a result-size limit
+    pub(crate) fn with_truncated(mut self, truncated: bool) -> Self {
+        self.truncated = truncated;
+        self
+    }
+
+    /// Get the result batches
+    pub fn batches(&self) -> &[RecordBatch] {
+        &self.batches
+    }
+
+    /// Get the total number of rows
+    pub fn row_count(&self) -> usize {
+        self.row_count
+    }
+
+    /// Whether the result was cut short by a result-size limit
+    ///
+    /// Only possible when `OptimizationConfig::truncate_oversized_results`
+    /// is enabled; otherwise oversized queries fail instead.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Check if the result is empty
+    pub fn is_empty(&self) -> bool {
+        self.row_count == 0
+    }
+
+    /// Display format of a result column, from the measure it shows
+    pub fn display_format(&self, column: &str) -> Option<&DisplayFormat> {
+        self.display_formats.get(column)
+    }
+
+    /// The batches with formatted measure columns rendered as strings
+    pub(crate) fn formatted_batches(&self) -> Result<Vec<RecordBatch>> {
+        if self.display_formats.is_empty() {
+            return Ok(self.batches.clone());
+        }
+
+        self.batches
+            .iter()
+            .map(|batch| {
+                let schema = batch.schema();
+                let mut fields = Vec::with_capacity(batch.num_columns());
+                let mut columns = Vec::with_capacity(batch.num_columns());
+                for (field, column) in schema.fields().iter().zip(batch.columns()) {
+                    let column = match self.display_formats.get(field.name()) {
+                        Some(format) => format.format_array(column)?,
+                        None => Arc::clone(column),
+                    };
+                    fields.push(Field::new(
+                        field.name(),
+                        column.data_type().clone(),
+                        field.is_nullable(),
+                    ));
+                    columns.push(column);
+                }
+                Ok(RecordBatch::try_new(Arc::new(ArrowSchema::new(fields)), columns)?)
+            })
+            .collect()
+    }
+
+    /// Get a pretty-printed string representation of the results
+    ///
+    /// Measure columns are shown with their display formats (see
+    /// [`ElastiCubeBuilder::format_measure`](crate::ElastiCubeBuilder::format_measure)).
+    /// Useful for debugging and testing
+    pub fn pretty_print(&self) -> Result<String> {
+        use arrow::util::pretty::pretty_format_batches;
+
+        pretty_format_batches(&self.formatted_batches()?)
+            .map(|display| display.to_string())
+            .map_err(|e| Error::query(format!("Failed to format results: {}", e)))
+    }
+
+    /// Convert the results to JSON objects, one per row
+    ///
+    /// Every column appears in every row (NULLs as `null`). Dates and
+    /// timestamps become ISO 8601 strings, decimals and other numbers become
+    /// JSON numbers, and lists and structs become arrays and objects.
+    pub fn to_json_rows(&self) -> Result<Vec<serde_json::Map<String, serde_json::Value>>> {
+        let mut writer = arrow_json::WriterBuilder::new()
+            .with_explicit_nulls(true)
+            .build::<_, arrow_json::writer::JsonArray>(Vec::new());
+        for batch in &self.batches {
+            writer.write(batch)?;
+        }
+        writer.finish()?;
+
+        let buffer = writer.into_inner();
+        if buffer.is_empty() {
+            return Ok(Vec::new());
+        }
+        serde_json::from_slice(&buffer)
+            .map_err(|e| Error::query(format!("Failed to convert results to JSON: {}", e)))
+    }
+
+    /// Deserialize each row into a `T`, matching columns to fields by name
+    ///
+    /// Conversions follow [`to_json_rows`](Self::to_json_rows), so dates and
+    /// timestamps deserialize as strings (or any type that reads them from
+    /// one). Extra columns are ignored unless `T` denies unknown fields;
+    /// missing or mistyped fields fail with the offending row number.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[derive(serde::Deserialize)]
+    /// struct RegionTotal {
+    ///     region: String,
+    ///     total: f64,
+    /// }
+    ///
+    /// let rows: Vec<RegionTotal> = result.deserialize()?;
+    /// ```
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<Vec<T>> {
+        self.to_json_rows()?
+            .into_iter()
+            .enumerate()
+            .map(|(row, values)| {
+                serde_json::from_value(serde_json::Value::Object(values))
+                    .map_err(|e| Error::data(format!("Failed to deserialize row {}: {}", row, e)))
+            })
+            .collect()
+    }
+
+    /// Write the results to a CSV file with a header row
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = File::create(path.as_ref())?;
+        let mut writer = arrow_csv::WriterBuilder::new()
+            .with_header(true)
+            .build(BufWriter::new(file));
+        for batch in &self.batches {
+            writer.write(batch)?;
+        }
+        Ok(())
+    }
+
+    /// Write the results to a Parquet file
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use parquet::basic::{Compression, ZstdLevel};
+    ///
+    /// result.write_parquet("out.parquet", Compression::ZSTD(ZstdLevel::default()))?;
+    /// ```
+    pub fn write_parquet(&self, path: impl AsRef<Path>, compression: Compression) -> Result<()> {
+        let schema = self
+            .batches
+            .first()
+            .map(|b| b.schema())
+            .ok_or_else(|| Error::io("Cannot write an empty result to Parquet: schema unknown"))?;
+        let props = WriterProperties::builder()
+            .set_compression(compression)
+            .build();
+        crate::storage::write_parquet_file(path.as_ref(), schema, &self.batches, props)
+    }
+
+    /// Write the results as newline-delimited JSON, one object per row
+    pub fn write_ndjson(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = File::create(path.as_ref())?;
+        let mut writer = arrow_json::LineDelimitedWriter::new(BufWriter::new(file));
+        for batch in &self.batches {
+            writer.write(batch)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Convert the results into a polars DataFrame
+    ///
+    /// Requires the "polars" feature to be enabled. An empty result becomes
+    /// an empty DataFrame with no columns.
+    #[cfg(feature = "polars")]
+    pub fn to_polars(&self) -> Result<polars::prelude::DataFrame> {
+        match self.batches.first() {
+            Some(batch) => {
+                crate::polars_interop::batches_to_dataframe(&batch.schema(), &self.batches)
+            }
+            None => Ok(polars::prelude::DataFrame::empty()),
+        }
+    }
+
+    /// Export the results through the Arrow C stream interface
+    ///
+    /// A zero-copy alternative to writing IPC for consumers in other
+    /// languages; see [`ElastiCube::export_ffi`] for how to hand the stream
+    /// over.
+    pub fn export_ffi(&self) -> Result<FFI_ArrowArrayStream> {
+        let schema = self
+            .batches
+            .first()
+            .map(|b| b.schema())
+            .ok_or_else(|| Error::data("Cannot export an empty result: schema unknown"))?;
+        let reader = RecordBatchIterator::new(self.batches.clone().into_iter().map(Ok), schema);
+        Ok(FFI_ArrowArrayStream::new(Box::new(reader)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ElastiCubeBuilder;
+    use arrow::array::Int32Array;
+    use arrow::datatypes::{Field, Schema as ArrowSchema};
+
+    fn create_test_cube() -> Result<ElastiCube> {
+        // Create test data
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("product", DataType::Utf8, false),
+            Field::new("sales", DataType::Float64, false),
+            Field::new("quantity", DataType::Int32, false),
+        ]));
+
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![
+                    "North", "South", "North", "East", "South",
+                ])),
+                Arc::new(StringArray::from(vec![
+                    "Widget", "Widget", "Gadget", "Widget", "Gadget",
+                ])),
+                Arc::new(Float64Array::from(vec![100.0, 200.0, 150.0, 175.0, 225.0])),
+                Arc::new(Int32Array::from(vec![10, 20, 15, 17, 22])),
+            ],
+        )
+        .unwrap();
+
+        ElastiCubeBuilder::new("test_cube")
+            .add_dimension("region", DataType::Utf8)?
+            .add_dimension("product", DataType::Utf8)?
+            .add_measure("sales", DataType::Float64, AggFunc::Sum)?
+            .add_measure("quantity", DataType::Int32, AggFunc::Sum)?
+            .load_record_batches(schema, vec![batch])?
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_query_select_all() {
+        let cube = create_test_cube().unwrap();
+        let arc_cube = Arc::new(cube);
+
+        let result = arc_cube.query().unwrap().execute().await.unwrap();
+
+        assert_eq!(result.row_count(), 5);
+        assert_eq!(result.batches().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_query_select_columns() {
+        let cube = create_test_cube().unwrap();
+        let arc_cube = Arc::new(cube);
+
+        let result = arc_cube
+            .query()
+            .unwrap()
+            .select(&["region", "sales"])
+            .execute()
+            .await
+            .unwrap();
+
+        assert_eq!(result.row_count(), 5);
+        // Check that we only got 2 columns
+        assert_eq!(result.batches()[0].num_columns(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_query_filter() {
+        let cube = create_test_cube().unwrap();
+        let arc_cube = Arc::new(cube);
+
+        let result = arc_cube
+            .query()
+            .unwrap()
+            .filter("sales > 150")
+            .execute()
+            .await
+            .unwrap();
+
+        assert_eq!(result.row_count(), 3); // 200, 175, 225
+    }
+
+    #[tokio::test]
+    async fn test_query_group_by() {
+        let cube = create_test_cube().unwrap();
+        let arc_cube = Arc::new(cube);
+
+        let result = arc_cube
+            .query()
+            .unwrap()
+            .select(&["region", "SUM(sales) as total_sales"])
+            .group_by(&["region"])
+            .execute()
+            .await
+            .unwrap();
+
+        assert_eq!(result.row_count(), 3); // North, South, East
+    }
+
+    #[tokio::test]
+    async fn test_query_order_by() {
+        let cube = create_test_cube().unwrap();
+        let arc_cube = Arc::new(cube);
+
+        let result = arc_cube
+            .query()
+            .unwrap()
+            .select(&["region", "sales"])
+            .order_by(&["sales DESC"])
+            .execute()
+            .await
+            .unwrap();
+
+        assert_eq!(result.row_count(), 5);
+        // First row should have highest sales (225)
+    }
+
+    #[tokio::test]
+    async fn test_query_limit() {
+        let cube = create_test_cube().unwrap();
+        let arc_cube = Arc::new(cube);
+
+        let result = arc_cube
+            .query()
+            .unwrap()
+            .limit(3)
+            .execute()
+            .await
+            .unwrap();
+
+        assert_eq!(result.row_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_query_sql() {
+        let cube = create_test_cube().unwrap();
+        let arc_cube = Arc::new(cube);
+
+        let result = arc_cube
+            .query()
+            .unwrap()
+            .sql("SELECT region, SUM(sales) as total FROM cube GROUP BY region ORDER BY total DESC")
+            .execute()
+            .await
+            .unwrap();
+
+        assert_eq!(result.row_count(), 3);
+
+        // Raw SQL can only read the cube, even when streamed
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("cube.parquet");
+        let copy = format!("COPY cube TO '{}' STORED AS PARQUET", target.display());
+        for sql in [copy.as_str(), "DROP TABLE cube", "SET datafusion.execution.batch_size = 1"] {
+            let query = || Arc::clone(&arc_cube).query().unwrap().sql(sql);
+            assert!(query().execute().await.is_err());
+            assert!(query().execute_stream().await.is_err());
+        }
+        assert!(!target.exists());
+    }
+
+    #[tokio::test]
+    async fn test_olap_slice() {
+        let cube = create_test_cube().unwrap();
+        let arc_cube = Arc::new(cube);
+
+        let result = arc_cube
+            .query()
+            .unwrap()
+            .slice("region", "North")
+            .execute()
+            .await
+            .unwrap();
+
+        assert_eq!(result.row_count(), 2); // 2 North entries
+    }
+
+    #[tokio::test]
+    async fn test_olap_dice() {
+        let cube = create_test_cube().unwrap();
+        let arc_cube = Arc::new(cube);
+
+        let result = arc_cube
+            .query()
+            .unwrap()
+            .dice(&[("region", "North"), ("product", "Widget")])
+            .execute()
+            .await
+            .unwrap();
+
+        assert_eq!(result.row_count(), 1); // 1 North Widget
+    }
+
+    #[tokio::test]
+    async fn test_complex_query() {
+        let cube = create_test_cube().unwrap();
+        let arc_cube = Arc::new(cube);
+
+        let result = arc_cube
+            .query()
+            .unwrap()
+            .select(&["region", "product", "SUM(sales) as total_sales", "AVG(quantity) as avg_qty"])
+            .filter("sales > 100")
+            .group_by(&["region", "product"])
+            .order_by(&["total_sales DESC"])
+            .limit(5)
+            .execute()
+            .await
+            .unwrap();
+
+        assert!(result.row_count() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_group_by_rollup_cube_and_sets() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        // 5 (region, product) groups + 3 region subtotals + grand total
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["region", "product", "SUM(sales) as total"])
+            .group_by_rollup(&["region", "product"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 9);
+
+        // CUBE adds the 2 product subtotals
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["region", "product", "SUM(sales) as total"])
+            .group_by_cube(&["region", "product"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 11);
+
+        let result = cube
+            .query()
+            .unwrap()
+            .select(&["region", "product", "SUM(sales) as total"])
+            .grouping_sets(&[&["region"][..], &["product"], &[]])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_pivot() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .group_by(&["region"])
+            .pivot("product", "sales", AggFunc::Sum)
+            .order_by(&["region"])
+            .execute()
+            .await
+            .unwrap();
+
+        // One row per region, one column per product
+        assert_eq!(result.row_count(), 3);
+        let batch = &result.batches()[0];
+        let schema = batch.schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, vec!["region", "Gadget", "Widget"]);
+
+        // East only sold Widgets
+        let gadget = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert!(gadget.is_null(0));
+
+        let ungrouped = cube
+            .clone()
+            .query()
+            .unwrap()
+            .pivot("product", "sales", AggFunc::Sum)
+            .execute()
+            .await
+            .unwrap_err();
+        assert!(ungrouped.to_string().contains("group_by"));
+
+        let no_values = cube
+            .query()
+            .unwrap()
+            .filter("sales > 1000")
+            .group_by(&["region"])
+            .pivot("product", "sales", AggFunc::Sum)
+            .execute()
+            .await
+            .unwrap_err();
+        assert!(no_values.to_string().contains("found no values"));
+    }
+
+    #[tokio::test]
+    async fn test_unpivot() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        // 5 rows x 2 unpivoted columns
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .unpivot(&["sales", "quantity"], "metric", "value")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 10);
+        let schema = result.batches()[0].schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, vec!["region", "product", "metric", "value"]);
+
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .unpivot(&["sales", "quantity"], "metric", "value")
+            .select(&["metric", "SUM(value) as total"])
+            .filter("region = 'North'")
+            .group_by(&["metric"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 2);
+
+        let result = cube
+            .query()
+            .unwrap()
+            .unpivot(&["missing"], "metric", "value")
+            .execute()
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hierarchy_drill_down_and_roll_up() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("year", DataType::Int32, false),
+            Field::new("quarter", DataType::Int32, false),
+            Field::new("month", DataType::Int32, false),
+            Field::new("sales", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![2024, 2024, 2024, 2025])),
+                Arc::new(Int32Array::from(vec![1, 1, 2, 1])),
+                Arc::new(Int32Array::from(vec![1, 2, 4, 1])),
+                Arc::new(Float64Array::from(vec![10.0, 20.0, 30.0, 40.0])),
+            ],
+        )
+        .unwrap();
+        let cube = Arc::new(
+            ElastiCubeBuilder::new("time_cube")
+                .add_dimension("year", DataType::Int32)
+                .unwrap()
+                .add_dimension("quarter", DataType::Int32)
+                .unwrap()
+                .add_dimension("month", DataType::Int32)
+                .unwrap()
+                .add_measure("sales", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .add_hierarchy(
+                    "time",
+                    vec!["year".to_string(), "quarter".to_string(), "month".to_string()],
+                )
+                .unwrap()
+                .load_record_batches(schema, vec![batch])
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        // year -> (year, quarter)
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["year", "SUM(sales) AS total"])
+            .group_by(&["year"])
+            .drill_down_hierarchy("time", "year")
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 3);
+        assert_eq!(result.batches()[0].num_columns(), 3);
+
+        // (year, quarter, month) -> (year, quarter) -> year
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["year", "quarter", "month", "SUM(sales) AS total"])
+            .group_by(&["year", "quarter", "month"])
+            .order_by(&["month"])
+            .roll_up_hierarchy("time")
+            .unwrap()
+            .roll_up_hierarchy("time")
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 2);
+
+        let builder = cube.clone().query().unwrap().group_by(&["month"]);
+        assert!(builder.drill_down_hierarchy("time", "month").is_err());
+        assert!(cube.query().unwrap().roll_up_hierarchy("time").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drill_through() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["region", "product", "SUM(sales) AS total"])
+            .group_by(&["region", "product"])
+            .drill_through(&[("region", "South"), ("product", "Gadget")])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 1);
+        assert_eq!(result.batches()[0].num_columns(), 4);
+
+        // Existing filter still applies; columns and limit chain afterwards
+        let result = cube
+            .query()
+            .unwrap()
+            .filter("sales > 120")
+            .drill_through(&[("product", "Widget")])
+            .select(&["region", "sales"])
+            .limit(1)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 1);
+        assert_eq!(result.batches()[0].num_columns(), 2);
+
+        // Dimension names are quoted, so case is kept
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("Region", DataType::Utf8, false),
+            Field::new("sales", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["North", "South", "South"])),
+                Arc::new(Float64Array::from(vec![100.0, 200.0, 150.0])),
+            ],
+        )
+        .unwrap();
+        let mixed_case = ElastiCubeBuilder::new("mixed_case")
+            .add_dimension("Region", DataType::Utf8)
+            .unwrap()
+            .add_measure("sales", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .load_record_batches(schema, vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+        let result = Arc::new(mixed_case)
+            .query()
+            .unwrap()
+            .drill_through(&[("Region", "South")])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_paginate() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        let mut pages = cube.clone().query().unwrap().order_by(&["sales"]).paginate(2);
+        let mut sizes = Vec::new();
+        while let Some(page) = pages.next_page().await.unwrap() {
+            sizes.push(page.row_count());
+        }
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert_eq!(pages.pages_fetched(), 3);
+
+        // Offset and limit bound the pages
+        let mut pages = cube
+            .clone()
+            .query()
+            .unwrap()
+            .order_by(&["sales"])
+            .offset(1)
+            .limit(3)
+            .paginate(2);
+        assert_eq!(pages.next_page().await.unwrap().unwrap().row_count(), 2);
+        assert_eq!(pages.next_page().await.unwrap().unwrap().row_count(), 1);
+        assert!(pages.next_page().await.unwrap().is_none());
+
+        let mut pages = cube.query().unwrap().paginate_by_key(2, "sales");
+        let mut total = 0;
+        let mut last = f64::MIN;
+        while let Some(page) = pages.next_page().await.unwrap() {
+            for batch in page.batches() {
+                let sales = batch
+                    .column_by_name("sales")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Float64Array>()
+                    .unwrap();
+                for value in sales.values() {
+                    assert!(*value > last);
+                    last = *value;
+                    total += 1;
+                }
+            }
+        }
+        assert_eq!(total, 5);
+    }
+
+    #[tokio::test]
+    async fn test_statistical_aggregates() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        let select = [
+            AggFunc::Corr.to_sql("sales, quantity") + " AS corr",
+            AggFunc::CovarSamp.to_sql("sales, quantity") + " AS covar",
+            AggFunc::Skewness.to_sql("sales") + " AS skew",
+            AggFunc::Kurtosis.to_sql("sales") + " AS kurt",
+        ];
+        let result = cube.query().unwrap().select(&select).execute().await.unwrap();
+        assert_eq!(result.row_count(), 1);
+
+        let batch = &result.batches()[0];
+        let corr = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap()
+            .value(0);
+        assert!(corr > 0.99);
+        assert!(!batch.column(2).is_null(0));
+        assert!(!batch.column(3).is_null(0));
+    }
+
+    #[tokio::test]
+    async fn test_array_and_string_agg() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        let products = AggFunc::StringAgg("|".to_string()).to_sql("product") + " AS products";
+        let sales = AggFunc::ArrayAgg.to_sql("sales") + " AS sales_list";
+        let result = cube
+            .query()
+            .unwrap()
+            .select(&["region".to_string(), products, sales])
+            .filter("region = 'North'")
+            .group_by(&["region"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 1);
+
+        let batch = &result.batches()[0];
+        let products = arrow::util::display::array_value_to_string(batch.column(1), 0).unwrap();
+        let mut parts: Vec<&str> = products.split('|').collect();
+        parts.sort();
+        assert_eq!(parts, vec!["Gadget", "Widget"]);
+        assert!(matches!(batch.column(2).data_type(), DataType::List(_)));
+    }
+
+    #[tokio::test]
+    async fn test_cte_and_from_query() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        // Products with any sale above 200 (only Gadget)
+        let big = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["product"])
+            .filter("sales > 200");
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .with_cte("big", big)
+            .unwrap()
+            .filter("product IN (SELECT product FROM big)")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 2);
+
+        // Region totals: North 250, South 425, East 175
+        let per_region = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["region", "SUM(sales) AS total"])
+            .group_by(&["region"]);
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .from_query(per_region)
+            .unwrap()
+            .filter("total > 200")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 2);
+
+        // CTEs merge into raw SQL
+        let north = cube.clone().query().unwrap().filter("region = 'North'");
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .with_cte("north", north)
+            .unwrap()
+            .sql("WITH widgets AS (SELECT * FROM north WHERE product = 'Widget') SELECT * FROM widgets")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 1);
+
+        let other = Arc::new(create_test_cube().unwrap());
+        assert!(cube.query().unwrap().from_query(other.query().unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_external_table_join() {
+        use std::io::Write;
+
+        let mut file = tempfile::Builder::new().suffix(".csv").tempfile().unwrap();
+        writeln!(file, "product,category").unwrap();
+        writeln!(file, "Widget,Hardware").unwrap();
+        writeln!(file, "Gadget,Electronics").unwrap();
+        file.flush().unwrap();
+
+        let cube = Arc::new(create_test_cube().unwrap());
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .with_external_table("ref", file.path().to_string_lossy())
+            .sql(
+                "SELECT r.category, SUM(c.sales) AS total FROM cube c \
+                 JOIN ref r ON c.product = r.product GROUP BY r.category",
+            )
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 2);
+
+        // Usable from the fluent API through subqueries
+        let result = cube
+            .query()
+            .unwrap()
+            .with_external_table("ref", file.path().to_string_lossy())
+            .filter("product IN (SELECT product FROM ref WHERE category = 'Hardware')")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_explain() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        let plan = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["region", "SUM(sales) AS total"])
+            .group_by(&["region"])
+            .explain()
+            .await
+            .unwrap();
+        assert!(plan.sql().contains("GROUP BY region"));
+        assert!(plan.logical_plan().unwrap().contains("Aggregate"));
+        assert!(plan.physical_plan().is_some());
+        assert!(plan.to_string().contains("logical_plan"));
+
+        let plan = cube
+            .query()
+            .unwrap()
+            .filter("sales > 100")
+            .explain_analyze()
+            .await
+            .unwrap();
+        assert!(plan.physical_plan().unwrap().contains("metrics"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_stream() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        let mut stream = cube
+            .query()
+            .unwrap()
+            .select(&["region", "sales"])
+            .filter("sales > 120")
+            .execute_stream()
+            .await
+            .unwrap();
+        assert_eq!(stream.schema().fields().len(), 2);
+
+        let mut rows = 0;
+        while let Some(batch) = stream.next().await {
+            rows += batch.unwrap().num_rows();
+        }
+        assert_eq!(rows, 4);
+    }
+
+    #[tokio::test]
+    async fn test_result_size_guardrails() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        let config = OptimizationConfig::new().with_max_result_rows(3);
+        let result = cube.clone().query_with_config(config.clone()).unwrap().execute().await;
+        assert!(result.is_err());
+
+        let config = config.with_truncate_oversized_results(true);
+        let result = cube
+            .clone()
+            .query_with_config(config)
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 3);
+        assert!(result.is_truncated());
+
+        let config = OptimizationConfig::new().with_max_result_bytes(1);
+        assert!(cube.clone().query_with_config(config).unwrap().execute().await.is_err());
+
+        // Within limits nothing changes
+        let config = OptimizationConfig::new().with_max_result_rows(5);
+        let result = cube.query_with_config(config).unwrap().execute().await.unwrap();
+        assert_eq!(result.row_count(), 5);
+        assert!(!result.is_truncated());
+    }
+
+    #[tokio::test]
+    async fn test_query_with_memory_limit() {
+        let cube = Arc::new(create_test_cube().unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let config = OptimizationConfig::new()
+            .with_memory_limit(64 * 1024 * 1024)
+            .with_spill_path(dir.path());
+
+        let result = cube
+            .query_with_config(config)
+            .unwrap()
+            .select(&["region", "SUM(sales) AS total"])
+            .group_by(&["region"])
+            .execute()
+            .await
+            .unwrap();
+        assert!(result.row_count() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_sampling() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        let result = cube.clone().query().unwrap().sample(1.0).execute().await.unwrap();
+        assert_eq!(result.row_count(), 5);
+
+        let result = cube.clone().query().unwrap().sample(0.5).execute().await.unwrap();
+        assert!(result.row_count() <= 5);
+
+        // Every region keeps at least one row, and the helper columns are hidden
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .sample_stratified(0.01, "region")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 3);
+        assert_eq!(result.batches()[0].num_columns(), 4);
+
+        assert!(cube.query().unwrap().sample(0.0).execute().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bucket_time() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("ts", DataType::Utf8, false),
+            Field::new("sales", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![
+                    "2024-01-01T10:00:00",
+                    "2024-01-03T23:30:00",
+                    "2024-01-08T09:00:00",
+                    "2024-02-15T12:00:00",
+                ])),
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0, 4.0])),
+            ],
+        )
+        .unwrap();
+        let cube = Arc::new(
+            ElastiCubeBuilder::new("events")
+                .add_dimension("ts", DataType::Utf8)
+                .unwrap()
+                .add_measure("sales", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .load_record_batches(schema, vec![batch])
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .bucket_time("ts", Granularity::Week)
+            .select(&["SUM(sales) AS total"])
+            .order_by(&["ts_week"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 3);
+        assert_eq!(result.batches()[0].schema().field(0).name(), "ts_week");
+
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .bucket_time("ts", Granularity::Month)
+            .select(&["COUNT(*) AS n"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 2);
+
+        // 23:30 UTC on Jan 3 is already Jan 4 in UTC+02:00
+        let days = |result: &crate::QueryResult| -> Vec<String> {
+            let buckets = arrow::compute::cast(result.batches()[0].column(0), &DataType::Utf8);
+            let buckets = buckets.unwrap();
+            let buckets = buckets.as_any().downcast_ref::<StringArray>().unwrap();
+            buckets.iter().map(|day| day.unwrap()[..10].to_string()).collect()
+        };
+        let utc = cube
+            .clone()
+            .query()
+            .unwrap()
+            .bucket_time("ts", Granularity::Day)
+            .select(&["COUNT(*) AS n"])
+            .order_by(&["ts_day"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(days(&utc), vec!["2024-01-01", "2024-01-03", "2024-01-08", "2024-02-15"]);
+
+        let local = cube
+            .query()
+            .unwrap()
+            .bucket_time_tz("ts", Granularity::Day, "+02:00")
+            .select(&["COUNT(*) AS n"])
+            .order_by(&["ts_day"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(days(&local), vec!["2024-01-01", "2024-01-04", "2024-01-08", "2024-02-15"]);
+    }
+
+    #[tokio::test]
+    async fn test_cumulative() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["region", "product", "sales"])
+            .cumulative("SUM(sales)", "product", &["region"])
+            .order_by(&["region", "product"])
+            .execute()
+            .await
+            .unwrap();
+        let batch = &result.batches()[0];
+        let running = batch
+            .column_by_name("cumulative_sum_sales")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        // East/Widget, North/Gadget, North/Widget, South/Gadget, South/Widget
+        assert_eq!(
+            running.values().to_vec(),
+            vec![175.0, 150.0, 250.0, 225.0, 425.0]
+        );
+
+        let result = cube
+            .query()
+            .unwrap()
+            .select(&["region", "COUNT(*) AS orders"])
+            .group_by(&["region"])
+            .cumulative("COUNT(*)", "region", &[] as &[&str])
+            .order_by(&["region"])
+            .execute()
+            .await
+            .unwrap();
+        let batch = &result.batches()[0];
+        let running = batch
+            .column_by_name("cumulative_count")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(running.values().to_vec(), vec![1, 3, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_resample_fills_gaps() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("ts", DataType::Utf8, false),
+            Field::new("value", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![
+                    "2024-01-01T10:00:00",
+                    "2024-01-02T08:00:00",
+                    "2024-01-02T20:00:00",
+                    "2024-01-05T12:00:00",
+                ])),
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0, 4.0])),
+            ],
+        )
+        .unwrap();
+        let cube = Arc::new(
+            ElastiCubeBuilder::new("readings")
+                .add_dimension("ts", DataType::Utf8)
+                .unwrap()
+                .add_measure("value", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .load_record_batches(schema, vec![batch])
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        let totals = |fill: FillStrategy| {
+            let cube = cube.clone();
+            async move {
+                let result = cube
+                    .query()
+                    .unwrap()
+                    .select(&["SUM(value) AS total"])
+                    .resample("ts", Granularity::Day, fill)
+                    .order_by(&["ts_day"])
+                    .execute()
+                    .await
+                    .unwrap();
+                assert_eq!(result.row_count(), 5);
+                let batch = arrow::compute::concat_batches(
+                    &result.batches()[0].schema(),
+                    result.batches(),
+                )
+                .unwrap();
+                let totals = batch
+                    .column_by_name("total")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Float64Array>()
+                    .unwrap()
+                    .clone();
+                totals.iter().collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(
+            totals(FillStrategy::Null).await,
+            vec![Some(1.0), Some(5.0), None, None, Some(4.0)]
+        );
+        assert_eq!(
+            totals(FillStrategy::Zero).await,
+            vec![Some(1.0), Some(5.0), Some(0.0), Some(0.0), Some(4.0)]
+        );
+        assert_eq!(
+            totals(FillStrategy::ForwardFill).await,
+            vec![Some(1.0), Some(5.0), Some(5.0), Some(5.0), Some(4.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_result_files() {
+        let cube = Arc::new(create_test_cube().unwrap());
+        let result = cube
+            .query()
+            .unwrap()
+            .select(&["region", "SUM(sales) AS total"])
+            .group_by(&["region"])
+            .order_by(&["region"])
+            .execute()
+            .await
+            .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        let csv_path = dir.path().join("out.csv");
+        result.write_csv(&csv_path).unwrap();
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        assert_eq!(csv.lines().next(), Some("region,total"));
+        assert_eq!(csv.lines().count(), 4);
+
+        let json_path = dir.path().join("out.ndjson");
+        result.write_ndjson(&json_path).unwrap();
+        let json = std::fs::read_to_string(&json_path).unwrap();
+        assert_eq!(json.lines().next(), Some(r#"{"region":"East","total":175.0}"#));
+
+        let parquet_path = dir.path().join("out.parquet");
+        result
+            .write_parquet(&parquet_path, Compression::SNAPPY)
+            .unwrap();
+        let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+            File::open(&parquet_path).unwrap(),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        let rows: usize = reader.map(|b| b.unwrap().num_rows()).sum();
+        assert_eq!(rows, 3);
+    }
+
+    #[tokio::test]
+    async fn test_export_ffi_streams() {
+        use arrow::ffi_stream::ArrowArrayStreamReader;
+        use arrow::record_batch::RecordBatchReader;
+
+        let cube = Arc::new(create_test_cube().unwrap());
+        let reader = ArrowArrayStreamReader::try_new(cube.export_ffi().unwrap()).unwrap();
+        assert_eq!(reader.schema(), cube.arrow_schema().clone());
+        let rows: usize = reader.map(|b| b.unwrap().num_rows()).sum();
+        assert_eq!(rows, 5);
+
+        let result = cube
+            .query()
+            .unwrap()
+            .select(&["region", "SUM(sales) AS total"])
+            .group_by(&["region"])
+            .execute()
+            .await
+            .unwrap();
+        let reader = ArrowArrayStreamReader::try_new(result.export_ffi().unwrap()).unwrap();
+        assert_eq!(reader.schema().field(1).name(), "total");
+        let rows: usize = reader.map(|b| b.unwrap().num_rows()).sum();
+        assert_eq!(rows, 3);
+
+        let empty = QueryResult::from_batches(Vec::new());
+        assert!(empty.export_ffi().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_order_by_dimension_sort_column() {
+        use crate::cube::Dimension;
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("month_name", DataType::Utf8, false),
+            Field::new("month_number", DataType::Int32, false),
             Field::new("sales", DataType::Float64, false),
-            Field::new("quantity", DataType::Int32, false),
         ]));
-
         let batch = RecordBatch::try_new(
             schema.clone(),
             vec![
-                Arc::new(StringArray::from(vec![
-                    "North", "South", "North", "East", "South",
-                ])),
-                Arc::new(StringArray::from(vec![
-                    "Widget", "Widget", "Gadget", "Widget", "Gadget",
-                ])),
-                Arc::new(Float64Array::from(vec![100.0, 200.0, 150.0, 175.0, 225.0])),
-                Arc::new(Int32Array::from(vec![10, 20, 15, 17, 22])),
+                Arc::new(StringArray::from(vec!["March", "January", "February", "January"])),
+                Arc::new(Int32Array::from(vec![3, 1, 2, 1])),
+                Arc::new(Float64Array::from(vec![30.0, 10.0, 20.0, 5.0])),
             ],
         )
         .unwrap();
+        let cube = Arc::new(
+            ElastiCubeBuilder::new("monthly")
+                .add_dimension_with(
+                    Dimension::new("month_name", DataType::Utf8).with_sort_column("month_number"),
+                )
+                .unwrap()
+                .add_measure("sales", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .load_record_batches(schema, vec![batch])
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+        let months = |result: &QueryResult| -> Vec<String> {
+            result.batches()[0]
+                .column(0)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .iter()
+                .map(|v| v.unwrap().to_string())
+                .collect()
+        };
 
-        ElastiCubeBuilder::new("test_cube")
-            .add_dimension("region", DataType::Utf8)?
-            .add_dimension("product", DataType::Utf8)?
-            .add_measure("sales", DataType::Float64, AggFunc::Sum)?
-            .add_measure("quantity", DataType::Int32, AggFunc::Sum)?
-            .load_record_batches(schema, vec![batch])?
-            .build()
-    }
-
-    #[tokio::test]
-    async fn test_query_select_all() {
-        let cube = create_test_cube().unwrap();
-        let arc_cube = Arc::new(cube);
+        let grouped = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["month_name", "SUM(sales) AS total"])
+            .group_by(&["month_name"])
+            .order_by(&["month_name DESC"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(months(&grouped), vec!["March", "February", "January"]);
 
-        let result = arc_cube.query().unwrap().execute().await.unwrap();
+        let rows = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["month_name"])
+            .order_by(&["month_name"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(months(&rows), vec!["January", "January", "February", "March"]);
 
-        assert_eq!(result.row_count(), 5);
-        assert_eq!(result.batches().len(), 1);
+        // The sort column must exist in the data
+        let missing = ElastiCubeBuilder::new("monthly")
+            .add_dimension_with(Dimension::new("month_name", DataType::Utf8).with_sort_column("nope"))
+            .unwrap()
+            .load_record_batches(cube.arrow_schema().clone(), cube.data().to_vec())
+            .unwrap()
+            .build();
+        assert!(missing.is_err());
     }
 
     #[tokio::test]
-    async fn test_query_select_columns() {
-        let cube = create_test_cube().unwrap();
-        let arc_cube = Arc::new(cube);
+    async fn test_display_formats_in_pretty_print() {
+        let batch = create_test_cube().unwrap().data()[0].clone();
+        let cube = Arc::new(
+            ElastiCubeBuilder::new("test_cube")
+                .add_dimension("region", DataType::Utf8)
+                .unwrap()
+                .add_dimension("product", DataType::Utf8)
+                .unwrap()
+                .add_measure("sales", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .format_measure("sales", DisplayFormat::new("$#,##0.00"))
+                .unwrap()
+                .add_measure("quantity", DataType::Int32, AggFunc::Sum)
+                .unwrap()
+                .format_measure("quantity", DisplayFormat::default().with_unit("units"))
+                .unwrap()
+                .load_record_batches(batch.schema(), vec![batch])
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
 
-        let result = arc_cube
+        let result = cube
             .query()
             .unwrap()
-            .select(&["region", "sales"])
+            .select(&["region", "SUM(sales)", "SUM(quantity) AS quantity", "COUNT(sales) AS n"])
+            .group_by(&["region"])
+            .order_by(&["region"])
             .execute()
             .await
             .unwrap();
 
-        assert_eq!(result.row_count(), 5);
-        // Check that we only got 2 columns
-        assert_eq!(result.batches()[0].num_columns(), 2);
+        let schema = result.batches()[0].schema();
+        assert!(result.display_format(schema.field(1).name()).is_some());
+        assert!(result.display_format("quantity").is_some());
+        assert!(result.display_format("n").is_none());
+        // The data itself stays numeric
+        assert_eq!(schema.field(1).data_type(), &DataType::Float64);
+
+        let printed = result.pretty_print().unwrap();
+        assert!(printed.contains("$250.00"), "{}", printed);
+        assert!(printed.contains("42 units"), "{}", printed);
+        assert!(result.to_markdown().unwrap().contains("$425.00"));
     }
 
     #[tokio::test]
-    async fn test_query_filter() {
-        let cube = create_test_cube().unwrap();
-        let arc_cube = Arc::new(cube);
-
-        let result = arc_cube
+    async fn test_to_json_rows() {
+        let cube = Arc::new(create_test_cube().unwrap());
+        let result = cube
+            .clone()
             .query()
             .unwrap()
-            .filter("sales > 150")
+            .select(&[
+                "region",
+                "SUM(sales) AS total",
+                "CAST(NULL AS INT) AS missing",
+                "DATE '2024-03-01' AS day",
+                "CAST(1.25 AS DECIMAL(10, 2)) AS rate",
+            ])
+            .group_by(&["region"])
+            .order_by(&["region"])
             .execute()
             .await
             .unwrap();
 
-        assert_eq!(result.row_count(), 3); // 200, 175, 225
+        let rows = result.to_json_rows().unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["region"], serde_json::json!("East"));
+        assert_eq!(rows[0]["total"], serde_json::json!(175.0));
+        assert!(rows[0]["missing"].is_null());
+        assert_eq!(rows[0]["day"], serde_json::json!("2024-03-01"));
+        assert_eq!(rows[0]["rate"].as_f64(), Some(1.25));
+
+        let empty = cube
+            .query()
+            .unwrap()
+            .filter("sales < 0")
+            .execute()
+            .await
+            .unwrap();
+        assert!(empty.to_json_rows().unwrap().is_empty());
     }
 
     #[tokio::test]
-    async fn test_query_group_by() {
-        let cube = create_test_cube().unwrap();
-        let arc_cube = Arc::new(cube);
+    async fn test_deserialize_rows() {
+        #[derive(Debug, serde::Deserialize, PartialEq)]
+        struct RegionTotal {
+            region: String,
+            total: f64,
+            orders: i64,
+        }
 
-        let result = arc_cube
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Mistyped {
+            region: i64,
+        }
+
+        let cube = Arc::new(create_test_cube().unwrap());
+        let result = cube
             .query()
             .unwrap()
-            .select(&["region", "SUM(sales) as total_sales"])
+            .select(&["region", "SUM(sales) AS total", "COUNT(*) AS orders"])
             .group_by(&["region"])
+            .order_by(&["region"])
             .execute()
             .await
             .unwrap();
 
-        assert_eq!(result.row_count(), 3); // North, South, East
+        let rows: Vec<RegionTotal> = result.deserialize().unwrap();
+        assert_eq!(
+            rows[0],
+            RegionTotal {
+                region: "East".to_string(),
+                total: 175.0,
+                orders: 1,
+            }
+        );
+        assert_eq!(rows.len(), 3);
+
+        let err = result.deserialize::<Mistyped>().unwrap_err();
+        assert!(err.to_string().contains("row 0"));
     }
 
     #[tokio::test]
-    async fn test_query_order_by() {
-        let cube = create_test_cube().unwrap();
-        let arc_cube = Arc::new(cube);
+    async fn test_group_by_all() {
+        let cube = Arc::new(create_test_cube().unwrap());
 
-        let result = arc_cube
+        let query = cube
+            .clone()
             .query()
             .unwrap()
-            .select(&["region", "sales"])
-            .order_by(&["sales DESC"])
+            .select(&[
+                "region",
+                "UPPER(product) AS product_code",
+                "SUM(sales) AS total",
+                "count (*) AS orders",
+            ])
+            .group_by_all();
+        assert_eq!(query.group_by_exprs, vec!["region", "UPPER(product)"]);
+
+        let result = query.execute().await.unwrap();
+        assert_eq!(result.row_count(), 5);
+
+        let result = cube
+            .query()
+            .unwrap()
+            .select(&["region", "AVG(quantity) AS avg_qty"])
+            .group_by_all()
             .execute()
             .await
             .unwrap();
+        assert_eq!(result.row_count(), 3);
+    }
 
-        assert_eq!(result.row_count(), 5);
-        // First row should have highest sales (225)
+    #[tokio::test]
+    async fn test_histogram() {
+        let cube = Arc::new(create_test_cube().unwrap());
+
+        // sales: 100, 200, 150, 175, 225
+        let histogram = cube
+            .clone()
+            .query()
+            .unwrap()
+            .histogram("sales", 5)
+            .await
+            .unwrap();
+        assert_eq!(histogram.edges(), &[100.0, 125.0, 150.0, 175.0, 200.0, 225.0]);
+        assert_eq!(histogram.counts(), &[1, 0, 1, 1, 2]);
+        assert_eq!(histogram.total(), 5);
+
+        let histogram = cube
+            .clone()
+            .query()
+            .unwrap()
+            .filter("region <> 'East'")
+            .histogram_with_edges("quantity", &[0.0, 15.0, 20.0])
+            .await
+            .unwrap();
+        assert_eq!(histogram.counts(), &[1, 2]);
+
+        assert!(cube
+            .clone()
+            .query()
+            .unwrap()
+            .histogram_with_edges("sales", &[10.0, 5.0])
+            .await
+            .is_err());
+
+        let empty = cube
+            .query()
+            .unwrap()
+            .filter("sales < 0")
+            .histogram("sales", 4)
+            .await
+            .unwrap();
+        assert!(empty.counts().is_empty());
     }
 
     #[tokio::test]
-    async fn test_query_limit() {
-        let cube = create_test_cube().unwrap();
-        let arc_cube = Arc::new(cube);
+    async fn test_session_reuse_and_prepared_queries() {
+        let mut cube = create_test_cube().unwrap();
+        let batch = RecordBatch::try_new(
+            cube.arrow_schema().clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["West"])),
+                Arc::new(StringArray::from(vec!["Widget"])),
+                Arc::new(Float64Array::from(vec![1000.0])),
+                Arc::new(Int32Array::from(vec![1])),
+            ],
+        )
+        .unwrap();
+        cube.append_rows(batch).unwrap();
+        let cube = Arc::new(cube);
 
-        let result = arc_cube
+        let count = |result: &QueryResult| {
+            result.batches()[0]
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .value(0)
+        };
+
+        // A pruned table registered by one query must not leak into the next
+        let filtered = cube
+            .clone()
             .query()
             .unwrap()
-            .limit(3)
+            .select(&["COUNT(*)"])
+            .filter("sales >= 1000")
             .execute()
             .await
             .unwrap();
+        assert_eq!(count(&filtered), 1);
+        let all = cube.clone().query().unwrap().select(&["COUNT(*)"]).execute().await.unwrap();
+        assert_eq!(count(&all), 6);
 
-        assert_eq!(result.row_count(), 3);
+        let prepared = cube
+            .query()
+            .unwrap()
+            .select(&["SUM(sales) AS total"])
+            .filter("region = $1")
+            .prepare()
+            .await
+            .unwrap();
+        let total = |result: QueryResult| {
+            result.batches()[0]
+                .column(0)
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap()
+                .value(0)
+        };
+        assert_eq!(total(prepared.execute(["North"]).await.unwrap()), 250.0);
+        assert_eq!(total(prepared.execute(["West"]).await.unwrap()), 1000.0);
+        assert!(prepared.sql().contains("$1"));
     }
 
     #[tokio::test]
-    async fn test_query_sql() {
-        let cube = create_test_cube().unwrap();
-        let arc_cube = Arc::new(cube);
+    async fn test_query_cache_shared_between_queries() {
+        let mut cube = create_test_cube().unwrap();
+        let config = OptimizationConfig::new().with_max_cache_bytes(1 << 20);
+        let total = |result: QueryResult| {
+            result.batches()[0]
+                .column(0)
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap()
+                .value(0)
+        };
 
-        let result = arc_cube
-            .query()
+        let shared = Arc::new(cube.clone());
+        for _ in 0..2 {
+            let result = Arc::clone(&shared)
+                .query_with_config(config.clone())
+                .unwrap()
+                .select(&["SUM(sales)"])
+                .execute()
+                .await
+                .unwrap();
+            assert_eq!(total(result), 850.0);
+        }
+        let cache = shared.query_cache(&config).unwrap().unwrap();
+        assert_eq!(cache.stats().hits, 1);
+        assert!(cache.size_bytes() > 0);
+
+        // Sampling is random, so it is never served from the cache
+        for _ in 0..2 {
+            Arc::clone(&shared)
+                .query_with_config(config.clone())
+                .unwrap()
+                .sample(0.5)
+                .execute()
+                .await
+                .unwrap();
+        }
+        assert_eq!(cache.stats().hits, 1);
+
+        // Updated data is a different cache key
+        cube.delete_rows("region = 'North'").await.unwrap();
+        let result = Arc::new(cube.clone())
+            .query_with_config(config.clone())
             .unwrap()
-            .sql("SELECT region, SUM(sales) as total FROM cube GROUP BY region ORDER BY total DESC")
+            .select(&["SUM(sales)"])
             .execute()
             .await
             .unwrap();
+        assert_eq!(total(result), 600.0);
+        assert_eq!(cache.stats().hits, 1);
 
-        assert_eq!(result.row_count(), 3);
+        // So is an update that leaves the row count and every min and max
+        let replacement = RecordBatch::try_new(
+            cube.arrow_schema().clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["South"])),
+                Arc::new(StringArray::from(vec!["Widget"])),
+                Arc::new(Float64Array::from(vec![210.0])),
+                Arc::new(Int32Array::from(vec![20])),
+            ],
+        )
+        .unwrap();
+        cube.update_rows("sales = 200", replacement).await.unwrap();
+        assert_eq!(cube.row_count(), 3);
+        let result = Arc::new(cube)
+            .query_with_config(config)
+            .unwrap()
+            .select(&["SUM(sales)"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(total(result), 610.0);
+        assert_eq!(cache.stats().hits, 1);
     }
 
     #[tokio::test]
-    async fn test_olap_slice() {
-        let cube = create_test_cube().unwrap();
-        let arc_cube = Arc::new(cube);
+    async fn test_disk_cache_shared_between_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = OptimizationConfig::new().with_disk_cache(dir.path(), 1 << 20);
+        let run = |cube: Arc<ElastiCube>| {
+            let config = config.clone();
+            async move {
+                cube.query_with_config(config)
+                    .unwrap()
+                    .select(&["SUM(sales)"])
+                    .execute()
+                    .await
+                    .unwrap()
+            }
+        };
 
-        let result = arc_cube
+        // Each build loads the data anew, as a restarted process would
+        let first = Arc::new(create_test_cube().unwrap());
+        run(Arc::clone(&first)).await;
+        let second = Arc::new(create_test_cube().unwrap());
+        run(Arc::clone(&second)).await;
+        assert_eq!(second.query_cache(&config).unwrap().unwrap().stats().hits, 1);
+
+        // Other data doesn't reuse the result
+        let mut changed = create_test_cube().unwrap();
+        changed.delete_rows("region = 'North'").await.unwrap();
+        let changed = Arc::new(changed);
+        run(Arc::clone(&changed)).await;
+        assert_eq!(changed.query_cache(&config).unwrap().unwrap().stats().hits, 0);
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_routing() {
+        let mut cube = create_test_cube().unwrap();
+        cube.add_aggregation("by_region", &["region"]).await.unwrap();
+        cube.add_aggregation("by_region_product", &["region", "product"])
+            .await
+            .unwrap();
+        assert_eq!(cube.aggregation_names(), vec!["by_region", "by_region_product"]);
+        assert!(cube.add_aggregation("by_region", &["region"]).await.is_err());
+        assert!(cube.add_aggregation("bad", &["missing"]).await.is_err());
+
+        let query = |cube: &Arc<ElastiCube>, routing: bool| {
+            Arc::clone(cube)
+                .query_with_config(
+                    OptimizationConfig::new()
+                        .with_query_cache(false)
+                        .with_aggregate_routing(routing),
+                )
+                .unwrap()
+                .select(&[
+                    "region",
+                    "SUM(sales)",
+                    "MAX(quantity) AS top",
+                    "AVG(quantity) AS avg_quantity",
+                    "COUNT(*)",
+                ])
+                .filter("region <> 'East'")
+                .group_by(&["region"])
+                .order_by(&["region"])
+        };
+        let routed = |mut builder: QueryBuilder| async move {
+            builder.resolve_sql().await.unwrap();
+            builder.aggregate_table.is_some()
+        };
+
+        // The routed query gives the same result as the scan
+        let shared = Arc::new(cube.clone());
+        assert!(routed(query(&shared, true)).await);
+        assert!(!routed(query(&shared, false)).await);
+        let columns = |result: QueryResult| {
+            let schema = result.batches()[0].schema();
+            let batch = arrow::compute::concat_batches(&schema, result.batches()).unwrap();
+            let names: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+            (names, batch.columns().to_vec())
+        };
+        let expected = columns(query(&shared, false).execute().await.unwrap());
+        let actual = columns(query(&shared, true).execute().await.unwrap());
+        assert_eq!(actual, expected);
+
+        // Filters on measures and unsupported aggregates need the raw rows
+        let by_measure = Arc::clone(&shared)
             .query()
             .unwrap()
-            .slice("region", "North")
-            .execute()
+            .select(&["region", "SUM(sales)"])
+            .filter("sales > 150")
+            .group_by(&["region"]);
+        assert!(!routed(by_measure).await);
+        let distinct = Arc::clone(&shared)
+            .query()
+            .unwrap()
+            .select(&["region", "COUNT(DISTINCT product)"])
+            .group_by(&["region"]);
+        assert!(!routed(distinct).await);
+
+        // Finer groupings use the aggregation that covers them
+        let by_product = Arc::clone(&shared)
+            .query()
+            .unwrap()
+            .select(&["product", "SUM(sales) AS total"])
+            .group_by(&["product"])
+            .order_by(&["total DESC"]);
+        assert!(routed(by_product.clone()).await);
+        let result = by_product.execute().await.unwrap();
+        let totals = result.batches()[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(totals.value(0), 475.0);
+
+        // Stale aggregations are ignored until refreshed
+        cube.delete_rows("region = 'North'").await.unwrap();
+        assert!(!routed(query(&Arc::new(cube.clone()), true)).await);
+        cube.refresh_aggregations().await.unwrap();
+        assert!(routed(query(&Arc::new(cube.clone()), true)).await);
+        assert!(cube.drop_aggregation("by_region"));
+        assert!(!cube.drop_aggregation("by_region"));
+    }
+
+    #[tokio::test]
+    async fn test_warm_cache() {
+        let cube = Arc::new(create_test_cube().unwrap());
+        let by_region = Arc::clone(&cube)
+            .query()
+            .unwrap()
+            .select(&["region", "SUM(sales) AS total"])
+            .group_by(&["region"]);
+        let by_product = Arc::clone(&cube)
+            .query()
+            .unwrap()
+            .sql("SELECT product, COUNT(*) AS n FROM cube GROUP BY product");
+        let sampled = Arc::clone(&cube).query().unwrap().sample(0.5);
+
+        let warmed = cube
+            .warm_cache(&[by_region.clone(), by_product.clone(), sampled])
             .await
             .unwrap();
+        assert_eq!(warmed, 2);
 
-        assert_eq!(result.row_count(), 2); // 2 North entries
+        let cache = cube
+            .query_cache(&OptimizationConfig::default())
+            .unwrap()
+            .unwrap();
+        let hits = cache.stats().hits;
+        by_region.execute().await.unwrap();
+        by_product.execute().await.unwrap();
+        assert_eq!(cache.stats().hits, hits + 2);
+
+        // Failing queries are reported
+        let broken = Arc::clone(&cube).query().unwrap().sql("SELECT missing FROM cube");
+        assert!(cube.warm_cache(&[broken]).await.is_err());
     }
 
     #[tokio::test]
-    async fn test_olap_dice() {
-        let cube = create_test_cube().unwrap();
-        let arc_cube = Arc::new(cube);
+    async fn test_identical_queries_coalesced() {
+        let cube = Arc::new(create_test_cube().unwrap());
+        let query = || {
+            Arc::clone(&cube)
+                .query()
+                .unwrap()
+                .select(&["product", "SUM(sales) AS total"])
+                .group_by(&["product"])
+                .order_by(&["product"])
+                .execute()
+        };
 
-        let result = arc_cube
+        let (a, b, c) = tokio::join!(query(), query(), query());
+        let (a, b, c) = (a.unwrap(), b.unwrap(), c.unwrap());
+        assert_eq!(a.batches(), b.batches());
+        assert_eq!(a.batches(), c.batches());
+
+        let cache = cube
+            .query_cache(&OptimizationConfig::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(cache.stats().coalesced, 2);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_query_history() {
+        let cube = Arc::new(create_test_cube().unwrap());
+        let query = || {
+            Arc::clone(&cube)
+                .query()
+                .unwrap()
+                .select(&["region", "SUM(sales) AS total"])
+                .group_by(&["region"])
+        };
+        query().execute().await.unwrap();
+        query().execute().await.unwrap();
+        let failed = Arc::clone(&cube)
             .query()
             .unwrap()
-            .dice(&[("region", "North"), ("product", "Widget")])
+            .sql("SELECT missing FROM cube")
+            .execute()
+            .await;
+        assert!(failed.is_err());
+
+        let history = cube.query_history();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].rows_scanned, 5);
+        assert_eq!(history[0].rows_returned, 3);
+        assert!(!history[0].cache_hit);
+        assert!(history[1].cache_hit);
+        assert_eq!(history[1].rows_scanned, 0);
+        assert_eq!(history[0].sql, history[1].sql);
+        assert!(history[2].error.is_some());
+        assert_eq!(cube.query_log().most_frequent(1)[0].1, 2);
+
+        // Clones of the cube share the log
+        let copy = (*cube).clone();
+        assert_eq!(copy.query_history().len(), 3);
+        cube.query_log().clear();
+        assert!(copy.query_history().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_audit_sink_records_principal() {
+        use crate::audit::MemoryAuditSink;
+
+        let sink = Arc::new(MemoryAuditSink::new());
+        let mut cube = create_test_cube().unwrap();
+        cube.set_audit_sink(sink.clone());
+        let cube = Arc::new(cube);
+
+        Arc::clone(&cube)
+            .query()
+            .unwrap()
+            .as_principal("alice")
+            .select(&["region", "SUM(sales) AS total"])
+            .group_by(&["region"])
             .execute()
             .await
             .unwrap();
+        let failed = Arc::clone(&cube)
+            .query()
+            .unwrap()
+            .sql("SELECT missing FROM cube")
+            .execute()
+            .await;
+        assert!(failed.is_err());
 
-        assert_eq!(result.row_count(), 1); // 1 North Widget
+        let events = sink.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].principal.as_deref(), Some("alice"));
+        assert_eq!(events[0].cube, "test_cube");
+        assert_eq!(events[0].rows_returned, 3);
+        assert!(events[0].error.is_none());
+        assert_eq!(events[1].principal, None);
+        assert!(events[1].error.is_some());
+
+        // Streamed queries are recorded when the stream is over
+        let stream = Arc::clone(&cube)
+            .query()
+            .unwrap()
+            .as_principal("bob")
+            .filter("region = 'North'")
+            .execute_stream()
+            .await
+            .unwrap();
+        assert_eq!(sink.events().len(), 2);
+        let batches: Vec<RecordBatch> = futures::TryStreamExt::try_collect(stream).await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+        let events = sink.events();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].principal.as_deref(), Some("bob"));
+        assert_eq!(events[2].rows_returned, 2);
+        assert!(events[2].error.is_none());
+        assert_eq!(cube.query_history().len(), 3);
+
+        // A stream abandoned early is recorded as such
+        let stream = Arc::clone(&cube).query().unwrap().execute_stream().await.unwrap();
+        drop(stream);
+        let events = sink.events();
+        assert_eq!(events.len(), 4);
+        assert!(events[3].error.as_deref().unwrap().contains("dropped"));
     }
 
     #[tokio::test]
-    async fn test_complex_query() {
-        let cube = create_test_cube().unwrap();
-        let arc_cube = Arc::new(cube);
+    async fn test_bridge_dimension_counts_rows_once() {
+        use crate::sources::RecordBatchSource;
 
-        let result = arc_cube
+        let visits = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                Field::new("patient_id", DataType::Int64, false),
+                Field::new("cost", DataType::Float64, false),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                Arc::new(Float64Array::from(vec![100.0, 50.0, 30.0])),
+            ],
+        )
+        .unwrap();
+        // Patient 1 has two diagnoses (flu listed twice), patient 3 none
+        let diagnoses = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                Field::new("patient_id", DataType::Int64, false),
+                Field::new("diagnosis", DataType::Utf8, false),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 1, 2, 1])),
+                Arc::new(StringArray::from(vec!["flu", "asthma", "flu", "flu"])),
+            ],
+        )
+        .unwrap();
+
+        let cube = Arc::new(
+            ElastiCubeBuilder::new("visits")
+                .add_bridge_dimension(
+                    "diagnosis",
+                    "patient_id",
+                    RecordBatchSource::new(diagnoses.schema(), vec![diagnoses]).unwrap(),
+                )
+                .unwrap()
+                .load_record_batches(visits.schema(), vec![visits])
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+        assert_eq!(cube.get_bridge("diagnosis").unwrap().row_count(), 4);
+
+        let by_diagnosis = cube
+            .clone()
             .query()
             .unwrap()
-            .select(&["region", "product", "SUM(sales) as total_sales", "AVG(quantity) as avg_qty"])
-            .filter("sales > 100")
-            .group_by(&["region", "product"])
-            .order_by(&["total_sales DESC"])
-            .limit(5)
+            .select(&["diagnosis", "SUM(cost) AS cost"])
+            .group_by(&["diagnosis"])
+            .order_by(&["diagnosis"])
             .execute()
             .await
             .unwrap();
+        let batch = &by_diagnosis.batches()[0];
+        let names = batch.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+        let costs = batch.column(1).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(names.value(0), "asthma");
+        assert_eq!(costs.value(0), 100.0);
+        assert_eq!(names.value(1), "flu");
+        assert_eq!(costs.value(1), 150.0);
+        // Rows without members keep a NULL group
+        assert!(names.is_null(2));
+        assert_eq!(costs.value(2), 30.0);
 
-        assert!(result.row_count() > 0);
+        // Filtering alone keeps patient 1 once
+        let filtered = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["SUM(cost) AS cost"])
+            .filter("diagnosis IN ('flu', 'asthma') AND cost > 10")
+            .execute()
+            .await
+            .unwrap();
+        let cost = filtered.batches()[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(cost.value(0), 150.0);
+
+        // Selecting it in a grouped query without grouping by it is rejected
+        let ungrouped = cube
+            .query()
+            .unwrap()
+            .select(&["patient_id", "COUNT(diagnosis) AS n"])
+            .group_by(&["patient_id"])
+            .execute()
+            .await;
+        assert!(ungrouped.is_err());
+    }
+
+    #[test]
+    fn test_split_conjuncts() {
+        assert_eq!(
+            split_conjuncts("a = 1 AND (b = 2 and c = 3) and d BETWEEN 1 AND 5 AND e = 'x and y'"),
+            vec!["a = 1", "(b = 2 and c = 3)", "d BETWEEN 1 AND 5", "e = 'x and y'"]
+        );
+        assert_eq!(split_conjuncts("brand = 'A'"), vec!["brand = 'A'"]);
+    }
+
+    #[test]
+    fn test_mentions() {
+        assert!(mentions("tag = 'red'", "tag"));
+        assert!(mentions("\"tag\" IN ('a', 'b')", "tag"));
+        assert!(mentions("\"customer tag\" = 'x'", "customer tag"));
+        assert!(!mentions("label = 'tag'", "tag"));
+        assert!(!mentions("label = 'it''s a tag'", "tag"));
+        assert!(!mentions("tags = 1", "tag"));
     }
 }
@@ -0,0 +1,199 @@
+//! Query history for finding slow or hot queries
+//!
+//! Every cube keeps a [`QueryLog`] of its most recent queries. The log is
+//! shared by clones of the cube, so it also covers queries run through the
+//! `Arc`s handed to query builders and through a [`SharedCube`].
+//!
+//! [`SharedCube`]: crate::SharedCube
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use crate::time::{Duration, SystemTime};
+
+/// One executed query
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryRecord {
+    /// SQL that ran, after calculated fields were expanded
+    pub sql: String,
+
+    /// When the query started
+    pub started_at: SystemTime,
+
+    /// Time until the result was available
+    pub duration: Duration,
+
+    /// Rows read from the cube's data (0 when served from the cache)
+    pub rows_scanned: usize,
+
+    /// Rows in the result
+    pub rows_returned: usize,
+
+    /// Whether the result came from the query cache
+    pub cache_hit: bool,
+
+    /// Error message, if the query failed
+    pub error: Option<String>,
+}
+
+/// Bounded log of the most recent queries on a cube
+///
+/// # Example
+/// ```rust,ignore
+/// for record in cube.query_log().slowest(5) {
+///     println!("{:?} {}", record.duration, record.sql);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct QueryLog {
+    inner: Mutex<LogInner>,
+}
+
+#[derive(Debug)]
+struct LogInner {
+    capacity: usize,
+    records: VecDeque<QueryRecord>,
+}
+
+impl QueryLog {
+    /// Number of queries kept unless configured otherwise
+    pub const DEFAULT_CAPACITY: usize = 1000;
+
+    /// Create a log keeping the last `capacity` queries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(LogInner {
+                capacity,
+                records: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LogInner> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Add a query, dropping the oldest once the log is full
+    pub fn record(&self, record: QueryRecord) {
+        let mut inner = self.lock();
+        if inner.capacity == 0 {
+            return;
+        }
+        while inner.records.len() >= inner.capacity {
+            inner.records.pop_front();
+        }
+        inner.records.push_back(record);
+    }
+
+    /// The logged queries, oldest first
+    pub fn records(&self) -> Vec<QueryRecord> {
+        self.lock().records.iter().cloned().collect()
+    }
+
+    /// The `n` slowest logged queries, slowest first
+    pub fn slowest(&self, n: usize) -> Vec<QueryRecord> {
+        let mut records = self.records();
+        records.sort_by(|a, b| b.duration.cmp(&a.duration));
+        records.truncate(n);
+        records
+    }
+
+    /// The `n` most frequently run queries with their run counts
+    ///
+    /// Ties are broken by the query that ran first.
+    pub fn most_frequent(&self, n: usize) -> Vec<(String, usize)> {
+        let inner = self.lock();
+        let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+        for (position, record) in inner.records.iter().enumerate() {
+            counts.entry(record.sql.as_str()).or_insert((0, position)).0 += 1;
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|(_, (a, a_first)), (_, (b, b_first))| {
+            b.cmp(a).then(a_first.cmp(b_first))
+        });
+        counts
+            .into_iter()
+            .take(n)
+            .map(|(sql, (count, _))| (sql.to_string(), count))
+            .collect()
+    }
+
+    /// Number of queries kept
+    pub fn capacity(&self) -> usize {
+        self.lock().capacity
+    }
+
+    /// Change the number of queries kept, dropping the oldest if needed
+    ///
+    /// A capacity of 0 turns logging off.
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut inner = self.lock();
+        inner.capacity = capacity;
+        while inner.records.len() > capacity {
+            inner.records.pop_front();
+        }
+    }
+
+    /// Number of logged queries
+    pub fn len(&self) -> usize {
+        self.lock().records.len()
+    }
+
+    /// Check if no queries are logged
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget all logged queries
+    pub fn clear(&self) {
+        self.lock().records.clear();
+    }
+}
+
+impl Default for QueryLog {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(sql: &str, millis: u64) -> QueryRecord {
+        QueryRecord {
+            sql: sql.to_string(),
+            started_at: SystemTime::now(),
+            duration: Duration::from_millis(millis),
+            rows_scanned: 10,
+            rows_returned: 1,
+            cache_hit: false,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_query_log_bounded() {
+        let log = QueryLog::new(3);
+        for (sql, millis) in [("x", 90), ("a", 5), ("b", 50), ("a", 20)] {
+            log.record(record(sql, millis));
+        }
+
+        // The oldest record was dropped
+        let sqls: Vec<String> = log.records().into_iter().map(|r| r.sql).collect();
+        assert_eq!(sqls, vec!["a", "b", "a"]);
+
+        let slowest = log.slowest(2);
+        assert_eq!(slowest[0].sql, "b");
+        assert_eq!(slowest[1].duration, Duration::from_millis(20));
+        assert_eq!(
+            log.most_frequent(2),
+            vec![("a".to_string(), 2), ("b".to_string(), 1)]
+        );
+
+        log.set_capacity(1);
+        assert_eq!(log.len(), 1);
+        log.set_capacity(0);
+        log.record(record("d", 1));
+        assert!(log.is_empty());
+    }
+}
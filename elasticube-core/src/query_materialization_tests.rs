@@ -125,6 +125,142 @@ mod tests {
         assert!(result.row_count() > 0, "Should have results");
     }
 
+    #[tokio::test]
+    async fn test_date_dimension() {
+        use crate::DateParts;
+        use arrow::array::{BooleanArray, Date32Array};
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("sale_date", DataType::Date32, false),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        // Sat 2022-01-08, Mon 2022-04-18, Sun 2023-01-08, Tue 2023-05-23
+        let dates = Arc::new(Date32Array::from(vec![19000, 19100, 19365, 19500]));
+        let amounts = Arc::new(Float64Array::from(vec![100.0, 150.0, 200.0, 250.0]));
+        let batch = RecordBatch::try_new(schema, vec![dates, amounts]).unwrap();
+
+        let cube = Arc::new(
+            ElastiCubeBuilder::new("sales")
+                .add_dimension("sale_date", DataType::Date32)
+                .unwrap()
+                .add_date_dimension("sale_date", DateParts::all())
+                .unwrap()
+                .add_measure("amount", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .with_data(vec![batch])
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        let hierarchy = cube.schema().get_hierarchy("sale_date_calendar").unwrap();
+        assert_eq!(
+            hierarchy.levels(),
+            ["sale_date_year", "sale_date_quarter", "sale_date_month", "sale_date"]
+        );
+
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["sale_date_is_weekend AS weekend", "SUM(amount) AS total"])
+            .group_by(&["sale_date_is_weekend"])
+            .order_by(&["weekend"])
+            .execute()
+            .await
+            .unwrap();
+        let batch = &result.batches()[0];
+        let weekend = batch.column(0).as_any().downcast_ref::<BooleanArray>().unwrap();
+        let total = batch.column(1).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert!(!weekend.value(0));
+        assert_eq!(total.value(0), 400.0);
+        assert_eq!(total.value(1), 300.0);
+
+        let quarters = cube
+            .query()
+            .unwrap()
+            .select(&["sale_date_year AS y", "sale_date_quarter AS q", "SUM(amount) AS total"])
+            .group_by(&["sale_date_year", "sale_date_quarter"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(quarters.row_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_date_dimension_in_timezone() {
+        use crate::DateParts;
+        use arrow::array::TimestampSecondArray;
+        use arrow::datatypes::TimeUnit;
+
+        // 2024-03-01 03:30 UTC, which is Thu 2024-02-29 22:30 in New York
+        let utc = DataType::Timestamp(TimeUnit::Second, Some("UTC".into()));
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("ordered_at", utc, false),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(TimestampSecondArray::from(vec![1_709_263_800]).with_timezone("UTC")),
+                Arc::new(Float64Array::from(vec![10.0])),
+            ],
+        )
+        .unwrap();
+
+        let mut cube = ElastiCubeBuilder::new("orders")
+            .with_timezone("America/New_York")
+            .unwrap()
+            .add_date_dimension("ordered_at", DateParts::none().with_month().with_day_of_week())
+            .unwrap()
+            .with_data(vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+        let local = DataType::Timestamp(TimeUnit::Second, Some("America/New_York".into()));
+        assert_eq!(cube.arrow_schema().field(0).data_type(), &local);
+        assert_eq!(cube.schema().get_dimension("ordered_at").unwrap().data_type(), &local);
+
+        // Naive timestamps are local wall time: Fri 2024-03-01 23:30
+        let naive = Arc::new(Schema::new(vec![
+            Field::new("ordered_at", DataType::Timestamp(TimeUnit::Second, None), false),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        let appended = RecordBatch::try_new(
+            naive,
+            vec![
+                Arc::new(TimestampSecondArray::from(vec![1_709_335_800])),
+                Arc::new(Float64Array::from(vec![20.0])),
+            ],
+        )
+        .unwrap();
+        cube.append_rows(appended).unwrap();
+
+        let cube = Arc::new(cube);
+        for (filter, expected) in [
+            ("ordered_at_month = 2 AND ordered_at_day_of_week = 4", 10.0),
+            ("ordered_at_month = 3 AND ordered_at_day_of_week = 5", 20.0),
+        ] {
+            let result = cube
+                .clone()
+                .query()
+                .unwrap()
+                .select(&["SUM(amount) AS total"])
+                .filter(filter)
+                .execute()
+                .await
+                .unwrap();
+            let total = result.batches()[0]
+                .column(0)
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap();
+            assert_eq!(total.value(0), expected, "{}", filter);
+        }
+
+        assert!(ElastiCubeBuilder::new("orders").with_timezone("Mars/Olympus").is_err());
+    }
+
     #[tokio::test]
     async fn test_calculated_measure_in_filter() {
         let batch = create_test_data();
@@ -262,4 +398,292 @@ mod tests {
 
         assert_eq!(result.row_count(), 4, "Should have all regions");
     }
+
+    #[tokio::test]
+    async fn test_filtered_measure_in_select() {
+        let batch = create_test_data();
+        let cube = Arc::new(
+            ElastiCubeBuilder::new("sales")
+                .add_dimension("region", DataType::Utf8)
+                .unwrap()
+                .add_measure("revenue", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .add_measure("quantity", DataType::Int32, AggFunc::Sum)
+                .unwrap()
+                .add_filtered_measure(
+                    "big_order_revenue",
+                    "revenue",
+                    AggFunc::Sum,
+                    "quantity >= 15",
+                )
+                .unwrap()
+                .add_filtered_measure("big_orders", "revenue", AggFunc::Count, "quantity >= 15")
+                .unwrap()
+                .with_data(vec![batch])
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        let result = cube
+            .query()
+            .unwrap()
+            .select(&["big_order_revenue", "big_orders"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 1);
+
+        let batch = &result.batches()[0];
+        let revenue = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        // South (1500) and West (1800)
+        assert_eq!(revenue.value(0), 3300.0);
+    }
+
+    #[tokio::test]
+    async fn test_distinct_count_measure() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("customer_id", DataType::Int32, false),
+            Field::new("revenue", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["North", "North", "South", "South"])),
+                Arc::new(Int32Array::from(vec![1, 1, 1, 2])),
+                Arc::new(Float64Array::from(vec![100.0, 50.0, 80.0, 20.0])),
+            ],
+        )
+        .unwrap();
+
+        let cube = Arc::new(
+            ElastiCubeBuilder::new("sales")
+                .add_dimension("region", DataType::Utf8)
+                .unwrap()
+                .add_dimension("customer_id", DataType::Int32)
+                .unwrap()
+                .add_measure("revenue", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .add_distinct_count_measure("customers", "customer_id")
+                .unwrap()
+                .with_data(vec![batch])
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        let count = |result: &crate::QueryResult, row: usize| {
+            result.batches()[0]
+                .column(result.batches()[0].num_columns() - 1)
+                .as_any()
+                .downcast_ref::<arrow::array::Int64Array>()
+                .unwrap()
+                .value(row)
+        };
+
+        let per_region = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["region", "customers"])
+            .group_by(&["region"])
+            .order_by(&["region"])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(count(&per_region, 0), 1);
+        assert_eq!(count(&per_region, 1), 2);
+
+        // Customer 1 buys in both regions but is counted once overall
+        let total = cube.clone().query().unwrap().select(&["customers"]).execute().await.unwrap();
+        assert_eq!(count(&total, 0), 2);
+
+        let filtered = cube
+            .query()
+            .unwrap()
+            .select(&["customers"])
+            .filter("revenue > 60")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(count(&filtered, 0), 1);
+    }
+
+    #[tokio::test]
+    async fn test_distinct_count_measure_mixed_case_key() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("CustomerID", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["North", "North", "South"])),
+                Arc::new(Int32Array::from(vec![1, 2, 2])),
+            ],
+        )
+        .unwrap();
+
+        let cube = ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_dimension("CustomerID", DataType::Int32)
+            .unwrap()
+            .add_distinct_count_measure("customers", "CustomerID")
+            .unwrap()
+            .with_data(vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let result =
+            Arc::new(cube).query().unwrap().select(&["customers"]).execute().await.unwrap();
+        let customers = result.batches()[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<arrow::array::Int64Array>()
+            .unwrap();
+        assert_eq!(customers.value(0), 2);
+    }
+
+    #[test]
+    fn test_distinct_count_measure_requires_known_column() {
+        let result = ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_distinct_count_measure("customers", "customer_id");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_filtered_measure_requires_known_measure() {
+        let result = ElastiCubeBuilder::new("sales")
+            .add_measure("revenue", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .add_filtered_measure("x", "missing", AggFunc::Sum, "revenue > 0");
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_udf_in_calculated_measure() {
+        use datafusion::common::cast::as_float64_array;
+        use datafusion::logical_expr::ColumnarValue;
+
+        let batch = create_test_data();
+        let mut cube = ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_measure("revenue", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .add_calculated_measure(
+                "revenue_with_vat",
+                "with_vat(revenue)",
+                DataType::Float64,
+                AggFunc::Sum,
+            )
+            .unwrap()
+            .with_data(vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        cube.register_udf("with_vat", vec![DataType::Float64], DataType::Float64, |args| {
+            let arrays = ColumnarValue::values_to_arrays(args)?;
+            let revenue = as_float64_array(&arrays[0])?;
+            let result: Float64Array = revenue.iter().map(|r| r.map(|r| r * 1.2)).collect();
+            Ok(ColumnarValue::Array(Arc::new(result)))
+        })
+        .unwrap();
+        assert_eq!(cube.udfs().len(), 1);
+
+        let result = Arc::new(cube)
+            .query()
+            .unwrap()
+            .select(&["SUM(revenue_with_vat) AS total"])
+            .execute()
+            .await
+            .unwrap();
+
+        let total = result.batches()[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap()
+            .value(0);
+        assert!((total - 5500.0 * 1.2).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_calculated_measure() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("revenue", DataType::Float64, false),
+            Field::new("cost", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["North", "North", "South"])),
+                Arc::new(Float64Array::from(vec![100.0, 900.0, 400.0])),
+                Arc::new(Float64Array::from(vec![50.0, 100.0, 300.0])),
+            ],
+        )
+        .unwrap();
+
+        let cube = Arc::new(
+            ElastiCubeBuilder::new("sales")
+                .add_dimension("region", DataType::Utf8)
+                .unwrap()
+                .add_measure("revenue", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .add_measure("cost", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .add_calculated_measure("profit", "revenue - cost", DataType::Float64, AggFunc::Sum)
+                .unwrap()
+                .add_aggregate_measure("margin", "SUM(profit) / SUM(revenue)", DataType::Float64)
+                .unwrap()
+                .with_data(vec![batch])
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        let result = cube
+            .clone()
+            .query()
+            .unwrap()
+            .select(&["region", "margin"])
+            .group_by(&["region"])
+            .order_by(&["region"])
+            .execute()
+            .await
+            .unwrap();
+
+        let batch = &result.batches()[0];
+        assert_eq!(batch.schema().field(1).name(), "margin");
+        let margin = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        // North: 850 / 1000, not the mean of the row margins (0.5 and 0.89)
+        assert!((margin.value(0) - 0.85).abs() < 1e-9);
+        assert!((margin.value(1) - 0.25).abs() < 1e-9);
+
+        // group_by_all leaves the aggregate measure out of the grouping
+        let result = cube
+            .query()
+            .unwrap()
+            .select(&["region", "margin"])
+            .group_by_all()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.row_count(), 2);
+    }
 }
@@ -0,0 +1,214 @@
+//! Markdown and HTML rendering of query results
+//!
+//! Complements [`QueryResult::pretty_print`] with formats that paste into
+//! reports, chat messages and notebooks. Long results and wide cells can be
+//! truncated through [`RenderOptions`].
+
+use crate::error::Result;
+use crate::query::QueryResult;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+
+/// Truncation settings for [`QueryResult::to_markdown_with`] and
+/// [`QueryResult::to_html_with`]
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    /// Maximum number of rows to render (None = all rows)
+    pub max_rows: Option<usize>,
+
+    /// Maximum characters per cell; longer values end in `…` (None = no limit)
+    pub max_width: Option<usize>,
+}
+
+impl RenderOptions {
+    /// Render at most `max_rows` rows
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = Some(max_rows);
+        self
+    }
+
+    /// Cut cells longer than `max_width` characters
+    pub fn with_max_width(mut self, max_width: usize) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+}
+
+/// Header and formatted cells of a result, plus how many rows were left out
+struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+    omitted: usize,
+}
+
+impl Table {
+    fn from_result(result: &QueryResult, options: &RenderOptions) -> Result<Self> {
+        let truncate = |value: String| match options.max_width {
+            Some(width) if value.chars().count() > width => {
+                let mut cut: String = value.chars().take(width.saturating_sub(1)).collect();
+                cut.push('…');
+                cut
+            }
+            _ => value,
+        };
+
+        let batches = result.formatted_batches()?;
+        let header = batches
+            .first()
+            .map(|batch| {
+                batch
+                    .schema()
+                    .fields()
+                    .iter()
+                    .map(|f| truncate(f.name().clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let limit = options.max_rows.unwrap_or(usize::MAX);
+        let format_options = FormatOptions::default();
+        let mut rows = Vec::new();
+        for batch in &batches {
+            if rows.len() >= limit {
+                break;
+            }
+            let formatters = batch
+                .columns()
+                .iter()
+                .map(|column| ArrayFormatter::try_new(column.as_ref(), &format_options))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
+            for row in 0..batch.num_rows().min(limit - rows.len()) {
+                rows.push(
+                    formatters
+                        .iter()
+                        .map(|f| truncate(f.value(row).to_string()))
+                        .collect(),
+                );
+            }
+        }
+
+        Ok(Self {
+            header,
+            omitted: result.row_count() - rows.len(),
+            rows,
+        })
+    }
+}
+
+impl QueryResult {
+    /// Render the results as a GitHub-flavored Markdown table
+    pub fn to_markdown(&self) -> Result<String> {
+        self.to_markdown_with(&RenderOptions::default())
+    }
+
+    /// Render the results as a Markdown table with truncation options
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let text = result.to_markdown_with(&RenderOptions::default().with_max_rows(20))?;
+    /// ```
+    pub fn to_markdown_with(&self, options: &RenderOptions) -> Result<String> {
+        let table = Table::from_result(self, options)?;
+        let escape = |cell: &str| cell.replace('|', "\\|").replace('\n', " ");
+        let line = |cells: &[String]| {
+            let cells: Vec<String> = cells.iter().map(|c| escape(c)).collect();
+            format!("| {} |\n", cells.join(" | "))
+        };
+
+        let mut out = line(&table.header);
+        out.push_str(&format!("|{}\n", "---|".repeat(table.header.len())));
+        for row in &table.rows {
+            out.push_str(&line(row));
+        }
+        if table.omitted > 0 {
+            out.push_str(&format!("\n… {} more rows\n", table.omitted));
+        }
+        Ok(out)
+    }
+
+    /// Render the results as an HTML `<table>`
+    pub fn to_html(&self) -> Result<String> {
+        self.to_html_with(&RenderOptions::default())
+    }
+
+    /// Render the results as an HTML `<table>` with truncation options
+    pub fn to_html_with(&self, options: &RenderOptions) -> Result<String> {
+        let table = Table::from_result(self, options)?;
+        let escape = |cell: &str| {
+            cell.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        };
+
+        let mut out = String::from("<table>\n<thead>\n<tr>");
+        for name in &table.header {
+            out.push_str(&format!("<th>{}</th>", escape(name)));
+        }
+        out.push_str("</tr>\n</thead>\n<tbody>\n");
+        for row in &table.rows {
+            out.push_str("<tr>");
+            for cell in row {
+                out.push_str(&format!("<td>{}</td>", escape(cell)));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</tbody>\n</table>\n");
+        if table.omitted > 0 {
+            out.push_str(&format!("<p>… {} more rows</p>\n", table.omitted));
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+    use arrow::record_batch::RecordBatch;
+    use std::sync::Arc;
+
+    fn sample_result() -> QueryResult {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("total", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![
+                    Some("North | West"),
+                    Some("<South>"),
+                    None,
+                ])),
+                Arc::new(Float64Array::from(vec![1.5, 2.0, 3.0])),
+            ],
+        )
+        .unwrap();
+        QueryResult::from_batches(vec![batch])
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let markdown = sample_result().to_markdown().unwrap();
+        assert_eq!(
+            markdown,
+            "| name | total |\n|---|---|\n| North \\| West | 1.5 |\n| <South> | 2.0 |\n|  | 3.0 |\n"
+        );
+
+        let truncated = sample_result()
+            .to_markdown_with(&RenderOptions::default().with_max_rows(1).with_max_width(4))
+            .unwrap();
+        assert!(truncated.contains("| Nor… | 1.5 |"));
+        assert!(truncated.ends_with("… 2 more rows\n"));
+    }
+
+    #[test]
+    fn test_to_html() {
+        let html = sample_result().to_html().unwrap();
+        assert!(html.contains("<th>name</th><th>total</th>"));
+        assert!(html.contains("<td>&lt;South&gt;</td>"));
+        assert_eq!(html.matches("<tr>").count(), 4);
+    }
+}
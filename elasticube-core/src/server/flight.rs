@@ -0,0 +1,412 @@
+//! Arrow Flight SQL front-end
+//!
+//! Serves the cubes of a [`CubeCatalog`] to Flight SQL clients such as the
+//! Arrow Flight SQL JDBC/ODBC drivers, ADBC and `pyarrow.flight`. Results
+//! are streamed as Arrow record batches, so clients receive the same
+//! columnar data the query produced without a row-format conversion.
+//!
+//! Statement queries and the catalog metadata commands (catalogs, schemas,
+//! tables and SQL info) are supported. The server is read-only: updates,
+//! prepared statements and transactions are rejected.
+
+use super::{error_status, CubeCatalog, PRINCIPAL_HEADER};
+use crate::error::{Error, Result};
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::sql::metadata::{SqlInfoData, SqlInfoDataBuilder};
+use arrow_flight::sql::server::FlightSqlService;
+use arrow_flight::sql::{
+    CommandGetCatalogs, CommandGetDbSchemas, CommandGetSqlInfo, CommandGetTables,
+    CommandStatementQuery, ProstMessageExt, SqlInfo, TicketStatementQuery,
+};
+use arrow_flight::{FlightDescriptor, FlightEndpoint, FlightInfo, Ticket};
+use futures::{Stream, StreamExt, TryStreamExt};
+use prost::Message;
+use std::net::SocketAddr;
+use tonic::{Request, Response, Status};
+
+/// Catalog name cubes are listed under
+///
+/// Matches the default catalog of the query session, so fully qualified
+/// names reported to clients can be used in SQL.
+pub const CATALOG_NAME: &str = "datafusion";
+
+/// Schema name cubes are listed under
+pub const SCHEMA_NAME: &str = "public";
+
+/// Table type reported for cubes
+const TABLE_TYPE: &str = "TABLE";
+
+type DoGetStream = <FlightSqlServer as FlightService>::DoGetStream;
+
+/// Flight SQL service answering queries against a [`CubeCatalog`]
+///
+/// # Example
+/// ```rust,ignore
+/// let catalog = CubeCatalog::new();
+/// catalog.register("sales", sales_cube)?;
+///
+/// FlightSqlServer::new(catalog)
+///     .serve("127.0.0.1:50051".parse()?)
+///     .await?;
+/// ```
+///
+/// Clients then connect with e.g.
+/// `jdbc:arrow-flight-sql://localhost:50051?useEncryption=false` and query
+/// `SELECT region, SUM(amount) FROM sales GROUP BY region`.
+#[derive(Debug, Clone)]
+pub struct FlightSqlServer {
+    catalog: CubeCatalog,
+}
+
+impl FlightSqlServer {
+    /// Create a service for the cubes of `catalog`
+    ///
+    /// Cubes registered with the catalog later are served as well.
+    pub fn new(catalog: CubeCatalog) -> Self {
+        Self { catalog }
+    }
+
+    /// The catalog being served
+    pub fn catalog(&self) -> &CubeCatalog {
+        &self.catalog
+    }
+
+    /// Wrap the service for use with a custom `tonic` server, e.g. to add TLS
+    /// or serve it next to other services
+    pub fn into_service(self) -> FlightServiceServer<Self> {
+        FlightServiceServer::new(self)
+    }
+
+    /// Listen on `addr` and serve Flight SQL requests until the server fails
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        tonic::transport::Server::builder()
+            .add_service(self.into_service())
+            .serve(addr)
+            .await
+            .map_err(|e| Error::io(format!("Flight SQL server on {} failed: {}", addr, e)))
+    }
+
+    fn sql_info() -> std::result::Result<SqlInfoData, Status> {
+        let mut builder = SqlInfoDataBuilder::new();
+        builder.append(SqlInfo::FlightSqlServerName, "ElastiCube");
+        builder.append(SqlInfo::FlightSqlServerVersion, env!("CARGO_PKG_VERSION"));
+        builder.append(SqlInfo::FlightSqlServerReadOnly, true);
+        builder.build().map_err(arrow_status)
+    }
+}
+
+/// Describe a result that is fetched by sending `command` back as the ticket
+fn flight_info(
+    command: impl ProstMessageExt,
+    schema: &SchemaRef,
+    descriptor: FlightDescriptor,
+) -> std::result::Result<Response<FlightInfo>, Status> {
+    let ticket = Ticket::new(command.as_any().encode_to_vec());
+    let info = FlightInfo::new()
+        .try_with_schema(schema)
+        .map_err(arrow_status)?
+        .with_endpoint(FlightEndpoint::new().with_ticket(ticket))
+        .with_descriptor(descriptor);
+    Ok(Response::new(info))
+}
+
+/// Encode record batches as a `DoGet` response
+fn batch_response(
+    schema: SchemaRef,
+    batches: impl Stream<Item = Result<RecordBatch>> + Send + 'static,
+) -> Response<DoGetStream> {
+    let batches = batches.map_err(|e| FlightError::ExternalError(Box::new(e)));
+    let stream = FlightDataEncoderBuilder::new()
+        .with_schema(schema)
+        .build(batches)
+        .map_err(Status::from);
+    Response::new(stream.boxed())
+}
+
+/// Encode a single metadata batch as a `DoGet` response
+fn metadata_response(
+    schema: SchemaRef,
+    batch: std::result::Result<RecordBatch, ArrowError>,
+) -> std::result::Result<Response<DoGetStream>, Status> {
+    let batch = batch.map_err(arrow_status)?;
+    Ok(batch_response(
+        schema,
+        futures::stream::once(async move { Ok(batch) }),
+    ))
+}
+
+fn arrow_status(error: ArrowError) -> Status {
+    Status::internal(error.to_string())
+}
+
+#[tonic::async_trait]
+impl FlightSqlService for FlightSqlServer {
+    type FlightService = FlightSqlServer;
+
+    async fn get_flight_info_statement(
+        &self,
+        query: CommandStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let schema = self
+            .catalog
+            .context()
+            .map_err(error_status)?
+            .query()
+            .sql(query.query.clone())
+            .schema()
+            .await
+            .map_err(error_status)?;
+
+        // The ticket carries the SQL, so any server sharing the catalog can
+        // answer the DoGet
+        let ticket = TicketStatementQuery {
+            statement_handle: query.query.into_bytes().into(),
+        };
+        flight_info(ticket, &schema, request.into_inner())
+    }
+
+    async fn do_get_statement(
+        &self,
+        ticket: TicketStatementQuery,
+        request: Request<Ticket>,
+    ) -> std::result::Result<Response<DoGetStream>, Status> {
+        let sql = String::from_utf8(ticket.statement_handle.to_vec())
+            .map_err(|_| Status::invalid_argument("Statement handle is not UTF-8 SQL"))?;
+        let mut query = self.catalog.context().map_err(error_status)?.query().sql(sql);
+        let principal = request.metadata().get(PRINCIPAL_HEADER);
+        if let Some(principal) = principal.and_then(|value| value.to_str().ok()) {
+            query = query.as_principal(principal);
+        }
+        let stream = query
+            .execute_stream()
+            .await
+            .map_err(error_status)?;
+        Ok(batch_response(stream.schema(), stream))
+    }
+
+    async fn get_flight_info_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn do_get_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<DoGetStream>, Status> {
+        let mut builder = query.into_builder();
+        builder.append(CATALOG_NAME);
+        let schema = builder.schema();
+        metadata_response(schema, builder.build())
+    }
+
+    async fn get_flight_info_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn do_get_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<DoGetStream>, Status> {
+        let mut builder = query.into_builder();
+        builder.append(CATALOG_NAME, SCHEMA_NAME);
+        let schema = builder.schema();
+        metadata_response(schema, builder.build())
+    }
+
+    async fn get_flight_info_tables(
+        &self,
+        query: CommandGetTables,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn do_get_tables(
+        &self,
+        query: CommandGetTables,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<DoGetStream>, Status> {
+        // The builder applies the request's name patterns and table types
+        let mut builder = query.into_builder();
+        for name in self.catalog.names() {
+            let Some(cube) = self.catalog.snapshot(&name) else {
+                continue;
+            };
+            builder
+                .append(
+                    CATALOG_NAME,
+                    SCHEMA_NAME,
+                    &name,
+                    TABLE_TYPE,
+                    cube.arrow_schema(),
+                )
+                .map_err(arrow_status)?;
+        }
+        let schema = builder.schema();
+        metadata_response(schema, builder.build())
+    }
+
+    async fn get_flight_info_sql_info(
+        &self,
+        query: CommandGetSqlInfo,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let info = Self::sql_info()?;
+        let schema = query.clone().into_builder(&info).schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn do_get_sql_info(
+        &self,
+        query: CommandGetSqlInfo,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<DoGetStream>, Status> {
+        let info = Self::sql_info()?;
+        let builder = query.into_builder(&info);
+        let schema = builder.schema();
+        metadata_response(schema, builder.build())
+    }
+
+    async fn register_sql_info(&self, _id: i32, _result: &SqlInfo) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ElastiCubeBuilder;
+    use crate::cube::AggFunc;
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+    use arrow_flight::decode::FlightRecordBatchStream;
+    use std::sync::Arc;
+
+    fn catalog() -> CubeCatalog {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["North", "South", "North"])),
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0])),
+            ],
+        )
+        .unwrap();
+        let cube = ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_measure("amount", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .load_record_batches(schema, vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let catalog = CubeCatalog::new();
+        catalog.register("sales", cube).unwrap();
+        catalog
+    }
+
+    async fn collect(response: Response<DoGetStream>) -> Vec<RecordBatch> {
+        let stream = response.into_inner().map_err(FlightError::from);
+        FlightRecordBatchStream::new_from_flight_data(stream)
+            .try_collect()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_flight_sql_statement() {
+        let server = FlightSqlServer::new(catalog());
+        let sql = "SELECT region, SUM(amount) AS total FROM sales GROUP BY region ORDER BY region";
+
+        let info = server
+            .get_flight_info_statement(
+                CommandStatementQuery {
+                    query: sql.to_string(),
+                    transaction_id: None,
+                },
+                Request::new(FlightDescriptor::new_cmd(sql.to_string())),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+        let schema = info.clone().try_decode_schema().unwrap();
+        assert_eq!(schema.field(1).name(), "total");
+
+        // The endpoint's ticket round-trips the statement
+        let ticket = info.endpoint[0].ticket.clone().unwrap();
+        let any = arrow_flight::sql::Any::decode(ticket.ticket.clone()).unwrap();
+        let statement: TicketStatementQuery = any.unpack().unwrap().unwrap();
+
+        let batches = collect(
+            server
+                .do_get_statement(statement, Request::new(ticket))
+                .await
+                .unwrap(),
+        )
+        .await;
+        let totals = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(totals.values(), &[4.0, 2.0]);
+
+        let bad = server
+            .get_flight_info_statement(
+                CommandStatementQuery {
+                    query: "SELECT * FROM missing".to_string(),
+                    transaction_id: None,
+                },
+                Request::new(FlightDescriptor::new_cmd("")),
+            )
+            .await;
+        assert!(bad.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_flight_sql_tables() {
+        let server = FlightSqlServer::new(catalog());
+        let query = CommandGetTables {
+            catalog: None,
+            db_schema_filter_pattern: None,
+            table_name_filter_pattern: Some("sal%".to_string()),
+            table_types: vec![],
+            include_schema: false,
+        };
+        let response = server
+            .do_get_tables(query, Request::new(Ticket::new("")))
+            .await
+            .unwrap();
+        let batches = collect(response).await;
+        let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 1);
+        let names = batches[0]
+            .column_by_name("table_name")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(names.value(0), "sales");
+    }
+}
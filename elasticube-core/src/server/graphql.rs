@@ -0,0 +1,501 @@
+//! GraphQL schema generated from cube schemas
+//!
+//! Every cube of a [`CubeCatalog`] becomes a field of the `Query` type that
+//! returns a list of rows. Dimensions (including virtual ones) and measures
+//! (including calculated ones) are the fields of the row type:
+//!
+//! ```graphql
+//! {
+//!   sales(filter: {region: ["North", "South"]}, orderBy: [revenue_DESC], limit: 10) {
+//!     region
+//!     revenue
+//!   }
+//! }
+//! ```
+//!
+//! Measures are aggregated with their default aggregation, grouped by the
+//! selected dimensions plus any listed in `groupBy`. A query selecting only
+//! measures returns one row of totals. `filter` takes a list of allowed
+//! values per dimension.
+//!
+//! The HTTP server serves the schema at `/graphql` when the `graphql`
+//! feature is enabled; [`schema`] builds it for use with other servers.
+
+use super::CubeCatalog;
+use crate::cube::{AggFunc, CubeSchema};
+use crate::error::{Error, Result};
+use crate::query::{quote_ident, QueryResult};
+use arrow::datatypes::{DataType, Field as ArrowField, Schema as ArrowSchema};
+use arrow::record_batch::RecordBatch;
+use async_graphql::dynamic::{
+    Enum, Field, FieldFuture, FieldValue, InputObject, InputValue, Object, ResolverContext, Schema,
+    TypeRef,
+};
+use async_graphql::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Build a GraphQL schema over the current cubes of `catalog`
+///
+/// The schema reflects the cubes and cube schemas at the time of the call;
+/// queries always read the latest data.
+///
+/// # Example
+/// ```rust,ignore
+/// let schema = graphql::schema(&catalog)?;
+/// let response = schema
+///     .execute("{ sales(groupBy: [region]) { region revenue } }")
+///     .await;
+/// ```
+pub fn schema(catalog: &CubeCatalog) -> Result<Schema> {
+    let mut query = Object::new("Query");
+    let mut builder = Schema::build("Query", None, None);
+    let mut type_names = HashSet::new();
+
+    for name in catalog.names() {
+        let Some(cube) = catalog.snapshot(&name) else {
+            continue;
+        };
+        let fields = CubeFields::new(&name, cube.schema(), cube.arrow_schema());
+        if fields.columns.is_empty() || !type_names.insert(fields.type_name.clone()) {
+            continue;
+        }
+
+        let mut row = Object::new(&fields.type_name);
+        let mut filter = InputObject::new(format!("{}Filter", fields.type_name));
+        let mut dimensions = Enum::new(format!("{}Dimension", fields.type_name));
+        let mut order = Enum::new(format!("{}Order", fields.type_name));
+        if let Some(description) = cube.schema().description() {
+            row = row.description(description);
+        }
+
+        for column in &fields.columns {
+            let field_name = column.name.clone();
+            row = row.field(Field::new(
+                &column.name,
+                TypeRef::named(column.scalar),
+                move |ctx| {
+                    let name = field_name.clone();
+                    FieldFuture::new(async move {
+                        let value = match ctx.parent_value.as_value() {
+                            Some(Value::Object(row)) => row.get(name.as_str()).cloned(),
+                            _ => None,
+                        };
+                        Ok(value.map(FieldValue::value))
+                    })
+                },
+            ));
+            order = order
+                .item(format!("{}_ASC", column.name))
+                .item(format!("{}_DESC", column.name));
+            if column.dimension {
+                filter = filter.field(InputValue::new(
+                    &column.name,
+                    TypeRef::named_nn_list(column.scalar),
+                ));
+                dimensions = dimensions.item(&column.name);
+            }
+        }
+
+        let has_dimensions = fields.columns.iter().any(|c| c.dimension);
+        let mut field = Field::new(
+            &fields.field_name,
+            TypeRef::named_nn_list_nn(&fields.type_name),
+            {
+                let catalog = catalog.clone();
+                let fields = Arc::new(fields.clone());
+                move |ctx| {
+                    let catalog = catalog.clone();
+                    let fields = fields.clone();
+                    FieldFuture::new(async move { resolve(&catalog, &fields, ctx).await })
+                }
+            },
+        )
+        .argument(InputValue::new(
+            "orderBy",
+            TypeRef::named_nn_list(order.type_name()),
+        ))
+        .argument(InputValue::new("limit", TypeRef::named(TypeRef::INT)))
+        .argument(InputValue::new("offset", TypeRef::named(TypeRef::INT)));
+        if let Some(description) = cube.schema().description() {
+            field = field.description(description);
+        }
+        builder = builder.register(row).register(order);
+
+        // GraphQL rejects empty input objects and enums
+        if has_dimensions {
+            field = field
+                .argument(InputValue::new("filter", TypeRef::named(filter.type_name())))
+                .argument(InputValue::new(
+                    "groupBy",
+                    TypeRef::named_nn_list(dimensions.type_name()),
+                ));
+            builder = builder.register(filter).register(dimensions);
+        }
+        query = query.field(field);
+    }
+
+    if type_names.is_empty() {
+        return Err(Error::config("No cubes to build a GraphQL schema from"));
+    }
+    builder
+        .register(query)
+        .finish()
+        .map_err(|e| Error::config(format!("Failed to build GraphQL schema: {}", e)))
+}
+
+/// The GraphQL view of one cube
+#[derive(Debug, Clone)]
+struct CubeFields {
+    /// Catalog name of the cube
+    cube: String,
+    field_name: String,
+    type_name: String,
+    columns: Vec<GraphQLColumn>,
+}
+
+/// A dimension or measure exposed as a row field
+#[derive(Debug, Clone)]
+struct GraphQLColumn {
+    name: String,
+
+    /// SQL selecting the column; measures are aggregated
+    expr: String,
+    scalar: &'static str,
+    dimension: bool,
+}
+
+impl CubeFields {
+    fn new(cube: &str, schema: &CubeSchema, arrow_schema: &ArrowSchema) -> Self {
+        let mut columns = Vec::new();
+        let mut names = HashSet::new();
+        let mut push = |name: &str, expr: String, scalar: Option<&'static str>, dimension| {
+            let name = graphql_name(name);
+            if let Some(scalar) = scalar {
+                if names.insert(name.clone()) {
+                    columns.push(GraphQLColumn {
+                        name,
+                        expr,
+                        scalar,
+                        dimension,
+                    });
+                }
+            }
+        };
+
+        // Calculated fields are expanded by name, so they must not be quoted
+        for dimension in schema.dimensions() {
+            if arrow_schema.field_with_name(dimension.name()).is_ok() {
+                let scalar = scalar_type(dimension.data_type());
+                push(dimension.name(), quote_ident(dimension.name()), scalar, true);
+            }
+        }
+        for dimension in schema.virtual_dimensions() {
+            let scalar = scalar_type(dimension.data_type());
+            push(dimension.name(), dimension.name().to_string(), scalar, true);
+        }
+        for measure in schema.measures() {
+            if arrow_schema.field_with_name(measure.name()).is_ok() {
+                let agg = measure.default_agg();
+                let scalar = aggregate_type(&agg, measure.data_type());
+                push(measure.name(), agg.to_sql(&quote_ident(measure.name())), scalar, false);
+            }
+        }
+        for measure in schema.calculated_measures() {
+            if measure.is_aggregate() {
+                let scalar = scalar_type(measure.data_type());
+                push(measure.name(), measure.name().to_string(), scalar, false);
+                continue;
+            }
+            let agg = measure.default_agg();
+            let scalar = aggregate_type(&agg, measure.data_type());
+            push(measure.name(), agg.to_sql(measure.name()), scalar, false);
+        }
+
+        let field_name = graphql_name(cube);
+        let mut type_name = field_name.trim_start_matches('_').to_string();
+        if let Some(first) = type_name.get_mut(..1) {
+            first.make_ascii_uppercase();
+        }
+        if type_name.is_empty() || type_name.starts_with(|c: char| c.is_ascii_digit()) {
+            type_name = format!("Cube{}", type_name);
+        }
+
+        Self {
+            cube: cube.to_string(),
+            field_name,
+            type_name,
+            columns,
+        }
+    }
+
+    fn column(&self, name: &str) -> Option<&GraphQLColumn> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+}
+
+/// Turn a name into a valid GraphQL name (`[_A-Za-z][_0-9A-Za-z]*`)
+fn graphql_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if sanitized.is_empty() || sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+/// GraphQL scalar a column of `data_type` is exposed as, if any
+fn scalar_type(data_type: &DataType) -> Option<&'static str> {
+    if data_type.is_integer() {
+        Some(TypeRef::INT)
+    } else if data_type.is_floating()
+        || matches!(data_type, DataType::Decimal128(..) | DataType::Decimal256(..))
+    {
+        Some(TypeRef::FLOAT)
+    } else if matches!(data_type, DataType::Boolean) {
+        Some(TypeRef::BOOLEAN)
+    } else if data_type.is_nested()
+        || matches!(data_type, DataType::Binary | DataType::LargeBinary)
+    {
+        None
+    } else {
+        Some(TypeRef::STRING)
+    }
+}
+
+/// GraphQL scalar of a measure aggregated with `agg`, if it can be exposed
+fn aggregate_type(agg: &AggFunc, data_type: &DataType) -> Option<&'static str> {
+    match agg {
+        AggFunc::Count | AggFunc::CountDistinct => Some(TypeRef::INT),
+        AggFunc::Avg
+        | AggFunc::Median
+        | AggFunc::StdDev
+        | AggFunc::Variance
+        | AggFunc::Skewness
+        | AggFunc::Kurtosis => Some(TypeRef::FLOAT),
+        AggFunc::StringAgg(_) => Some(TypeRef::STRING),
+        // Lists have no scalar type, and two-column aggregates need a
+        // second argument the measure doesn't name
+        AggFunc::ArrayAgg | AggFunc::Corr | AggFunc::CovarSamp | AggFunc::CovarPop => None,
+        AggFunc::Sum | AggFunc::Min | AggFunc::Max | AggFunc::First | AggFunc::Last => {
+            scalar_type(data_type)
+        }
+    }
+}
+
+/// Render a GraphQL input value as a SQL literal
+fn sql_literal(value: &Value) -> async_graphql::Result<String> {
+    match value {
+        Value::Number(number) => Ok(number.to_string()),
+        Value::String(string) => Ok(format!("'{}'", string.replace('\'', "''"))),
+        Value::Boolean(boolean) => Ok(boolean.to_string().to_uppercase()),
+        Value::Null => Ok("NULL".to_string()),
+        other => Err(format!("Unsupported filter value {}", other).into()),
+    }
+}
+
+async fn resolve<'a>(
+    catalog: &CubeCatalog,
+    fields: &CubeFields,
+    ctx: ResolverContext<'a>,
+) -> async_graphql::Result<Option<FieldValue<'a>>> {
+    let cube = catalog
+        .snapshot(&fields.cube)
+        .ok_or_else(|| format!("Cube '{}' not found", fields.cube))?;
+
+    let mut selected: Vec<&GraphQLColumn> = Vec::new();
+    for field in ctx.ctx.field().selection_set() {
+        if let Some(column) = fields.column(field.name()) {
+            if !selected.iter().any(|c| c.name == column.name) {
+                selected.push(column);
+            }
+        }
+    }
+    if selected.is_empty() {
+        return Err("Select at least one dimension or measure".into());
+    }
+
+    let mut group_by: Vec<&str> = selected
+        .iter()
+        .filter(|c| c.dimension)
+        .map(|c| c.expr.as_str())
+        .collect();
+    if let Some(dimensions) = ctx.args.get("groupBy") {
+        for dimension in dimensions.list()?.iter() {
+            let column = fields
+                .column(dimension.enum_name()?)
+                .ok_or("Unknown groupBy dimension")?;
+            if !group_by.contains(&column.expr.as_str()) {
+                group_by.push(&column.expr);
+            }
+        }
+    }
+
+    let mut conditions = Vec::new();
+    if let Some(filter) = ctx.args.get("filter") {
+        for (name, values) in filter.object()?.iter() {
+            let column = fields
+                .column(name.as_str())
+                .ok_or_else(|| format!("Unknown filter field '{}'", name))?;
+            if values.is_null() {
+                continue;
+            }
+            let values = values
+                .list()?
+                .iter()
+                .map(|value| sql_literal(value.as_value()))
+                .collect::<async_graphql::Result<Vec<_>>>()?;
+            conditions.push(if values.is_empty() {
+                "FALSE".to_string()
+            } else {
+                format!("{} IN ({})", column.expr, values.join(", "))
+            });
+        }
+    }
+
+    let mut order_by = Vec::new();
+    if let Some(order) = ctx.args.get("orderBy") {
+        for item in order.list()?.iter() {
+            let item = item.enum_name()?;
+            let (name, direction) = item.rsplit_once('_').ok_or("Invalid orderBy value")?;
+            let column = fields
+                .column(name)
+                .ok_or_else(|| format!("Unknown orderBy field '{}'", name))?;
+            order_by.push(format!("{} {}", column.expr, direction));
+        }
+    }
+
+    let exprs: Vec<&str> = selected.iter().map(|c| c.expr.as_str()).collect();
+    let mut query = cube.query()?.select(&exprs);
+    if !conditions.is_empty() {
+        query = query.filter(conditions.join(" AND "));
+    }
+    if !group_by.is_empty() {
+        query = query.group_by(&group_by);
+    }
+    if !order_by.is_empty() {
+        query = query.order_by(&order_by);
+    }
+    if let Some(limit) = ctx.args.get("limit") {
+        query = query.limit(usize::try_from(limit.u64()?)?);
+    }
+    if let Some(offset) = ctx.args.get("offset") {
+        query = query.offset(usize::try_from(offset.u64()?)?);
+    }
+    let result = query.execute().await?;
+
+    // Name the result columns after the GraphQL fields they answer
+    let names: Vec<&str> = selected.iter().map(|c| c.name.as_str()).collect();
+    let batches = result
+        .batches()
+        .iter()
+        .map(|batch| rename_columns(batch, &names))
+        .collect::<Result<Vec<_>>>()?;
+    let rows = QueryResult::from_batches(batches)
+        .to_json_rows()?
+        .into_iter()
+        .map(|row| Value::from_json(serde_json::Value::Object(row)).map(FieldValue::value))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(Some(FieldValue::list(rows)))
+}
+
+fn rename_columns(batch: &RecordBatch, names: &[&str]) -> Result<RecordBatch> {
+    let fields: Vec<ArrowField> = batch
+        .schema()
+        .fields()
+        .iter()
+        .zip(names)
+        .map(|(field, name)| field.as_ref().clone().with_name(*name))
+        .collect();
+    Ok(RecordBatch::try_new(
+        Arc::new(ArrowSchema::new(fields)),
+        batch.columns().to_vec(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ElastiCubeBuilder;
+    use arrow::array::{Float64Array, StringArray};
+    use serde_json::json;
+
+    fn catalog() -> CubeCatalog {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("region", DataType::Utf8, false),
+            ArrowField::new("revenue", DataType::Float64, false),
+            ArrowField::new("cost", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["North", "South", "North", "East"])),
+                Arc::new(Float64Array::from(vec![10.0, 20.0, 30.0, 40.0])),
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0, 4.0])),
+            ],
+        )
+        .unwrap();
+        let cube = ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_measure("revenue", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .add_measure("cost", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .add_calculated_measure("profit", "revenue - cost", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .load_record_batches(schema, vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let catalog = CubeCatalog::new();
+        catalog.register("sales", cube).unwrap();
+        catalog
+    }
+
+    #[tokio::test]
+    async fn test_graphql_grouped_query() {
+        let schema = schema(&catalog()).unwrap();
+        let sdl = schema.sdl();
+        assert!(sdl.contains("type Sales"));
+        assert!(sdl.contains("input SalesFilter"));
+
+        let response = schema
+            .execute(
+                r#"{
+                    sales(filter: {region: ["North", "South"]}, orderBy: [profit_DESC]) {
+                        region
+                        profit
+                    }
+                }"#,
+            )
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "sales": [
+                { "region": "North", "profit": 36.0 },
+                { "region": "South", "profit": 18.0 },
+            ]})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_graphql_totals_and_errors() {
+        let schema = schema(&catalog()).unwrap();
+
+        let response = schema.execute("{ sales { revenue cost } }").await;
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "sales": [{ "revenue": 100.0, "cost": 10.0 }] })
+        );
+
+        let response = schema.execute("{ sales { missing } }").await;
+        assert!(!response.errors.is_empty());
+
+        assert!(super::schema(&CubeCatalog::new()).is_err());
+    }
+}
@@ -0,0 +1,386 @@
+//! gRPC front-end
+//!
+//! Serves the cubes of a [`CubeCatalog`] through the `CubeService` defined
+//! in `proto/elasticube.proto`, for services that want a typed RPC
+//! interface rather than SQL over Flight or HTTP. Query results are
+//! streamed as chunks of an Arrow IPC stream, one per record batch, and
+//! rows can be appended to a cube with `AppendRows` once writes are enabled.
+//! The service does no authentication of its own.
+
+use super::{error_status, CubeCatalog, QueryRequest, PRINCIPAL_HEADER};
+use crate::error::{Error, Result};
+use crate::query::QueryStream;
+use crate::shared::SharedCube;
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use futures::{Stream, StreamExt, TryStreamExt};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use tonic::{Request, Response, Status};
+
+/// Messages and client/server stubs generated from `proto/elasticube.proto`
+pub mod proto {
+    tonic::include_proto!("elasticube.v1");
+}
+
+use proto::cube_service_server::{CubeService, CubeServiceServer};
+use proto::{
+    AppendRowsRequest, AppendRowsResponse, ArrowChunk, ExecuteQueryRequest, GetSchemaRequest,
+    GetSchemaResponse,
+};
+
+type ChunkStream = Pin<Box<dyn Stream<Item = std::result::Result<ArrowChunk, Status>> + Send>>;
+
+/// gRPC service answering requests against a [`CubeCatalog`]
+///
+/// # Example
+/// ```rust,ignore
+/// let catalog = CubeCatalog::new();
+/// catalog.register("sales", sales_cube)?;
+///
+/// GrpcServer::new(catalog)
+///     .serve("127.0.0.1:50052".parse()?)
+///     .await?;
+/// ```
+///
+/// Rust clients can use the generated
+/// [`CubeServiceClient`](proto::cube_service_client::CubeServiceClient);
+/// other languages generate theirs from `proto/elasticube.proto`.
+#[derive(Debug, Clone)]
+pub struct GrpcServer {
+    catalog: CubeCatalog,
+
+    /// Whether `AppendRows` is served
+    allow_writes: bool,
+}
+
+impl GrpcServer {
+    /// Create a service for the cubes of `catalog`
+    ///
+    /// The service only answers reads until [`with_writes`](Self::with_writes)
+    /// enables `AppendRows`.
+    pub fn new(catalog: CubeCatalog) -> Self {
+        Self {
+            catalog,
+            allow_writes: false,
+        }
+    }
+
+    /// Serve `AppendRows` requests
+    ///
+    /// Requests are not authenticated, so anyone who can reach the service
+    /// can then add rows to any cube. Only enable writes on a trusted
+    /// network or behind an authenticating proxy.
+    pub fn with_writes(mut self, allow: bool) -> Self {
+        self.allow_writes = allow;
+        self
+    }
+
+    /// The catalog being served
+    pub fn catalog(&self) -> &CubeCatalog {
+        &self.catalog
+    }
+
+    /// Wrap the service for use with a custom `tonic` server, e.g. to add TLS
+    /// or serve it next to the service's own RPCs
+    pub fn into_service(self) -> CubeServiceServer<Self> {
+        CubeServiceServer::new(self)
+    }
+
+    /// Listen on `addr` and serve gRPC requests until the server fails
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        tonic::transport::Server::builder()
+            .add_service(self.into_service())
+            .serve(addr)
+            .await
+            .map_err(|e| Error::io(format!("gRPC server on {} failed: {}", addr, e)))
+    }
+
+    fn cube(&self, name: &str) -> std::result::Result<SharedCube, Status> {
+        self.catalog
+            .cube(name)
+            .ok_or_else(|| Status::not_found(format!("No cube named '{}'", name)))
+    }
+}
+
+#[tonic::async_trait]
+impl CubeService for GrpcServer {
+    type ExecuteQueryStream = ChunkStream;
+
+    async fn execute_query(
+        &self,
+        request: Request<ExecuteQueryRequest>,
+    ) -> std::result::Result<Response<ChunkStream>, Status> {
+        let principal = request
+            .metadata()
+            .get(PRINCIPAL_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let request = request.into_inner();
+        let cube = self.cube(&request.cube)?.snapshot();
+        let query = QueryRequest {
+            sql: request.sql,
+            select: request.select,
+            filter: request.filter,
+            group_by: request.group_by,
+            order_by: request.order_by,
+            limit: request.limit.map(|n| n as usize),
+            offset: request.offset.map(|n| n as usize),
+        };
+
+        let mut query = query.apply(cube.query().map_err(error_status)?);
+        if let Some(principal) = principal {
+            query = query.as_principal(principal);
+        }
+        let stream = query
+            .execute_stream()
+            .await
+            .map_err(error_status)?;
+        let chunks = ipc_chunks(stream)
+            .map_err(error_status)?
+            .map_ok(|data| ArrowChunk { data })
+            .map_err(error_status);
+        Ok(Response::new(chunks.boxed()))
+    }
+
+    async fn get_schema(
+        &self,
+        request: Request<GetSchemaRequest>,
+    ) -> std::result::Result<Response<GetSchemaResponse>, Status> {
+        let cube = self.cube(&request.into_inner().cube)?.snapshot();
+        let cube_schema_json = serde_json::to_string(cube.schema())
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let arrow_schema = StreamWriter::try_new(Vec::new(), cube.arrow_schema())
+            .and_then(|writer| writer.into_inner())
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(GetSchemaResponse {
+            cube_schema_json,
+            arrow_schema,
+            row_count: cube.row_count() as u64,
+        }))
+    }
+
+    async fn append_rows(
+        &self,
+        request: Request<AppendRowsRequest>,
+    ) -> std::result::Result<Response<AppendRowsResponse>, Status> {
+        if !self.allow_writes {
+            return Err(Status::permission_denied("Writes are not enabled on this server"));
+        }
+        let request = request.into_inner();
+        let cube = self.cube(&request.cube)?;
+        let batches = StreamReader::try_new(request.data.as_slice(), None)
+            .and_then(|reader| reader.collect::<std::result::Result<Vec<_>, _>>())
+            .map_err(|e| Status::invalid_argument(format!("Invalid Arrow IPC stream: {}", e)))?;
+
+        let rows_appended = cube
+            .update(|cube| cube.append_batches(batches))
+            .await
+            .map_err(error_status)?;
+        Ok(Response::new(AppendRowsResponse {
+            rows_appended: rows_appended as u64,
+            row_count: cube.row_count() as u64,
+        }))
+    }
+}
+
+/// Encode a result as an Arrow IPC stream, one chunk per batch
+///
+/// The first chunk carries the schema and the last the end-of-stream marker.
+fn ipc_chunks(stream: QueryStream) -> Result<impl Stream<Item = Result<Vec<u8>>> + Send> {
+    let mut writer = StreamWriter::try_new(Vec::new(), &stream.schema())?;
+    let header = std::mem::take(writer.get_mut());
+
+    let chunks = futures::stream::try_unfold(
+        (stream, Some(writer)),
+        |(mut stream, writer)| async move {
+            let Some(mut writer) = writer else {
+                return Ok(None);
+            };
+            match stream.next().await {
+                Some(batch) => {
+                    writer.write(&batch?)?;
+                    let chunk = std::mem::take(writer.get_mut());
+                    Ok(Some((chunk, (stream, Some(writer)))))
+                }
+                None => {
+                    writer.finish()?;
+                    let chunk = std::mem::take(writer.get_mut());
+                    Ok(Some((chunk, (stream, None))))
+                }
+            }
+        },
+    );
+    Ok(futures::stream::once(async move { Ok(header) }).chain(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ElastiCubeBuilder;
+    use crate::cube::AggFunc;
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+    use arrow::record_batch::RecordBatch;
+    use std::sync::Arc;
+
+    fn sales_batch(regions: Vec<&str>, amounts: Vec<f64>) -> RecordBatch {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(regions)),
+                Arc::new(Float64Array::from(amounts)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn server() -> GrpcServer {
+        let batch = sales_batch(vec!["North", "South", "North"], vec![10.0, 20.0, 5.0]);
+        let cube = ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_measure("amount", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .load_record_batches(batch.schema(), vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+        let catalog = CubeCatalog::new();
+        catalog.register("sales", cube).unwrap();
+        GrpcServer::new(catalog)
+    }
+
+    fn ipc_bytes(batch: &RecordBatch) -> Vec<u8> {
+        let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema()).unwrap();
+        writer.write(batch).unwrap();
+        writer.into_inner().unwrap()
+    }
+
+    async fn append(
+        server: &GrpcServer,
+        batch: &RecordBatch,
+    ) -> std::result::Result<Response<AppendRowsResponse>, Status> {
+        server
+            .append_rows(Request::new(AppendRowsRequest {
+                cube: "sales".to_string(),
+                data: ipc_bytes(batch),
+            }))
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_execute_query_streams_ipc() {
+        let server = server();
+        let request = ExecuteQueryRequest {
+            cube: "sales".to_string(),
+            select: vec!["region".to_string(), "SUM(amount) AS total".to_string()],
+            group_by: vec!["region".to_string()],
+            order_by: vec!["region".to_string()],
+            ..Default::default()
+        };
+        let chunks: Vec<ArrowChunk> = server
+            .execute_query(Request::new(request))
+            .await
+            .unwrap()
+            .into_inner()
+            .try_collect()
+            .await
+            .unwrap();
+
+        let data: Vec<u8> = chunks.into_iter().flat_map(|chunk| chunk.data).collect();
+        let batches: Vec<RecordBatch> = StreamReader::try_new(data.as_slice(), None)
+            .unwrap()
+            .map(|b| b.unwrap())
+            .collect();
+        let totals = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(totals.value(0), 15.0);
+        assert_eq!(totals.value(1), 20.0);
+
+        let missing = ExecuteQueryRequest {
+            cube: "budget".to_string(),
+            ..Default::default()
+        };
+        let status = server.execute_query(Request::new(missing)).await.err().unwrap();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        // SQL can only read
+        let copy = ExecuteQueryRequest {
+            cube: "sales".to_string(),
+            sql: Some("COPY cube TO 'sales.csv' STORED AS CSV".to_string()),
+            ..Default::default()
+        };
+        assert!(server.execute_query(Request::new(copy)).await.is_err());
+
+        // Queries are audited as the caller named in the metadata
+        let sink = Arc::new(crate::audit::MemoryAuditSink::new());
+        let mut audited = server.catalog().snapshot("sales").unwrap().as_ref().clone();
+        audited.set_audit_sink(sink.clone());
+        server.catalog().register("sales", audited).unwrap();
+        let mut request = Request::new(ExecuteQueryRequest {
+            cube: "sales".to_string(),
+            ..Default::default()
+        });
+        request
+            .metadata_mut()
+            .insert(PRINCIPAL_HEADER, "svc-reports".parse().unwrap());
+        let _: Vec<ArrowChunk> = server
+            .execute_query(request)
+            .await
+            .unwrap()
+            .into_inner()
+            .try_collect()
+            .await
+            .unwrap();
+        let events = sink.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].principal.as_deref(), Some("svc-reports"));
+        assert_eq!(events[0].rows_returned, 3);
+    }
+
+    #[tokio::test]
+    async fn test_schema_and_append_rows() {
+        let batch = sales_batch(vec!["East"], vec![7.0]);
+        let read_only = server();
+        let denied = append(&read_only, &batch).await.err().unwrap();
+        assert_eq!(denied.code(), tonic::Code::PermissionDenied);
+        assert_eq!(read_only.catalog().snapshot("sales").unwrap().row_count(), 3);
+
+        let server = server().with_writes(true);
+        let schema = server
+            .get_schema(Request::new(GetSchemaRequest {
+                cube: "sales".to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(schema.row_count, 3);
+        assert!(schema.cube_schema_json.contains("\"amount\""));
+        let reader = StreamReader::try_new(schema.arrow_schema.as_slice(), None).unwrap();
+        assert_eq!(reader.schema().field(0).name(), "region");
+
+        let appended = append(&server, &batch).await.unwrap().into_inner();
+        assert_eq!(appended.rows_appended, 1);
+        assert_eq!(appended.row_count, 4);
+        assert_eq!(server.catalog().snapshot("sales").unwrap().row_count(), 4);
+
+        let invalid = server
+            .append_rows(Request::new(AppendRowsRequest {
+                cube: "sales".to_string(),
+                data: b"not arrow".to_vec(),
+            }))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(invalid.code(), tonic::Code::InvalidArgument);
+    }
+}
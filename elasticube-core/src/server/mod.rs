@@ -0,0 +1,309 @@
+//! Hosting cubes for remote clients
+//!
+//! A [`CubeCatalog`] names the cubes a server exposes. Each cube is held as
+//! a [`SharedCube`], so the application can keep updating it while the
+//! server answers queries; every query sees the versions published when it
+//! started. Cubes are queried as tables named after their catalog entry, so
+//! SQL sent to a server can join them like a [`CubeContext`] does.
+//!
+//! The protocol front-ends are behind features:
+//!
+//! - `flight-sql`: Arrow Flight SQL ([`flight::FlightSqlServer`])
+//! - `server`: JSON/Arrow over HTTP ([`rest::RestServer`])
+//! - `graphql`: a GraphQL schema over the cubes ([`graphql::schema`]), also
+//!   served by the HTTP server
+//! - `pgwire`: the PostgreSQL wire protocol ([`postgres::PostgresServer`])
+//! - `grpc`: a gRPC service with streamed Arrow results ([`grpc::GrpcServer`])
+//!
+//! Queries are recorded in the audit trail of the cubes they read, as run by
+//! the caller named in the [`PRINCIPAL_HEADER`] header (HTTP, gRPC, Flight
+//! SQL) or by the PostgreSQL startup user.
+
+use crate::context::CubeContext;
+use crate::cube::ElastiCube;
+use crate::definition::CubeDefinition;
+use crate::error::{Error, Result};
+use crate::optimization::OptimizationConfig;
+use crate::query::QueryBuilder;
+use crate::shared::SharedCube;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+#[cfg(feature = "flight-sql")]
+pub mod flight;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "pgwire")]
+pub mod postgres;
+#[cfg(feature = "server")]
+pub mod rest;
+
+/// Request header naming the caller a query is audited as
+///
+/// The servers don't authenticate it; set it from a proxy that does.
+pub const PRINCIPAL_HEADER: &str = "x-elasticube-principal";
+
+/// Named cubes served together
+///
+/// Cloning a catalog is cheap and every clone sees the same cubes.
+///
+/// # Example
+/// ```rust,ignore
+/// let catalog = CubeCatalog::new();
+/// catalog.register("sales", sales_cube)?;
+/// catalog.load_definition("cubes/budget.yaml")?;
+///
+/// FlightSqlServer::new(catalog).serve("127.0.0.1:50051".parse()?).await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CubeCatalog {
+    inner: Arc<CatalogInner>,
+}
+
+#[derive(Debug, Default)]
+struct CatalogInner {
+    cubes: RwLock<IndexMap<String, SharedCube>>,
+
+    /// Settings for the session each query runs in
+    config: OptimizationConfig,
+}
+
+impl CubeCatalog {
+    /// Create an empty catalog with default optimization settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty catalog whose queries use custom optimization settings
+    pub fn with_config(config: OptimizationConfig) -> Self {
+        Self {
+            inner: Arc::new(CatalogInner {
+                cubes: RwLock::new(IndexMap::new()),
+                config,
+            }),
+        }
+    }
+
+    /// Serve a cube under `name`, replacing any cube with the same name
+    ///
+    /// Pass a [`SharedCube`] clone to keep publishing updates to it.
+    pub fn register(&self, name: impl Into<String>, cube: impl Into<SharedCube>) -> Result<()> {
+        let name = name.into();
+        if name.is_empty() {
+            return Err(Error::config("Cube name cannot be empty"));
+        }
+        self.inner
+            .cubes
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(name, cube.into());
+        Ok(())
+    }
+
+    /// Build the cube a definition file declares and serve it under its name
+    ///
+    /// Returns the name the cube was registered under.
+    pub fn load_definition(&self, path: impl AsRef<Path>) -> Result<String> {
+        let cube = CubeDefinition::from_file(path)?.into_builder()?.build()?;
+        let name = cube.schema().name().to_string();
+        self.register(name.clone(), cube)?;
+        Ok(name)
+    }
+
+    /// Stop serving a cube, returning it if it was registered
+    pub fn deregister(&self, name: &str) -> Option<SharedCube> {
+        self.inner
+            .cubes
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .shift_remove(name)
+    }
+
+    /// Get a served cube by name
+    pub fn cube(&self, name: &str) -> Option<SharedCube> {
+        self.inner
+            .cubes
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(name)
+            .cloned()
+    }
+
+    /// The current version of a served cube
+    pub fn snapshot(&self, name: &str) -> Option<Arc<ElastiCube>> {
+        self.cube(name).map(|cube| cube.snapshot())
+    }
+
+    /// Names of the served cubes, in registration order
+    pub fn names(&self) -> Vec<String> {
+        self.inner
+            .cubes
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// Number of served cubes
+    pub fn len(&self) -> usize {
+        self.inner
+            .cubes
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .len()
+    }
+
+    /// Check if no cubes are served
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A query context over the current version of every served cube
+    pub fn context(&self) -> Result<CubeContext> {
+        let cubes: Vec<(String, Arc<ElastiCube>)> = self
+            .inner
+            .cubes
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .map(|(name, cube)| (name.clone(), cube.snapshot()))
+            .collect();
+
+        let mut ctx = CubeContext::with_config(self.inner.config.clone())?;
+        for (name, cube) in cubes {
+            ctx.register(name, cube)?;
+        }
+        Ok(ctx)
+    }
+}
+
+/// A query on one cube, as sent by remote clients
+///
+/// Either `sql` (referring to the cube as `cube`) or the structured fields
+/// are used, mirroring the [`QueryBuilder`] methods of the same names.
+///
+/// ```json
+/// {"select": ["region", "SUM(sales) AS total"], "group_by": ["region"],
+///  "order_by": ["total DESC"], "limit": 10}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueryRequest {
+    pub sql: Option<String>,
+    pub select: Vec<String>,
+    pub filter: Option<String>,
+    pub group_by: Vec<String>,
+    pub order_by: Vec<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl QueryRequest {
+    /// Apply the request to a query on the target cube
+    pub fn apply(self, mut query: QueryBuilder) -> QueryBuilder {
+        if let Some(sql) = self.sql {
+            query = query.sql(sql);
+        }
+        if !self.select.is_empty() {
+            query = query.select(&self.select);
+        }
+        if let Some(filter) = self.filter {
+            query = query.filter(filter);
+        }
+        if !self.group_by.is_empty() {
+            query = query.group_by(&self.group_by);
+        }
+        if !self.order_by.is_empty() {
+            query = query.order_by(&self.order_by);
+        }
+        if let Some(limit) = self.limit {
+            query = query.limit(limit);
+        }
+        if let Some(offset) = self.offset {
+            query = query.offset(offset);
+        }
+        query
+    }
+}
+
+/// The gRPC status a failed query is reported with
+///
+/// Errors in the request itself are the client's to fix; anything else is
+/// an internal error.
+#[cfg(any(feature = "flight-sql", feature = "grpc"))]
+pub(crate) fn error_status(error: Error) -> tonic::Status {
+    match error {
+        Error::Query(_) | Error::Schema(_) => tonic::Status::invalid_argument(error.to_string()),
+        _ => tonic::Status::internal(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ElastiCubeBuilder;
+    use crate::cube::AggFunc;
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+    use arrow::record_batch::RecordBatch;
+
+    fn sales_cube(amounts: Vec<f64>) -> ElastiCube {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        let regions: Vec<String> = (0..amounts.len()).map(|i| format!("R{}", i)).collect();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(regions)),
+                Arc::new(Float64Array::from(amounts)),
+            ],
+        )
+        .unwrap();
+
+        ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_measure("amount", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .load_record_batches(schema, vec![batch])
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_catalog_sees_published_updates() {
+        let catalog = CubeCatalog::new();
+        let shared = SharedCube::new(sales_cube(vec![1.0, 2.0]));
+        catalog.register("sales", shared.clone()).unwrap();
+        catalog.register("budget", sales_cube(vec![5.0])).unwrap();
+        assert_eq!(catalog.names(), vec!["sales", "budget"]);
+        assert!(catalog.register("", sales_cube(vec![1.0])).is_err());
+
+        let count = |catalog: CubeCatalog| async move {
+            catalog
+                .context()
+                .unwrap()
+                .query()
+                .sql("SELECT * FROM sales")
+                .execute()
+                .await
+                .unwrap()
+                .row_count()
+        };
+        assert_eq!(count(catalog.clone()).await, 2);
+
+        shared.replace(sales_cube(vec![1.0, 2.0, 3.0])).await;
+        assert_eq!(count(catalog.clone()).await, 3);
+
+        assert!(catalog.deregister("budget").is_some());
+        assert_eq!(catalog.len(), 1);
+    }
+}
@@ -0,0 +1,652 @@
+//! PostgreSQL wire-protocol front-end
+//!
+//! Lets `psql`, Grafana, Metabase and Postgres drivers connect to a
+//! [`CubeCatalog`] as if it were a Postgres database. Each cube is a table
+//! named after its catalog entry; SQL is run by DataFusion, so it follows
+//! DataFusion's dialect rather than Postgres'.
+//!
+//! The server is read-only and has no authentication: it accepts any user
+//! and password, and audits queries as run by the startup user. Statements
+//! other than queries are rejected, except the session and transaction
+//! statements drivers send on their own (`SET`, `BEGIN`, `COMMIT`, ...),
+//! which are acknowledged and ignored. `SHOW` answers a few common
+//! settings. Tools that browse the `pg_catalog` tables may not be able to
+//! list the cubes.
+//!
+//! Results are always sent in text format with Postgres types mapped from
+//! the Arrow types. Parameters of prepared statements are substituted into
+//! the SQL as literals.
+
+use super::CubeCatalog;
+use crate::error::{Error, Result};
+use crate::query::QueryStream;
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{DataType, Schema as ArrowSchema};
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use async_trait::async_trait;
+use datafusion::sql::parser::DFParser;
+use futures::{stream, StreamExt};
+use pgwire::api::auth::noop::NoopStartupHandler;
+use pgwire::api::copy::NoopCopyHandler;
+use pgwire::api::portal::Portal;
+use pgwire::api::query::{ExtendedQueryHandler, SimpleQueryHandler};
+use pgwire::api::results::{
+    DataRowEncoder, DescribePortalResponse, DescribeStatementResponse, FieldFormat, FieldInfo,
+    QueryResponse, Response, Tag,
+};
+use pgwire::api::stmt::{NoopQueryParser, StoredStatement};
+use pgwire::api::{ClientInfo, NoopErrorHandler, PgWireServerHandlers, Type, METADATA_USER};
+use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+use pgwire::messages::data::DataRow;
+use pgwire::tokio::process_socket;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Version reported to clients by `SHOW server_version`
+const SERVER_VERSION: &str = "16.0";
+
+/// Postgres wire-protocol server for the cubes of a [`CubeCatalog`]
+///
+/// # Example
+/// ```rust,ignore
+/// PostgresServer::new(catalog).serve("127.0.0.1:5432".parse()?).await?;
+/// ```
+///
+/// ```bash
+/// psql -h localhost -p 5432 -c "SELECT region, SUM(amount) FROM sales GROUP BY region"
+/// ```
+#[derive(Debug, Clone)]
+pub struct PostgresServer {
+    catalog: CubeCatalog,
+}
+
+impl PostgresServer {
+    /// Create a server for the cubes of `catalog`
+    pub fn new(catalog: CubeCatalog) -> Self {
+        Self { catalog }
+    }
+
+    /// The catalog being served
+    pub fn catalog(&self) -> &CubeCatalog {
+        &self.catalog
+    }
+
+    /// Listen on `addr` and serve connections until accepting one fails
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| Error::io(format!("Failed to listen on {}: {}", addr, e)))?;
+        let handlers = Arc::new(Handlers {
+            handler: Arc::new(Handler::new(self.catalog)),
+        });
+
+        loop {
+            let (socket, _) = listener.accept().await?;
+            let handlers = handlers.clone();
+            tokio::spawn(async move {
+                // A failed connection only affects that client
+                let _ = process_socket(socket, None, handlers).await;
+            });
+        }
+    }
+}
+
+struct Handlers {
+    handler: Arc<Handler>,
+}
+
+impl PgWireServerHandlers for Handlers {
+    type StartupHandler = Handler;
+    type SimpleQueryHandler = Handler;
+    type ExtendedQueryHandler = Handler;
+    type CopyHandler = NoopCopyHandler;
+    type ErrorHandler = NoopErrorHandler;
+
+    fn simple_query_handler(&self) -> Arc<Self::SimpleQueryHandler> {
+        self.handler.clone()
+    }
+
+    fn extended_query_handler(&self) -> Arc<Self::ExtendedQueryHandler> {
+        self.handler.clone()
+    }
+
+    fn startup_handler(&self) -> Arc<Self::StartupHandler> {
+        self.handler.clone()
+    }
+
+    fn copy_handler(&self) -> Arc<Self::CopyHandler> {
+        Arc::new(NoopCopyHandler)
+    }
+
+    fn error_handler(&self) -> Arc<Self::ErrorHandler> {
+        Arc::new(NoopErrorHandler)
+    }
+}
+
+/// Answers the statements of every connection
+struct Handler {
+    catalog: CubeCatalog,
+    parser: Arc<NoopQueryParser>,
+}
+
+/// What a statement asks the server to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum StatementKind {
+    /// Run through DataFusion
+    Query,
+    /// Session or transaction control, acknowledged with this tag
+    Acknowledge(&'static str),
+    /// Report a setting
+    Show(String),
+    /// Anything that would modify data or the catalog
+    Rejected,
+}
+
+impl StatementKind {
+    fn classify(sql: &str) -> Self {
+        let mut words = sql
+            .trim_start_matches(|c: char| c.is_whitespace() || c == '(')
+            .split_whitespace();
+        let keyword = words.next().unwrap_or_default().to_ascii_uppercase();
+        match keyword.as_str() {
+            "SELECT" | "WITH" | "VALUES" | "EXPLAIN" | "DESCRIBE" => StatementKind::Query,
+            "SET" | "RESET" => StatementKind::Acknowledge("SET"),
+            "BEGIN" | "START" => StatementKind::Acknowledge("BEGIN"),
+            "COMMIT" | "END" => StatementKind::Acknowledge("COMMIT"),
+            "ROLLBACK" | "ABORT" => StatementKind::Acknowledge("ROLLBACK"),
+            "DISCARD" => StatementKind::Acknowledge("DISCARD ALL"),
+            "DEALLOCATE" => StatementKind::Acknowledge("DEALLOCATE"),
+            "SHOW" => StatementKind::Show(
+                words
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .trim_end_matches(';')
+                    .to_ascii_lowercase(),
+            ),
+            _ => StatementKind::Rejected,
+        }
+    }
+}
+
+impl Handler {
+    fn new(catalog: CubeCatalog) -> Self {
+        Self {
+            catalog,
+            parser: Arc::new(NoopQueryParser::new()),
+        }
+    }
+
+    /// Run every statement of a query string on behalf of `user`
+    async fn execute(
+        &self,
+        sql: &str,
+        user: Option<&str>,
+    ) -> PgWireResult<Vec<Response<'static>>> {
+        let mut responses = Vec::new();
+        for statement in split_statements(sql) {
+            responses.push(self.execute_statement(&statement, user).await?);
+        }
+        if responses.is_empty() {
+            responses.push(Response::EmptyQuery);
+        }
+        Ok(responses)
+    }
+
+    async fn execute_statement(
+        &self,
+        sql: &str,
+        user: Option<&str>,
+    ) -> PgWireResult<Response<'static>> {
+        match StatementKind::classify(sql) {
+            StatementKind::Query => {
+                let mut query = self.catalog.context().map_err(user_error)?.query().sql(sql);
+                if let Some(user) = user {
+                    query = query.as_principal(user);
+                }
+                let stream = query
+                    .execute_stream()
+                    .await
+                    .map_err(user_error)?;
+                Ok(query_response(stream))
+            }
+            StatementKind::Acknowledge(tag) => Ok(Response::Execution(Tag::new(tag))),
+            StatementKind::Show(setting) => show(&setting),
+            StatementKind::Rejected => Err(pg_error(
+                "25006",
+                format!("Only queries are supported, got: {}", sql),
+            )),
+        }
+    }
+
+    /// Result columns of a statement, planned without running it
+    async fn describe(&self, sql: &str) -> PgWireResult<Vec<FieldInfo>> {
+        match StatementKind::classify(sql) {
+            StatementKind::Query => {
+                // Placeholders have no values yet; NULL keeps the plan valid
+                let sql = substitute_parameters(sql, |_| Ok("NULL".to_string()))?;
+                let schema = self
+                    .catalog
+                    .context()
+                    .map_err(user_error)?
+                    .query()
+                    .sql(sql)
+                    .schema()
+                    .await
+                    .map_err(user_error)?;
+                Ok(field_infos(&schema))
+            }
+            StatementKind::Show(setting) => Ok(vec![text_field(&setting)]),
+            StatementKind::Acknowledge(_) | StatementKind::Rejected => Ok(Vec::new()),
+        }
+    }
+}
+
+impl NoopStartupHandler for Handler {}
+
+#[async_trait]
+impl SimpleQueryHandler for Handler {
+    async fn do_query<'a, C>(&self, client: &mut C, query: &str) -> PgWireResult<Vec<Response<'a>>>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        let user = client.metadata().get(METADATA_USER).cloned();
+        self.execute(query, user.as_deref()).await
+    }
+}
+
+#[async_trait]
+impl ExtendedQueryHandler for Handler {
+    type Statement = String;
+    type QueryParser = NoopQueryParser;
+
+    fn query_parser(&self) -> Arc<Self::QueryParser> {
+        self.parser.clone()
+    }
+
+    async fn do_query<'a, 'b: 'a, C>(
+        &'b self,
+        client: &mut C,
+        portal: &'a Portal<Self::Statement>,
+        _max_rows: usize,
+    ) -> PgWireResult<Response<'a>>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        let sql = bind_parameters(portal)?;
+        if sql.trim().is_empty() {
+            return Ok(Response::EmptyQuery);
+        }
+        let user = client.metadata().get(METADATA_USER).cloned();
+        self.execute_statement(&sql, user.as_deref()).await
+    }
+
+    async fn do_describe_statement<C>(
+        &self,
+        _client: &mut C,
+        statement: &StoredStatement<Self::Statement>,
+    ) -> PgWireResult<DescribeStatementResponse>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        let mut parameter_types = statement.parameter_types.clone();
+        let count = parameter_count(&statement.statement);
+        if parameter_types.len() < count {
+            parameter_types.resize(count, Type::UNKNOWN);
+        }
+        let fields = self.describe(&statement.statement).await?;
+        Ok(DescribeStatementResponse::new(parameter_types, fields))
+    }
+
+    async fn do_describe_portal<C>(
+        &self,
+        _client: &mut C,
+        portal: &Portal<Self::Statement>,
+    ) -> PgWireResult<DescribePortalResponse>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        let fields = self.describe(&bind_parameters(portal)?).await?;
+        Ok(DescribePortalResponse::new(fields))
+    }
+}
+
+/// Split a query string into statements
+///
+/// SQL the parser doesn't understand is passed on as a single statement so
+/// DataFusion reports the error.
+fn split_statements(sql: &str) -> Vec<String> {
+    if sql.trim().trim_matches(';').trim().is_empty() {
+        return Vec::new();
+    }
+    match DFParser::parse_sql(sql) {
+        Ok(statements) => statements.iter().map(|s| s.to_string()).collect(),
+        Err(_) => vec![sql.to_string()],
+    }
+}
+
+/// Replace `$n` placeholders outside string literals and quoted identifiers
+fn substitute_parameters(
+    sql: &str,
+    mut value: impl FnMut(usize) -> PgWireResult<String>,
+) -> PgWireResult<String> {
+    let mut output = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut quote: Option<char> = None;
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                }
+                output.push(c);
+            }
+            None if c == '\'' || c == '"' => {
+                quote = Some(c);
+                output.push(c);
+            }
+            None if c == '$' && chars.peek().is_some_and(|d| d.is_ascii_digit()) => {
+                let mut digits = String::new();
+                while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(*d);
+                    chars.next();
+                }
+                let index: usize = digits
+                    .parse()
+                    .map_err(|_| pg_error("42P02", format!("Invalid parameter ${}", digits)))?;
+                if index == 0 {
+                    return Err(pg_error("42P02", "Parameters are numbered from $1"));
+                }
+                output.push_str(&value(index - 1)?);
+            }
+            None => output.push(c),
+        }
+    }
+    Ok(output)
+}
+
+/// Highest placeholder number used by a statement
+fn parameter_count(sql: &str) -> usize {
+    let mut count = 0;
+    // Only the indices are of interest, so every value is a dummy
+    let _ = substitute_parameters(sql, |index| {
+        count = count.max(index + 1);
+        Ok(String::new())
+    });
+    count
+}
+
+/// The portal's SQL with its parameter values substituted as literals
+fn bind_parameters(portal: &Portal<String>) -> PgWireResult<String> {
+    let types = &portal.statement.parameter_types;
+    substitute_parameters(&portal.statement.statement, |index| {
+        if index >= portal.parameter_len() {
+            return Err(pg_error(
+                "08P01",
+                format!("No value bound for parameter ${}", index + 1),
+            ));
+        }
+        let pg_type = types.get(index).cloned().unwrap_or(Type::UNKNOWN);
+        let literal = match pg_type {
+            Type::BOOL => portal
+                .parameter::<bool>(index, &pg_type)?
+                .map(|b| if b { "TRUE" } else { "FALSE" }.to_string()),
+            Type::INT2 => portal
+                .parameter::<i16>(index, &pg_type)?
+                .map(|n| n.to_string()),
+            Type::INT4 => portal
+                .parameter::<i32>(index, &pg_type)?
+                .map(|n| n.to_string()),
+            Type::INT8 => portal
+                .parameter::<i64>(index, &pg_type)?
+                .map(|n| n.to_string()),
+            Type::FLOAT4 => portal
+                .parameter::<f32>(index, &pg_type)?
+                .map(|n| n.to_string()),
+            Type::FLOAT8 => portal
+                .parameter::<f64>(index, &pg_type)?
+                .map(|n| n.to_string()),
+            _ => portal
+                .parameter::<String>(index, &Type::VARCHAR)?
+                .map(|s| format!("'{}'", s.replace('\'', "''"))),
+        };
+        Ok(literal.unwrap_or_else(|| "NULL".to_string()))
+    })
+}
+
+/// Postgres type a column of `data_type` is reported as
+fn pg_type(data_type: &DataType) -> Type {
+    match data_type {
+        DataType::Boolean => Type::BOOL,
+        DataType::Int8 | DataType::Int16 | DataType::UInt8 => Type::INT2,
+        DataType::Int32 | DataType::UInt16 => Type::INT4,
+        DataType::Int64 | DataType::UInt32 => Type::INT8,
+        DataType::UInt64 | DataType::Decimal128(..) | DataType::Decimal256(..) => Type::NUMERIC,
+        DataType::Float16 | DataType::Float32 => Type::FLOAT4,
+        DataType::Float64 => Type::FLOAT8,
+        DataType::Date32 | DataType::Date64 => Type::DATE,
+        DataType::Timestamp(_, None) => Type::TIMESTAMP,
+        DataType::Timestamp(_, Some(_)) => Type::TIMESTAMPTZ,
+        DataType::Time32(_) | DataType::Time64(_) => Type::TIME,
+        DataType::Interval(_) | DataType::Duration(_) => Type::INTERVAL,
+        _ => Type::VARCHAR,
+    }
+}
+
+fn field_infos(schema: &ArrowSchema) -> Vec<FieldInfo> {
+    schema
+        .fields()
+        .iter()
+        .map(|field| {
+            FieldInfo::new(
+                field.name().clone(),
+                None,
+                None,
+                pg_type(field.data_type()),
+                FieldFormat::Text,
+            )
+        })
+        .collect()
+}
+
+fn text_field(name: &str) -> FieldInfo {
+    FieldInfo::new(name.to_string(), None, None, Type::VARCHAR, FieldFormat::Text)
+}
+
+/// Format options producing the text representations Postgres clients parse
+fn format_options() -> FormatOptions<'static> {
+    FormatOptions::new()
+        .with_timestamp_format(Some("%Y-%m-%d %H:%M:%S%.f"))
+        .with_timestamp_tz_format(Some("%Y-%m-%d %H:%M:%S%.f%:z"))
+}
+
+/// Encode a batch as data rows in text format
+fn encode_batch(fields: &Arc<Vec<FieldInfo>>, batch: &RecordBatch) -> Vec<PgWireResult<DataRow>> {
+    let options = format_options();
+    let formatters = batch
+        .columns()
+        .iter()
+        .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+        .collect::<std::result::Result<Vec<_>, _>>();
+    let formatters = match formatters {
+        Ok(formatters) => formatters,
+        Err(e) => return vec![Err(user_error(Error::from(e)))],
+    };
+
+    (0..batch.num_rows())
+        .map(|row| {
+            let mut encoder = DataRowEncoder::new(fields.clone());
+            for (column, formatter) in batch.columns().iter().zip(&formatters) {
+                let value = if column.is_null(row) {
+                    None
+                } else if let Some(booleans) = column.as_boolean_opt() {
+                    Some(if booleans.value(row) { "t" } else { "f" }.to_string())
+                } else {
+                    Some(formatter.value(row).to_string())
+                };
+                encoder.encode_field(&value)?;
+            }
+            encoder.finish()
+        })
+        .collect()
+}
+
+fn query_response(stream: QueryStream) -> Response<'static> {
+    let fields = Arc::new(field_infos(&stream.schema()));
+    let rows = {
+        let fields = fields.clone();
+        stream.flat_map(move |batch| {
+            let rows = match batch {
+                Ok(batch) => encode_batch(&fields, &batch),
+                Err(e) => vec![Err(user_error(e))],
+            };
+            stream::iter(rows)
+        })
+    };
+    Response::Query(QueryResponse::new(fields, rows))
+}
+
+/// Answer `SHOW setting` for the settings drivers commonly ask for
+fn show(setting: &str) -> PgWireResult<Response<'static>> {
+    let value = match setting {
+        "server_version" => SERVER_VERSION,
+        "server_encoding" | "client_encoding" => "UTF8",
+        "transaction isolation level" | "transaction_isolation" => "read committed",
+        "standard_conforming_strings" => "on",
+        "datestyle" => "ISO, MDY",
+        "timezone" => "UTC",
+        "search_path" => "public",
+        _ => {
+            return Err(pg_error(
+                "42704",
+                format!("Unrecognized configuration parameter \"{}\"", setting),
+            ))
+        }
+    };
+
+    let fields = Arc::new(vec![text_field(setting)]);
+    let mut encoder = DataRowEncoder::new(fields.clone());
+    encoder.encode_field(&Some(value.to_string()))?;
+    let row = encoder.finish();
+    Ok(Response::Query(QueryResponse::new(
+        fields,
+        stream::iter(vec![row]),
+    )))
+}
+
+fn pg_error(code: &str, message: impl Into<String>) -> PgWireError {
+    PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".to_string(),
+        code.to_string(),
+        message.into(),
+    )))
+}
+
+fn user_error(error: Error) -> PgWireError {
+    let code = match error {
+        Error::Query(_) | Error::Schema(_) => "42000",
+        _ => "XX000",
+    };
+    pg_error(code, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ElastiCubeBuilder;
+    use crate::cube::AggFunc;
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::Field;
+
+    fn handler() -> Handler {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("amount", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["North", "South", "North"])),
+                Arc::new(Float64Array::from(vec![Some(1.5), None, Some(3.0)])),
+            ],
+        )
+        .unwrap();
+        let cube = ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_measure("amount", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .load_record_batches(schema, vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let catalog = CubeCatalog::new();
+        catalog.register("sales", cube).unwrap();
+        Handler::new(catalog)
+    }
+
+    #[test]
+    fn test_classify_statements() {
+        assert_eq!(StatementKind::classify("  select 1"), StatementKind::Query);
+        assert_eq!(
+            StatementKind::classify("SET extra_float_digits = 3"),
+            StatementKind::Acknowledge("SET")
+        );
+        assert_eq!(
+            StatementKind::classify("SHOW TRANSACTION ISOLATION LEVEL"),
+            StatementKind::Show("transaction isolation level".to_string())
+        );
+        assert_eq!(
+            StatementKind::classify("DROP TABLE sales"),
+            StatementKind::Rejected
+        );
+    }
+
+    #[test]
+    fn test_substitute_parameters() {
+        let sql = substitute_parameters("SELECT '$1', \"$2\" FROM t WHERE a = $1 AND b = $2", |i| {
+            Ok(format!("v{}", i))
+        })
+        .unwrap();
+        assert_eq!(sql, "SELECT '$1', \"$2\" FROM t WHERE a = v0 AND b = v1");
+        assert_eq!(parameter_count("SELECT $3, $1"), 3);
+        assert!(substitute_parameters("SELECT $0", |_| Ok(String::new())).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_encodes_text_rows() {
+        let handler = handler();
+        let responses = handler
+            .execute("SET client_encoding = 'UTF8'; SELECT region, amount FROM sales", None)
+            .await
+            .unwrap();
+        assert_eq!(responses.len(), 2);
+        assert!(matches!(responses[0], Response::Execution(_)));
+        assert!(matches!(responses[1], Response::Query(_)));
+
+        let result = handler
+            .catalog
+            .context()
+            .unwrap()
+            .query()
+            .sql("SELECT region, amount FROM sales")
+            .execute()
+            .await
+            .unwrap();
+        let batch = &result.batches()[0];
+        let fields = Arc::new(field_infos(&batch.schema()));
+        assert_eq!(fields[1].datatype(), &Type::FLOAT8);
+        let rows = encode_batch(&fields, batch);
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().all(|row| row.is_ok()));
+
+        assert!(handler.execute("DELETE FROM sales", None).await.is_err());
+        assert!(handler.execute("SELECT * FROM missing", None).await.is_err());
+        assert!(matches!(
+            handler.execute(" ; ", None).await.unwrap()[0],
+            Response::EmptyQuery
+        ));
+    }
+}
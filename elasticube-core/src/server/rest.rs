@@ -0,0 +1,451 @@
+//! HTTP front-end
+//!
+//! A small JSON API over the cubes of a [`CubeCatalog`]:
+//!
+//! | Method | Path                  | Response                               |
+//! |--------|-----------------------|----------------------------------------|
+//! | GET    | `/cubes`              | name and row count of every cube       |
+//! | GET    | `/cubes/{name}`       | the cube's schema and columns          |
+//! | POST   | `/cubes/{name}/query` | result of a [`QueryRequest`] body      |
+//! | POST   | `/graphql`            | GraphQL response (`graphql` feature)   |
+//! | GET    | `/graphql`            | GraphQL schema in SDL (`graphql` feature) |
+//!
+//! Query results are streamed as they are computed. The `Accept` header
+//! picks the encoding: a JSON array of row objects by default,
+//! `application/x-ndjson` for one object per line, or
+//! `application/vnd.apache.arrow.stream` for an Arrow IPC stream.
+//!
+//! Errors are returned as `{"error": "..."}` with a 4xx or 5xx status. An
+//! error after the first batch was sent can only end the response early.
+
+use super::{CubeCatalog, QueryRequest, PRINCIPAL_HEADER};
+use crate::error::{Error, Result};
+use crate::query::QueryStream;
+use arrow::datatypes::Schema as ArrowSchema;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+
+/// Media type of Arrow IPC streams
+pub const ARROW_STREAM_MEDIA_TYPE: &str = "application/vnd.apache.arrow.stream";
+
+/// Media type of newline-delimited JSON
+pub const NDJSON_MEDIA_TYPE: &str = "application/x-ndjson";
+
+/// HTTP service answering queries against a [`CubeCatalog`]
+///
+/// # Example
+/// ```rust,ignore
+/// RestServer::new(catalog).serve("127.0.0.1:8080".parse()?).await?;
+/// ```
+///
+/// ```bash
+/// curl localhost:8080/cubes/sales/query \
+///     -H 'Content-Type: application/json' \
+///     -d '{"select": ["region", "SUM(amount) AS total"], "group_by": ["region"]}'
+/// ```
+#[derive(Debug, Clone)]
+pub struct RestServer {
+    catalog: CubeCatalog,
+}
+
+impl RestServer {
+    /// Create a service for the cubes of `catalog`
+    pub fn new(catalog: CubeCatalog) -> Self {
+        Self { catalog }
+    }
+
+    /// The catalog being served
+    pub fn catalog(&self) -> &CubeCatalog {
+        &self.catalog
+    }
+
+    /// The API routes, e.g. to nest under a prefix of an existing axum app
+    pub fn router(self) -> Router {
+        let router = Router::new()
+            .route("/cubes", get(list_cubes))
+            .route("/cubes/{name}", get(cube_schema))
+            .route("/cubes/{name}/query", post(query_cube));
+        #[cfg(feature = "graphql")]
+        let router = router.route("/graphql", get(graphql_sdl).post(graphql_query));
+        router.with_state(self.catalog)
+    }
+
+    /// Listen on `addr` and serve HTTP requests until the server fails
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| Error::io(format!("Failed to listen on {}: {}", addr, e)))?;
+        axum::serve(listener, self.router())
+            .await
+            .map_err(|e| Error::io(format!("HTTP server on {} failed: {}", addr, e)))
+    }
+}
+
+/// Entry of the `/cubes` listing
+#[derive(Debug, Serialize)]
+struct CubeSummary {
+    name: String,
+    row_count: usize,
+}
+
+async fn list_cubes(State(catalog): State<CubeCatalog>) -> Json<Vec<CubeSummary>> {
+    let cubes = catalog
+        .names()
+        .into_iter()
+        .filter_map(|name| {
+            let row_count = catalog.snapshot(&name)?.row_count();
+            Some(CubeSummary { name, row_count })
+        })
+        .collect();
+    Json(cubes)
+}
+
+async fn cube_schema(
+    State(catalog): State<CubeCatalog>,
+    Path(name): Path<String>,
+) -> std::result::Result<Json<Value>, ApiError> {
+    let cube = catalog.snapshot(&name).ok_or_else(|| ApiError::not_found(&name))?;
+    let columns: Vec<Value> = cube
+        .arrow_schema()
+        .fields()
+        .iter()
+        .map(|field| {
+            json!({
+                "name": field.name(),
+                "type": field.data_type().to_string(),
+                "nullable": field.is_nullable(),
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "name": name,
+        "row_count": cube.row_count(),
+        "columns": columns,
+        "schema": serde_json::to_value(cube.schema()).map_err(|e| ApiError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("Failed to serialize schema: {}", e),
+        })?,
+    })))
+}
+
+async fn query_cube(
+    State(catalog): State<CubeCatalog>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<QueryRequest>,
+) -> std::result::Result<Response, ApiError> {
+    let cube = catalog.snapshot(&name).ok_or_else(|| ApiError::not_found(&name))?;
+    let mut query = request.apply(cube.query()?);
+    if let Some(principal) = headers.get(PRINCIPAL_HEADER).and_then(|v| v.to_str().ok()) {
+        query = query.as_principal(principal);
+    }
+    let stream = query.execute_stream().await?;
+
+    let format = ResultFormat::from_headers(&headers);
+    let body = encode_body(stream, format)?;
+    Ok((
+        [(header::CONTENT_TYPE, HeaderValue::from_static(format.media_type()))],
+        body,
+    )
+        .into_response())
+}
+
+/// The schema is rebuilt for every request so it follows cubes being
+/// registered and replaced
+#[cfg(feature = "graphql")]
+async fn graphql_query(
+    State(catalog): State<CubeCatalog>,
+    Json(request): Json<async_graphql::Request>,
+) -> std::result::Result<Json<async_graphql::Response>, ApiError> {
+    let schema = super::graphql::schema(&catalog)?;
+    Ok(Json(schema.execute(request).await))
+}
+
+#[cfg(feature = "graphql")]
+async fn graphql_sdl(
+    State(catalog): State<CubeCatalog>,
+) -> std::result::Result<String, ApiError> {
+    Ok(super::graphql::schema(&catalog)?.sdl())
+}
+
+/// Encoding of a query result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResultFormat {
+    Json,
+    NdJson,
+    Arrow,
+}
+
+impl ResultFormat {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let accept = headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if accept.contains(ARROW_STREAM_MEDIA_TYPE) {
+            ResultFormat::Arrow
+        } else if accept.contains(NDJSON_MEDIA_TYPE) {
+            ResultFormat::NdJson
+        } else {
+            ResultFormat::Json
+        }
+    }
+
+    fn media_type(self) -> &'static str {
+        match self {
+            ResultFormat::Json => "application/json",
+            ResultFormat::NdJson => NDJSON_MEDIA_TYPE,
+            ResultFormat::Arrow => ARROW_STREAM_MEDIA_TYPE,
+        }
+    }
+}
+
+/// Incremental encoder turning batches into body chunks
+enum Encoder {
+    /// JSON array; counts rows to place the separators
+    Json { rows: usize },
+    NdJson,
+    Arrow(StreamWriter<Vec<u8>>),
+}
+
+impl Encoder {
+    /// Create an encoder and the chunk that opens the body
+    fn start(format: ResultFormat, schema: &ArrowSchema) -> Result<(Self, Vec<u8>)> {
+        Ok(match format {
+            ResultFormat::Json => (Encoder::Json { rows: 0 }, b"[".to_vec()),
+            ResultFormat::NdJson => (Encoder::NdJson, Vec::new()),
+            ResultFormat::Arrow => {
+                let mut writer = StreamWriter::try_new(Vec::new(), schema)?;
+                let header = std::mem::take(writer.get_mut());
+                (Encoder::Arrow(writer), header)
+            }
+        })
+    }
+
+    fn batch(&mut self, batch: &RecordBatch) -> Result<Vec<u8>> {
+        match self {
+            Encoder::Json { rows } => {
+                let mut chunk = Vec::new();
+                for line in ndjson(batch)?.split(|b| *b == b'\n') {
+                    if line.is_empty() {
+                        continue;
+                    }
+                    if *rows > 0 {
+                        chunk.push(b',');
+                    }
+                    chunk.extend_from_slice(line);
+                    *rows += 1;
+                }
+                Ok(chunk)
+            }
+            Encoder::NdJson => ndjson(batch),
+            Encoder::Arrow(writer) => {
+                writer.write(batch)?;
+                Ok(std::mem::take(writer.get_mut()))
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        match self {
+            Encoder::Json { .. } => Ok(b"]".to_vec()),
+            Encoder::NdJson => Ok(Vec::new()),
+            Encoder::Arrow(writer) => {
+                writer.finish()?;
+                Ok(std::mem::take(writer.get_mut()))
+            }
+        }
+    }
+}
+
+/// Encode a batch as one JSON object per line, writing NULLs explicitly
+fn ndjson(batch: &RecordBatch) -> Result<Vec<u8>> {
+    let mut writer = arrow_json::WriterBuilder::new()
+        .with_explicit_nulls(true)
+        .build::<_, arrow_json::writer::LineDelimited>(Vec::new());
+    writer.write(batch)?;
+    writer.finish()?;
+    Ok(writer.into_inner())
+}
+
+fn encode_body(stream: QueryStream, format: ResultFormat) -> Result<Body> {
+    let (encoder, header) = Encoder::start(format, &stream.schema())?;
+    let chunks = futures::stream::try_unfold(
+        (stream, Some(encoder)),
+        |(mut stream, encoder)| async move {
+            let Some(mut encoder) = encoder else {
+                return Ok(None);
+            };
+            match stream.next().await {
+                Some(batch) => {
+                    let chunk = encoder.batch(&batch?)?;
+                    Ok(Some((chunk, (stream, Some(encoder)))))
+                }
+                None => Ok(Some((encoder.finish()?, (stream, None)))),
+            }
+        },
+    );
+    let body = futures::stream::once(async move { Ok::<_, Error>(header) }).chain(chunks);
+    Ok(Body::from_stream(body))
+}
+
+/// Error response of the API
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn not_found(cube: &str) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: format!("Cube '{}' not found", cube),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        let status = match error {
+            Error::Query(_) | Error::Schema(_) | Error::Dimension(_) | Error::Measure(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self {
+            status,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ElastiCubeBuilder;
+    use crate::cube::AggFunc;
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field};
+    use axum::http::Request;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn router() -> Router {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("amount", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["North", "South", "North"])),
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0])),
+            ],
+        )
+        .unwrap();
+        let cube = ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_measure("amount", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .load_record_batches(schema, vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let catalog = CubeCatalog::new();
+        catalog.register("sales", cube).unwrap();
+        RestServer::new(catalog).router()
+    }
+
+    async fn send(request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = router().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, body.to_vec())
+    }
+
+    fn get(path: &str) -> Request<Body> {
+        Request::get(path).body(Body::empty()).unwrap()
+    }
+
+    fn query(body: Value, accept: &str) -> Request<Body> {
+        Request::post("/cubes/sales/query")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, accept)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rest_metadata() {
+        let (status, body) = send(get("/cubes")).await;
+        assert_eq!(status, StatusCode::OK);
+        let cubes: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(cubes, json!([{ "name": "sales", "row_count": 3 }]));
+
+        let (status, body) = send(get("/cubes/sales")).await;
+        assert_eq!(status, StatusCode::OK);
+        let schema: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(schema["columns"][1]["name"], "amount");
+        assert_eq!(schema["columns"][1]["type"], "Float64");
+
+        let (status, _) = send(get("/cubes/missing")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_rest_query_formats() {
+        let request = json!({
+            "select": ["region", "SUM(amount) AS total"],
+            "group_by": ["region"],
+            "order_by": ["region"],
+        });
+
+        let (status, body) = send(query(request.clone(), "application/json")).await;
+        assert_eq!(status, StatusCode::OK);
+        let rows: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            rows,
+            json!([
+                { "region": "North", "total": 4.0 },
+                { "region": "South", "total": 2.0 },
+            ])
+        );
+
+        let (_, body) = send(query(request.clone(), NDJSON_MEDIA_TYPE)).await;
+        assert_eq!(String::from_utf8(body).unwrap().lines().count(), 2);
+
+        let (_, body) = send(query(request, ARROW_STREAM_MEDIA_TYPE)).await;
+        let reader =
+            arrow::ipc::reader::StreamReader::try_new(std::io::Cursor::new(body), None).unwrap();
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(rows, 2);
+
+        let bad = json!({ "select": ["nope"] });
+        let (status, body) = send(query(bad, "application/json")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error: Value = serde_json::from_slice(&body).unwrap();
+        assert!(error["error"].as_str().unwrap().contains("nope"));
+    }
+}
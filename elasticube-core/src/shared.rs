@@ -0,0 +1,261 @@
+//! Concurrent-safe shared cube handle
+//!
+//! Queries take an `Arc<ElastiCube>` while updates need `&mut ElastiCube`,
+//! which forces applications that serve queries and ingest data at the same
+//! time to clone whole cubes around. [`SharedCube`] holds the current cube
+//! behind an `Arc` that readers grab cheaply; writers modify a private copy
+//! and swap it in atomically when done. Queries already running keep the
+//! version they started with.
+
+use crate::cube::ElastiCube;
+use crate::error::Result;
+use crate::frozen::FrozenCube;
+use crate::query::QueryBuilder;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, RwLock};
+use tokio::sync::{Mutex, MutexGuard};
+
+/// A cube shared between concurrent readers and writers
+///
+/// Cloning a `SharedCube` is cheap and every clone sees the same data.
+///
+/// # Example
+/// ```rust,ignore
+/// let shared = SharedCube::new(cube);
+///
+/// // Readers query the current version without blocking writers
+/// let result = shared.query()?.select(&["region", "SUM(sales)"]).execute().await?;
+///
+/// // Writers stage changes and publish them at once
+/// let mut writer = shared.write().await;
+/// writer.append_rows(batch)?;
+/// writer.delete_rows("date < '2024-01-01'").await?;
+/// writer.commit();
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedCube {
+    inner: Arc<SharedInner>,
+}
+
+#[derive(Debug)]
+struct SharedInner {
+    /// Published version; the lock is only held to read or replace the `Arc`
+    current: RwLock<Arc<ElastiCube>>,
+
+    /// Serializes writers so concurrent updates are not lost
+    write_lock: Mutex<()>,
+}
+
+impl SharedCube {
+    /// Share a cube
+    pub fn new(cube: ElastiCube) -> Self {
+        Self {
+            inner: Arc::new(SharedInner {
+                current: RwLock::new(Arc::new(cube)),
+                write_lock: Mutex::new(()),
+            }),
+        }
+    }
+
+    /// The currently published cube
+    ///
+    /// The returned snapshot is unaffected by later commits.
+    pub fn snapshot(&self) -> Arc<ElastiCube> {
+        self.inner
+            .current
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// The currently published cube as a read-only handle
+    ///
+    /// Like [`snapshot`](Self::snapshot), but without a way to modify the
+    /// copy either.
+    pub fn freeze(&self) -> FrozenCube {
+        FrozenCube::from(self.snapshot())
+    }
+
+    /// Start a query against the currently published cube
+    pub fn query(&self) -> Result<QueryBuilder> {
+        self.snapshot().query()
+    }
+
+    /// Number of rows in the currently published cube
+    pub fn row_count(&self) -> usize {
+        self.snapshot().row_count()
+    }
+
+    /// Start an update
+    ///
+    /// Waits for any other writer to finish, then returns a guard holding a
+    /// copy of the current cube. Changes made through the guard are published
+    /// by [`CubeWriter::commit`] and discarded if the guard is dropped.
+    /// Copying is cheap because the Arrow buffers are shared.
+    pub async fn write(&self) -> CubeWriter<'_> {
+        let guard = self.inner.write_lock.lock().await;
+        let staged = (*self.snapshot()).clone();
+        CubeWriter {
+            shared: self,
+            staged,
+            _guard: guard,
+        }
+    }
+
+    /// Apply a synchronous update and publish it
+    ///
+    /// Nothing is published if `update` returns an error.
+    pub async fn update<T>(&self, update: impl FnOnce(&mut ElastiCube) -> Result<T>) -> Result<T> {
+        let mut writer = self.write().await;
+        let value = update(&mut writer)?;
+        writer.commit();
+        Ok(value)
+    }
+
+    /// Publish an entirely new cube, e.g. one rebuilt from its sources
+    pub async fn replace(&self, cube: ElastiCube) {
+        let _guard = self.inner.write_lock.lock().await;
+        self.publish(cube);
+    }
+
+    fn publish(&self, cube: ElastiCube) {
+        *self
+            .inner
+            .current
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(cube);
+    }
+}
+
+impl From<ElastiCube> for SharedCube {
+    fn from(cube: ElastiCube) -> Self {
+        Self::new(cube)
+    }
+}
+
+/// Exclusive update access to a [`SharedCube`], created by [`SharedCube::write`]
+///
+/// Dereferences to the staged [`ElastiCube`], so all update methods are
+/// available on it.
+#[derive(Debug)]
+pub struct CubeWriter<'a> {
+    shared: &'a SharedCube,
+    staged: ElastiCube,
+    _guard: MutexGuard<'a, ()>,
+}
+
+impl CubeWriter<'_> {
+    /// Publish the staged cube to all readers
+    pub fn commit(self) {
+        self.shared.publish(self.staged);
+    }
+
+    /// Discard the staged changes
+    pub fn rollback(self) {}
+}
+
+impl Deref for CubeWriter<'_> {
+    type Target = ElastiCube;
+
+    fn deref(&self) -> &ElastiCube {
+        &self.staged
+    }
+}
+
+impl DerefMut for CubeWriter<'_> {
+    fn deref_mut(&mut self) -> &mut ElastiCube {
+        &mut self.staged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ElastiCubeBuilder;
+    use crate::cube::AggFunc;
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+    use arrow::record_batch::RecordBatch;
+
+    fn sales_batch(schema: &Arc<ArrowSchema>, regions: Vec<&str>, sales: Vec<f64>) -> RecordBatch {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(regions)),
+                Arc::new(Float64Array::from(sales)),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_shared_cube_readers_and_writers() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("sales", DataType::Float64, false),
+        ]));
+        let cube = ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_measure("sales", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .load_record_batches(
+                schema.clone(),
+                vec![sales_batch(&schema, vec!["North", "South"], vec![1.0, 2.0])],
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let shared = SharedCube::new(cube);
+        let before = shared.snapshot();
+
+        // Staged changes are invisible until committed
+        let mut writer = shared.write().await;
+        writer
+            .append_rows(sales_batch(&schema, vec!["East"], vec![3.0]))
+            .unwrap();
+        writer.delete_rows("region = 'North'").await.unwrap();
+        assert_eq!(shared.row_count(), 2);
+        writer.commit();
+
+        assert_eq!(shared.row_count(), 2);
+        assert_eq!(before.row_count(), 2);
+        let result = shared
+            .query()
+            .unwrap()
+            .select(&["SUM(sales) AS total"])
+            .execute()
+            .await
+            .unwrap();
+        let total = result.batches()[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(total, 5.0);
+
+        // Concurrent writers are serialized, so no update is lost
+        let tasks: Vec<_> = (0..4)
+            .map(|i| {
+                let shared = shared.clone();
+                let batch = sales_batch(&schema, vec!["West"], vec![i as f64]);
+                tokio::spawn(async move { shared.update(|cube| cube.append_rows(batch)).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        assert_eq!(shared.row_count(), 6);
+
+        // Failed updates publish nothing
+        let failed = shared
+            .update(|cube| {
+                cube.append_rows(sales_batch(&schema, vec!["North"], vec![9.0]))?;
+                Err::<(), _>(crate::Error::data("abort"))
+            })
+            .await;
+        assert!(failed.is_err());
+        assert_eq!(shared.row_count(), 6);
+    }
+}
@@ -1,7 +1,300 @@
 //! Storage backend for ElastiCube data
+//!
+//! Cubes are persisted as a directory holding two files:
+//!
+//! - `schema.json`: the [`CubeSchema`] (dimensions, measures, hierarchies,
+//!   calculated measures and virtual dimensions) as written by
+//!   [`CubeSchema::to_json`]
+//! - `data.arrow`: the data batches in the Arrow IPC file format
+//!
+//! See [`ElastiCube::save`] and [`ElastiCube::load`].
+//!
+//! In memory, a cube can be partitioned by a column so that every batch
+//! holds a single value of it (see [`ElastiCube::partition_by`]). Deletes and
+//! filtered queries on that column can then drop or skip whole batches.
+//!
+//! Cubes built with `ElastiCubeBuilder::load_parquet_lazy` keep no batches
+//! at all: queries read their Parquet files through a DataFusion listing
+//! table, so the data can be far larger than memory.
 
-// Placeholder module for storage functionality
-// Will be implemented in Phase 1.3 (Arrow Integration)
+use crate::cube::{BatchCompression, CubeSchema, ElastiCube};
+use crate::error::{Error, Result};
+use arrow::array::UInt32Array;
+use arrow::ipc::reader::{FileReader, StreamReader};
+use arrow::datatypes::SchemaRef;
+use arrow::ipc::writer::{FileWriter, IpcWriteOptions, StreamWriter};
+use arrow::record_batch::RecordBatch;
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use indexmap::IndexMap;
+use datafusion::catalog::TableProvider;
+use datafusion::datasource::file_format::parquet::ParquetFormat;
+use datafusion::datasource::listing::{
+    ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// File holding the cube schema
+const SCHEMA_FILE: &str = "schema.json";
+
+/// File holding the cube data
+const DATA_FILE: &str = "data.arrow";
+
+/// Write a cube's schema and data into `dir`, creating it if needed
+pub(crate) fn save_cube(cube: &ElastiCube, dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+
+    std::fs::write(dir.join(SCHEMA_FILE), cube.schema().to_json()?)?;
+
+    let file = BufWriter::new(File::create(dir.join(DATA_FILE))?);
+    let mut writer = FileWriter::try_new(file, cube.arrow_schema())?;
+    for batch in &cube.materialized_batches()? {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Read a cube previously written by [`save_cube`]
+pub(crate) fn load_cube(dir: &Path) -> Result<ElastiCube> {
+    let json = std::fs::read_to_string(dir.join(SCHEMA_FILE)).map_err(|e| {
+        Error::io(format!(
+            "Failed to read cube schema in '{}': {}",
+            dir.display(),
+            e
+        ))
+    })?;
+    let schema = CubeSchema::from_json(&json)?;
+
+    let reader = FileReader::try_new(BufReader::new(File::open(dir.join(DATA_FILE))?), None)?;
+    let arrow_schema = reader.schema();
+    let data = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+
+    ElastiCube::new(schema, Arc::clone(&arrow_schema), data)
+}
+
+/// Settings for [`ElastiCube::export_parquet_with`]
+#[derive(Debug, Clone)]
+pub struct ParquetExportOptions {
+    /// Compression codec for all columns (default: Snappy)
+    pub compression: Compression,
+
+    /// Maximum rows per row group (None = the Parquet writer's default)
+    pub max_row_group_size: Option<usize>,
+}
+
+impl Default for ParquetExportOptions {
+    fn default() -> Self {
+        Self {
+            compression: Compression::SNAPPY,
+            max_row_group_size: None,
+        }
+    }
+}
+
+impl ParquetExportOptions {
+    /// Set the compression codec
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set the maximum number of rows per row group
+    pub fn with_max_row_group_size(mut self, rows: usize) -> Self {
+        self.max_row_group_size = Some(rows);
+        self
+    }
+
+    fn writer_properties(&self) -> WriterProperties {
+        let mut builder = WriterProperties::builder().set_compression(self.compression);
+        if let Some(rows) = self.max_row_group_size {
+            builder = builder.set_max_row_group_size(rows);
+        }
+        builder.build()
+    }
+}
+
+/// Write a cube's data to a single Parquet file
+pub(crate) fn export_parquet(
+    cube: &ElastiCube,
+    path: &Path,
+    options: &ParquetExportOptions,
+) -> Result<()> {
+    if options.max_row_group_size == Some(0) {
+        return Err(Error::config("Parquet row group size must be greater than zero"));
+    }
+    write_parquet_file(
+        path,
+        cube.arrow_schema().clone(),
+        &cube.materialized_batches()?,
+        options.writer_properties(),
+    )
+}
+
+/// Write batches to a Parquet file with the given writer settings
+pub(crate) fn write_parquet_file(
+    path: &Path,
+    schema: SchemaRef,
+    batches: &[RecordBatch],
+    props: WriterProperties,
+) -> Result<()> {
+    let file = File::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, schema, Some(props))
+        .map_err(|e| Error::io(format!("Failed to create Parquet writer: {}", e)))?;
+    for batch in batches {
+        writer
+            .write(batch)
+            .map_err(|e| Error::io(format!("Failed to write Parquet data: {}", e)))?;
+    }
+    writer
+        .close()
+        .map_err(|e| Error::io(format!("Failed to finish Parquet file: {}", e)))?;
+    Ok(())
+}
+
+/// Split batches so that each holds a single value of `column`
+///
+/// Batches that already hold one value are kept as they are; the rows of
+/// mixed batches are grouped by value in order of first appearance. NULL is
+/// treated as a value of its own.
+pub(crate) fn partition_batches(
+    batches: &[RecordBatch],
+    column: &str,
+) -> Result<Vec<RecordBatch>> {
+    let mut partitioned = Vec::with_capacity(batches.len());
+    for batch in batches {
+        let keys = partition_keys(batch, column)?;
+        if keys.iter().all(|key| key == &keys[0]) {
+            partitioned.push(batch.clone());
+            continue;
+        }
+
+        let mut groups: IndexMap<OwnedRow, Vec<u32>> = IndexMap::new();
+        for (row, key) in keys.into_iter().enumerate() {
+            groups.entry(key).or_default().push(row as u32);
+        }
+        for indices in groups.into_values() {
+            partitioned.push(arrow::compute::take_record_batch(
+                batch,
+                &UInt32Array::from(indices),
+            )?);
+        }
+    }
+    Ok(partitioned)
+}
+
+/// Number of distinct `column` values across batches partitioned by it
+pub(crate) fn count_partitions(batches: &[RecordBatch], column: &str) -> Result<usize> {
+    let mut values = std::collections::HashSet::new();
+    for batch in batches {
+        if let Some(key) = partition_keys(&batch.slice(0, batch.num_rows().min(1)), column)?
+            .into_iter()
+            .next()
+        {
+            values.insert(key);
+        }
+    }
+    Ok(values.len())
+}
+
+/// Comparable encoding of every row's `column` value
+fn partition_keys(batch: &RecordBatch, column: &str) -> Result<Vec<OwnedRow>> {
+    let array = batch.column_by_name(column).ok_or_else(|| {
+        Error::schema(format!("Partition column '{}' not found in cube data", column))
+    })?;
+    let converter = RowConverter::new(vec![SortField::new(array.data_type().clone())])?;
+    let rows = converter.convert_columns(&[array.clone()])?;
+    Ok(rows.iter().map(|row| row.owned()).collect())
+}
+
+/// Encode a batch as an Arrow IPC stream with compressed buffers
+pub(crate) fn compress_batch(batch: &RecordBatch, compression: BatchCompression) -> Result<Vec<u8>> {
+    let options = IpcWriteOptions::default().try_with_compression(Some(compression.ipc_type()))?;
+    let mut writer = StreamWriter::try_new_with_options(Vec::new(), &batch.schema(), options)?;
+    writer.write(batch)?;
+    Ok(writer.into_inner()?)
+}
+
+/// Decode a batch written by [`compress_batch`]
+pub(crate) fn decompress_batch(bytes: &[u8]) -> Result<RecordBatch> {
+    let mut reader = StreamReader::try_new(bytes, None)?;
+    reader
+        .next()
+        .transpose()?
+        .ok_or_else(|| Error::data("Compressed batch holds no data"))
+}
+
+/// Parquet files at `path`: the file itself, or every `.parquet` file below
+/// a directory, in path order
+pub(crate) fn parquet_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir).map_err(|e| {
+            Error::io(format!("Failed to read directory '{}': {}", dir.display(), e))
+        })?;
+        for entry in entries {
+            let entry_path = entry?.path();
+            if entry_path.is_dir() {
+                pending.push(entry_path);
+            } else if entry_path.extension().is_some_and(|e| e == "parquet") {
+                files.push(entry_path);
+            }
+        }
+    }
+    files.sort();
+
+    if files.is_empty() {
+        return Err(Error::data(format!(
+            "No Parquet files found at '{}'",
+            path.display()
+        )));
+    }
+    Ok(files)
+}
+
+/// Arrow schema of the first file and total row count of Parquet files,
+/// read from their footers only
+pub(crate) fn inspect_parquet(files: &[PathBuf]) -> Result<(SchemaRef, usize)> {
+    let mut schema = None;
+    let mut rows = 0;
+    for file in files {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(file)?).map_err(|e| {
+            Error::io(format!("Failed to read Parquet file '{}': {}", file.display(), e))
+        })?;
+        rows += reader.metadata().file_metadata().num_rows() as usize;
+        schema.get_or_insert_with(|| reader.schema().clone());
+    }
+    let schema = schema.ok_or_else(|| Error::data("No Parquet files to read"))?;
+    Ok((schema, rows))
+}
+
+/// A DataFusion table reading the Parquet files at `path` on demand
+pub(crate) fn parquet_table(path: &str, schema: SchemaRef) -> Result<Arc<dyn TableProvider>> {
+    // Listing table URLs treat a trailing slash as "directory"
+    let location = if Path::new(path).is_dir() && !path.ends_with('/') {
+        format!("{}/", path)
+    } else {
+        path.to_string()
+    };
+    let url = ListingTableUrl::parse(&location)?;
+    let options = ListingOptions::new(Arc::new(ParquetFormat::default()))
+        .with_file_extension(".parquet");
+    let config = ListingTableConfig::new(url)
+        .with_listing_options(options)
+        .with_schema(schema);
+    Ok(Arc::new(ListingTable::try_new(config)?))
+}
 
 /// Storage backend using Apache Arrow
 #[derive(Debug)]
@@ -21,3 +314,117 @@ impl Default for ArrowStorage {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::ElastiCubeBuilder;
+    use crate::cube::{AggFunc, ElastiCube};
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+    use arrow::record_batch::RecordBatch;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_save_and_load_round_trip() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("sales", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["North", "South", "North"])),
+                Arc::new(Float64Array::from(vec![10.0, 20.0, 30.0])),
+            ],
+        )
+        .unwrap();
+        let cube = ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_measure("sales", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .add_calculated_measure("double_sales", "sales * 2", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .load_record_batches(schema, vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sales.cube");
+        cube.save(&path).unwrap();
+
+        let loaded = Arc::new(ElastiCube::load(&path).unwrap());
+        assert_eq!(loaded.schema().name(), "sales");
+        assert_eq!(loaded.row_count(), 3);
+        assert_eq!(loaded.arrow_schema(), cube.arrow_schema());
+        assert!(loaded.schema().get_calculated_measure("double_sales").is_some());
+
+        let result = loaded
+            .query()
+            .unwrap()
+            .select(&["SUM(double_sales) AS total"])
+            .execute()
+            .await
+            .unwrap();
+        let total = result.batches()[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(total, 120.0);
+
+        assert!(ElastiCube::load(dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_export_parquet() {
+        use super::ParquetExportOptions;
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("region", DataType::Utf8, false),
+            Field::new("sales", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["North", "South", "East", "West", "North"])),
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0, 4.0, 5.0])),
+            ],
+        )
+        .unwrap();
+        let cube = ElastiCubeBuilder::new("sales")
+            .add_dimension("region", DataType::Utf8)
+            .unwrap()
+            .add_measure("sales", DataType::Float64, AggFunc::Sum)
+            .unwrap()
+            .load_record_batches(schema, vec![batch])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sales.parquet");
+        cube.export_parquet_with(
+            &path,
+            &ParquetExportOptions::default()
+                .with_compression(parquet::basic::Compression::UNCOMPRESSED)
+                .with_max_row_group_size(2),
+        )
+        .unwrap();
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 5);
+        assert_eq!(metadata.num_row_groups(), 3);
+
+        assert!(cube
+            .export_parquet_with(
+                &path,
+                &ParquetExportOptions::default().with_max_row_group_size(0)
+            )
+            .is_err());
+    }
+}
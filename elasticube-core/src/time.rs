@@ -0,0 +1,11 @@
+//! Clock types that also work in the browser
+//!
+//! `std::time::Instant::now()` and `SystemTime::now()` panic on
+//! `wasm32-unknown-unknown`, so on that target the crate reads the clock
+//! through `web-time`, which uses `performance.now()` and `Date.now()`.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(target_arch = "wasm32")]
+pub use web_time::{Duration, Instant, SystemTime, UNIX_EPOCH};
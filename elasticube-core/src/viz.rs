@@ -0,0 +1,213 @@
+//! Terminal charts for query results
+//!
+//! Quick text visualizations for examples and command-line use: a
+//! horizontal bar chart per category and a simple line chart for trends.
+//! Both return the chart as a `String` ready to print.
+
+use crate::error::{Error, Result};
+use crate::query::QueryResult;
+use arrow::array::{Array, Float64Array};
+use arrow::datatypes::DataType;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+
+/// Width in characters of the longest bar
+const BAR_WIDTH: usize = 40;
+
+/// Height in lines of a line chart
+const LINE_HEIGHT: usize = 10;
+
+/// Most points drawn in a line chart; longer series are sampled evenly
+const MAX_LINE_POINTS: usize = 80;
+
+impl QueryResult {
+    /// Render a horizontal bar chart of `measure_col` for each `dim_col` value
+    ///
+    /// Bars are scaled so the largest value spans the full width; negative
+    /// values get an empty bar. Rows with a NULL measure are skipped.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// println!("{}", result.bar_chart("region", "total")?);
+    /// // East  ████████████████████████████ 175
+    /// // North ████████████████████████████████████████ 250
+    /// ```
+    pub fn bar_chart(&self, dim_col: &str, measure_col: &str) -> Result<String> {
+        let points = self.chart_points(dim_col, measure_col)?;
+        let label_width = points
+            .iter()
+            .map(|(label, _)| label.chars().count())
+            .max()
+            .unwrap_or(0);
+        let max = points.iter().map(|(_, v)| *v).fold(0.0_f64, f64::max);
+
+        let mut out = String::new();
+        for (label, value) in &points {
+            let length = if max > 0.0 && *value > 0.0 {
+                ((value / max) * BAR_WIDTH as f64).round() as usize
+            } else {
+                0
+            };
+            out.push_str(&format!(
+                "{:<width$} {} {}\n",
+                label,
+                "█".repeat(length),
+                value,
+                width = label_width
+            ));
+        }
+        Ok(out)
+    }
+
+    /// Render a line chart of `y_col` against the row order of `x_col`
+    ///
+    /// Points are plotted left to right in result order, so order the query
+    /// by `x_col`. The y-axis is labelled with the minimum and maximum and
+    /// the x-axis with the first and last `x_col` values.
+    pub fn line_chart(&self, x_col: &str, y_col: &str) -> Result<String> {
+        let mut points = self.chart_points(x_col, y_col)?;
+        if points.is_empty() {
+            return Ok(String::new());
+        }
+        if points.len() > MAX_LINE_POINTS {
+            let step = points.len() as f64 / MAX_LINE_POINTS as f64;
+            points = (0..MAX_LINE_POINTS)
+                .map(|i| points[(i as f64 * step) as usize].clone())
+                .collect();
+        }
+
+        let min = points.iter().map(|(_, v)| *v).fold(f64::INFINITY, f64::min);
+        let max = points.iter().map(|(_, v)| *v).fold(f64::NEG_INFINITY, f64::max);
+        let range = max - min;
+
+        let mut grid = vec![vec![' '; points.len()]; LINE_HEIGHT];
+        for (column, (_, value)) in points.iter().enumerate() {
+            let level = if range > 0.0 {
+                (((value - min) / range) * (LINE_HEIGHT - 1) as f64).round() as usize
+            } else {
+                0
+            };
+            grid[LINE_HEIGHT - 1 - level][column] = '*';
+        }
+
+        let max_label = max.to_string();
+        let min_label = min.to_string();
+        let axis_width = max_label.len().max(min_label.len());
+
+        let mut out = String::new();
+        for (line, cells) in grid.iter().enumerate() {
+            let label = match line {
+                0 => max_label.as_str(),
+                l if l == LINE_HEIGHT - 1 => min_label.as_str(),
+                _ => "",
+            };
+            let cells: String = cells.iter().collect();
+            out.push_str(&format!("{:>width$} │{}\n", label, cells, width = axis_width));
+        }
+        out.push_str(&format!(
+            "{:>width$} └{}\n",
+            "",
+            "─".repeat(points.len()),
+            width = axis_width
+        ));
+
+        let first = &points[0].0;
+        let last = &points[points.len() - 1].0;
+        let gap = points
+            .len()
+            .saturating_sub(first.chars().count())
+            .max(last.chars().count() + 1);
+        out.push_str(&format!(
+            "{:>width$}  {}{:>gap$}\n",
+            "",
+            first,
+            last,
+            width = axis_width,
+            gap = gap
+        ));
+        Ok(out)
+    }
+
+    /// Collect (label, value) pairs from two columns, skipping NULL values
+    fn chart_points(&self, label_col: &str, value_col: &str) -> Result<Vec<(String, f64)>> {
+        let format_options = FormatOptions::default();
+        let mut points = Vec::with_capacity(self.row_count());
+
+        for batch in self.batches() {
+            let column = |name: &str| {
+                batch
+                    .column_by_name(name)
+                    .ok_or_else(|| Error::query(format!("Column '{}' not found in result", name)))
+            };
+            let labels = ArrayFormatter::try_new(column(label_col)?.as_ref(), &format_options)?;
+            let values = arrow::compute::cast(column(value_col)?, &DataType::Float64)
+                .map_err(|e| {
+                    Error::query(format!("Column '{}' is not numeric: {}", value_col, e))
+                })?;
+            let values = values
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .ok_or_else(|| Error::query(format!("Column '{}' is not numeric", value_col)))?;
+
+            for row in 0..batch.num_rows() {
+                if values.is_valid(row) {
+                    points.push((labels.value(row).to_string(), values.value(row)));
+                }
+            }
+        }
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{Field, Schema as ArrowSchema};
+    use arrow::record_batch::RecordBatch;
+    use std::sync::Arc;
+
+    fn series(labels: Vec<&str>, values: Vec<Option<i64>>) -> QueryResult {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("label", DataType::Utf8, false),
+            Field::new("value", DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(labels)),
+                Arc::new(Int64Array::from(values)),
+            ],
+        )
+        .unwrap();
+        QueryResult::from_batches(vec![batch])
+    }
+
+    #[test]
+    fn test_bar_chart() {
+        let result = series(vec!["East", "North", "West"], vec![Some(20), Some(40), None]);
+        let chart = result.bar_chart("label", "value").unwrap();
+        let lines: Vec<&str> = chart.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], format!("East  {} 20", "█".repeat(20)));
+        assert_eq!(lines[1], format!("North {} 40", "█".repeat(40)));
+
+        assert!(result.bar_chart("label", "missing").is_err());
+    }
+
+    #[test]
+    fn test_line_chart() {
+        let result = series(
+            vec!["Jan", "Feb", "Mar", "Apr"],
+            vec![Some(1), Some(10), Some(5), Some(10)],
+        );
+        let chart = result.line_chart("label", "value").unwrap();
+        let lines: Vec<&str> = chart.lines().collect();
+
+        assert_eq!(lines.len(), LINE_HEIGHT + 2);
+        assert_eq!(lines[0], "10 │ * *");
+        assert_eq!(lines[LINE_HEIGHT - 1], " 1 │*   ");
+        assert!(lines[LINE_HEIGHT + 1].trim_start().starts_with("Jan"));
+        assert!(lines[LINE_HEIGHT + 1].ends_with("Apr"));
+    }
+}
@@ -23,6 +23,7 @@ Example:
 
 from ._elasticube import (
     PyElastiCubeBuilder as ElastiCubeBuilder,
+    PyCubeTemplate as CubeTemplate,
     PyElastiCube as ElastiCube,
     PyQueryBuilder as QueryBuilder,
 )
@@ -44,6 +45,7 @@ _jupyter_enabled = enable_jupyter_integration()
 __version__ = "1.1.0"
 __all__ = [
     "ElastiCubeBuilder",
+    "CubeTemplate",
     "ElastiCube",
     "QueryBuilder",
     "CubeVisualizer",
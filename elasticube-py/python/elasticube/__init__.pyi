@@ -1,6 +1,6 @@
 """Type stubs for elasticube"""
 
-from typing import List, Optional, Dict, Tuple, Any
+from typing import Callable, List, Optional, Dict, Tuple, Any
 import pyarrow as pa
 import pandas as pd
 
@@ -21,7 +21,15 @@ class ElastiCubeBuilder:
         """
         ...
 
-    def add_measure(self, name: str, data_type: str, agg_func: str) -> None:
+    def add_measure(
+        self,
+        name: str,
+        data_type: str,
+        agg_func: str,
+        format: Optional[str] = None,
+        unit: Optional[str] = None,
+        precision: Optional[int] = None,
+    ) -> None:
         """
         Add a measure to the cube.
 
@@ -29,6 +37,9 @@ class ElastiCubeBuilder:
             name: Name of the measure
             data_type: Data type (e.g., 'int32', 'float64')
             agg_func: Aggregation function ('sum', 'avg', 'min', 'max', 'count')
+            format: Display pattern (e.g., '$#,##0.00', '0.0%')
+            unit: Unit shown after values (e.g., 'kg')
+            precision: Number of decimals shown
         """
         ...
 
@@ -69,6 +80,20 @@ class ElastiCubeBuilder:
         """
         ...
 
+    def add_bridge_dimension(self, name: str, key: str, path: str) -> None:
+        """
+        Add a many-to-many dimension whose members come from a bridge file.
+
+        Grouping by the dimension counts each row once per member; filtering
+        on it alone keeps each matching row once.
+
+        Args:
+            name: Dimension name, also the member column of the bridge
+            key: Cube column the bridge rows refer to
+            path: CSV, Parquet or JSON file of (key, member) rows
+        """
+        ...
+
     def with_description(self, description: str) -> None:
         """
         Set the cube description.
@@ -78,6 +103,153 @@ class ElastiCubeBuilder:
         """
         ...
 
+    def with_timezone(self, timezone: str) -> None:
+        """
+        Express the cube's timestamps in a local time zone.
+
+        Timestamps without a zone are read as local time, and date parts
+        and time buckets follow local days.
+
+        Args:
+            timezone: IANA name such as "America/New_York" or an offset like "+02:00"
+        """
+        ...
+
+    def with_type_coercion(self, mode: str) -> None:
+        """
+        Cast loaded columns to the declared types instead of failing the build.
+
+        Args:
+            mode: "strict" (default), "safe" (fail on values that don't convert)
+                or "lenient" (values that don't convert become null)
+        """
+        ...
+
+    def map_column(self, source: str, column: str) -> None:
+        """
+        Load a source column into a cube column of another name.
+
+        Args:
+            source: Column name in the source, e.g. a CSV header
+            column: Declared dimension or measure it loads into
+        """
+        ...
+
+    def ignore_extra_columns(self, ignore: bool) -> None:
+        """
+        Drop source columns the cube doesn't use instead of keeping them.
+
+        Only applies when dimensions or measures are declared.
+        """
+        ...
+
+    def with_error_policy(self, policy: str, max: int = 1000) -> None:
+        """
+        Choose how rows that fail to parse are handled.
+
+        Args:
+            policy: "fail" (default) or "skip_bad_rows"; skipping is supported
+                by CSV sources and the skipped rows appear in load_report()
+            max: Most rows that may be skipped before the load fails
+        """
+        ...
+
+    def with_constraint(self, condition: str) -> None:
+        """
+        Require every loaded row to satisfy a SQL condition, such as
+        "quantity >= 0". Checked when the cube is built and on every refresh.
+        """
+        ...
+
+    def not_null(self, column: str) -> None:
+        """Require a column to have no NULL values."""
+        ...
+
+    def unique(self, columns: List[str]) -> None:
+        """Require the combination of columns to be unique across rows."""
+        ...
+
+    def with_violation_policy(self, policy: str) -> None:
+        """
+        Choose whether rows breaking a constraint fail the load.
+
+        Args:
+            policy: "fail" (default) or "report", which loads the data and lists
+                the violations under "violations" in the cube's load_report()
+        """
+        ...
+
+    def on_progress(self, callback: Callable[[Dict[str, Any]], None]) -> None:
+        """
+        Call `callback` as the sources of the cube are read.
+
+        The callback receives a dict with source, batches, rows, bytes,
+        total_bytes (None if unknown), elapsed and eta in seconds (eta None
+        if unknown), fraction (0 to 1, None if unknown) and done.
+        """
+        ...
+
+    def with_auto_classification(self, enabled: bool) -> None:
+        """
+        Classify loaded columns as dimensions or measures from their data.
+
+        Numeric columns become measures summed by default; strings, dates and
+        identifier-like integers become dimensions; text with too many distinct
+        values is left out. Only used when no columns are declared.
+        """
+        ...
+
+    def with_sql_transform(self, column: str, sql: str) -> None:
+        """
+        Store the value of a SQL expression in a column as it is loaded.
+
+        Transforms also run on every refresh, in the order they were added.
+
+        Args:
+            column: Column to replace, or to add if the source lacks it
+            sql: Expression over the loaded columns, e.g. "upper(trim(region))"
+        """
+        ...
+
+    def infer_schema(self) -> Dict[str, Any]:
+        """
+        Preview the source's schema with a suggested role for each column.
+
+        Only the first rows of the source are read.
+
+        Returns:
+            Dictionary with sample_rows and columns: a list of dicts with name,
+            data_type, role ("dimension" or "measure"), aggregation and reason
+        """
+        ...
+
+    def template(self) -> CubeTemplate:
+        """
+        Copy the builder's definitions, without its data source, into a
+        template for building many cubes alike.
+        """
+        ...
+
+    def describe_column(self, name: str, description: str) -> None:
+        """
+        Describe a column for catalogs and generated documentation.
+
+        Args:
+            name: Dimension, measure, calculated measure or virtual dimension
+            description: Human-readable description of the column
+        """
+        ...
+
+    def tag_column(self, name: str, tag: str) -> None:
+        """
+        Tag a dimension or measure.
+
+        Args:
+            name: Dimension or measure name
+            tag: Tag such as "pii" or "finance"
+        """
+        ...
+
     def load_csv(self, path: str) -> None:
         """
         Load data from a CSV file.
@@ -114,13 +286,39 @@ class ElastiCubeBuilder:
         """
         ...
 
+class CubeTemplate:
+    """Cube definitions without data, created by ElastiCubeBuilder.template()."""
+
+    def builder(self, name: Optional[str] = None) -> ElastiCubeBuilder:
+        """
+        Start a builder from the template, ready for a data source.
+
+        Args:
+            name: Cube name, if different from the template's
+        """
+        ...
+
 class ElastiCube:
     """OLAP Cube for multidimensional analysis."""
 
-    def query(self) -> QueryBuilder:
+    def query(
+        self,
+        cache: bool = True,
+        cache_ttl: Optional[float] = None,
+        max_cache_entries: Optional[int] = None,
+        max_cache_bytes: Optional[int] = None,
+    ) -> QueryBuilder:
         """
         Create a new query builder.
 
+        Results are cached between queries on the cube.
+
+        Args:
+            cache: Set to False to bypass the result cache
+            cache_ttl: Seconds a cached result stays valid
+            max_cache_entries: Maximum number of cached results
+            max_cache_bytes: Maximum total size of cached results in bytes
+
         Returns:
             QueryBuilder instance for constructing queries
         """
@@ -152,7 +350,8 @@ class ElastiCube:
         Get all measures.
 
         Returns:
-            List of measure dictionaries with keys: name, data_type, agg_func
+            List of measure dictionaries with keys: name, data_type, agg_func,
+            format, unit, precision
         """
         ...
 
@@ -210,6 +409,31 @@ class ElastiCube:
         """
         ...
 
+    def load_report(self) -> Dict[str, Any]:
+        """
+        Report of the latest load from the source.
+
+        Returns:
+            Dictionary with "coercions": a list of dicts with column, from, to
+            and nulled (values that became null), "classifications": the role
+            chosen for each column by auto-classification, if enabled,
+            "skipped_rows": a list of dicts with row and reason, and
+            "violations": a list of dicts with constraint, rows and examples
+            (positions of the first offending rows)
+        """
+        ...
+
+    def describe(self) -> Dict[str, Any]:
+        """
+        Describe the cube for catalogs and documentation tooling.
+
+        Returns:
+            Dictionary with name, description, row_count, primary_key, timezone,
+            columns (name, kind, data_type, aggregation, expression,
+            description, tags, format) and hierarchies
+        """
+        ...
+
     def statistics(self) -> Dict[str, Any]:
         """
         Get cube statistics.
@@ -220,6 +444,81 @@ class ElastiCube:
         """
         ...
 
+    def profile(
+        self,
+        sample_rows: Optional[int] = None,
+        top_k: int = 10,
+        histogram_bins: int = 10,
+    ) -> Dict[str, Any]:
+        """
+        Profile the cube's data for a quick quality overview.
+
+        Null counts, min and max cover every row; distinct counts, top values
+        and histograms are computed over a sample of large cubes.
+
+        Args:
+            sample_rows: Rows sampled (default: 100,000)
+            top_k: Number of most frequent values listed per column
+            histogram_bins: Bins of the histogram over numeric columns
+
+        Returns:
+            Dictionary with row_count, sampled_rows and columns (name,
+            data_type, null_count, null_rate, distinct_count, min, max,
+            top_values, histogram)
+        """
+        ...
+
+    def union(self, other: "ElastiCube", coercion: str = "strict") -> "ElastiCube":
+        """
+        Combine this cube's rows with those of another cube.
+
+        Args:
+            other: Cube with the same columns, in any order
+            coercion: "strict", "safe" or "lenient", casting the other cube's
+                columns to this cube's types as allowed
+
+        Returns:
+            A new cube with this cube's definitions; neither cube is changed
+        """
+        ...
+
+    @staticmethod
+    def merge_schemas(
+        name: str, cubes: List["ElastiCube"], coercion: str = "strict"
+    ) -> "ElastiCube":
+        """
+        Combine cubes with overlapping columns, such as regional shards.
+
+        Args:
+            name: Name of the merged cube
+            cubes: Cubes to merge; the first defining a column sets its type
+            coercion: "strict", "safe" or "lenient", for columns typed
+                differently in later cubes
+
+        Returns:
+            A cube with every column of every cube; rows from cubes without a
+            column hold None in it
+        """
+        ...
+
+    def diff(self, other: "ElastiCube", keys: List[str]) -> Dict[str, pa.Table]:
+        """
+        Compare this cube's rows with another cube's, matching them on key columns.
+
+        Useful for validating a new pipeline run against the previous one.
+
+        Args:
+            other: Cube with the same columns, e.g. a newer pipeline run
+            keys: Columns identifying a row in either cube
+
+        Returns:
+            Dictionary of tables: "added" (rows only the other cube has),
+            "removed" (rows only this cube has), "changed" (the other cube's
+            rows whose values differ) and "changed_before" (this cube's rows
+            for the same keys, in the same order)
+        """
+        ...
+
     def append_rows(self, data: pa.Table) -> int:
         """
         Append rows from a PyArrow Table.
@@ -278,6 +577,38 @@ class ElastiCube:
         """
         ...
 
+    def rename_dimension(self, old: str, new: str) -> None:
+        """
+        Rename a dimension, updating expressions, hierarchies and keys that use it.
+
+        Args:
+            old: Current dimension name
+            new: New dimension name
+        """
+        ...
+
+    def rename_measure(self, old: str, new: str) -> None:
+        """
+        Rename a measure, updating calculated measures that use it.
+
+        Args:
+            old: Current measure name
+            new: New measure name
+        """
+        ...
+
+    def rewrite_renamed_columns(self, sql: str) -> str:
+        """
+        Rewrite SQL written against earlier column names to the current ones.
+
+        Args:
+            sql: Query text, e.g. a saved dashboard query
+
+        Returns:
+            The query with renamed columns replaced
+        """
+        ...
+
 class QueryBuilder:
     """Builder for constructing cube queries."""
 
@@ -382,6 +713,17 @@ class QueryBuilder:
         """
         ...
 
+    def pretty_print(self) -> str:
+        """
+        Execute the query and render the result as a text table.
+
+        Measure columns are shown with their display formats.
+
+        Returns:
+            The formatted table
+        """
+        ...
+
     def to_pandas(self) -> pd.DataFrame:
         """
         Execute the query and return results as Pandas DataFrame.
@@ -6,11 +6,12 @@
 use pyo3::prelude::*;
 use pyo3::types::{PyBytes, IntoPyDict};
 
-use elasticube_core::{AggFunc, ElastiCube, ElastiCubeBuilder};
+use elasticube_core::{AggFunc, Coercion, ColumnRole, ColumnSuggestion, DisplayFormat, ErrorPolicy, LoadProgress, Constraint, CubeTemplate, ElastiCube, ViolationPolicy, ElastiCubeBuilder, OptimizationConfig};
 use arrow::datatypes::DataType;
 use arrow::ipc::writer::StreamWriter;
 use arrow::ipc::reader::StreamReader;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Python wrapper for ElastiCubeBuilder
 #[pyclass]
@@ -31,60 +32,67 @@ impl PyElastiCubeBuilder {
     /// Add a dimension to the cube
     fn add_dimension(&mut self, name: String, data_type: String) -> PyResult<()> {
         let dt = parse_datatype(&data_type)?;
-        let builder = self.builder.take().ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
-        })?;
-
-        self.builder = Some(builder.add_dimension(name, dt)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?);
+        self.builder_mut()?
+            .insert_dimension(name, dt)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
         Ok(())
     }
 
     /// Add a measure to the cube
+    ///
+    /// `format` (e.g. "$#,##0.00" or "0.0%"), `unit` and `precision` control
+    /// how the measure is shown by `QueryBuilder.pretty_print()`.
+    ///
+    /// # Example
+    /// ```python
+    /// builder.add_measure("revenue", "float64", "sum", format="$#,##0.00")
+    /// builder.add_measure("weight", "float64", "sum", unit="kg", precision=1)
+    /// ```
+    #[pyo3(signature = (name, data_type, agg_func, format=None, unit=None, precision=None))]
     fn add_measure(
         &mut self,
         name: String,
         data_type: String,
         agg_func: String,
+        format: Option<String>,
+        unit: Option<String>,
+        precision: Option<u8>,
     ) -> PyResult<()> {
         let dt = parse_datatype(&data_type)?;
         let agg = parse_agg_func(&agg_func)?;
-        let builder = self.builder.take().ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
-        })?;
-
-        self.builder = Some(builder.add_measure(name, dt, agg)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?);
+        let display = DisplayFormat {
+            pattern: format,
+            unit,
+            precision,
+        };
+        let builder = self.builder_mut()?;
+        builder.insert_measure(&name, dt, agg)
+            .and_then(|builder| {
+                if display.is_empty() {
+                    Ok(builder)
+                } else {
+                    builder.set_measure_format(&name, display)
+                }
+            })
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
         Ok(())
     }
 
     /// Load data from a CSV file
     fn load_csv(&mut self, path: String) -> PyResult<()> {
-        let builder = self.builder.take().ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
-        })?;
-
-        self.builder = Some(builder.load_csv(path));
+        self.builder_mut()?.set_source(elasticube_core::CsvSource::new(path));
         Ok(())
     }
 
     /// Load data from a Parquet file
     fn load_parquet(&mut self, path: String) -> PyResult<()> {
-        let builder = self.builder.take().ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
-        })?;
-
-        self.builder = Some(builder.load_parquet(path));
+        self.builder_mut()?.set_source(elasticube_core::ParquetSource::new(path));
         Ok(())
     }
 
     /// Load data from a JSON file
     fn load_json(&mut self, path: String) -> PyResult<()> {
-        let builder = self.builder.take().ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
-        })?;
-
-        self.builder = Some(builder.load_json(path));
+        self.builder_mut()?.set_source(elasticube_core::JsonSource::new(path));
         Ok(())
     }
 
@@ -100,12 +108,9 @@ impl PyElastiCubeBuilder {
     /// builder.add_hierarchy("time", ["year", "quarter", "month"])
     /// ```
     fn add_hierarchy(&mut self, name: String, levels: Vec<String>) -> PyResult<()> {
-        let builder = self.builder.take().ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
-        })?;
-
-        self.builder = Some(builder.add_hierarchy(name, levels)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?);
+        self.builder_mut()?
+            .insert_hierarchy(name, levels)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
         Ok(())
     }
 
@@ -130,12 +135,76 @@ impl PyElastiCubeBuilder {
     ) -> PyResult<()> {
         let dt = parse_datatype(&data_type)?;
         let agg = parse_agg_func(&agg_func)?;
-        let builder = self.builder.take().ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
-        })?;
+        self.builder_mut()?
+            .insert_calculated_measure(name, expression, dt, agg)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Add a filtered (conditional) measure
+    ///
+    /// # Arguments
+    /// * `name` - Name for the filtered measure
+    /// * `measure` - Existing measure to aggregate
+    /// * `agg_func` - Aggregation function
+    /// * `condition` - SQL condition selecting the rows to aggregate
+    ///
+    /// # Example
+    /// ```python
+    /// builder.add_filtered_measure("enterprise_revenue", "revenue", "sum", "segment = 'Enterprise'")
+    /// ```
+    fn add_filtered_measure(
+        &mut self,
+        name: String,
+        measure: String,
+        agg_func: String,
+        condition: String,
+    ) -> PyResult<()> {
+        let agg = parse_agg_func(&agg_func)?;
+        self.builder_mut()?
+            .insert_filtered_measure(name, measure, agg, condition)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(())
+    }
 
-        self.builder = Some(builder.add_calculated_measure(name, expression, dt, agg)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?);
+    /// Add a measure counting the distinct values of a key column
+    ///
+    /// # Arguments
+    /// * `name` - Name for the measure
+    /// * `key` - Dimension or measure whose distinct values are counted
+    ///
+    /// # Example
+    /// ```python
+    /// builder.add_distinct_count_measure("customers", "customer_id")
+    /// ```
+    fn add_distinct_count_measure(&mut self, name: String, key: String) -> PyResult<()> {
+        self.builder_mut()?
+            .insert_distinct_count_measure(name, key)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Add a calculated measure computed from aggregates, evaluated per group
+    ///
+    /// # Arguments
+    /// * `name` - Name for the measure
+    /// * `expression` - SQL expression over aggregates
+    /// * `data_type` - Result data type
+    ///
+    /// # Example
+    /// ```python
+    /// builder.add_aggregate_measure("margin", "SUM(profit) / SUM(revenue)", "float64")
+    /// ```
+    fn add_aggregate_measure(
+        &mut self,
+        name: String,
+        expression: String,
+        data_type: String,
+    ) -> PyResult<()> {
+        let dt = parse_datatype(&data_type)?;
+        self.builder_mut()?
+            .insert_aggregate_measure(name, expression, dt)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
         Ok(())
     }
 
@@ -157,12 +226,37 @@ impl PyElastiCubeBuilder {
         data_type: String,
     ) -> PyResult<()> {
         let dt = parse_datatype(&data_type)?;
-        let builder = self.builder.take().ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
-        })?;
+        self.builder_mut()?
+            .insert_virtual_dimension(name, expression, dt)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(())
+    }
 
-        self.builder = Some(builder.add_virtual_dimension(name, expression, dt)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?);
+    /// Add a many-to-many dimension whose members come from a bridge file
+    ///
+    /// # Arguments
+    /// * `name` - Dimension name, also the member column of the bridge
+    /// * `key` - Cube column the bridge rows refer to
+    /// * `path` - CSV, Parquet or JSON file of (key, member) rows
+    ///
+    /// # Example
+    /// ```python
+    /// builder.add_bridge_dimension("diagnosis", "patient_id", "diagnoses.csv")
+    /// ```
+    fn add_bridge_dimension(&mut self, name: String, key: String, path: String) -> PyResult<()> {
+        use elasticube_core::{CsvSource, JsonSource, ParquetSource};
+
+        let builder = self.builder_mut()?;
+        let extension = std::path::Path::new(&path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let added = match extension.as_deref() {
+            Some("csv") => builder.insert_bridge_dimension(name, key, CsvSource::new(path)),
+            Some("json") => builder.insert_bridge_dimension(name, key, JsonSource::new(path)),
+            _ => builder.insert_bridge_dimension(name, key, ParquetSource::new(path)),
+        };
+        added.map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
         Ok(())
     }
 
@@ -176,11 +270,265 @@ impl PyElastiCubeBuilder {
     /// builder.with_description("Sales data cube for 2024")
     /// ```
     fn with_description(&mut self, description: String) -> PyResult<()> {
-        let builder = self.builder.take().ok_or_else(|| {
+        self.builder_mut()?.set_description(description);
+        Ok(())
+    }
+
+    /// Express the cube's timestamps in a local time zone
+    ///
+    /// # Arguments
+    /// * `timezone` - IANA name such as "America/New_York" or an offset like "+02:00"
+    ///
+    /// # Example
+    /// ```python
+    /// builder.with_timezone("America/New_York")
+    /// ```
+    fn with_timezone(&mut self, timezone: String) -> PyResult<()> {
+        self.builder_mut()?
+            .set_timezone(timezone)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Cast loaded columns to the declared types instead of failing the build
+    ///
+    /// # Arguments
+    /// * `mode` - "strict" (default), "safe" (fail on values that don't convert)
+    ///   or "lenient" (values that don't convert become null)
+    ///
+    /// # Example
+    /// ```python
+    /// builder.with_type_coercion("safe")
+    /// ```
+    fn with_type_coercion(&mut self, mode: String) -> PyResult<()> {
+        let coercion: Coercion = mode.parse()
+            .map_err(|e: elasticube_core::Error| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        self.builder_mut()?.set_type_coercion(coercion);
+        Ok(())
+    }
+
+    /// Load a source column into a cube column of another name
+    ///
+    /// # Arguments
+    /// * `source` - Column name in the source, e.g. a CSV header
+    /// * `column` - Declared dimension or measure it loads into
+    ///
+    /// # Example
+    /// ```python
+    /// builder.map_column("Sales Amount", "sales")
+    /// ```
+    fn map_column(&mut self, source: String, column: String) -> PyResult<()> {
+        self.builder_mut()?.insert_column_mapping(source, column);
+        Ok(())
+    }
+
+    /// Drop source columns the cube doesn't use instead of keeping them
+    ///
+    /// # Example
+    /// ```python
+    /// builder.ignore_extra_columns(True)
+    /// ```
+    fn ignore_extra_columns(&mut self, ignore: bool) -> PyResult<()> {
+        self.builder_mut()?.set_ignore_extra_columns(ignore);
+        Ok(())
+    }
+
+    /// Choose how rows that fail to parse are handled
+    ///
+    /// # Arguments
+    /// * `policy` - "fail" (default) or "skip_bad_rows"
+    /// * `max` - Most rows "skip_bad_rows" may skip before the load fails
+    ///
+    /// # Example
+    /// ```python
+    /// builder.with_error_policy("skip_bad_rows", max=1000)
+    /// ```
+    #[pyo3(signature = (policy, max=1000))]
+    fn with_error_policy(&mut self, policy: String, max: usize) -> PyResult<()> {
+        let policy = match policy.to_lowercase().as_str() {
+            "fail" => ErrorPolicy::Fail,
+            "skip_bad_rows" => ErrorPolicy::SkipBadRows { max },
+            other => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Unknown error policy '{}'; expected 'fail' or 'skip_bad_rows'",
+                    other
+                )))
+            }
+        };
+        self.builder_mut()?.set_error_policy(policy);
+        Ok(())
+    }
+
+    /// Require every loaded row to satisfy a SQL condition
+    ///
+    /// Constraints are checked when the cube is built and on every refresh.
+    ///
+    /// # Example
+    /// ```python
+    /// builder.with_constraint("quantity >= 0")
+    /// ```
+    fn with_constraint(&mut self, condition: String) -> PyResult<()> {
+        self.builder_mut()?.insert_constraint(Constraint::Check(condition));
+        Ok(())
+    }
+
+    /// Require a column to have no NULL values
+    fn not_null(&mut self, column: String) -> PyResult<()> {
+        self.builder_mut()?.insert_constraint(Constraint::NotNull(column));
+        Ok(())
+    }
+
+    /// Require the combination of `columns` to be unique across rows
+    ///
+    /// # Example
+    /// ```python
+    /// builder.unique(["transaction_id"])
+    /// ```
+    fn unique(&mut self, columns: Vec<String>) -> PyResult<()> {
+        self.builder_mut()?.insert_constraint(Constraint::Unique(columns));
+        Ok(())
+    }
+
+    /// Choose whether rows breaking a constraint fail the load
+    ///
+    /// # Arguments
+    /// * `policy` - "fail" (default) or "report", which loads the data and
+    ///   lists the violations under "violations" in the cube's load_report()
+    fn with_violation_policy(&mut self, policy: String) -> PyResult<()> {
+        let policy = match policy.to_lowercase().as_str() {
+            "fail" => ViolationPolicy::Fail,
+            "report" => ViolationPolicy::Report,
+            other => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Unknown violation policy '{}'; expected 'fail' or 'report'",
+                    other
+                )))
+            }
+        };
+        self.builder_mut()?.set_violation_policy(policy);
+        Ok(())
+    }
+
+    /// Call `callback` with a dict as the sources of the cube are read
+    ///
+    /// The dict has source, batches, rows, bytes, total_bytes (or None),
+    /// elapsed and eta (seconds, eta None when unknown), fraction and done.
+    ///
+    /// # Example
+    /// ```python
+    /// builder.on_progress(lambda p: print(p["rows"], p["fraction"]))
+    /// ```
+    fn on_progress(&mut self, callback: Py<PyAny>) -> PyResult<()> {
+        self.builder_mut()?.set_progress_callback(move |progress| {
+            Python::attach(|py| {
+                let result = progress_to_dict(py, progress)
+                    .and_then(|dict| callback.call1(py, (dict,)));
+                if let Err(e) = result {
+                    e.write_unraisable(py, None);
+                }
+            })
+        });
+        Ok(())
+    }
+
+    /// Classify loaded columns as dimensions or measures from their data
+    ///
+    /// Only used when no dimensions or measures are declared. The decisions
+    /// are listed under "classifications" in the cube's load_report().
+    ///
+    /// # Example
+    /// ```python
+    /// builder.with_auto_classification(True)
+    /// ```
+    fn with_auto_classification(&mut self, enabled: bool) -> PyResult<()> {
+        self.builder_mut()?.set_auto_classification(enabled);
+        Ok(())
+    }
+
+    /// Store the value of a SQL expression in a column as it is loaded
+    ///
+    /// # Arguments
+    /// * `column` - Column to replace, or to add if the source lacks it
+    /// * `sql` - Expression over the loaded columns
+    ///
+    /// # Example
+    /// ```python
+    /// builder.with_sql_transform("region", "upper(trim(region))")
+    /// builder.with_sql_transform("amount", "amount_cents / 100.0")
+    /// ```
+    fn with_sql_transform(&mut self, column: String, sql: String) -> PyResult<()> {
+        self.builder_mut()?.insert_sql_transform(column, sql);
+        Ok(())
+    }
+
+    /// Preview the source's schema with a suggested role for each column
+    ///
+    /// Only the first rows of the source are read.
+    ///
+    /// Returns:
+    ///     Dictionary with sample_rows and columns: a list of dicts with name,
+    ///     data_type, role ("dimension" or "measure"), aggregation and reason
+    fn infer_schema<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, pyo3::types::PyDict>> {
+        let builder = self.builder.as_ref().ok_or_else(|| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
         })?;
+        let preview = builder.infer_schema()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+
+        let columns = pyo3::types::PyList::empty(py);
+        for column in &preview.columns {
+            columns.append(suggestion_to_dict(py, column)?)?;
+        }
+
+        let result = pyo3::types::PyDict::new(py);
+        result.set_item("sample_rows", preview.sample_rows)?;
+        result.set_item("columns", columns)?;
+        Ok(result)
+    }
+
+    /// Copy the builder's definitions, without its data source, into a template
+    ///
+    /// # Example
+    /// ```python
+    /// template = builder.template()
+    /// monday = template.builder()
+    /// monday.load_csv("sales_monday.csv")
+    /// ```
+    fn template(&self) -> PyResult<PyCubeTemplate> {
+        let builder = self.builder.as_ref().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
+        })?;
+        Ok(PyCubeTemplate {
+            template: builder.template(),
+        })
+    }
 
-        self.builder = Some(builder.with_description(description));
+    /// Describe a column for catalogs and generated documentation
+    ///
+    /// # Arguments
+    /// * `name` - Dimension, measure, calculated measure or virtual dimension
+    /// * `description` - Human-readable description of the column
+    ///
+    /// # Example
+    /// ```python
+    /// builder.describe_column("revenue", "Net revenue after discounts")
+    /// ```
+    fn describe_column(&mut self, name: String, description: String) -> PyResult<()> {
+        self.builder_mut()?
+            .set_column_description(&name, description)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Tag a dimension or measure
+    ///
+    /// # Arguments
+    /// * `name` - Dimension or measure name
+    /// * `tag` - Tag such as "pii" or "finance"
+    fn tag_column(&mut self, name: String, tag: String) -> PyResult<()> {
+        self.builder_mut()?
+            .insert_column_tag(&name, tag)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
         Ok(())
     }
 
@@ -231,16 +579,9 @@ impl PyElastiCubeBuilder {
             ));
         }
 
-        // Extract schema from first batch
-        let schema = batches[0].schema();
-
-        // Take the builder and add the batches
-        let builder = self.builder.take().ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
-        })?;
-
-        self.builder = Some(builder.load_record_batches(schema, batches)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?);
+        self.builder_mut()?
+            .set_data(batches)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
         Ok(())
     }
 
@@ -315,16 +656,9 @@ impl PyElastiCubeBuilder {
             ));
         }
 
-        // Extract schema from first batch
-        let schema = batches[0].schema();
-
-        // Take the builder and add the batches
-        let builder = self.builder.take().ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
-        })?;
-
-        self.builder = Some(builder.load_record_batches(schema, batches)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?);
+        self.builder_mut()?
+            .set_data(batches)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
         Ok(())
     }
 
@@ -366,16 +700,9 @@ impl PyElastiCubeBuilder {
             ));
         }
 
-        // Extract schema from first batch
-        let schema = batches[0].schema();
-
-        // Take the builder and add the batches
-        let builder = self.builder.take().ok_or_else(|| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
-        })?;
-
-        self.builder = Some(builder.load_record_batches(schema, batches)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?);
+        self.builder_mut()?
+            .set_data(batches)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
         Ok(())
     }
 
@@ -400,6 +727,39 @@ impl PyElastiCubeBuilder {
     }
 }
 
+impl PyElastiCubeBuilder {
+    /// The wrapped builder, unless `build` has consumed it
+    ///
+    /// Configuration goes through the builder's in-place methods, so a call
+    /// that fails leaves the builder usable.
+    fn builder_mut(&mut self) -> PyResult<&mut ElastiCubeBuilder> {
+        self.builder.as_mut().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Builder already consumed")
+        })
+    }
+}
+
+/// Python wrapper for CubeTemplate
+#[pyclass]
+struct PyCubeTemplate {
+    template: CubeTemplate,
+}
+
+#[pymethods]
+impl PyCubeTemplate {
+    /// Start a builder from the template, optionally under another cube name
+    #[pyo3(signature = (name=None))]
+    fn builder(&self, name: Option<String>) -> PyElastiCubeBuilder {
+        let builder = match name {
+            Some(name) => self.template.builder_named(name),
+            None => self.template.builder(),
+        };
+        PyElastiCubeBuilder {
+            builder: Some(builder),
+        }
+    }
+}
+
 /// Python wrapper for ElastiCube
 ///
 /// Uses Mutex for interior mutability to support update operations
@@ -411,14 +771,46 @@ struct PyElastiCube {
 #[pymethods]
 impl PyElastiCube {
     /// Create a query builder
-    fn query(&self) -> PyResult<PyQueryBuilder> {
+    ///
+    /// Results are cached between queries on the cube. The optional
+    /// arguments tune that cache:
+    /// * `cache` - Set to False to bypass the cache
+    /// * `cache_ttl` - Seconds a cached result stays valid
+    /// * `max_cache_entries` - Maximum number of cached results
+    /// * `max_cache_bytes` - Maximum total size of cached results
+    ///
+    /// # Example
+    /// ```python
+    /// result = cube.query(cache_ttl=60, max_cache_bytes=256 * 1024 * 1024)
+    /// ```
+    #[pyo3(signature = (cache=true, cache_ttl=None, max_cache_entries=None, max_cache_bytes=None))]
+    fn query(
+        &self,
+        cache: bool,
+        cache_ttl: Option<f64>,
+        max_cache_entries: Option<usize>,
+        max_cache_bytes: Option<usize>,
+    ) -> PyResult<PyQueryBuilder> {
         let cube = self.cube.lock()
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
 
         // Clone the cube to create an Arc for the query
         let cube_arc = Arc::new((*cube).clone());
 
-        let query_builder = cube_arc.query()
+        let mut config = OptimizationConfig::new().with_query_cache(cache);
+        if let Some(seconds) = cache_ttl {
+            let ttl = Duration::try_from_secs_f64(seconds)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid cache_ttl: {}", e)))?;
+            config = config.with_cache_ttl(ttl);
+        }
+        if let Some(entries) = max_cache_entries {
+            config = config.with_max_cache_entries(entries);
+        }
+        if let Some(bytes) = max_cache_bytes {
+            config = config.with_max_cache_bytes(bytes);
+        }
+
+        let query_builder = cube_arc.query_with_config(config)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
         Ok(PyQueryBuilder {
             builder: Some(query_builder),
@@ -446,6 +838,105 @@ impl PyElastiCube {
         Ok(cube.batch_count())
     }
 
+    /// Combine this cube's rows with those of another cube
+    ///
+    /// Args:
+    ///     other: Cube with the same columns, in any order
+    ///     coercion: "strict" (default), "safe" or "lenient", casting the other
+    ///         cube's columns to this cube's types as allowed
+    ///
+    /// Returns:
+    ///     A new cube with this cube's definitions; neither cube is changed
+    #[pyo3(signature = (other, coercion="strict"))]
+    fn union(&self, other: PyRef<'_, PyElastiCube>, coercion: &str) -> PyResult<PyElastiCube> {
+        let coercion: Coercion = coercion.parse()
+            .map_err(|e: elasticube_core::Error| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        // Copy this cube out first so a cube can be combined with itself
+        let cube = self.cube.lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?
+            .clone();
+        let other = other.cube.lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+
+        let combined = cube.union_with(&other, coercion)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(PyElastiCube {
+            cube: Arc::new(Mutex::new(combined)),
+        })
+    }
+
+    /// Combine cubes with overlapping columns, such as regional shards
+    ///
+    /// Args:
+    ///     name: Name of the merged cube
+    ///     cubes: Cubes to merge; the first defining a column sets its type
+    ///     coercion: "strict" (default), "safe" or "lenient", for columns typed
+    ///         differently in later cubes
+    ///
+    /// Returns:
+    ///     A cube with every column of every cube; rows from cubes without a
+    ///     column hold None in it
+    #[staticmethod]
+    #[pyo3(signature = (name, cubes, coercion="strict"))]
+    fn merge_schemas(
+        name: String,
+        cubes: Vec<PyRef<'_, PyElastiCube>>,
+        coercion: &str,
+    ) -> PyResult<PyElastiCube> {
+        let coercion: Coercion = coercion.parse()
+            .map_err(|e: elasticube_core::Error| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let shards = cubes
+            .iter()
+            .map(|cube| {
+                cube.cube.lock()
+                    .map(|cube| cube.clone())
+                    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))
+            })
+            .collect::<PyResult<Vec<ElastiCube>>>()?;
+        let shards: Vec<&ElastiCube> = shards.iter().collect();
+
+        let merged = ElastiCube::merge_schemas(name, &shards, coercion)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(PyElastiCube {
+            cube: Arc::new(Mutex::new(merged)),
+        })
+    }
+
+    /// Compare this cube's rows with another cube's, matching them on key columns
+    ///
+    /// Args:
+    ///     other: Cube with the same columns, e.g. a newer pipeline run
+    ///     keys: Columns identifying a row in either cube
+    ///
+    /// Returns:
+    ///     Dictionary of PyArrow Tables: "added" (rows only the other cube
+    ///     has), "removed" (rows only this cube has), "changed" (the other
+    ///     cube's rows whose values differ) and "changed_before" (this cube's
+    ///     rows for the same keys, in the same order)
+    fn diff<'py>(
+        &self,
+        py: Python<'py>,
+        other: PyRef<'_, PyElastiCube>,
+        keys: Vec<String>,
+    ) -> PyResult<Bound<'py, pyo3::types::PyDict>> {
+        // Copy this cube out first so a cube can be compared with itself
+        let cube = self.cube.lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?
+            .clone();
+        let other = other.cube.lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+
+        let diff = cube.diff(&other, &keys)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+
+        let dict = pyo3::types::PyDict::new(py);
+        dict.set_item("added", batch_to_table(py, &diff.added)?)?;
+        dict.set_item("removed", batch_to_table(py, &diff.removed)?)?;
+        dict.set_item("changed", batch_to_table(py, &diff.changed)?)?;
+        dict.set_item("changed_before", batch_to_table(py, &diff.changed_before)?)?;
+        Ok(dict)
+    }
+
     /// Append rows from PyArrow Table/RecordBatch
     ///
     /// Args:
@@ -617,6 +1108,45 @@ impl PyElastiCube {
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
     }
 
+    /// Rename a dimension, updating expressions, hierarchies and keys that use it
+    ///
+    /// Args:
+    ///     old: Current dimension name
+    ///     new: New dimension name
+    fn rename_dimension(&self, old: String, new: String) -> PyResult<()> {
+        let mut cube = self.cube.lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+
+        cube.rename_dimension(&old, &new)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// Rename a measure, updating calculated measures that use it
+    ///
+    /// Args:
+    ///     old: Current measure name
+    ///     new: New measure name
+    fn rename_measure(&self, old: String, new: String) -> PyResult<()> {
+        let mut cube = self.cube.lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+
+        cube.rename_measure(&old, &new)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// Rewrite SQL written against earlier column names to the current ones
+    ///
+    /// Args:
+    ///     sql: Query text, e.g. a saved dashboard query
+    ///
+    /// Returns:
+    ///     The query with renamed columns replaced
+    fn rewrite_renamed_columns(&self, sql: String) -> PyResult<String> {
+        let cube = self.cube.lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+        Ok(cube.rewrite_renamed_columns(&sql))
+    }
+
     /// Append rows from a Polars DataFrame
     ///
     /// This method provides a convenient way to incrementally load data from Polars
@@ -745,7 +1275,8 @@ impl PyElastiCube {
     /// Get all measures
     ///
     /// Returns:
-    ///     List of measure dictionaries with keys: name, data_type, agg_func
+    ///     List of measure dictionaries with keys: name, data_type, agg_func,
+    ///     format, unit, precision
     fn measures<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, pyo3::types::PyList>> {
         let cube = self.cube.lock()
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
@@ -758,6 +1289,9 @@ impl PyElastiCube {
             dict.set_item("name", measure.name())?;
             dict.set_item("data_type", format!("{:?}", measure.data_type()))?;
             dict.set_item("agg_func", format!("{:?}", measure.default_agg()))?;
+            dict.set_item("format", measure.format())?;
+            dict.set_item("unit", measure.unit())?;
+            dict.set_item("precision", measure.precision())?;
             py_list.append(dict)?;
         }
 
@@ -801,6 +1335,8 @@ impl PyElastiCube {
             dict.set_item("name", dim.name())?;
             dict.set_item("data_type", format!("{:?}", dim.data_type()))?;
             dict.set_item("cardinality", dim.cardinality())?;
+            dict.set_item("description", dim.description())?;
+            dict.set_item("tags", dim.tags().to_vec())?;
             Ok(Some(dict))
         } else {
             Ok(None)
@@ -823,6 +1359,11 @@ impl PyElastiCube {
             dict.set_item("name", measure.name())?;
             dict.set_item("data_type", format!("{:?}", measure.data_type()))?;
             dict.set_item("agg_func", format!("{:?}", measure.default_agg()))?;
+            dict.set_item("format", measure.format())?;
+            dict.set_item("unit", measure.unit())?;
+            dict.set_item("precision", measure.precision())?;
+            dict.set_item("description", measure.description())?;
+            dict.set_item("tags", measure.tags().to_vec())?;
             Ok(Some(dict))
         } else {
             Ok(None)
@@ -860,6 +1401,72 @@ impl PyElastiCube {
         Ok(cube.schema().description().map(|s| s.to_string()))
     }
 
+    /// Report of the latest load from the source
+    ///
+    /// Returns:
+    ///     Dictionary with "coercions": a list of dicts with column, from, to
+    ///     and nulled (values that became null), "classifications": the role
+    ///     chosen for each column by auto-classification, if enabled, and
+    ///     "skipped_rows": a list of dicts with row and reason
+    fn load_report<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, pyo3::types::PyDict>> {
+        let cube = self.cube.lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+
+        let coercions = pyo3::types::PyList::empty(py);
+        for coercion in &cube.load_report().coercions {
+            let dict = pyo3::types::PyDict::new(py);
+            dict.set_item("column", &coercion.column)?;
+            dict.set_item("from", coercion.from.to_string())?;
+            dict.set_item("to", coercion.to.to_string())?;
+            dict.set_item("nulled", coercion.nulled)?;
+            coercions.append(dict)?;
+        }
+
+        let classifications = pyo3::types::PyList::empty(py);
+        for decision in &cube.load_report().classifications {
+            classifications.append(suggestion_to_dict(py, decision)?)?;
+        }
+
+        let skipped_rows = pyo3::types::PyList::empty(py);
+        for bad_row in &cube.load_report().skipped_rows {
+            let dict = pyo3::types::PyDict::new(py);
+            dict.set_item("row", bad_row.row)?;
+            dict.set_item("reason", &bad_row.reason)?;
+            skipped_rows.append(dict)?;
+        }
+
+        let violations = pyo3::types::PyList::empty(py);
+        for violation in &cube.load_report().violations {
+            let dict = pyo3::types::PyDict::new(py);
+            dict.set_item("constraint", violation.constraint.to_string())?;
+            dict.set_item("rows", violation.rows)?;
+            dict.set_item("examples", violation.examples.clone())?;
+            violations.append(dict)?;
+        }
+
+        let report = pyo3::types::PyDict::new(py);
+        report.set_item("coercions", coercions)?;
+        report.set_item("classifications", classifications)?;
+        report.set_item("skipped_rows", skipped_rows)?;
+        report.set_item("violations", violations)?;
+        Ok(report)
+    }
+
+    /// Describe the cube for catalogs and documentation tooling
+    ///
+    /// Returns:
+    ///     Dictionary with the cube's name, description, row_count, primary_key,
+    ///     timezone, columns (name, kind, data_type, aggregation, expression,
+    ///     description, tags, format) and hierarchies
+    fn describe<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let cube = self.cube.lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+
+        let json = cube.describe().to_json()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        py.import("json")?.call_method1("loads", (json,))
+    }
+
     /// Get cube statistics
     ///
     /// Returns:
@@ -893,6 +1500,76 @@ impl PyElastiCube {
 
         Ok(dict)
     }
+
+    /// Profile the cube's data for a quick quality overview
+    ///
+    /// Args:
+    ///     sample_rows: Rows sampled for distinct counts, top values and
+    ///         histograms (default: 100,000)
+    ///     top_k: Number of most frequent values listed per column (default: 10)
+    ///     histogram_bins: Bins of the histogram over numeric columns (default: 10)
+    ///
+    /// Returns:
+    ///     Dictionary with row_count, sampled_rows and columns (name, data_type,
+    ///     null_count, null_rate, distinct_count, min, max, top_values as
+    ///     (value, count) pairs, and histogram as lower/upper/count bins)
+    #[pyo3(signature = (sample_rows=None, top_k=10, histogram_bins=10))]
+    fn profile<'py>(
+        &self,
+        py: Python<'py>,
+        sample_rows: Option<usize>,
+        top_k: usize,
+        histogram_bins: usize,
+    ) -> PyResult<Bound<'py, pyo3::types::PyDict>> {
+        let cube = self.cube.lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+
+        let mut options = elasticube_core::ProfileOptions::default()
+            .with_top_k(top_k)
+            .with_histogram_bins(histogram_bins);
+        if let Some(rows) = sample_rows {
+            options = options.with_sample_rows(rows);
+        }
+        let profile = cube.profile_with(options)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        let dict = pyo3::types::PyDict::new(py);
+        dict.set_item("row_count", profile.row_count)?;
+        dict.set_item("sampled_rows", profile.sampled_rows)?;
+
+        let columns = pyo3::types::PyList::empty(py);
+        for column in &profile.columns {
+            let col_dict = pyo3::types::PyDict::new(py);
+            col_dict.set_item("name", &column.name)?;
+            col_dict.set_item("data_type", column.data_type.to_string())?;
+            col_dict.set_item("null_count", column.null_count)?;
+            col_dict.set_item("null_rate", column.null_rate)?;
+            col_dict.set_item("distinct_count", column.distinct_count)?;
+            col_dict.set_item("min", column.min.as_ref().map(|v| v.to_string()))?;
+            col_dict.set_item("max", column.max.as_ref().map(|v| v.to_string()))?;
+            col_dict.set_item("top_values", column.top_values.clone())?;
+
+            let histogram = match &column.histogram {
+                Some(bins) => {
+                    let list = pyo3::types::PyList::empty(py);
+                    for bin in bins {
+                        let bin_dict = pyo3::types::PyDict::new(py);
+                        bin_dict.set_item("lower", bin.lower)?;
+                        bin_dict.set_item("upper", bin.upper)?;
+                        bin_dict.set_item("count", bin.count)?;
+                        list.append(bin_dict)?;
+                    }
+                    Some(list)
+                }
+                None => None,
+            };
+            col_dict.set_item("histogram", histogram)?;
+            columns.append(col_dict)?;
+        }
+        dict.set_item("columns", columns)?;
+
+        Ok(dict)
+    }
 }
 
 /// Python wrapper for QueryBuilder
@@ -1113,6 +1790,27 @@ impl PyQueryBuilder {
         Ok(table)
     }
 
+    /// Execute the query and render the result as a text table
+    ///
+    /// Measure columns are shown with their display formats.
+    fn pretty_print(&mut self, py: Python<'_>) -> PyResult<String> {
+        let builder = self.builder.take().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Query builder already executed")
+        })?;
+
+        let result = Python::detach(py, || {
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(async {
+                    builder.execute().await
+                        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+                })
+        })?;
+
+        result.pretty_print()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
     /// Execute query and return as Pandas DataFrame
     fn to_pandas<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
         let arrow_table = self.execute(py)?;
@@ -1286,6 +1984,62 @@ fn pyarrow_to_recordbatches<'py>(
     Ok(batches)
 }
 
+/// Convert a load progress update to a Python dict
+fn progress_to_dict<'py>(
+    py: Python<'py>,
+    progress: &LoadProgress,
+) -> PyResult<Bound<'py, pyo3::types::PyDict>> {
+    let dict = pyo3::types::PyDict::new(py);
+    dict.set_item("source", &progress.source)?;
+    dict.set_item("batches", progress.batches)?;
+    dict.set_item("rows", progress.rows)?;
+    dict.set_item("bytes", progress.bytes)?;
+    dict.set_item("total_bytes", progress.total_bytes)?;
+    dict.set_item("elapsed", progress.elapsed.as_secs_f64())?;
+    dict.set_item("eta", progress.eta().map(|eta| eta.as_secs_f64()))?;
+    dict.set_item("fraction", progress.fraction())?;
+    dict.set_item("done", progress.done)?;
+    Ok(dict)
+}
+
+/// Convert a RecordBatch to a PyArrow Table through Arrow IPC
+fn batch_to_table<'py>(
+    py: Python<'py>,
+    batch: &arrow::record_batch::RecordBatch,
+) -> PyResult<Bound<'py, PyAny>> {
+    let mut buffer = Vec::new();
+    {
+        let mut writer = StreamWriter::try_new(&mut buffer, &batch.schema())
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        writer.write(batch)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        writer.finish()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    }
+
+    let ipc = py.import("pyarrow.ipc")?;
+    let reader = ipc.call_method1("open_stream", (PyBytes::new(py, &buffer),))?;
+    reader.call_method0("read_all")
+}
+
+/// Convert a column role suggestion to a Python dict
+fn suggestion_to_dict<'py>(
+    py: Python<'py>,
+    column: &ColumnSuggestion,
+) -> PyResult<Bound<'py, pyo3::types::PyDict>> {
+    let dict = pyo3::types::PyDict::new(py);
+    dict.set_item("name", &column.name)?;
+    dict.set_item("data_type", column.data_type.to_string())?;
+    dict.set_item("role", match column.role {
+        ColumnRole::Dimension => "dimension",
+        ColumnRole::Measure => "measure",
+        ColumnRole::Skipped => "skipped",
+    })?;
+    dict.set_item("aggregation", column.aggregation.as_ref().map(|agg| agg.to_string()))?;
+    dict.set_item("reason", &column.reason)?;
+    Ok(dict)
+}
+
 /// Helper function to parse DataType from string
 fn parse_datatype(s: &str) -> PyResult<DataType> {
     match s.to_lowercase().as_str() {
@@ -1309,28 +2063,15 @@ fn parse_datatype(s: &str) -> PyResult<DataType> {
 
 /// Helper function to parse AggFunc from string
 fn parse_agg_func(s: &str) -> PyResult<AggFunc> {
-    match s.to_lowercase().as_str() {
-        "sum" => Ok(AggFunc::Sum),
-        "avg" | "average" | "mean" => Ok(AggFunc::Avg),
-        "min" => Ok(AggFunc::Min),
-        "max" => Ok(AggFunc::Max),
-        "count" => Ok(AggFunc::Count),
-        "count_distinct" | "countdistinct" => Ok(AggFunc::CountDistinct),
-        "median" => Ok(AggFunc::Median),
-        "stddev" | "std" => Ok(AggFunc::StdDev),
-        "variance" | "var" => Ok(AggFunc::Variance),
-        "first" => Ok(AggFunc::First),
-        "last" => Ok(AggFunc::Last),
-        _ => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-            format!("Unknown aggregation function: {}", s),
-        )),
-    }
+    s.parse::<AggFunc>()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
 }
 
 /// Python module definition
 #[pymodule]
 fn _elasticube(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyElastiCubeBuilder>()?;
+    m.add_class::<PyCubeTemplate>()?;
     m.add_class::<PyElastiCube>()?;
     m.add_class::<PyQueryBuilder>()?;
     Ok(())
@@ -86,6 +86,17 @@ class TestCubeBuilder:
         builder.add_measure("quantity", "int64", "avg")
         assert builder is not None
 
+    def test_failed_call_keeps_builder(self, sample_csv):
+        """Test that a rejected call leaves the builder usable."""
+        builder = ElastiCubeBuilder("csv_cube")
+        builder.add_dimension("region", "utf8")
+        with pytest.raises(ValueError):
+            builder.add_dimension("region", "utf8")
+        builder.add_measure("sales", "float64", "sum")
+        builder.load_csv(sample_csv)
+        cube = builder.build()
+        assert cube.row_count() > 0
+
     def test_load_csv(self, sample_csv):
         """Test loading data from CSV."""
         builder = ElastiCubeBuilder("csv_cube")
@@ -272,6 +283,24 @@ class TestAggregations:
         assert df is not None
         assert df['min_sales'].iloc[0] <= df['max_sales'].iloc[0]
 
+    def test_measure_format(self, sample_csv):
+        """Test that pretty_print applies measure display formats."""
+        builder = ElastiCubeBuilder("formatted")
+        builder.add_measure("sales", "float64", "sum", format="$#,##0.00")
+        builder.add_measure("quantity", "int64", "sum", unit="units")
+        builder.load_csv(sample_csv)
+        cube = builder.build()
+
+        measure = cube.get_measure("sales")
+        assert measure["format"] == "$#,##0.00"
+        assert measure["precision"] is None
+
+        query = cube.query()
+        query.select(["SUM(sales) as sales", "SUM(quantity) as quantity"])
+        text = query.pretty_print()
+        assert "$8,200.00" in text
+        assert "820 units" in text
+
 
 class TestOLAPOperations:
     """Test OLAP-specific operations."""
@@ -0,0 +1,233 @@
+//! WebAssembly bindings for ElastiCube
+//!
+//! Builds cubes from Arrow IPC bytes and queries them in the browser, so
+//! dashboards can slice moderate datasets client-side without a server
+//! round trip per interaction. Results come back either as Arrow IPC stream
+//! bytes (for Arrow JS, Perspective, DuckDB-Wasm, ...) or as JSON rows.
+//!
+//! ```js
+//! import init, { CubeBuilder } from "elasticube-wasm";
+//!
+//! await init();
+//! const bytes = new Uint8Array(await (await fetch("/sales.arrow")).arrayBuffer());
+//! const builder = new CubeBuilder("sales");
+//! builder.addDimension("region", "string");
+//! builder.addMeasure("revenue", "float64", "sum");
+//! builder.loadIpc(bytes);
+//! const cube = builder.build();
+//!
+//! const rows = JSON.parse(cube.queryJson("SELECT region, SUM(revenue) FROM cube GROUP BY region"));
+//! ```
+//!
+//! Queries run to completion on the calling thread; move the cube into a
+//! Web Worker to keep the page responsive over larger datasets.
+
+use arrow::datatypes::{DataType, TimeUnit};
+use arrow::ipc::reader::{FileReader, StreamReader};
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use elasticube_core::{AggFunc, ElastiCube, ElastiCubeBuilder, Error, QueryBuilder, Result};
+use futures::TryStreamExt;
+use std::future::Future;
+use std::io::Cursor;
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+/// Magic bytes that open an Arrow IPC file (as opposed to a stream)
+const IPC_FILE_MAGIC: &[u8] = b"ARROW1";
+
+thread_local! {
+    // DataFusion spawns tasks while executing a plan, so queries need a
+    // runtime to drive them; the browser only gives us one thread
+    static RUNTIME: tokio::runtime::Runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Failed to create tokio runtime");
+}
+
+type JsResult<T> = std::result::Result<T, JsError>;
+
+/// Run a future to completion on the thread's runtime
+fn block_on<F: Future>(future: F) -> F::Output {
+    RUNTIME.with(|runtime| runtime.block_on(future))
+}
+
+/// Builder for a cube, mirroring `ElastiCubeBuilder`
+///
+/// Without any `addDimension`/`addMeasure` calls every column of the loaded
+/// data becomes a dimension.
+#[wasm_bindgen]
+pub struct CubeBuilder {
+    builder: Option<ElastiCubeBuilder>,
+}
+
+#[wasm_bindgen]
+impl CubeBuilder {
+    /// Create a new cube builder
+    #[wasm_bindgen(constructor)]
+    pub fn new(name: String) -> CubeBuilder {
+        CubeBuilder {
+            builder: Some(ElastiCubeBuilder::new(name)),
+        }
+    }
+
+    /// Add a dimension to the cube
+    #[wasm_bindgen(js_name = addDimension)]
+    pub fn add_dimension(&mut self, name: String, data_type: String) -> JsResult<()> {
+        let data_type = parse_datatype(&data_type)?;
+        let builder = self.take()?.add_dimension(name, data_type)?;
+        self.builder = Some(builder);
+        Ok(())
+    }
+
+    /// Add a measure to the cube
+    #[wasm_bindgen(js_name = addMeasure)]
+    pub fn add_measure(
+        &mut self,
+        name: String,
+        data_type: String,
+        agg_func: String,
+    ) -> JsResult<()> {
+        let data_type = parse_datatype(&data_type)?;
+        let agg_func: AggFunc = agg_func.parse()?;
+        let builder = self.take()?.add_measure(name, data_type, agg_func)?;
+        self.builder = Some(builder);
+        Ok(())
+    }
+
+    /// Add a calculated measure derived from other measures
+    #[wasm_bindgen(js_name = addCalculatedMeasure)]
+    pub fn add_calculated_measure(
+        &mut self,
+        name: String,
+        expression: String,
+        data_type: String,
+        agg_func: String,
+    ) -> JsResult<()> {
+        let data_type = parse_datatype(&data_type)?;
+        let agg_func: AggFunc = agg_func.parse()?;
+        let builder = self
+            .take()?
+            .add_calculated_measure(name, expression, data_type, agg_func)?;
+        self.builder = Some(builder);
+        Ok(())
+    }
+
+    /// Load the cube's data from Arrow IPC stream or file bytes
+    #[wasm_bindgen(js_name = loadIpc)]
+    pub fn load_ipc(&mut self, bytes: &[u8]) -> JsResult<()> {
+        let batches = read_ipc(bytes)?;
+        let builder = self.take()?.with_data(batches)?;
+        self.builder = Some(builder);
+        Ok(())
+    }
+
+    /// Build the cube, consuming the builder's configuration
+    pub fn build(&mut self) -> JsResult<Cube> {
+        let cube = self.take()?.build()?;
+        Ok(Cube {
+            cube: Arc::new(cube),
+        })
+    }
+}
+
+impl CubeBuilder {
+    fn take(&mut self) -> Result<ElastiCubeBuilder> {
+        self.builder
+            .take()
+            .ok_or_else(|| Error::builder("Builder already consumed"))
+    }
+}
+
+/// A built cube, queryable with SQL
+///
+/// SQL refers to the cube as `cube`, as with `QueryBuilder::sql`.
+#[wasm_bindgen]
+pub struct Cube {
+    cube: Arc<ElastiCube>,
+}
+
+#[wasm_bindgen]
+impl Cube {
+    /// Build a cube straight from Arrow IPC bytes, with every column as a dimension
+    #[wasm_bindgen(js_name = fromIpc)]
+    pub fn from_ipc(name: String, bytes: &[u8]) -> JsResult<Cube> {
+        let mut builder = CubeBuilder::new(name);
+        builder.load_ipc(bytes)?;
+        builder.build()
+    }
+
+    /// Cube name
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.cube.schema().name().to_string()
+    }
+
+    /// Number of rows in the cube
+    #[wasm_bindgen(getter, js_name = rowCount)]
+    pub fn row_count(&self) -> usize {
+        self.cube.row_count()
+    }
+
+    /// The cube schema (dimensions, measures, hierarchies, ...) as JSON
+    #[wasm_bindgen(js_name = schemaJson)]
+    pub fn schema_json(&self) -> JsResult<String> {
+        Ok(serde_json::to_string(self.cube.schema())?)
+    }
+
+    /// Run a SQL query and return the result as Arrow IPC stream bytes
+    pub fn query(&self, sql: String) -> JsResult<Vec<u8>> {
+        let query = self.cube.clone().query()?.sql(sql);
+        Ok(block_on(write_ipc(query))?)
+    }
+
+    /// Run a SQL query and return the result as a JSON array of row objects
+    #[wasm_bindgen(js_name = queryJson)]
+    pub fn query_json(&self, sql: String) -> JsResult<String> {
+        let query = self.cube.clone().query()?.sql(sql);
+        let result = block_on(query.execute())?;
+        Ok(serde_json::to_string(&result.to_json_rows()?)?)
+    }
+
+    /// Append rows from Arrow IPC bytes, returning the number of rows added
+    #[wasm_bindgen(js_name = appendIpc)]
+    pub fn append_ipc(&mut self, bytes: &[u8]) -> JsResult<usize> {
+        let batches = read_ipc(bytes)?;
+        Ok(Arc::make_mut(&mut self.cube).append_batches(batches)?)
+    }
+}
+
+/// Decode Arrow IPC bytes in either the stream or the file format
+fn read_ipc(bytes: &[u8]) -> Result<Vec<RecordBatch>> {
+    let batches: Vec<RecordBatch> = if bytes.starts_with(IPC_FILE_MAGIC) {
+        FileReader::try_new(Cursor::new(bytes), None)?.collect::<std::result::Result<_, _>>()?
+    } else {
+        StreamReader::try_new(bytes, None)?.collect::<std::result::Result<_, _>>()?
+    };
+    Ok(batches)
+}
+
+/// Execute a query and encode its result as an Arrow IPC stream
+async fn write_ipc(query: QueryBuilder) -> Result<Vec<u8>> {
+    let mut stream = query.execute_stream().await?;
+    let mut writer = StreamWriter::try_new(Vec::new(), &stream.schema())?;
+    while let Some(batch) = stream.try_next().await? {
+        writer.write(&batch)?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// Parse a data type name as accepted by the Python bindings
+fn parse_datatype(s: &str) -> Result<DataType> {
+    match s.to_lowercase().as_str() {
+        "int32" | "int" => Ok(DataType::Int32),
+        "int64" | "long" => Ok(DataType::Int64),
+        "float32" | "float" => Ok(DataType::Float32),
+        "float64" | "double" => Ok(DataType::Float64),
+        "utf8" | "string" | "str" => Ok(DataType::Utf8),
+        "bool" | "boolean" => Ok(DataType::Boolean),
+        "date32" | "date" => Ok(DataType::Date32),
+        "date64" => Ok(DataType::Date64),
+        "timestamp" => Ok(DataType::Timestamp(TimeUnit::Microsecond, None)),
+        _ => Err(Error::schema(format!("Unknown data type: {}", s))),
+    }
+}
@@ -287,6 +287,14 @@ async fn main() -> Result<()> {
         .await?;
     println!("{}\n", result);
 
+    // Step 13: Revenue Distribution
+    println!("=== ANALYSIS 11: Transaction Revenue Distribution ===");
+    let histogram = cube.query()?.histogram("revenue", 8).await?;
+    for (lower, upper, count) in histogram.bins() {
+        println!("  {:>10.2} - {:>10.2}: {}", lower, upper, count);
+    }
+    println!();
+
     println!("=== Sales Analytics Complete ===\n");
     println!("Key Insights:");
     println!("  ✓ Customer segmentation reveals Enterprise customers drive highest revenue");
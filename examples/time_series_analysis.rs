@@ -11,7 +11,7 @@
 //! Run with: cargo run --example time_series_analysis
 
 use arrow_schema::DataType;
-use elasticube_core::{AggFunc, ElastiCubeBuilder, Result};
+use elasticube_core::{AggFunc, ElastiCubeBuilder, Granularity, Result};
 use std::fs::File;
 use std::io::Write;
 use tempfile::tempdir;
@@ -147,15 +147,14 @@ async fn main() -> Result<()> {
     println!("=== ANALYSIS 2: Monthly Trends ===");
     let result = cube
         .query()?
+        .bucket_time("timestamp", Granularity::Month)
         .select(&[
-            "year_month as month",
             "count(*) as readings",
             "avg(temperature) as avg_temp",
             "avg(humidity) as avg_humidity",
             "sum(power_consumption) as total_power",
         ])
-        .group_by(&["year_month"])
-        .order_by(&["year_month"])
+        .order_by(&["timestamp_month"])
         .execute()
         .await?;
     println!("{}\n", result);
@@ -185,14 +184,14 @@ async fn main() -> Result<()> {
     println!("=== ANALYSIS 4: Power Consumption by Location (Monthly) ===");
     let result = cube
         .query()?
+        .bucket_time("timestamp", Granularity::Month)
         .select(&[
-            "year_month as month",
             "location",
             "sum(power_consumption) as total_power",
             "avg(temperature) as avg_temp",
         ])
-        .group_by(&["year_month", "location"])
-        .order_by(&["year_month", "total_power DESC"])
+        .group_by(&["location"])
+        .order_by(&["timestamp_month", "total_power DESC"])
         .execute()
         .await?;
     println!("{}\n", result);
@@ -201,15 +200,15 @@ async fn main() -> Result<()> {
     println!("=== ANALYSIS 5: Device Type Performance (Monthly) ===");
     let result = cube
         .query()?
+        .bucket_time("timestamp", Granularity::Month)
         .select(&[
-            "year_month as month",
             "device_type",
             "avg(temperature) as avg_temp",
             "avg(humidity) as avg_humidity",
             "sum(power_consumption) as total_power",
         ])
-        .group_by(&["year_month", "device_type"])
-        .order_by(&["year_month", "device_type"])
+        .group_by(&["device_type"])
+        .order_by(&["timestamp_month", "device_type"])
         .execute()
         .await?;
     println!("{}\n", result);
@@ -218,15 +217,15 @@ async fn main() -> Result<()> {
     println!("=== ANALYSIS 6: Warning Status Over Time ===");
     let result = cube
         .query()?
+        .bucket_time("timestamp", Granularity::Month)
         .select(&[
-            "year_month as month",
             "status",
             "count(*) as occurrences",
             "avg(temperature) as avg_temp",
             "avg(humidity) as avg_humidity",
         ])
-        .group_by(&["year_month", "status"])
-        .order_by(&["year_month", "status"])
+        .group_by(&["status"])
+        .order_by(&["timestamp_month", "status"])
         .execute()
         .await?;
     println!("{}\n", result);